@@ -18,6 +18,45 @@ fn main()
 {
     windows_icon().expect("Failed to embed icon");
     fltk().expect("Failed to build fltk files");
+    embedded_hashes().expect("Failed to hash embedded binaries");
+}
+
+/// Set environment variables with the SHA-256 hash of the binaries that are
+/// embedded with `flate!`, so `depends::try_write_deps` can check if they
+/// have already been extracted.
+fn embedded_hashes() -> Result<(), BuildError>
+{
+    println!("cargo:rerun-if-changed=Binaries");
+    let windows = env::var("CARGO_CFG_TARGET_OS").is_ok_and(|v| v == "windows");
+    let mut items = match windows
+    {
+        true => vec![
+            ("BEANS_BUTLER_SHA256", "Binaries/butler.exe"),
+            ("BEANS_BUTLER_LIB_1_SHA256", "Binaries/7z.dll"),
+            ("BEANS_BUTLER_LIB_2_SHA256", "Binaries/c7zip.dll"),
+        ],
+        false => vec![
+            ("BEANS_BUTLER_SHA256", "Binaries/butler"),
+            ("BEANS_BUTLER_LIB_1_SHA256", "Binaries/7z.so"),
+            ("BEANS_BUTLER_LIB_2_SHA256", "Binaries/libc7zip.so"),
+        ]
+    };
+    if windows
+    {
+        items.push(("BEANS_ARIA2C_SHA256", "Binaries/aria2c.exe"));
+    }
+    for (key, location) in items
+    {
+        use sha2::Digest;
+        let data = std::fs::read(location)?;
+        let mut hex = String::new();
+        for b in sha2::Sha256::digest(&data).iter()
+        {
+            hex.push_str(&format!("{:02x}", b));
+        }
+        println!("cargo:rustc-env={}={}", key, hex);
+    }
+    Ok(())
 }
 
 /// generate files for fltk ui stuff
@@ -1,19 +1,20 @@
-use std::sync::RwLock;
-
 use lazy_static::lazy_static;
-use log::{debug,
-          error};
+use log::debug;
 
 use crate::BeansError;
 
 /// Default `appvar.json` to use.
 pub const JSON_DATA_DEFAULT: &str = include_str!("appvar.json");
 lazy_static! {
-    static ref JSON_DATA: RwLock<String> = RwLock::new(JSON_DATA_DEFAULT.to_string());
-    static ref AVD_INSTANCE: RwLock<Option<AppVarData>> = RwLock::new(None);
+    static ref AVD_DEFAULT: AppVarData =
+        AppVarData::parse(JSON_DATA_DEFAULT).expect("Failed to deserialize JSON_DATA_DEFAULT");
 }
 
 /// Configuration for the compiled application.
+///
+/// The mod that is being worked on is from `RunnerContext.appvar`, so a host
+/// application can work on more than one mod at once by creating a
+/// `RunnerContext` for each of them.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct AppVarData
 {
@@ -24,26 +25,12 @@ pub struct AppVarData
 }
 impl AppVarData
 {
-    /// Parse `JSON_DATA` to AppVarData. Should only be called by
-    /// `reset_appvar()`.
-    ///
-    /// NOTE panics when `serde_json::from_str()` is Err, or when
-    /// `JSON_DATA.read()` is Err. REMARKS does not set `AVD_INSTANCE` to
-    /// generated data, since this is only done by `AppVarData::reset()`.
-    pub fn parse() -> Self
+    /// Parse the content of an `appvar.json` file.
+    pub fn parse(json: &str) -> Result<Self, BeansError>
     {
-        debug!("[AppVarData::parse] trying to get JSON_DATA");
-        let x = JSON_DATA.read();
-        if let Ok(data) = x
-        {
-            debug!("[AppVarData::parse] JSON_DATA= {:#?}", data);
-            return serde_json::from_str(&data).expect("Failed to deserialize JSON_DATA");
-        }
-        if let Err(e) = x
-        {
-            panic!("[AppVarData::parse] Failed to read JSON_DATA {:#?}", e);
-        }
-        unreachable!();
+        let data: Self = serde_json::from_str(json)?;
+        debug!("[AppVarData::parse] {:#?}", data);
+        Ok(data)
     }
 
     /// Substitute values in the `source` string for what is defined in here.
@@ -61,95 +48,26 @@ impl AppVarData
             .replace("$URL_VERSIONS", &self.remote_info.versions_url)
     }
 
-    /// Try and read the data from `AVD_INSTANCE` and return when some.
-    /// Otherwise, when it's none, we return `AppVarData::reset()`
-    ///
-    /// NOTE this function panics when Err on `AVD_INSTANCE.read()`.
-    pub fn get() -> Self
+    /// Name of the mod directory in the sourcemods folder, with a path
+    /// separator on each side. e.g; `/open_fortress/`
+    pub fn data_dir(&self) -> String
     {
-        let avd_read = AVD_INSTANCE.read();
-        if let Ok(v) = avd_read
-        {
-            let vc = v.clone();
-            if let Some(x) = vc
-            {
-                #[cfg(debug_assertions)]
-                debug!("[AppVarData::get] Instance exists in AVD_INSTANCE, so lets return that.");
-                return x;
-            }
-        }
-        else if let Err(e) = avd_read
-        {
-            panic!("[AppVarData::get] Failed to read AVD_INSTANCE {:#?}", e);
-        }
-
-        Self::reset()
+        format!(
+            "{}{}{}",
+            crate::PATH_SEP,
+            self.mod_info.sourcemod_name,
+            crate::PATH_SEP
+        )
     }
 
-    /// Set the content of `AVD_INSTANCE` to the result of `AppVarData::parse()`
+    /// Get the data that was compiled in (`JSON_DATA_DEFAULT`). This never
+    /// changes, use `RunnerContext.appvar` for the mod that is being worked
+    /// on.
     ///
-    /// NOTE this function panics when Err on `AVD_INSTANCE.write()`
-    pub fn reset() -> Self
-    {
-        let instance = AppVarData::parse();
-
-        match AVD_INSTANCE.write()
-        {
-            Ok(mut data) =>
-            {
-                *data = Some(instance.clone());
-                debug!(
-                    "[reset_appvar] set content of AVD_INSTANCE to {:#?}",
-                    instance
-                );
-            }
-            Err(e) =>
-            {
-                panic!("[AppVarData::reset] Failed to set AVD_INSTANCE! {:#?}", e);
-            }
-        }
-
-        instance
-    }
-
-    /// Serialize `data` into JSON, then set the content of `JSON_DATA` to the
-    /// serialize content. Once that is done, `AppVarData::reset()` will be
-    /// called.
-    ///
-    /// If `serde_json::to_string` fails, an error is printed in console and
-    /// `sentry::capture_error` is called.
-    pub fn set_json_data(data: AppVarData) -> Result<(), BeansError>
+    /// NOTE panics when `JSON_DATA_DEFAULT` can't be deserialized.
+    pub fn get() -> Self
     {
-        debug!("[AppVarData::set_json_data] {:#?}", data);
-        match serde_json::to_string(&data)
-        {
-            Ok(v) =>
-            {
-                if let Ok(mut ms) = JSON_DATA.write()
-                {
-                    *ms = v.to_string();
-                    debug!(
-                        "[AppVarData::set_json_data] successfully set data, calling reset_appvar()"
-                    );
-                }
-                Self::reset();
-                Ok(())
-            }
-            Err(e) =>
-            {
-                error!(
-                    "[AppVarData::set_json_data] Failed to serialize data to string! {:}",
-                    e
-                );
-                debug!("{:#?}", e);
-                sentry::capture_error(&e);
-
-                Err(BeansError::AppVarDataSerializeFailure {
-                    error: e,
-                    data: data.clone()
-                })
-            }
-        }
+        AVD_DEFAULT.clone()
     }
 }
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -4,16 +4,17 @@ use log::{debug,
           error,
           info};
 
-use crate::{depends,
+use crate::{config::Config,
+            depends,
             helper,
             BeansError,
             DownloadFailureReason};
 
-pub fn can_use_aria2() -> bool
+pub fn can_use_aria2(config: &Config) -> bool
 {
-    get_executable_location().is_some()
+    get_executable_location(config).is_some()
 }
-pub fn get_executable_location() -> Option<String>
+pub fn get_executable_location(config: &Config) -> Option<String>
 {
     if let Some(r) = helper::get_program_env_location(String::from("aria2c"))
     {
@@ -23,7 +24,7 @@ pub fn get_executable_location() -> Option<String>
     {
         return Some(r);
     }
-    if let Some(x) = depends::get_aria2c_location()
+    if let Some(x) = depends::get_aria2c_location(config)
         && helper::file_exists(x.clone())
     {
         return Some(x);
@@ -34,10 +35,11 @@ pub fn get_executable_location() -> Option<String>
 
 pub async fn download_file(
     url: String,
-    out_location: String
+    out_location: String,
+    config: &Config
 ) -> Result<ExitStatus, BeansError>
 {
-    let exe_location = match get_executable_location()
+    let exe_location = match get_executable_location(config)
     {
         Some(x) => x,
         None =>
@@ -62,7 +64,7 @@ pub async fn download_file(
     );
 
     let user_agent = crate::get_user_agent();
-    if let Some(over) = crate::env_aria2c_override_args()
+    if let Some(over) = &config.aria2c_args_override
     {
         let repl = over
             .replace("%OUT_DIR%", &output_directory)
@@ -77,7 +79,7 @@ pub async fn download_file(
     }
     else
     {
-        if let Some(extra) = crate::env_aria2c_extra_args()
+        if let Some(extra) = &config.aria2c_args
         {
             debug!(
                 "[aria2::download_file] (prepend) extra arguments: {}",
@@ -99,6 +101,9 @@ pub async fn download_file(
             &url
         ]);
     }
+    // stdout might be used for JSON (see `events::JsonSink`), so aria2c's
+    // progress is written to stderr instead.
+    cmd.stdout(std::io::stderr());
     debug!("[aria2::download_file] spawn\n{:#?}", cmd);
     let cmd_string = format!("{:#?}", cmd);
     match cmd.spawn()
@@ -1,68 +1,244 @@
 use std::{backtrace::Backtrace,
-          process::ExitStatus};
+          process::{ExitStatus,
+                    Stdio}};
 
 use log::{debug,
           error,
-          info};
+          info,
+          trace,
+          warn};
+use tokio::{io::{AsyncBufReadExt,
+                 AsyncReadExt,
+                 BufReader},
+            process::Command};
+use tokio_util::sync::CancellationToken;
 
-use crate::{depends,
+use crate::{config::Config,
+            depends,
+            events::{EventSink,
+                     Phase,
+                     Progress},
             helper,
             BeansError,
             DownloadFailureReason};
 
-pub fn verify(
+/// Maximum amount of times that a butler command is run when it fails because
+/// of a network error.
+pub const BUTLER_MAX_ATTEMPTS: usize = 3;
+/// Seconds to wait before running a butler command again. Multiplied by the
+/// attempt number.
+pub const BUTLER_RETRY_DELAY_SECS: u64 = 2;
+/// Amount of lines from the end of butler's output that are included in
+/// errors.
+const BUTLER_STDERR_TAIL_LINES: usize = 5;
+/// Length of the progress bar for butler commands. butler reports progress
+/// as a fraction, so this is how precise the bar is.
+const BUTLER_PROGRESS_LENGTH: u64 = 1000;
+/// Maximum amount of files listed by `print_changed_files`
+const CHANGED_FILES_DISPLAY_LIMIT: usize = 20;
+
+/// What caused a butler command to fail. See `classify_failure`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButlerFailureKind
+{
+    Network,
+    CorruptPatch,
+    DiskFull,
+    SignatureMismatch,
+    Other
+}
+
+/// Line written to stdout by butler when it's run with `--json`.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ButlerMessage
+{
+    Log
+    {
+        #[serde(default)]
+        level: String,
+        #[serde(default)]
+        message: String
+    },
+    Progress
+    {
+        /// From `0.0` to `1.0`
+        #[serde(default)]
+        progress: f64,
+        /// Seconds
+        #[serde(default)]
+        eta: Option<f64>,
+        /// Bytes per second
+        #[serde(default)]
+        bps: Option<f64>
+    },
+    Result
+    {
+        #[serde(default)]
+        value: serde_json::Value
+    },
+    Error
+    {
+        #[serde(default)]
+        message: String
+    },
+    #[serde(other)]
+    Other
+}
+
+/// Exit status, and the messages written by a butler command.
+#[derive(Debug)]
+struct ButlerOutput
+{
+    status: ExitStatus,
+    /// Everything written to stderr, and every log and error message that
+    /// was written to stdout.
+    log: String,
+    /// Messages from `ButlerMessage::Error`. Used by `classify_failure`.
+    errors: Vec<String>,
+    stderr: String
+}
+
+/// Result of a butler command (or `wharf::patch`) that changes files in the
+/// mod directory. butler only reports how many files it changed, so the
+/// names come from the signature or the patch.
+#[derive(Debug, Clone, Default)]
+pub struct ButlerReport
+{
+    /// Files that were created or changed (relative to the mod directory),
+    /// sorted.
+    pub changed_files: Vec<String>,
+    /// Files that were removed (relative to the mod directory), sorted.
+    pub removed_files: Vec<String>
+}
+
+impl ButlerReport
+{
+    /// Add the files from `other`.
+    pub fn extend(
+        &mut self,
+        other: ButlerReport
+    )
+    {
+        for (files, new) in [
+            (&mut self.changed_files, other.changed_files),
+            (&mut self.removed_files, other.removed_files)
+        ]
+        {
+            files.extend(new);
+            files.sort();
+            files.dedup();
+        }
+    }
+}
+
+pub async fn verify(
     signature_url: String,
     gamedir: String,
-    remote: String
-) -> Result<ExitStatus, BeansError>
-{
-    let mut cmd = std::process::Command::new(depends::get_butler_location());
-    cmd.args([
-        "verify",
-        &signature_url,
-        &gamedir,
-        format!("--heal=archive,{}", remote).as_str()
-    ]);
-    debug!("[butler::verify] {:#?}", cmd);
-    match cmd.spawn()
-    {
-        Err(e) => Err(BeansError::ButlerVerifyFailure {
-            signature_url,
-            gamedir,
-            remote,
-            error: e,
-            backtrace: Backtrace::capture()
-        }),
-        Ok(mut v) =>
-        {
-            let w = v.wait()?;
-            debug!("[butler::verify] Exited with {:#?}", w);
-            if let Some(c) = w.code()
-            {
-                if c != 0
-                {
-                    error!("[butler::verify] exited with code {c}, which isn't good!");
-                    panic!("[butler::verify] exited with code {c}");
-                }
-            }
-            Ok(w)
+    remote: String,
+    config: &Config,
+    cancel: &CancellationToken,
+    events: &dyn EventSink
+) -> Result<(), BeansError>
+{
+    let output = run_with_retry(
+        "butler::verify",
+        Phase::Heal,
+        "Verifying files",
+        cancel,
+        events,
+        || {
+            let mut cmd = create_command(config);
+            cmd.args([
+                "verify",
+                &signature_url,
+                &gamedir,
+                format!("--heal=archive,{}", remote).as_str()
+            ]);
+            cmd
+        }
+    )
+    .await;
+    if cancel.is_cancelled()
+    {
+        return Err(BeansError::Cancelled);
+    }
+    let output = match output
+    {
+        Ok(v) => v,
+        Err(e) =>
+        {
+            return Err(BeansError::ButlerVerifyFailure {
+                signature_url,
+                gamedir,
+                remote,
+                error: e,
+                backtrace: Backtrace::capture()
+            });
         }
+    };
+    if output.status.success()
+    {
+        return Ok(());
+    }
+    Err(failure_error("verify", None, gamedir, &output))
+}
+/// Check `gamedir` against the signature at `signature_url` with
+/// `wharf::verify_signature`, then use `verify` to heal it when some files
+/// don't match. The files that didn't match are the ones that were changed.
+pub async fn verify_and_heal(
+    signature_url: String,
+    gamedir: String,
+    remote: String,
+    config: &Config,
+    cancel: &CancellationToken,
+    events: &dyn EventSink
+) -> Result<ButlerReport, BeansError>
+{
+    let report = helper::cancellable(
+        cancel,
+        crate::wharf::verify_signature_url(signature_url.clone(), gamedir.clone(), config, events)
+    )
+    .await?;
+    crate::wharf::print_damaged_files(&report, events);
+    if report.is_valid()
+    {
+        return Ok(ButlerReport::default());
     }
+    info!(
+        "[butler::verify_and_heal] Repairing {} files",
+        report.damaged.len()
+    );
+    verify(signature_url, gamedir, remote, config, cancel, events).await?;
+    Ok(ButlerReport {
+        changed_files: report.damaged.into_iter().map(|f| f.path).collect(),
+        removed_files: Vec::new()
+    })
 }
+/// Download the patch at `dl_url`, then apply it to `gamedir` with
+/// `wharf::patch`. butler is only used when the patch couldn't be applied
+/// natively.
 pub async fn patch_dl(
     dl_url: String,
     staging_dir: String,
     patch_filename: String,
-    gamedir: String
-) -> Result<ExitStatus, BeansError>
+    gamedir: String,
+    config: &Config,
+    cancel: &CancellationToken,
+    events: &dyn EventSink
+) -> Result<ButlerReport, BeansError>
 {
     if helper::file_exists(staging_dir.clone())
     {
         std::fs::remove_dir_all(&staging_dir)?;
     }
-    let tmp_file = helper::get_tmp_file(patch_filename);
+    let tmp_file = helper::get_download_file(config, patch_filename);
     info!("[butler::patch_dl] downloading {} to {}", dl_url, tmp_file);
-    helper::download_with_progress(dl_url, tmp_file.clone()).await?;
+    helper::cancellable(
+        cancel,
+        helper::download_with_progress(dl_url, tmp_file.clone(), config, events)
+    )
+    .await?;
 
     if !helper::file_exists(tmp_file.clone())
     {
@@ -73,26 +249,70 @@ pub async fn patch_dl(
             backtrace: std::backtrace::Backtrace::capture()
         });
     }
+    if cancel.is_cancelled()
+    {
+        return Err(BeansError::Cancelled);
+    }
 
-    patch(tmp_file, staging_dir, gamedir)
+    match crate::wharf::patch(tmp_file.clone(), staging_dir.clone(), gamedir.clone())
+    {
+        Ok(v) => Ok(v),
+        // might be something that isn't supported by `wharf::patch` yet, so
+        // let butler try.
+        Err(BeansError::WharfPatchInvalid {
+            reason, ..
+        }) =>
+        {
+            warn!("[butler::patch_dl] Failed to apply patch natively ({reason}), using butler instead");
+            patch(tmp_file, staging_dir, gamedir, config, cancel, events).await
+        }
+        Err(e) => Err(e)
+    }
 }
 
-pub fn patch(
+pub async fn patch(
     patchfile_location: String,
     staging_dir: String,
-    gamedir: String
-) -> Result<ExitStatus, BeansError>
+    gamedir: String,
+    config: &Config,
+    cancel: &CancellationToken,
+    events: &dyn EventSink
+) -> Result<ButlerReport, BeansError>
 {
-    let mut cmd = std::process::Command::new(depends::get_butler_location());
-    cmd.args([
-        "apply",
-        &format!("--staging-dir={}", &staging_dir),
-        &patchfile_location,
-        &gamedir
-    ]);
-    debug!("[butler::patch] {:#?}", &cmd);
-    match cmd.spawn()
+    let changes = match crate::wharf::patch_changes(patchfile_location.clone())
+    {
+        Ok(v) => v,
+        Err(e) =>
+        {
+            warn!("[butler::patch] Couldn't read which files are changed by the patch ({e:})");
+            ButlerReport::default()
+        }
+    };
+    let output = run_with_retry(
+        "butler::patch",
+        Phase::Patch,
+        "Patching files",
+        cancel,
+        events,
+        || {
+            let mut cmd = create_command(config);
+            cmd.args([
+                "apply",
+                &format!("--staging-dir={}", &staging_dir),
+                &patchfile_location,
+                &gamedir
+            ]);
+            cmd
+        }
+    )
+    .await;
+    if cancel.is_cancelled()
     {
+        return Err(BeansError::Cancelled);
+    }
+    let output = match output
+    {
+        Ok(v) => v,
         Err(e) =>
         {
             let xe = BeansError::ButlerPatchFailure {
@@ -103,21 +323,658 @@ pub fn patch(
             };
             error!("[butler::patch] {:#?}", xe);
             sentry::capture_error(&xe);
-            Err(xe)
+            return Err(xe);
+        }
+    };
+    if output.status.success()
+    {
+        return Ok(changes);
+    }
+    Err(failure_error(
+        "apply",
+        Some(patchfile_location),
+        gamedir,
+        &output
+    ))
+}
+
+/// Emit the files in `files` as a message, with `verb` describing what
+/// happened to them (e.g; "repaired"). Only the first
+/// `CHANGED_FILES_DISPLAY_LIMIT` are listed.
+///
+/// Also see `print_report`.
+pub fn print_changed_files(
+    verb: &str,
+    files: &[String],
+    events: &dyn EventSink
+)
+{
+    if files.is_empty()
+    {
+        events.message(format!("No files were {verb}."));
+        return;
+    }
+    let mut message = format!("{} file(s) were {verb};", files.len());
+    for f in files.iter().take(CHANGED_FILES_DISPLAY_LIMIT)
+    {
+        message.push_str(&format!("\n    {f}"));
+    }
+    if files.len() > CHANGED_FILES_DISPLAY_LIMIT
+    {
+        message.push_str(&format!(
+            "\n    ...and {} more",
+            files.len() - CHANGED_FILES_DISPLAY_LIMIT
+        ));
+    }
+    events.message(message);
+}
+
+/// Emit the changed files in `report` with `verb`, then the removed files
+/// (when there are any). See `print_changed_files`.
+pub fn print_report(
+    verb: &str,
+    report: &ButlerReport,
+    events: &dyn EventSink
+)
+{
+    print_changed_files(verb, &report.changed_files, events);
+    if !report.removed_files.is_empty()
+    {
+        print_changed_files("removed", &report.removed_files, events);
+    }
+}
+
+/// Create a command for butler, with `--json` so it's output can be parsed
+/// by `run`.
+fn create_command(config: &Config) -> Command
+{
+    let mut cmd = Command::new(depends::get_butler_location(config));
+    cmd.arg("--json");
+    cmd
+}
+
+/// Run the command from `create_cmd`, and run it again (up to
+/// `BUTLER_MAX_ATTEMPTS` times) when it fails because of a network error.
+/// Progress is reported to `events` as `phase`, with `label` as the message.
+///
+/// Returns the output of the last attempt, which might not be successful.
+/// Nothing is run again once `cancel` is cancelled.
+async fn run_with_retry<F>(
+    name: &str,
+    phase: Phase,
+    label: &str,
+    cancel: &CancellationToken,
+    events: &dyn EventSink,
+    create_cmd: F
+) -> Result<ButlerOutput, std::io::Error>
+where
+    F: Fn() -> Command
+{
+    let mut attempt = 1;
+    loop
+    {
+        let mut cmd = create_cmd();
+        debug!("[{name}] {:#?}", cmd);
+        let output = run(&mut cmd, phase, label, cancel, events).await?;
+        debug!("[{name}] Exited with {:#?}", output.status);
+        if output.status.success()
+        {
+            return Ok(output);
         }
-        Ok(mut v) =>
+
+        let kind = classify_failure(&output.errors, &output.stderr);
+        error!("[{name}] exited with {} ({:?})", output.status, kind);
+        if kind != ButlerFailureKind::Network
+            || attempt >= BUTLER_MAX_ATTEMPTS
+            || cancel.is_cancelled()
         {
-            let w = v.wait()?;
-            debug!("Exited with {:#?}", w);
-            if let Some(c) = w.code()
+            return Ok(output);
+        }
+        let delay = BUTLER_RETRY_DELAY_SECS * attempt as u64;
+        warn!(
+            "[{name}] Network error, trying again in {delay}s (attempt {} of {})",
+            attempt + 1,
+            BUTLER_MAX_ATTEMPTS
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_secs(delay)) => {},
+            _ = cancel.cancelled() => return Ok(output)
+        }
+        attempt += 1;
+    }
+}
+
+/// Run `cmd` and wait for it to exit. The messages that butler writes to
+/// stdout are parsed with `handle_message`, and stderr is captured (and
+/// logged) so it can be used by `classify_failure`.
+///
+/// butler is killed when `cancel` is cancelled (or when the returned future
+/// is dropped) before it exits. When it's cancelled, this waits for butler to
+/// exit, then returns an `Interrupted` error.
+async fn run(
+    cmd: &mut Command,
+    phase: Phase,
+    label: &str,
+    cancel: &CancellationToken,
+    events: &dyn EventSink
+) -> Result<ButlerOutput, std::io::Error>
+{
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = cmd.spawn()?;
+
+    let pb = Progress::start(
+        events,
+        phase,
+        label.to_string(),
+        Some(BUTLER_PROGRESS_LENGTH)
+    );
+    let mut log = String::new();
+    let mut errors: Vec<String> = Vec::new();
+    // stderr is read at the same time as stdout, so butler doesn't get stuck
+    // when the pipe is full.
+    let stderr = child.stderr.take();
+    let read_stderr = async {
+        let mut content: Vec<u8> = Vec::new();
+        if let Some(mut pipe) = stderr
+            && let Err(e) = pipe.read_to_end(&mut content).await
+        {
+            debug!("[butler::run] Failed to read stderr {:#?}", e);
+        }
+        String::from_utf8_lossy(&content).to_string()
+    };
+    let stdout = child.stdout.take();
+    let read_stdout = async {
+        let Some(pipe) = stdout
+        else
+        {
+            return;
+        };
+        let mut lines = BufReader::new(pipe).lines();
+        loop
+        {
+            match lines.next_line().await
             {
-                if c != 0
+                Ok(Some(l)) => handle_message(&l, &pb, &mut log, &mut errors),
+                Ok(None) => break,
+                Err(e) =>
                 {
-                    error!("[butler::patch] exited with code {c}, which isn't good!");
-                    panic!("[butler::patch] exited with code {c}");
+                    debug!("[butler::run] Failed to read stdout {:#?}", e);
+                    break;
                 }
             }
-            Ok(w)
         }
+    };
+    let stderr = tokio::select! {
+        (stderr, _) = async { tokio::join!(read_stderr, read_stdout) } => stderr,
+        _ = cancel.cancelled() =>
+        {
+            debug!("[butler::run] Cancelled, killing butler");
+            child.kill().await?;
+            pb.fail();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "butler was killed since it was cancelled"
+            ));
+        }
+    };
+    let status = child.wait().await?;
+    match status.success()
+    {
+        true => pb.finish(),
+        false => pb.fail()
+    }
+
+    if !stderr.trim().is_empty()
+    {
+        debug!("[butler::run] stderr\n{}", stderr.trim_end());
+    }
+    log.push_str(&stderr);
+    Ok(ButlerOutput {
+        status,
+        log,
+        errors,
+        stderr
+    })
+}
+
+/// Parse a line that butler wrote to stdout, then update `pb` or write it
+/// to our log. Log and error messages are added to `log`, and error messages
+/// are also added to `errors`.
+fn handle_message(
+    line: &str,
+    pb: &Progress,
+    log: &mut String,
+    errors: &mut Vec<String>
+)
+{
+    if line.trim().is_empty()
+    {
+        return;
+    }
+    let message = match serde_json::from_str::<ButlerMessage>(line)
+    {
+        Ok(v) => v,
+        Err(_) =>
+        {
+            pb.suspend(|| debug!("[butler] {line}"));
+            log.push_str(line);
+            log.push('\n');
+            return;
+        }
+    };
+    match message
+    {
+        ButlerMessage::Log {
+            level,
+            message
+        } =>
+        {
+            pb.suspend(|| match level.as_str()
+            {
+                "error" => error!("[butler] {message}"),
+                "warning" | "warn" => warn!("[butler] {message}"),
+                "info" => info!("[butler] {message}"),
+                _ => debug!("[butler] {message}")
+            });
+            log.push_str(&message);
+            log.push('\n');
+        }
+        ButlerMessage::Progress {
+            progress, ..
+        } =>
+        {
+            let position = (progress.clamp(0.0, 1.0) * BUTLER_PROGRESS_LENGTH as f64) as u64;
+            pb.set_position(position);
+        }
+        ButlerMessage::Result {
+            value
+        } => pb.suspend(|| debug!("[butler] result {value}")),
+        ButlerMessage::Error {
+            message
+        } =>
+        {
+            pb.suspend(|| error!("[butler] {message}"));
+            log.push_str(&message);
+            log.push('\n');
+            errors.push(message);
+        }
+        ButlerMessage::Other => trace!("[butler] {line}")
+    }
+}
+
+/// Phrases (matched on whole words, see `normalize_words`) in butler's
+/// error messages, and what they mean.
+const BUTLER_FAILURE_PHRASES: &[(&str, ButlerFailureKind)] = &[
+    ("no space left on device", ButlerFailureKind::DiskFull),
+    ("not enough space on the disk", ButlerFailureKind::DiskFull),
+    ("dial tcp", ButlerFailureKind::Network),
+    ("no such host", ButlerFailureKind::Network),
+    ("connection refused", ButlerFailureKind::Network),
+    ("connection reset by peer", ButlerFailureKind::Network),
+    ("i o timeout", ButlerFailureKind::Network),
+    ("tls handshake timeout", ButlerFailureKind::Network),
+    (
+        "timeout awaiting response headers",
+        ButlerFailureKind::Network
+    ),
+    ("network is unreachable", ButlerFailureKind::Network),
+    ("hash mismatch", ButlerFailureKind::SignatureMismatch),
+    ("size mismatch", ButlerFailureKind::SignatureMismatch),
+    ("signature mismatch", ButlerFailureKind::SignatureMismatch),
+    ("wounds", ButlerFailureKind::SignatureMismatch),
+    ("invalid magic", ButlerFailureKind::CorruptPatch),
+    ("wrong magic", ButlerFailureKind::CorruptPatch),
+    ("magic number mismatch", ButlerFailureKind::CorruptPatch),
+    ("unexpected eof", ButlerFailureKind::CorruptPatch),
+    ("corrupted block", ButlerFailureKind::CorruptPatch),
+    ("corrupted input", ButlerFailureKind::CorruptPatch),
+    ("crc check failed", ButlerFailureKind::CorruptPatch)
+];
+
+/// Find what caused butler to fail. The error messages that it wrote to
+/// stdout (`ButlerOutput.errors`) are used, and `stderr` is only used when
+/// there aren't any (like when butler crashed before it could write one).
+pub fn classify_failure(
+    errors: &[String],
+    stderr: &str
+) -> ButlerFailureKind
+{
+    let messages: Vec<String> = match errors.is_empty()
+    {
+        true => stderr.lines().map(normalize_words).collect(),
+        false => errors.iter().map(|m| normalize_words(m)).collect()
+    };
+    let has = |kind: ButlerFailureKind| {
+        BUTLER_FAILURE_PHRASES
+            .iter()
+            .filter(|(_, k)| *k == kind)
+            .any(|(phrase, _)| {
+                let phrase = format!(" {phrase} ");
+                messages.iter().any(|m| m.contains(&phrase))
+            })
+    };
+    // e.g; "HTTP 503"
+    let http_server_error = messages.iter().any(|m| {
+        let words: Vec<&str> = m.split_whitespace().collect();
+        words
+            .windows(2)
+            .any(|w| w[0] == "http" && w[1].len() == 3 && w[1].starts_with('5'))
+    });
+
+    if has(ButlerFailureKind::DiskFull)
+    {
+        ButlerFailureKind::DiskFull
+    }
+    else if has(ButlerFailureKind::Network) || http_server_error
+    {
+        ButlerFailureKind::Network
+    }
+    else if has(ButlerFailureKind::SignatureMismatch)
+    {
+        ButlerFailureKind::SignatureMismatch
+    }
+    else if has(ButlerFailureKind::CorruptPatch)
+    {
+        ButlerFailureKind::CorruptPatch
+    }
+    else
+    {
+        ButlerFailureKind::Other
+    }
+}
+
+/// Lowercase `message`, and replace everything that isn't a letter or a
+/// number with a single space. There is a space at the start and the end, so
+/// phrases can be matched on whole words with `contains(" {phrase} ")`.
+fn normalize_words(message: &str) -> String
+{
+    let words: Vec<String> = message
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    format!(" {} ", words.join(" "))
+}
+
+/// Last few lines of what butler wrote to stderr, and the log and error
+/// messages that it wrote to stdout.
+fn stderr_tail(stderr: &str) -> String
+{
+    let lines: Vec<&str> = stderr
+        .split(['\n', '\r'])
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect();
+    let start = lines.len().saturating_sub(BUTLER_STDERR_TAIL_LINES);
+    lines[start..].join("\n")
+}
+
+/// Create the error for a butler `command` that didn't exit successfully.
+fn failure_error(
+    command: &str,
+    patchfile_location: Option<String>,
+    gamedir: String,
+    output: &ButlerOutput
+) -> BeansError
+{
+    let stderr = stderr_tail(&output.log);
+    let error = match (
+        classify_failure(&output.errors, &output.stderr),
+        patchfile_location
+    )
+    {
+        (ButlerFailureKind::Network, _) => BeansError::ButlerNetworkFailure {
+            gamedir,
+            exit_code: output.status.code(),
+            stderr
+        },
+        (ButlerFailureKind::DiskFull, _) => BeansError::ButlerDiskFull {
+            gamedir,
+            stderr
+        },
+        (ButlerFailureKind::CorruptPatch, Some(patchfile_location)) =>
+        {
+            BeansError::ButlerPatchCorrupt {
+                patchfile_location,
+                gamedir,
+                stderr
+            }
+        }
+        (ButlerFailureKind::SignatureMismatch, _) => BeansError::ButlerSignatureMismatch {
+            gamedir,
+            stderr
+        },
+        _ => BeansError::ButlerExitFailure {
+            command: command.to_string(),
+            gamedir,
+            exit_code: output.status.code(),
+            stderr
+        }
+    };
+    info!("[butler::failure_error] {:}", error);
+    error
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// Messages from the `"type":"error"` lines in `stdout`, like `run`
+    /// collects them.
+    fn errors_from_stdout(stdout: &str) -> Vec<String>
+    {
+        stdout
+            .lines()
+            .filter_map(|l| match serde_json::from_str::<ButlerMessage>(l)
+            {
+                Ok(ButlerMessage::Error {
+                    message
+                }) => Some(message),
+                _ => None
+            })
+            .collect()
+    }
+
+    #[test]
+    fn classify_json_errors()
+    {
+        let cases: &[(&str, ButlerFailureKind)] = &[
+            (
+                r#"{"type":"error","message":"Get \"https://cdn.example.com/of-18.pws\": dial tcp: lookup cdn.example.com on 127.0.0.53:53: no such host","time":1718000000}"#,
+                ButlerFailureKind::Network
+            ),
+            (
+                r#"{"type":"error","message":"read tcp 192.168.1.20:51234->104.21.3.4:443: read: connection reset by peer","time":1718000000}"#,
+                ButlerFailureKind::Network
+            ),
+            (
+                r#"{"type":"error","message":"htfs: HTTP 503 while getting https://cdn.example.com/of-18.pws","time":1718000000}"#,
+                ButlerFailureKind::Network
+            ),
+            (
+                r#"{"type":"error","message":"write /home/kate/.local/share/Steam/steamapps/sourcemods/open_fortress/maps/ctf_2fort.bsp: no space left on device","time":1718000000}"#,
+                ButlerFailureKind::DiskFull
+            ),
+            (
+                r#"{"type":"error","message":"write C:\\Games\\Steam\\steamapps\\sourcemods\\open_fortress\\maps\\ctf_2fort.bsp: There is not enough space on the disk.","time":1718000000}"#,
+                ButlerFailureKind::DiskFull
+            ),
+            (
+                r#"{"type":"error","message":"3 wounds, 12.50 MiB of damaged data","time":1718000000}"#,
+                ButlerFailureKind::SignatureMismatch
+            ),
+            (
+                r#"{"type":"error","message":"hash mismatch for maps/ctf_2fort.bsp","time":1718000000}"#,
+                ButlerFailureKind::SignatureMismatch
+            ),
+            (
+                r#"{"type":"error","message":"while reading patch: unexpected EOF","time":1718000000}"#,
+                ButlerFailureKind::CorruptPatch
+            ),
+            (
+                r#"{"type":"error","message":"decompressing patch: zstd: corrupted block","time":1718000000}"#,
+                ButlerFailureKind::CorruptPatch
+            ),
+            (
+                r#"{"type":"error","message":"open /tmp/of-17to18.pwr: permission denied","time":1718000000}"#,
+                ButlerFailureKind::Other
+            )
+        ];
+        for (stdout, expected) in cases
+        {
+            let errors = errors_from_stdout(stdout);
+            assert_eq!(errors.len(), 1, "{stdout}");
+            assert_eq!(classify_failure(&errors, ""), *expected, "{stdout}");
+        }
+    }
+
+    #[test]
+    fn classify_ignores_log_and_progress_lines()
+    {
+        // only the error message is used, even though the log lines mention
+        // zstd and a file that contains "wound".
+        let stdout = [
+            r#"{"type":"log","level":"info","message":"Patching with zstd-compressed patch (decompressing as needed)","time":1718000000}"#,
+            r#"{"type":"progress","progress":0.45,"eta":12.5,"bps":1048576,"time":1718000000}"#,
+            r#"{"type":"log","level":"debug","message":"models/player/wounded_merc.mdl: 1 blocks to heal","time":1718000000}"#,
+            r#"{"type":"error","message":"Get \"https://cdn.example.com/archive/of-18.zip\": net/http: TLS handshake timeout","time":1718000000}"#
+        ]
+        .join("\n");
+        let stderr = "decompress: zstd 45%\nhealing models/player/wounded_merc.mdl\n";
+        assert_eq!(
+            classify_failure(&errors_from_stdout(&stdout), stderr),
+            ButlerFailureKind::Network
+        );
+        // same for stderr, when there aren't any error messages.
+        assert_eq!(classify_failure(&[], stderr), ButlerFailureKind::Other);
+    }
+
+    #[test]
+    fn classify_stderr()
+    {
+        let cases: &[(&str, ButlerFailureKind)] = &[
+            (
+                "Patching 12%\nwrite /home/kate/sourcemods/open_fortress/pak01_000.vpk: no space left on device\n",
+                ButlerFailureKind::DiskFull
+            ),
+            (
+                "dial tcp 104.21.3.4:443: i/o timeout\n",
+                ButlerFailureKind::Network
+            ),
+            (
+                "panic: invalid magic for patch file\n\ngoroutine 1 [running]:\n",
+                ButlerFailureKind::CorruptPatch
+            ),
+            ("exit status 1\n", ButlerFailureKind::Other)
+        ];
+        for (stderr, expected) in cases
+        {
+            assert_eq!(classify_failure(&[], stderr), *expected, "{stderr}");
+        }
+    }
+
+    /// `run` with `sh` instead of butler, which writes more than a pipe can
+    /// hold to stderr before exiting.
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn run_parses_stdout_and_captures_stderr()
+    {
+        let script = [
+            r#"echo '{"type":"progress","progress":0.5}'"#,
+            "echo 'not json'",
+            "yes line | head -n 50000 >&2",
+            r#"echo '{"type":"error","message":"no space left on device"}'"#,
+            "echo done >&2",
+            "exit 1"
+        ]
+        .join("; ");
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", &script]);
+        let (events, handle) = crate::events::ChannelSink::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let output = runtime
+            .block_on(run(
+                &mut cmd,
+                Phase::Patch,
+                "Patching files",
+                &CancellationToken::new(),
+                &events
+            ))
+            .unwrap();
+
+        assert!(!output.status.success());
+        assert_eq!(output.errors, vec!["no space left on device"]);
+        assert_eq!(output.stderr.len(), 50000 * 5 + 5);
+        assert!(output.stderr.ends_with("line\ndone\n"));
+        assert!(output
+            .log
+            .starts_with("not json\nno space left on device\n"));
+        drop(events);
+        let progress: Vec<u64> = handle
+            .events
+            .try_iter()
+            .filter_map(|e| match e
+            {
+                crate::events::Event::Progress {
+                    current, ..
+                } => Some(current),
+                _ => None
+            })
+            .collect();
+        assert_eq!(progress, vec![BUTLER_PROGRESS_LENGTH / 2]);
+    }
+
+    /// `run` with a command that never exits, which is cancelled while it's
+    /// running.
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn run_kills_the_command_when_cancelled()
+    {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            r#"echo '{"type":"progress","progress":0.1}'; sleep 30"#
+        ]);
+        let (events, handle) = crate::events::ChannelSink::new();
+        let cancel = CancellationToken::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let started = std::time::Instant::now();
+        let result = runtime.block_on(async {
+            let cancel_later = async {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                cancel.cancel();
+            };
+            let (result, _) = tokio::join!(
+                run(&mut cmd, Phase::Patch, "Patching files", &cancel, &events),
+                cancel_later
+            );
+            result
+        });
+
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::Interrupted);
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        drop(events);
+        // the progress bar failed, instead of being left unfinished.
+        let last = handle.events.try_iter().last();
+        assert!(
+            matches!(
+                last,
+                Some(crate::events::Event::Finished {
+                    success: false,
+                    ..
+                })
+            ),
+            "{last:?}"
+        );
     }
 }
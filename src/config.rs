@@ -0,0 +1,652 @@
+//! Settings for beans. The value of each setting is taken from the first of
+//! these that has it;
+//! 1. Command-line arguments (like `--debug` and `--no-pause`)
+//! 2. Environment variables (see `ConfigKey.env`)
+//! 3. `config.toml` in `helper::get_config_dir()`
+//! 4. The default value
+//!
+//! The result is loaded once with `Config::load`, and kept in
+//! `RunnerOptions.config`.
+//!
+//! `config.toml` can be changed with the `config set` and `config unset`
+//! commands, which only accept the keys in `CONFIG_KEYS`.
+use log::{debug,
+          warn};
+
+use crate::{helper,
+            BeansError};
+
+/// Name of the config file in `helper::get_config_dir()`
+pub const CONFIG_FILENAME: &str = "config.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigValueType
+{
+    Bool,
+    String
+}
+
+/// Setting that can be used with `config get` and `config set`.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct ConfigKey
+{
+    pub name: &'static str,
+    pub value_type: ConfigValueType,
+    /// Environment variables that are used instead of the config file, in
+    /// order of precedence. Booleans are `true` when any of them are.
+    pub env: &'static [&'static str],
+    pub description: &'static str
+}
+
+/// Every setting in `ConfigValues`.
+///
+/// `aria2c_args_override` replaces the following in it's value;
+///
+/// | Look For | Replace With |
+/// | -------- | ------------ |
+/// | `%OUT_DIR%` | Output directory (argument `-d` with aria2c) |
+/// | `%OUT_FILENAME%` | Output Filename (argument `--out=` with aria2c) |
+/// | `%USER_AGENT%` | Used for the `--user-agent=` aria2c argument|
+/// | `%URL%` | URL to download from
+pub const CONFIG_KEYS: &[ConfigKey] = &[
+    ConfigKey {
+        name: "debug",
+        value_type: ConfigValueType::Bool,
+        env: &["BEANS_DEBUG", "ADASTRAL_DEBUG"],
+        description: "Enable debug logging (same as --debug)"
+    },
+    ConfigKey {
+        name: "pause_once_done",
+        value_type: ConfigValueType::Bool,
+        env: &[],
+        description: "Wait for enter/return to be pressed before exiting (disabled with --no-pause)"
+    },
+    ConfigKey {
+        name: "headless",
+        value_type: ConfigValueType::Bool,
+        env: &["BEANS_HEADLESS", "ADASTRAL_HEADLESS"],
+        description: "Show dialogs even when a display couldn't be detected"
+    },
+    ConfigKey {
+        name: "sourcemods_location",
+        value_type: ConfigValueType::String,
+        env: &[],
+        description: "sourcemods directory to use when --location isn't provided, instead of detecting it from Steam"
+    },
+    ConfigKey {
+        name: "tmp_dir",
+        value_type: ConfigValueType::String,
+        env: &["ADASTRAL_TMPDIR"],
+        description: "Directory to use for downloads and temporary files, instead of the cache directory"
+    },
+    ConfigKey {
+        name: "butler_path",
+        value_type: ConfigValueType::String,
+        env: &["BEANS_BUTLER_PATH", "ADASTRAL_BUTLER_PATH"],
+        description: "Location of butler to use, instead of the one that comes with beans"
+    },
+    ConfigKey {
+        name: "disable_aria2c",
+        value_type: ConfigValueType::Bool,
+        env: &["BEANS_DISABLE_ARIA2C", "ADASTRAL_DISABLE_ARIA2C"],
+        description: "Don't use aria2c to download packages"
+    },
+    ConfigKey {
+        name: "aria2c_args",
+        value_type: ConfigValueType::String,
+        env: &["BEANS_ARIA2C_ARGS", "ADASTRAL_ARIA2C_ARGS"],
+        description: "Extra arguments for aria2c"
+    },
+    ConfigKey {
+        name: "aria2c_args_override",
+        value_type: ConfigValueType::String,
+        env: &["BEANS_ARIA2C_ARGS_OVERRIDE", "ADASTRAL_ARIA2C_ARGS_OVERRIDE"],
+        description: "Arguments for aria2c, used instead of the default arguments. %OUT_DIR%, %OUT_FILENAME%, %USER_AGENT% and %URL% are replaced"
+    }
+];
+
+/// Find the key called `name` in `CONFIG_KEYS`.
+pub fn find_key(name: &str) -> Result<&'static ConfigKey, BeansError>
+{
+    CONFIG_KEYS
+        .iter()
+        .find(|k| k.name == name)
+        .ok_or_else(|| BeansError::ConfigKeyInvalid {
+            key: name.to_string(),
+            reason: String::from("it doesn't exist")
+        })
+}
+
+/// Parse `value` as the type of `key`. Booleans can be `true`/`false`,
+/// `1`/`0`, `yes`/`no` or `on`/`off`.
+pub fn parse_value(
+    key: &ConfigKey,
+    value: &str
+) -> Result<toml::Value, BeansError>
+{
+    match key.value_type
+    {
+        ConfigValueType::String => Ok(toml::Value::String(value.to_string())),
+        ConfigValueType::Bool => match value.trim().to_lowercase().as_str()
+        {
+            "1" | "true" | "yes" | "on" => Ok(toml::Value::Boolean(true)),
+            "0" | "false" | "no" | "off" => Ok(toml::Value::Boolean(false)),
+            _ => Err(BeansError::ConfigKeyInvalid {
+                key: key.name.to_string(),
+                reason: format!("{value:?} isn't true or false")
+            })
+        }
+    }
+}
+
+/// Format a value from `ConfigValues::get` for printing.
+pub fn format_value(value: &toml::Value) -> String
+{
+    match value
+    {
+        toml::Value::String(s) => s.clone(),
+        v => v.to_string()
+    }
+}
+
+/// Value of `key` from it's environment variables (read with `get_var`), and
+/// the variable it came from. See `ConfigValues::from_vars`.
+fn find_env_value<F>(
+    key: &ConfigKey,
+    get_var: F
+) -> Option<(toml::Value, &'static str)>
+where
+    F: Fn(&str) -> Option<String>
+{
+    let mut result: Option<(toml::Value, &'static str)> = None;
+    for var in key.env.iter()
+    {
+        let value = match get_var(var)
+        {
+            Some(v) if !v.trim().is_empty() => v,
+            _ => continue
+        };
+        match parse_value(key, &value)
+        {
+            Ok(v) =>
+            {
+                let is_true = v == toml::Value::Boolean(true);
+                if result.is_none() || is_true
+                {
+                    result = Some((v, var));
+                }
+                if key.value_type == ConfigValueType::String || is_true
+                {
+                    break;
+                }
+            }
+            Err(e) => debug!("[config::find_env_value] Ignoring {var} ({e:})")
+        }
+    }
+    result
+}
+
+/// One layer of settings. `None` means that the layer doesn't have a value,
+/// so the value from the next layer is used.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigValues
+{
+    pub debug: Option<bool>,
+    pub pause_once_done: Option<bool>,
+    pub headless: Option<bool>,
+    pub sourcemods_location: Option<String>,
+    pub tmp_dir: Option<String>,
+    pub butler_path: Option<String>,
+    pub disable_aria2c: Option<bool>,
+    pub aria2c_args: Option<String>,
+    pub aria2c_args_override: Option<String>
+}
+
+impl ConfigValues
+{
+    /// Values that are used when no other layer has them.
+    pub fn defaults() -> Self
+    {
+        Self {
+            debug: Some(false),
+            pause_once_done: Some(true),
+            headless: Some(false),
+            disable_aria2c: Some(false),
+            ..Default::default()
+        }
+    }
+
+    /// Read the values from the environment variables in `CONFIG_KEYS`. Empty
+    /// variables, and booleans that can't be parsed, are ignored.
+    pub fn from_env() -> Self
+    {
+        Self::from_vars(|var| helper::try_get_env_var(var.to_string()))
+    }
+
+    /// Same as `from_env`, but variables are read with `get_var`.
+    ///
+    /// For strings the first variable in `ConfigKey.env` that is set is used.
+    /// Booleans are `true` when any of their variables are, so
+    /// `BEANS_DEBUG=0 ADASTRAL_DEBUG=1` still enables debug logging.
+    pub fn from_vars<F>(get_var: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>
+    {
+        let mut table = toml::Table::new();
+        for key in CONFIG_KEYS.iter()
+        {
+            if let Some((v, _)) = find_env_value(key, &get_var)
+            {
+                table.insert(key.name.to_string(), v);
+            }
+        }
+        Self::from_table(table).unwrap_or_default()
+    }
+
+    /// Read the config file at `location`. When it doesn't exist, every value
+    /// is `None`.
+    pub fn read(location: String) -> Result<Self, BeansError>
+    {
+        let content = match std::fs::read_to_string(&location)
+        {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) =>
+            {
+                return Err(BeansError::FileOpenFailure {
+                    location,
+                    error
+                });
+            }
+        };
+        toml::from_str(&content).map_err(|e| BeansError::ConfigInvalid {
+            location,
+            reason: e.message().to_string()
+        })
+    }
+
+    /// Write to the config file at `location`. Values that are `None` aren't
+    /// written.
+    pub fn write(
+        &self,
+        location: String
+    ) -> Result<(), BeansError>
+    {
+        let content = toml::to_string_pretty(self).map_err(|e| BeansError::ConfigInvalid {
+            location: location.clone(),
+            reason: format!("{e:}")
+        })?;
+        if let Err(error) = std::fs::write(&location, content)
+        {
+            return Err(BeansError::FileWriteFailure {
+                location,
+                error
+            });
+        }
+        Ok(())
+    }
+
+    fn to_table(&self) -> toml::Table
+    {
+        match toml::Table::try_from(self)
+        {
+            Ok(v) => v,
+            Err(e) =>
+            {
+                warn!("[ConfigValues::to_table] {e:}");
+                toml::Table::new()
+            }
+        }
+    }
+
+    fn from_table(table: toml::Table) -> Result<Self, String>
+    {
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.message().to_string())
+    }
+
+    /// Values from `self`, or from `fallback` when they're `None` in `self`.
+    pub fn or(
+        &self,
+        fallback: &ConfigValues
+    ) -> ConfigValues
+    {
+        let mut table = fallback.to_table();
+        table.extend(self.to_table());
+        Self::from_table(table).unwrap_or_else(|_| self.clone())
+    }
+
+    /// Value of the key called `name`.
+    pub fn get(
+        &self,
+        name: &str
+    ) -> Option<toml::Value>
+    {
+        self.to_table().remove(name)
+    }
+
+    /// Set the value of the key called `name`. When `value` is `None`, the
+    /// value is removed.
+    pub fn set(
+        &mut self,
+        name: &str,
+        value: Option<toml::Value>
+    ) -> Result<(), BeansError>
+    {
+        let key = find_key(name)?;
+        let mut table = self.to_table();
+        match value
+        {
+            Some(v) => table.insert(key.name.to_string(), v),
+            None => table.remove(key.name)
+        };
+        *self = Self::from_table(table).map_err(|reason| BeansError::ConfigKeyInvalid {
+            key: key.name.to_string(),
+            reason
+        })?;
+        Ok(())
+    }
+}
+
+/// Where the value of a setting came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource
+{
+    Argument,
+    Environment(&'static str),
+    File,
+    Default
+}
+
+impl std::fmt::Display for ConfigSource
+{
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>
+    ) -> std::fmt::Result
+    {
+        match self
+        {
+            ConfigSource::Argument => write!(f, "argument"),
+            ConfigSource::Environment(var) => write!(f, "environment variable {var}"),
+            ConfigSource::File => write!(f, "config file"),
+            ConfigSource::Default => write!(f, "default")
+        }
+    }
+}
+
+/// Settings with every layer applied. See the module documentation for the
+/// order that the layers are used in.
+#[derive(Debug, Clone)]
+pub struct Config
+{
+    pub debug: bool,
+    pub pause_once_done: bool,
+    pub headless: bool,
+    pub sourcemods_location: Option<String>,
+    pub tmp_dir: Option<String>,
+    pub butler_path: Option<String>,
+    pub disable_aria2c: bool,
+    pub aria2c_args: Option<String>,
+    pub aria2c_args_override: Option<String>,
+
+    pub arguments: ConfigValues,
+    pub env: ConfigValues,
+    pub file: ConfigValues
+}
+
+impl Config
+{
+    /// Apply every layer.
+    pub fn new(
+        arguments: ConfigValues,
+        env: ConfigValues,
+        file: ConfigValues
+    ) -> Self
+    {
+        let v = arguments.or(&env).or(&file).or(&ConfigValues::defaults());
+        Self {
+            debug: v.debug.unwrap_or_default(),
+            pause_once_done: v.pause_once_done.unwrap_or(true),
+            headless: v.headless.unwrap_or_default(),
+            sourcemods_location: v.sourcemods_location,
+            tmp_dir: v.tmp_dir,
+            butler_path: v.butler_path,
+            disable_aria2c: v.disable_aria2c.unwrap_or_default(),
+            aria2c_args: v.aria2c_args,
+            aria2c_args_override: v.aria2c_args_override,
+            arguments,
+            env,
+            file
+        }
+    }
+
+    /// Location of the config file.
+    pub fn location() -> String
+    {
+        helper::join_path(helper::get_config_dir(), CONFIG_FILENAME.to_string())
+    }
+
+    /// Value of the key called `name`, and where it came from. `None` when
+    /// it's not set in any layer.
+    pub fn get_value(
+        &self,
+        name: &str
+    ) -> Option<(toml::Value, ConfigSource)>
+    {
+        if let Some(v) = self.arguments.get(name)
+        {
+            return Some((v, ConfigSource::Argument));
+        }
+        if let Some(v) = self.env.get(name)
+        {
+            let var = CONFIG_KEYS
+                .iter()
+                .find(|k| k.name == name)
+                .and_then(|k| find_env_value(k, |var| helper::try_get_env_var(var.to_string())))
+                .map(|(_, var)| var)
+                .unwrap_or("");
+            return Some((v, ConfigSource::Environment(var)));
+        }
+        if let Some(v) = self.file.get(name)
+        {
+            return Some((v, ConfigSource::File));
+        }
+        ConfigValues::defaults()
+            .get(name)
+            .map(|v| (v, ConfigSource::Default))
+    }
+
+    /// Read the environment variables and the config file, and use
+    /// `arguments` (from the command-line) instead of them.
+    ///
+    /// When the config file can't be read, a warning is logged and it's
+    /// ignored.
+    pub fn load(arguments: ConfigValues) -> Self
+    {
+        let file = ConfigValues::read(Self::location()).unwrap_or_else(|e| {
+            warn!("[Config::load] {e:}");
+            ConfigValues::default()
+        });
+        Self::new(arguments, ConfigValues::from_env(), file)
+    }
+
+    /// Set the value of `name` in the config file, and return the config with
+    /// the new file. When `value` is `None`, it's removed from the config
+    /// file.
+    pub fn save_value(
+        &self,
+        name: &str,
+        value: Option<toml::Value>
+    ) -> Result<Self, BeansError>
+    {
+        let location = Self::location();
+        let mut file = ConfigValues::read(location.clone())?;
+        file.set(name, value)?;
+        file.write(location.clone())?;
+        debug!("[Config::save_value] Saved {name} to {location}");
+        Ok(Self::new(
+            self.arguments.clone(),
+            ConfigValues::from_env(),
+            file
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// `ConfigValues::from_vars` with the variables in `vars`.
+    fn from_vars(vars: &[(&str, &str)]) -> ConfigValues
+    {
+        ConfigValues::from_vars(|var| {
+            vars.iter()
+                .find(|(k, _)| *k == var)
+                .map(|(_, v)| v.to_string())
+        })
+    }
+
+    #[test]
+    fn new_precedence()
+    {
+        let arguments = ConfigValues {
+            debug: Some(true),
+            ..Default::default()
+        };
+        let env = ConfigValues {
+            debug: Some(false),
+            tmp_dir: Some(String::from("/env")),
+            ..Default::default()
+        };
+        let file = ConfigValues {
+            tmp_dir: Some(String::from("/file")),
+            butler_path: Some(String::from("/file/butler")),
+            headless: Some(true),
+            ..Default::default()
+        };
+        let config = Config::new(arguments, env, file);
+        assert!(config.debug);
+        assert_eq!(config.tmp_dir.as_deref(), Some("/env"));
+        assert_eq!(config.butler_path.as_deref(), Some("/file/butler"));
+        assert!(config.headless);
+        assert!(config.pause_once_done);
+        assert!(!config.disable_aria2c);
+        assert_eq!(config.sourcemods_location, None);
+
+        assert_eq!(
+            config.get_value("debug"),
+            Some((toml::Value::Boolean(true), ConfigSource::Argument))
+        );
+        assert_eq!(
+            config.get_value("butler_path"),
+            Some((
+                toml::Value::String(String::from("/file/butler")),
+                ConfigSource::File
+            ))
+        );
+        assert_eq!(
+            config.get_value("pause_once_done"),
+            Some((toml::Value::Boolean(true), ConfigSource::Default))
+        );
+        assert_eq!(config.get_value("aria2c_args"), None);
+    }
+
+    #[test]
+    fn from_vars_parsing()
+    {
+        let values = from_vars(&[
+            ("BEANS_DEBUG", "yes"),
+            ("BEANS_HEADLESS", "maybe"),
+            ("ADASTRAL_HEADLESS", "off"),
+            ("BEANS_DISABLE_ARIA2C", "  "),
+            ("BEANS_ARIA2C_ARGS", ""),
+            ("ADASTRAL_ARIA2C_ARGS", "--foo"),
+            ("ADASTRAL_TMPDIR", "/tmp/beans")
+        ]);
+        assert_eq!(values, ConfigValues {
+            debug: Some(true),
+            headless: Some(false),
+            aria2c_args: Some(String::from("--foo")),
+            tmp_dir: Some(String::from("/tmp/beans")),
+            ..Default::default()
+        });
+        assert_eq!(from_vars(&[]), ConfigValues::default());
+    }
+
+    #[test]
+    fn from_vars_precedence()
+    {
+        // strings use the first variable that is set
+        let values = from_vars(&[
+            ("BEANS_BUTLER_PATH", "/beans"),
+            ("ADASTRAL_BUTLER_PATH", "/adastral")
+        ]);
+        assert_eq!(values.butler_path.as_deref(), Some("/beans"));
+
+        // booleans are true when any of them are
+        for (beans, adastral, expected) in [
+            ("0", "1", true),
+            ("1", "0", true),
+            ("0", "false", false),
+            ("", "true", true),
+            ("invalid", "0", false)
+        ]
+        {
+            let values = from_vars(&[("BEANS_DEBUG", beans), ("ADASTRAL_DEBUG", adastral)]);
+            assert_eq!(
+                values.debug,
+                Some(expected),
+                "BEANS_DEBUG={beans:?} ADASTRAL_DEBUG={adastral:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn read_write_round_trip()
+    {
+        let location = std::env::temp_dir()
+            .join(format!("beans-config-{}.toml", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let _ = std::fs::remove_file(&location);
+        assert_eq!(
+            ConfigValues::read(location.clone()).unwrap(),
+            ConfigValues::default()
+        );
+
+        let mut values = ConfigValues::default();
+        values
+            .set("debug", Some(toml::Value::Boolean(true)))
+            .unwrap();
+        values
+            .set(
+                "sourcemods_location",
+                Some(toml::Value::String(String::from("/home/user/sourcemods")))
+            )
+            .unwrap();
+        assert!(values.set("nope", None).is_err());
+        values.write(location.clone()).unwrap();
+        assert_eq!(ConfigValues::read(location.clone()).unwrap(), values);
+
+        values.set("debug", None).unwrap();
+        values.write(location.clone()).unwrap();
+        let read = ConfigValues::read(location.clone()).unwrap();
+        assert_eq!(read.debug, None);
+        assert_eq!(
+            read.sourcemods_location.as_deref(),
+            Some("/home/user/sourcemods")
+        );
+
+        std::fs::write(&location, "unknown_key = 1\n").unwrap();
+        assert!(matches!(
+            ConfigValues::read(location.clone()),
+            Err(BeansError::ConfigInvalid { .. })
+        ));
+        std::fs::remove_file(&location).unwrap();
+    }
+}
@@ -1,13 +1,23 @@
-use std::backtrace::Backtrace;
 #[cfg(target_os = "linux")]
 use std::os::unix::fs::PermissionsExt;
+use std::{backtrace::Backtrace,
+          sync::Arc};
 
 use log::{debug,
           error,
           info};
+use tokio_util::sync::CancellationToken;
 
 use crate::{appvar::AppVarData,
+            config::{Config,
+                     ConfigValues},
             depends,
+            events::{EventSink,
+                     Phase,
+                     Progress,
+                     TerminalSink},
+            extract::PackageFormat,
+            flags::LaunchFlag,
             helper,
             helper::{find_sourcemod_path,
                      parse_location,
@@ -24,18 +34,76 @@ pub struct RunnerContext
     pub sourcemod_path: String,
     pub remote_version_list: RemoteVersionResponse,
     pub current_version: Option<usize>,
-    pub appvar: crate::appvar::AppVarData
+    /// Set when the version file couldn't be read. `current_version` is `None`
+    /// when this is `true`, see `RunnerContext::repair_version_file`.
+    pub version_file_corrupt: bool,
+    /// Mod that is being worked on.
+    pub appvar: crate::appvar::AppVarData,
+    pub options: RunnerOptions
+}
+
+/// Options for how workflows behave with a `RunnerContext`.
+#[derive(Debug, Clone)]
+pub struct RunnerOptions
+{
+    /// When `true`, everything that prompts the user for Y/N uses the default
+    /// option (`--confirm`).
+    pub confirm: bool,
+    /// Once everything is done, wait for the user to press enter before
+    /// exiting. Just like the `pause` thing in batch.
+    pub pause_once_done: bool,
+    pub flags: LaunchFlag,
+    /// Where progress and messages from workflows go, and what answers their
+    /// questions. Displayed in the terminal by default.
+    pub events: Arc<dyn EventSink>,
+    /// Settings from the arguments, environment variables and config file.
+    pub config: Config,
+    /// Once cancelled, workflows stop at the next step with
+    /// `BeansError::Cancelled`, and clean up like they do for any other error.
+    pub cancel: CancellationToken
+}
+
+impl Default for RunnerOptions
+{
+    fn default() -> Self
+    {
+        Self {
+            confirm: false,
+            pause_once_done: false,
+            flags: LaunchFlag::default(),
+            events: Arc::new(TerminalSink::default()),
+            config: Config::load(ConfigValues::default()),
+            cancel: CancellationToken::new()
+        }
+    }
 }
 
 impl RunnerContext
 {
+    /// Create a context for the mod that was compiled in (see
+    /// `AppVarData::get`), with the default `RunnerOptions`.
     pub async fn create_auto(sml_via: SourceModDirectoryParam) -> Result<Self, BeansError>
     {
-        depends::try_write_deps();
-        if let Err(e) = depends::try_install_vcredist().await
+        Self::create(sml_via, AppVarData::get(), RunnerOptions::default()).await
+    }
+
+    /// Create a context for the mod in `appvar`. The sourcemods directory is
+    /// found with `sml_via`, and the version list is fetched from
+    /// `appvar.remote_info.versions_url`.
+    pub async fn create(
+        sml_via: SourceModDirectoryParam,
+        appvar: AppVarData,
+        options: RunnerOptions
+    ) -> Result<Self, BeansError>
+    {
+        depends::try_write_deps(&options.config);
+        if let Err(e) =
+            depends::try_install_vcredist(&options.config, options.events.as_ref()).await
         {
             sentry::capture_error(&e);
-            println!("Failed to install vcredist! {:}", e);
+            options
+                .events
+                .warning(format!("Failed to install vcredist! {:}", e));
             debug!(
                 "[RunnerContext::create_auto] Failed to install vcredist! {:#?}",
                 e
@@ -65,35 +133,154 @@ impl RunnerContext
                 l
             }
         });
-        let version_list = version::get_version_list().await?;
+        let version_list = version::get_version_list(&appvar).await?;
+
+        let mut ctx = Self {
+            sourcemod_path: parse_location(sourcemod_path.clone()),
+            remote_version_list: version_list,
+            current_version: None,
+            version_file_corrupt: false,
+            appvar,
+            options
+        };
+        ctx.load_current_version().await?;
+        Ok(ctx)
+    }
 
-        if helper::install_state(Some(sourcemod_path.clone())) == InstallType::OtherSource
+    /// Set `current_version` from the version file in the mod directory, and
+    /// migrate `.revision` to `.adastral` when required.
+    ///
+    /// When the version file is corrupt, `version_file_corrupt` is set and
+    /// `current_version` is left as `None`.
+    pub async fn load_current_version(&mut self) -> Result<(), BeansError>
+    {
+        match self.read_current_version()
         {
-            version::update_version_file(Some(sourcemod_path.clone()))?;
+            Ok(v) =>
+            {
+                self.current_version = v;
+                self.version_file_corrupt = false;
+                Ok(())
+            }
+            Err(
+                e @ BeansError::VersionFileCorrupt {
+                    ..
+                }
+            ) =>
+            {
+                error!("[RunnerContext::load_current_version] {e:}");
+                self.events().warning(format!("{e:}"));
+                self.current_version = None;
+                self.version_file_corrupt = true;
+                Ok(())
+            }
+            Err(e) => Err(e)
         }
+    }
 
-        Ok(Self {
-            sourcemod_path: parse_location(sourcemod_path.clone()),
-            remote_version_list: version_list,
-            current_version: crate::version::get_current_version(Some(sourcemod_path.clone())),
-            appvar: AppVarData::get()
-        })
+    fn read_current_version(&self) -> Result<Option<usize>, BeansError>
+    {
+        let sourcemod_path = self.sourcemod_path.clone();
+        match helper::install_state(Some(sourcemod_path.clone()), &self.appvar)
+        {
+            InstallType::OtherSource =>
+            {
+                version::update_version_file(Some(sourcemod_path.clone()), &self.appvar)?;
+            }
+            InstallType::OtherSourceManual =>
+            {
+                info!("[RunnerContext::read_current_version] Found an installation that wasn't installed by beans. Use the adopt command so it can be updated and verified.");
+            }
+            _ =>
+            {}
+        }
+        version::get_current_version(Some(sourcemod_path), &self.appvar)
     }
+
+    /// When `version_file_corrupt` is set, ask the user if they want to
+    /// repair the version file (see `AdoptWorkflow::repair`).
+    ///
+    /// Returns `false` when the version file is still corrupt, since the user
+    /// didn't want it to be repaired (or didn't accept the version that was
+    /// found).
+    pub async fn repair_version_file(&mut self) -> Result<bool, BeansError>
+    {
+        if !self.version_file_corrupt
+        {
+            return Ok(true);
+        }
+        if !self.prompt_confirm("Do you want to repair the version file?")
+        {
+            return Ok(false);
+        }
+        crate::workflows::AdoptWorkflow::repair(self).await?;
+        Ok(!self.version_file_corrupt)
+    }
+
+    /// Same as `RunnerContext::repair_version_file`, but the error from
+    /// reading the version file is returned when it wasn't repaired.
+    pub async fn require_version_file(&mut self) -> Result<(), BeansError>
+    {
+        if self.repair_version_file().await?
+        {
+            return Ok(());
+        }
+        self.read_current_version().map(|_| ())
+    }
+
     /// Sets `remote_version_list` from `version::get_version_list()`
     pub async fn set_remote_version_list(&mut self) -> Result<(), BeansError>
     {
-        self.remote_version_list = version::get_version_list().await?;
+        self.remote_version_list = version::get_version_list(&self.appvar).await?;
         Ok(())
     }
 
+    /// Ask the user a yes/no question with `EventSink::confirm`.
+    ///
+    /// Will always return `true` when `self.options.confirm` is `true`.
+    pub fn prompt_confirm(
+        &self,
+        question: &str
+    ) -> bool
+    {
+        if self.options.confirm
+        {
+            info!("[RunnerContext::prompt_confirm] skipping since options.confirm is true");
+            return true;
+        }
+        self.events().confirm(question)
+    }
+
+    /// Shorthand for `self.options.events`
+    pub fn events(&self) -> &dyn EventSink
+    {
+        self.options.events.as_ref()
+    }
+
+    /// Shorthand for `self.options.config`
+    pub fn config(&self) -> &Config
+    {
+        &self.options.config
+    }
+
+    /// `Err(BeansError::Cancelled)` once `self.options.cancel` is cancelled.
+    pub fn check_cancelled(&self) -> Result<(), BeansError>
+    {
+        match self.options.cancel.is_cancelled()
+        {
+            true => Err(BeansError::Cancelled),
+            false => Ok(())
+        }
+    }
+
     /// Get the location of the sourcemod mod
-    /// {sourcemod_dir}{crate::DATA_DIR}
+    /// {sourcemod_dir}{AppVarData::data_dir}
     /// e.g; /home/kate/.var/app/com.valvesoftware.Steam/.local/share/Steam/
     /// steamapps/sourcemods/open_fortress/      C:\Games\Steam\steamapps\
     /// sourcemods\open_fortress\
     pub fn get_mod_location(&mut self) -> String
     {
-        helper::join_path(self.sourcemod_path.clone(), crate::data_dir())
+        helper::join_path(self.sourcemod_path.clone(), self.appvar.data_dir())
     }
 
     /// Get staging location for butler.
@@ -243,12 +430,13 @@ impl RunnerContext
     /// Download package with Progress Bar.
     /// Ok is the location to where it was downloaded to.
     pub async fn download_package(
+        &self,
         version: RemoteVersion,
         version_id: usize
     ) -> Result<String, BeansError>
     {
-        let av = AppVarData::get();
-        let mut out_loc = helper::get_tmp_dir();
+        let av = &self.appvar;
+        let mut out_loc = helper::get_download_dir(self.config());
 
         if let Some(size) = version.pre_sz
         {
@@ -258,7 +446,7 @@ impl RunnerContext
             }
         }
 
-        let out_filename = match crate::aria2::can_use_aria2()
+        let out_filename = match crate::aria2::can_use_aria2(self.config())
         {
             true => format!("{}_{}.pkg", av.mod_info.sourcemod_name, version_id),
             false => format!("presz_{}", helper::generate_rand_str(12))
@@ -266,39 +454,186 @@ impl RunnerContext
         out_loc = helper::join_path(out_loc, out_filename);
 
         info!("[RunnerContext::download_package] writing to {}", out_loc);
-        helper::download_with_progress(
-            format!(
-                "{}{}",
-                &av.remote_info.base_url,
-                version.file.expect("No URL for latest package!")
-            ),
-            out_loc.clone()
+        helper::cancellable(
+            &self.options.cancel,
+            helper::download_with_progress(
+                format!(
+                    "{}{}",
+                    &av.remote_info.base_url,
+                    version.file.expect("No URL for latest package!")
+                ),
+                out_loc.clone(),
+                self.config(),
+                self.events()
+            )
         )
         .await?;
 
         Ok(out_loc)
     }
 
-    /// Extract zstd_location to the detected sourcemods directory.
-    /// TODO replace unwrap/expect with match error handling
+    /// Extract the package at `package_location` to the detected sourcemods
+    /// directory. The format of the package is detected with
+    /// `PackageFormat::detect`.
+    ///
+    /// `size_hint` should be the size of the package once it's installed
+    /// (`RemoteVersion.post_sz`). It's used for the progress bar when the
+    /// decompressed size can't be read from the package.
+    ///
+    /// Progress is reported to `events` as `Phase::Extract`. For compressed
+    /// tarballs, it covers decompressing the package and extracting the files.
     pub fn extract_package(
-        zstd_location: String,
-        out_dir: String
+        package_location: String,
+        out_dir: String,
+        size_hint: Option<usize>,
+        config: &Config,
+        events: &dyn EventSink
     ) -> Result<(), BeansError>
     {
-        let tar_tmp_location = helper::get_tmp_file("data.tar".to_string());
-
-        if let Err(e) =
-            crate::extract::decompress_zstd(zstd_location.clone(), tar_tmp_location.clone(), true)
+        let format = PackageFormat::detect(package_location.clone())?;
+        info!(
+            "[RunnerContext::extract_package] Detected {} as a {} file",
+            package_location, format
+        );
+        match format
         {
-            debug!("{:#?}", e);
-            error!(
-                "[RunnerContext::extract_package] Failed to decompress file {} ({:})",
-                zstd_location, e
-            );
-            return Err(e);
+            PackageFormat::Zip =>
+            {
+                let expected_size = match crate::extract::zip_content_size(package_location.clone())
+                {
+                    Some(v) => v,
+                    None => size_hint.unwrap_or(0) as u64
+                };
+                let pb = Progress::start(
+                    events,
+                    Phase::Extract,
+                    String::from("Extracting files"),
+                    Some(expected_size)
+                );
+                if let Err(e) =
+                    crate::extract::unpack_zip_with_progress(package_location.clone(), out_dir, &pb)
+                {
+                    debug!("{:#?}", e);
+                    error!(
+                        "[RunnerContext::extract_package] Failed to unpack zip {} ({:})",
+                        package_location, e
+                    );
+                    return Err(e);
+                }
+                pb.finish();
+                Ok(())
+            }
+            PackageFormat::Tar =>
+            {
+                let pb = Progress::start(
+                    events,
+                    Phase::Extract,
+                    String::from("Extracting files"),
+                    Some(std::fs::metadata(&package_location)?.len())
+                );
+                if let Err(e) = crate::extract::unpack_tarball_with_progress(
+                    package_location.clone(),
+                    out_dir,
+                    &pb
+                )
+                {
+                    debug!("{:#?}", e);
+                    error!(
+                        "[RunnerContext::extract_package] Failed to unpack tarball {} ({:})",
+                        package_location, e
+                    );
+                    return Err(e);
+                }
+                pb.finish();
+                Ok(())
+            }
+            PackageFormat::TarZstd | PackageFormat::TarXz | PackageFormat::TarGzip =>
+            {
+                Self::extract_compressed_tarball(
+                    package_location,
+                    out_dir,
+                    format,
+                    size_hint,
+                    config,
+                    events
+                )
+            }
+            PackageFormat::Unsupported(_) | PackageFormat::Unknown(_) =>
+            {
+                error!(
+                    "[RunnerContext::extract_package] Unsupported package format {} ({})",
+                    format, package_location
+                );
+                Err(BeansError::UnsupportedPackageFormat {
+                    location: package_location,
+                    format: format.to_string()
+                })
+            }
         }
-        if let Err(e) = crate::extract::unpack_tarball(tar_tmp_location.clone(), out_dir, true)
+    }
+
+    /// Decompress the tarball at `package_location` to a temporary file, then
+    /// extract it to `out_dir`.
+    fn extract_compressed_tarball(
+        package_location: String,
+        out_dir: String,
+        format: PackageFormat,
+        size_hint: Option<usize>,
+        config: &Config,
+        events: &dyn EventSink
+    ) -> Result<(), BeansError>
+    {
+        let tar_tmp_location = helper::get_tmp_file(config, "data.tar".to_string());
+
+        let content_size = match format
+        {
+            PackageFormat::TarZstd => crate::extract::zstd_content_size(package_location.clone()),
+            _ => None
+        };
+        let expected_size = match content_size
+        {
+            Some(v) => v,
+            None => match size_hint
+            {
+                Some(v) => v as u64,
+                None => std::fs::metadata(&package_location)?.len() * 2
+            }
+        };
+        debug!("[RunnerContext::extract_package] expected_size={expected_size}");
+        // one progress bar for decompressing the package, then reading the
+        // decompressed tarball while extracting, so we go through the
+        // decompressed size twice.
+        let pb = Progress::start(
+            events,
+            Phase::Extract,
+            format!(
+                "Extracting {}",
+                helper::get_filename(package_location.clone())
+            ),
+            Some(expected_size * 2)
+        );
+
+        let tar_size = match crate::extract::decompress_with_progress(
+            package_location.clone(),
+            tar_tmp_location.clone(),
+            format,
+            &pb
+        )
+        {
+            Ok(v) => v,
+            Err(e) =>
+            {
+                debug!("{:#?}", e);
+                error!(
+                    "[RunnerContext::extract_package] Failed to decompress file {} ({:})",
+                    package_location, e
+                );
+                return Err(e);
+            }
+        };
+        pb.set_length(tar_size * 2);
+        if let Err(e) =
+            crate::extract::unpack_tarball_with_progress(tar_tmp_location.clone(), out_dir, &pb)
         {
             debug!("{:#?}", e);
             error!(
@@ -307,6 +642,9 @@ impl RunnerContext
             );
             return Err(e);
         }
+        // the padding at the end of the tarball isn't read.
+        pb.set_position(tar_size * 2);
+        pb.finish();
         if helper::file_exists(tar_tmp_location.clone())
         {
             if let Err(e) = std::fs::remove_file(tar_tmp_location.clone())
@@ -4,70 +4,195 @@ use std::backtrace::Backtrace;
 use std::os::unix::fs::PermissionsExt;
 
 use log::{debug,
-          error};
+          error,
+          warn};
 
 #[cfg(target_os = "windows")]
 use crate::ARIA2C_BINARY;
-use crate::{helper,
+use crate::{config::Config,
+            events::EventSink,
+            helper,
             BeansError,
             BUTLER_BINARY,
+            BUTLER_BINARY_SHA256,
             BUTLER_LIB_1,
-            BUTLER_LIB_2};
+            BUTLER_LIB_1_SHA256,
+            BUTLER_LIB_2,
+            BUTLER_LIB_2_SHA256};
 
-/// try and write aria2c and butler if it doesn't exist
+/// try and write aria2c and butler if it doesn't exist, or it's different to
+/// the version that is embedded in beans (checked with the hashes from
+/// `build.rs`).
 /// paths that are used will be fetched from binary_locations()
-pub fn try_write_deps()
+///
+/// Binaries that were extracted to the shared directory that older versions
+/// of beans used are removed first (with `helper::migrate_legacy_dirs`).
+///
+/// butler isn't written when `butler_path` (or `BEANS_BUTLER_PATH`) is set.
+/// It's version is checked with `check_butler_version` instead.
+pub fn try_write_deps(config: &Config)
 {
-    safe_write_file(get_butler_location().as_str(), &BUTLER_BINARY);
-    safe_write_file(get_butler_1_location().as_str(), &BUTLER_LIB_1);
-    safe_write_file(get_butler_2_location().as_str(), &BUTLER_LIB_2);
+    helper::migrate_legacy_dirs(config, &[
+        BUTLER_LOCATION,
+        BUTLER_1,
+        BUTLER_2,
+        ARIA2C_LOCATION
+    ]);
+    match &config.butler_path
+    {
+        Some(location) =>
+        {
+            debug!("[depends::try_write_deps] Using butler from {location}");
+            check_butler_version(config);
+        }
+        None =>
+        {
+            safe_write_file(
+                get_butler_location(config).as_str(),
+                &BUTLER_BINARY,
+                BUTLER_BINARY_SHA256
+            );
+            safe_write_file(
+                get_butler_1_location(config).as_str(),
+                &BUTLER_LIB_1,
+                BUTLER_LIB_1_SHA256
+            );
+            safe_write_file(
+                get_butler_2_location(config).as_str(),
+                &BUTLER_LIB_2,
+                BUTLER_LIB_2_SHA256
+            );
+        }
+    }
     #[cfg(target_os = "windows")]
-    if let Some(s) = get_aria2c_location()
+    if let Some(s) = get_aria2c_location(config)
     {
-        safe_write_file(s.as_str(), &ARIA2C_BINARY);
+        safe_write_file(s.as_str(), &ARIA2C_BINARY, crate::ARIA2C_BINARY_SHA256);
     }
     #[cfg(not(target_os = "windows"))]
-    if helper::file_exists(get_butler_location())
+    if config.butler_path.is_none() && helper::file_exists(get_butler_location(config))
     {
         let p = std::fs::Permissions::from_mode(0o0744_u32);
-        if let Err(e) = std::fs::set_permissions(get_butler_location(), p)
+        if let Err(e) = std::fs::set_permissions(get_butler_location(config), p)
         {
             sentry::capture_error(&e);
             error!(
                 "[depends::try_write_deps] Failed to set permissions for {}",
-                get_butler_location()
+                get_butler_location(config)
             );
             error!("[depends::try_write_deps] {:#?}", e);
         }
         debug!(
             "[depends::try_write_deps] set perms on {}",
-            get_butler_location()
+            get_butler_location(config)
         );
     }
 }
+/// Write `data` to `location` when it doesn't exist, or when the SHA-256
+/// hash of the existing file isn't `hash` (since it's from an older version
+/// of beans, or it's corrupt).
 fn safe_write_file(
     location: &str,
-    data: &[u8]
+    data: &[u8],
+    hash: &str
 )
 {
-    if !helper::file_exists(location.to_string())
+    if helper::file_exists(location.to_string())
     {
-        if let Err(e) = std::fs::write(location, data)
+        match helper::sha256_file(location.to_string())
         {
-            sentry::capture_error(&e);
-            error!("[depends::try_write_deps] failed to extract {}", location);
-            error!("[depends::try_write_deps] {:#?}", e);
+            Ok(v) if v == hash => return,
+            Ok(v) =>
+            {
+                warn!("[depends::try_write_deps] {location} is outdated or corrupt (expected {hash}, found {v}), extracting it again");
+            }
+            Err(e) =>
+            {
+                warn!("[depends::try_write_deps] Failed to check {location}, extracting it again ({e:})");
+            }
         }
-        else
+    }
+    if let Err(e) = std::fs::write(location, data)
+    {
+        sentry::capture_error(&e);
+        error!("[depends::try_write_deps] failed to extract {}", location);
+        error!("[depends::try_write_deps] {:#?}", e);
+    }
+    else
+    {
+        debug!("[depends::try_write_deps] extracted {}", location);
+    }
+}
+
+/// Run `butler --version`, and warn when it's older than
+/// `BUTLER_MIN_VERSION`, or when it couldn't be run.
+///
+/// Returns the version of butler, when it could be found.
+pub fn check_butler_version(config: &Config) -> Option<(u32, u32, u32)>
+{
+    let location = get_butler_location(config);
+    let output = match std::process::Command::new(&location)
+        .arg("--version")
+        .output()
+    {
+        Ok(v) => v,
+        Err(e) =>
         {
-            debug!("[depends::try_write_deps] extracted {}", location);
+            warn!("[depends::check_butler_version] Failed to run {location} ({e:})");
+            return None;
         }
+    };
+    // older versions write it to stderr.
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    debug!(
+        "[depends::check_butler_version] {location} --version: {}",
+        text.trim()
+    );
+    let version = match parse_butler_version(&text)
+    {
+        Some(v) => v,
+        None =>
+        {
+            warn!("[depends::check_butler_version] Couldn't find the version of {location}, so it might not work with beans");
+            return None;
+        }
+    };
+    if version < BUTLER_MIN_VERSION
+    {
+        let (major, minor, patch) = BUTLER_MIN_VERSION;
+        warn!(
+            "[depends::check_butler_version] {location} is v{}.{}.{}, but beans needs v{major}.{minor}.{patch} or later. Update butler, or unset butler_path (BEANS_BUTLER_PATH) to use the version that comes with beans.",
+            version.0, version.1, version.2
+        );
+    }
+    Some(version)
+}
+
+/// Find the version in the output of `butler --version` (for example;
+/// `v15.21.0, built on Jan 1 2021 @ 00:00:00, ref 0000000`)
+fn parse_butler_version(text: &str) -> Option<(u32, u32, u32)>
+{
+    let version = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .find_map(|w| w.strip_prefix('v'))?;
+    let mut parts = version.split('.').map(|p| p.parse::<u32>());
+    match (parts.next(), parts.next(), parts.next())
+    {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch))) => Some((major, minor, patch)),
+        _ => None
     }
 }
 
 /// will not do anything since this only runs on windows
 #[cfg(not(target_os = "windows"))]
-pub async fn try_install_vcredist() -> Result<(), BeansError>
+pub async fn try_install_vcredist(
+    _config: &Config,
+    _events: &dyn EventSink
+) -> Result<(), BeansError>
 {
     // ignored since we aren't windows :3
     Ok(())
@@ -75,7 +200,10 @@ pub async fn try_install_vcredist() -> Result<(), BeansError>
 /// try to download and install vcredist from microsoft via aria2c
 /// TODO use request instead of aria2c for downloading this.
 #[cfg(target_os = "windows")]
-pub async fn try_install_vcredist() -> Result<(), BeansError>
+pub async fn try_install_vcredist(
+    config: &Config,
+    events: &dyn EventSink
+) -> Result<(), BeansError>
 {
     if !match winreg::RegKey::predef(winreg::enums::HKEY_LOCAL_MACHINE).open_subkey(String::from(
         "Software\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64"
@@ -94,12 +222,14 @@ pub async fn try_install_vcredist() -> Result<(), BeansError>
     }
 
     log::info!("Installing Visual C++ Redistributable");
-    let mut out_loc = helper::get_tmp_dir();
+    let mut out_loc = helper::get_download_dir(config);
     out_loc = helper::join_path(out_loc, "vc_redist.exe".to_string());
 
     helper::download_with_progress(
         String::from("https://aka.ms/vs/17/release/vc_redist.x86.exe"),
-        out_loc.clone()
+        out_loc.clone(),
+        config,
+        events
     )
     .await?;
 
@@ -132,47 +262,52 @@ pub async fn try_install_vcredist() -> Result<(), BeansError>
     Ok(())
 }
 
-pub fn butler_exists() -> bool
+pub fn butler_exists(config: &Config) -> bool
 {
-    helper::file_exists(get_butler_location())
-        && helper::file_exists(get_butler_1_location())
-        && helper::file_exists(get_butler_2_location())
+    if config.butler_path.is_some()
+    {
+        return helper::file_exists(get_butler_location(config));
+    }
+    helper::file_exists(get_butler_location(config))
+        && helper::file_exists(get_butler_1_location(config))
+        && helper::file_exists(get_butler_2_location(config))
 }
 
-pub fn get_butler_location() -> String
+/// Location of butler. Will be `butler_path` in the config when it's set,
+/// otherwise it's where the embedded butler is extracted to.
+pub fn get_butler_location(config: &Config) -> String
 {
-    let mut path = get_tmp_dir();
+    if let Some(location) = &config.butler_path
+    {
+        return location.clone();
+    }
+    let mut path = helper::get_bin_dir(config);
     path.push_str(BUTLER_LOCATION);
     path
 }
-pub fn get_butler_1_location() -> String
+pub fn get_butler_1_location(config: &Config) -> String
 {
-    let mut path = get_tmp_dir();
+    let mut path = helper::get_bin_dir(config);
     path.push_str(BUTLER_1);
     path
 }
-pub fn get_butler_2_location() -> String
+pub fn get_butler_2_location(config: &Config) -> String
 {
-    let mut path = get_tmp_dir();
+    let mut path = helper::get_bin_dir(config);
     path.push_str(BUTLER_2);
     path
 }
 /// Will always return `Some()` on Windows, and `None` on any other platform.
-pub fn get_aria2c_location() -> Option<String>
+pub fn get_aria2c_location(config: &Config) -> Option<String>
 {
     if cfg!(target_os = "windows")
     {
-        let mut path = get_tmp_dir();
+        let mut path = helper::get_bin_dir(config);
         path.push_str(ARIA2C_LOCATION);
         return Some(path);
     }
     None
 }
-fn get_tmp_dir() -> String
-{
-    let path = helper::get_tmp_dir();
-    helper::format_directory_path(path)
-}
 
 #[cfg(target_os = "windows")]
 const BUTLER_LOCATION: &str = "butler.exe";
@@ -189,3 +324,8 @@ const BUTLER_2: &str = "c7zip.dll";
 const BUTLER_2: &str = "libc7zip.so";
 
 const ARIA2C_LOCATION: &str = "aria2c.exe";
+
+/// Oldest version of butler that is known to work with beans (it needs
+/// `--json`, `apply --staging-dir` and `verify --heal`). Only checked when
+/// `butler_path` is set.
+pub const BUTLER_MIN_VERSION: (u32, u32, u32) = (15, 0, 0);
@@ -32,6 +32,12 @@ pub enum BeansError
         location: String,
         error: std::io::Error
     },
+    #[error("Failed to delete file {location} ({error:})")]
+    FileDeleteFailure
+    {
+        location: String,
+        error: std::io::Error
+    },
     #[error("Failed to create directory {location} ({error:})")]
     DirectoryCreateFailure
     {
@@ -45,6 +51,27 @@ pub enum BeansError
         location: String,
         error: std::io::Error
     },
+    #[error("Failed to move {location} to the trash ({reason})")]
+    TrashFailure
+    {
+        location: String, reason: String
+    },
+    #[error("Failed to export user data to {location} ({error:})")]
+    UserDataExportFailure
+    {
+        location: String,
+        error: std::io::Error
+    },
+    #[error("Unable to move to {location} since it already exists")]
+    MoveTargetExists
+    {
+        location: String
+    },
+    #[error("Failed to verify the copy at {location} ({reason}). The original installation has not been removed.")]
+    MoveVerifyFailure
+    {
+        location: String, reason: String
+    },
     #[error("Failed to extract {src_file} to directory {target_dir} ({error:})")]
     TarExtractFailure
     {
@@ -62,6 +89,19 @@ pub enum BeansError
         error: std::io::Error,
         backtrace: Backtrace
     },
+    #[error("Failed to extract {src_file} to directory {target_dir} ({error:})")]
+    ZipExtractFailure
+    {
+        src_file: String,
+        target_dir: String,
+        error: zip::result::ZipError,
+        backtrace: Backtrace
+    },
+    #[error("Unable to install {location} since its format ({format}) isn't supported. Packages must be a .tar.zst, .tar.xz, .tar.gz, .tar or .zip file.")]
+    UnsupportedPackageFormat
+    {
+        location: String, format: String
+    },
     #[error("Failed to send request ({error:})")]
     Reqwest
     {
@@ -120,6 +160,88 @@ pub enum BeansError
         backtrace: Backtrace
     },
 
+    #[error("butler failed to download what it needed for {gamedir}\n{stderr}")]
+    ButlerNetworkFailure
+    {
+        gamedir: String,
+        exit_code: Option<i32>,
+        stderr: String
+    },
+    #[error("The patch {patchfile_location} is corrupt\n{stderr}")]
+    ButlerPatchCorrupt
+    {
+        patchfile_location: String,
+        gamedir: String,
+        stderr: String
+    },
+    #[error("There isn't enough free space for butler to finish in {gamedir}\n{stderr}")]
+    ButlerDiskFull
+    {
+        gamedir: String, stderr: String
+    },
+    #[error("The files in {gamedir} don't match the signature\n{stderr}")]
+    ButlerSignatureMismatch
+    {
+        gamedir: String, stderr: String
+    },
+    #[error("butler {command} exited with code {exit_code:?}\n{stderr}")]
+    ButlerExitFailure
+    {
+        command: String,
+        gamedir: String,
+        exit_code: Option<i32>,
+        stderr: String
+    },
+    /// The patch couldn't be applied with `wharf::patch`, because it's
+    /// corrupt, or it doesn't match the installed files.
+    #[error("Failed to apply the patch {location} ({reason})")]
+    WharfPatchInvalid
+    {
+        location: String, reason: String
+    },
+    #[error("Failed to read the signature {location} ({reason})")]
+    WharfSignatureInvalid
+    {
+        location: String, reason: String
+    },
+    /// `config.toml` couldn't be parsed.
+    #[error("Failed to read the config file {location} ({reason})")]
+    ConfigInvalid
+    {
+        location: String, reason: String
+    },
+    /// The key doesn't exist in `config::CONFIG_KEYS`, or the value has the
+    /// wrong type.
+    #[error("Invalid value for config key {key} ({reason})")]
+    ConfigKeyInvalid
+    {
+        key: String, reason: String
+    },
+    /// The command waits for user input, so it can't be used with
+    /// `--output json`.
+    #[error("{command} can't be used with --output json")]
+    JsonOutputUnsupported
+    {
+        command: String
+    },
+    /// `install --clean` would remove most (or all) of the installation, so
+    /// nothing was removed.
+    #[error("Refusing to clean the installation with {location} ({reason})")]
+    CleanInstallRefused
+    {
+        location: String, reason: String
+    },
+    /// The version that was provided as an argument isn't a number.
+    #[error("\"{version}\" isn't a valid version")]
+    InvalidVersionArgument
+    {
+        version: String
+    },
+    /// The workflow was stopped with `RunnerOptions.cancel` (like with the
+    /// `cancel` method of the rpc server).
+    #[error("Cancelled")]
+    Cancelled,
+
     #[error("Could not find file {location}")]
     FileNotFound
     {
@@ -171,6 +293,11 @@ pub enum BeansError
         old_location: String,
         old_content: String
     },
+    #[error("The version file at {location} is corrupt since {reason}. Use the adopt command to repair it.")]
+    VersionFileCorrupt
+    {
+        location: String, reason: String
+    },
     #[error("Failed to read version file at {location}. ({error:})")]
     VersionFileReadFailure
     {
@@ -246,6 +373,381 @@ pub enum BeansError
         backtrace: Backtrace
     }
 }
+/// Exit code used when something fails that isn't a `BeansError`.
+pub const GENERAL_FAILURE_EXIT_CODE: i32 = 1;
+
+/// Process exit code, identifier, and description for every `BeansError`
+/// variant. Both the code and the identifier are stable, so once a variant has
+/// been released its code must never change or be reused. New variants get
+/// the next unused code.
+///
+/// Codes start at 20 so they don't conflict with `GENERAL_FAILURE_EXIT_CODE`,
+/// clap usage errors (2), or the codes used by `CheckUpdateWorkflow`.
+pub const BEANS_ERROR_CODES: &[(i32, &str, &str)] = &[
+    (20, "free_space_check_failure", "Not enough free space"),
+    (
+        21,
+        "sourcemod_location_not_found",
+        "Couldn't find the sourcemods directory"
+    ),
+    (22, "file_open_failure", "Failed to open a file"),
+    (23, "file_write_failure", "Failed to write a file"),
+    (24, "file_delete_failure", "Failed to delete a file"),
+    (
+        25,
+        "directory_create_failure",
+        "Failed to create a directory"
+    ),
+    (
+        26,
+        "directory_delete_failure",
+        "Failed to delete a directory"
+    ),
+    (27, "trash_failure", "Failed to move to the trash"),
+    (28, "user_data_export_failure", "Failed to export user data"),
+    (29, "move_target_exists", "Move target already exists"),
+    (
+        30,
+        "move_verify_failure",
+        "Failed to verify a moved installation"
+    ),
+    (31, "tar_extract_failure", "Failed to extract a package"),
+    (
+        32,
+        "tar_unpack_item_failure",
+        "Failed to extract a file from a package"
+    ),
+    (
+        33,
+        "zip_extract_failure",
+        "Failed to extract a .zip package"
+    ),
+    (
+        34,
+        "unsupported_package_format",
+        "Package format isn't supported"
+    ),
+    (35, "request_failure", "Network request failed"),
+    (36, "json_failure", "Failed to read or write JSON"),
+    (
+        37,
+        "latest_version_already_installed",
+        "Latest version is already installed"
+    ),
+    (38, "download_failure", "Failed to download a file"),
+    (39, "io_failure", "General IO error"),
+    (40, "not_installed", "Mod isn't installed"),
+    (41, "butler_verify_failure", "butler verify failed"),
+    (42, "butler_patch_failure", "butler apply failed"),
+    (43, "file_not_found", "File not found"),
+    (
+        44,
+        "remote_version_not_found",
+        "Version not found on the server"
+    ),
+    (45, "steam_not_found", "Couldn't find Steam"),
+    (46, "registry_key_failure", "Failed to read a registry key"),
+    (
+        47,
+        "version_file_migration_failure",
+        "Failed to migrate the version file"
+    ),
+    (
+        48,
+        "version_file_migration_delete_failure",
+        "Failed to delete the old version file"
+    ),
+    (
+        49,
+        "version_file_serialize_failure",
+        "Failed to write the version file"
+    ),
+    (
+        50,
+        "version_file_parse_failure",
+        "Failed to parse the old version file"
+    ),
+    (51, "version_file_corrupt", "Version file is corrupt"),
+    (
+        52,
+        "version_file_read_failure",
+        "Failed to read the version file"
+    ),
+    (
+        53,
+        "appvar_serialize_failure",
+        "Failed to serialize AppVarData"
+    ),
+    (54, "gameinfo_read_failure", "Failed to read gameinfo.txt"),
+    (
+        55,
+        "gameinfo_permission_failure",
+        "Failed to set permissions on gameinfo.txt"
+    ),
+    (
+        56,
+        "gameinfo_backup_failure",
+        "Failed to backup gameinfo.txt"
+    ),
+    (57, "clean_temp_failure", "Failed to clean temporary files"),
+    (58, "game_still_running", "Game is still running"),
+    (59, "aria2c_failure", "aria2c failed"),
+    (
+        60,
+        "read_file_attributes_failure",
+        "Failed to read file attributes"
+    ),
+    (
+        61,
+        "set_file_attributes_failure",
+        "Failed to set file attributes"
+    ),
+    (
+        62,
+        "clean_install_refused",
+        "Package doesn't look like it's for the installed mod"
+    ),
+    (
+        63,
+        "butler_network_failure",
+        "butler failed to download files"
+    ),
+    (64, "butler_patch_corrupt", "Patch is corrupt"),
+    (65, "butler_disk_full", "Not enough free space for butler"),
+    (
+        66,
+        "butler_signature_mismatch",
+        "Files don't match the signature"
+    ),
+    (67, "butler_exit_failure", "butler failed"),
+    (
+        68,
+        "wharf_patch_invalid",
+        "Patch is corrupt or doesn't match the installed files"
+    ),
+    (69, "wharf_signature_invalid", "Signature is corrupt"),
+    (70, "config_invalid", "Config file is invalid"),
+    (
+        71,
+        "config_key_invalid",
+        "Config key doesn't exist, or the value is invalid"
+    ),
+    (
+        72,
+        "json_output_unsupported",
+        "Command can't be used with --output json"
+    ),
+    (
+        73,
+        "invalid_version_argument",
+        "Version argument isn't a number"
+    ),
+    (74, "cancelled", "Cancelled before it finished")
+];
+
+impl BeansError
+{
+    /// Stable identifier for this error. See `BEANS_ERROR_CODES`
+    pub fn id(&self) -> &'static str
+    {
+        match self
+        {
+            BeansError::FreeSpaceCheckFailure {
+                ..
+            } => "free_space_check_failure",
+            BeansError::SourceModLocationNotFound => "sourcemod_location_not_found",
+            BeansError::FileOpenFailure {
+                ..
+            } => "file_open_failure",
+            BeansError::FileWriteFailure {
+                ..
+            } => "file_write_failure",
+            BeansError::FileDeleteFailure {
+                ..
+            } => "file_delete_failure",
+            BeansError::DirectoryCreateFailure {
+                ..
+            } => "directory_create_failure",
+            BeansError::DirectoryDeleteFailure {
+                ..
+            } => "directory_delete_failure",
+            BeansError::TrashFailure {
+                ..
+            } => "trash_failure",
+            BeansError::UserDataExportFailure {
+                ..
+            } => "user_data_export_failure",
+            BeansError::MoveTargetExists {
+                ..
+            } => "move_target_exists",
+            BeansError::MoveVerifyFailure {
+                ..
+            } => "move_verify_failure",
+            BeansError::TarExtractFailure {
+                ..
+            } => "tar_extract_failure",
+            BeansError::TarUnpackItemFailure {
+                ..
+            } => "tar_unpack_item_failure",
+            BeansError::ZipExtractFailure {
+                ..
+            } => "zip_extract_failure",
+            BeansError::UnsupportedPackageFormat {
+                ..
+            } => "unsupported_package_format",
+            BeansError::Reqwest {
+                ..
+            } => "request_failure",
+            BeansError::SerdeJson {
+                ..
+            } => "json_failure",
+            BeansError::LatestVersionAlreadyInstalled {
+                ..
+            } => "latest_version_already_installed",
+            BeansError::DownloadFailure {
+                ..
+            } => "download_failure",
+            BeansError::IO {
+                ..
+            } => "io_failure",
+            BeansError::TargetSourcemodNotInstalled {
+                ..
+            } => "not_installed",
+            BeansError::ButlerVerifyFailure {
+                ..
+            } => "butler_verify_failure",
+            BeansError::ButlerPatchFailure {
+                ..
+            } => "butler_patch_failure",
+            BeansError::CleanInstallRefused {
+                ..
+            } => "clean_install_refused",
+            BeansError::ButlerNetworkFailure {
+                ..
+            } => "butler_network_failure",
+            BeansError::ButlerPatchCorrupt {
+                ..
+            } => "butler_patch_corrupt",
+            BeansError::ButlerDiskFull {
+                ..
+            } => "butler_disk_full",
+            BeansError::ButlerSignatureMismatch {
+                ..
+            } => "butler_signature_mismatch",
+            BeansError::ButlerExitFailure {
+                ..
+            } => "butler_exit_failure",
+            BeansError::WharfPatchInvalid {
+                ..
+            } => "wharf_patch_invalid",
+            BeansError::WharfSignatureInvalid {
+                ..
+            } => "wharf_signature_invalid",
+            BeansError::ConfigInvalid {
+                ..
+            } => "config_invalid",
+            BeansError::ConfigKeyInvalid {
+                ..
+            } => "config_key_invalid",
+            BeansError::JsonOutputUnsupported {
+                ..
+            } => "json_output_unsupported",
+            BeansError::InvalidVersionArgument {
+                ..
+            } => "invalid_version_argument",
+            BeansError::Cancelled => "cancelled",
+            BeansError::FileNotFound {
+                ..
+            } => "file_not_found",
+            BeansError::RemoteVersionNotFound {
+                ..
+            } => "remote_version_not_found",
+            BeansError::SteamNotFound => "steam_not_found",
+            BeansError::RegistryKeyFailure {
+                ..
+            } => "registry_key_failure",
+            BeansError::VersionFileMigrationFailure {
+                ..
+            } => "version_file_migration_failure",
+            BeansError::VersionFileMigrationDeleteFailure {
+                ..
+            } => "version_file_migration_delete_failure",
+            BeansError::VersionFileSerialize {
+                ..
+            } => "version_file_serialize_failure",
+            BeansError::VersionFileParseFailure {
+                ..
+            } => "version_file_parse_failure",
+            BeansError::VersionFileCorrupt {
+                ..
+            } => "version_file_corrupt",
+            BeansError::VersionFileReadFailure {
+                ..
+            } => "version_file_read_failure",
+            BeansError::AppVarDataSerializeFailure {
+                ..
+            } => "appvar_serialize_failure",
+            BeansError::GameInfoFileReadFail {
+                ..
+            } => "gameinfo_read_failure",
+            BeansError::GameInfoPermissionSetFail {
+                ..
+            } => "gameinfo_permission_failure",
+            BeansError::GameinfoBackupFailure {
+                ..
+            } => "gameinfo_backup_failure",
+            BeansError::CleanTempFailure {
+                ..
+            } => "clean_temp_failure",
+            BeansError::GameStillRunning {
+                ..
+            } => "game_still_running",
+            BeansError::Aria2cExitCode {
+                ..
+            } => "aria2c_failure",
+            BeansError::ReadFileAttributesError {
+                ..
+            } => "read_file_attributes_failure",
+            BeansError::WindowsSetFileAttributeError {
+                ..
+            } => "set_file_attributes_failure"
+        }
+    }
+
+    /// Stable process exit code for this error. See `BEANS_ERROR_CODES`
+    pub fn code(&self) -> i32
+    {
+        let id = self.id();
+        BEANS_ERROR_CODES
+            .iter()
+            .find(|(_, i, _)| *i == id)
+            .map(|(c, ..)| *c)
+            .unwrap_or(GENERAL_FAILURE_EXIT_CODE)
+    }
+
+    /// Create the object that is written to stderr when `--json-errors` is
+    /// used.
+    pub fn to_json(&self) -> serde_json::Value
+    {
+        serde_json::json!({
+            "code": self.code(),
+            "id": self.id(),
+            "message": format!("{self:}"),
+            "hint": self.hint()
+        })
+    }
+
+    /// Table of every exit code, for `--help`.
+    pub fn code_table() -> String
+    {
+        let mut result = String::new();
+        for (code, id, description) in BEANS_ERROR_CODES.iter()
+        {
+            result.push_str(&format!("  {:<4}{:<40}{}\n", code, id, description));
+        }
+        result
+    }
+}
 #[derive(Debug)]
 pub enum Aria2cExitCodeReason
 {
@@ -0,0 +1,1048 @@
+use std::{io::{BufRead,
+               Read,
+               Write},
+          sync::{mpsc,
+                 Arc,
+                 Mutex},
+          time::{Duration,
+                 Instant}};
+
+use indicatif::{ProgressBar,
+                ProgressStyle};
+use log::debug;
+
+use crate::BeansError;
+
+/// Minimum amount of time between `Event::Progress` being emitted for the
+/// same phase. The last update is always emitted by `Progress::finish`.
+const PROGRESS_EMIT_INTERVAL: Duration = Duration::from_millis(50);
+/// indicatif template for `Phase::Heal` and `Phase::Patch`, which are
+/// reported as a fraction instead of bytes.
+const PERCENT_PROGRESS_BAR_TEMPLATE: &str =
+    "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {percent}% ({eta})";
+
+/// Something that a workflow is doing. Operations (see `Phase::is_operation`)
+/// contain the other phases, like `Install` containing `Download` then
+/// `Extract`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase
+{
+    Install,
+    Update,
+    Verify,
+    Uninstall,
+    Relocate,
+    Adopt,
+    /// Downloading a file. Progress is measured in bytes.
+    Download,
+    /// Decompressing a package to a tarball. Progress is measured in bytes.
+    Decompress,
+    /// Extracting files from a package. Progress is measured in bytes.
+    Extract,
+    /// Checking files against a signature. Progress is measured in bytes.
+    Check,
+    /// Repairing files with butler. Progress is measured in
+    /// `BUTLER_PROGRESS_LENGTH` steps.
+    Heal,
+    /// Applying a patch. Progress is measured in `BUTLER_PROGRESS_LENGTH`
+    /// steps.
+    Patch,
+    /// Copying files to a different directory. Progress is measured in bytes.
+    Copy
+}
+
+impl Phase
+{
+    /// `true` for phases that are started by a workflow, instead of by one of
+    /// the steps that a workflow does.
+    pub fn is_operation(&self) -> bool
+    {
+        matches!(
+            self,
+            Phase::Install
+                | Phase::Update
+                | Phase::Verify
+                | Phase::Uninstall
+                | Phase::Relocate
+                | Phase::Adopt
+        )
+    }
+}
+
+/// Emitted by workflows to an `EventSink`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event
+{
+    PhaseStarted
+    {
+        phase: Phase,
+        message: String,
+        /// Value of `Event::Progress.current` once the phase is done. `None`
+        /// when progress isn't reported, or when it isn't known yet.
+        total: Option<u64>
+    },
+    Progress
+    {
+        phase: Phase,
+        current: u64,
+        total: Option<u64>
+    },
+    FileExtracted
+    {
+        phase: Phase,
+        /// Relative to the directory that the package is being extracted to.
+        path: String
+    },
+    /// Something the user should see, like the files that were repaired.
+    Message
+    {
+        message: String
+    },
+    Warning
+    {
+        message: String
+    },
+    /// Emitted before waiting for an answer with `EventSink::confirm` or
+    /// `EventSink::input`.
+    PromptRequired
+    {
+        question: String, kind: PromptKind
+    },
+    /// Output of a workflow that only shows information (like
+    /// `StatusWorkflow` with `--json`).
+    Report
+    {
+        data: serde_json::Value
+    },
+    Finished
+    {
+        phase: Phase,
+        success: bool,
+        /// For operations, the message to display once it's done (like
+        /// `INSTALL_FINISH_MSG`).
+        message: Option<String>
+    }
+}
+
+/// What kind of answer an `Event::PromptRequired` expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptKind
+{
+    /// Yes or no. Yes is the default.
+    Confirm,
+    /// Any text. Empty is the default.
+    Text
+}
+
+/// Receives the events from a workflow, and answers the questions that it
+/// asks. Set with `RunnerOptions.events`.
+///
+/// Implementations must be safe to use from multiple threads, since some
+/// steps (like `wharf::verify_signature`) report progress from more than one.
+pub trait EventSink: std::fmt::Debug + Send + Sync
+{
+    fn emit(
+        &self,
+        event: Event
+    );
+
+    /// Ask the user a yes/no question. Yes is the default option.
+    fn confirm(
+        &self,
+        question: &str
+    ) -> bool;
+
+    /// Ask the user to enter some text. `None` when there is nobody to ask, so
+    /// the caller should use it's default.
+    fn input(
+        &self,
+        question: &str
+    ) -> Option<String>;
+
+    /// Run `f` without anything that is being drawn by this sink getting in the
+    /// way (like writing a log message while a progress bar is displayed).
+    fn suspend(
+        &self,
+        f: &mut dyn FnMut()
+    )
+    {
+        f()
+    }
+
+    fn message(
+        &self,
+        message: String
+    )
+    {
+        self.emit(Event::Message {
+            message
+        });
+    }
+
+    fn warning(
+        &self,
+        message: String
+    )
+    {
+        self.emit(Event::Warning {
+            message
+        });
+    }
+
+    fn report(
+        &self,
+        data: serde_json::Value
+    )
+    {
+        self.emit(Event::Report {
+            data
+        });
+    }
+
+    fn phase_started(
+        &self,
+        phase: Phase,
+        message: String
+    )
+    {
+        self.emit(Event::PhaseStarted {
+            phase,
+            message,
+            total: None
+        });
+    }
+
+    fn finished(
+        &self,
+        phase: Phase,
+        success: bool,
+        message: Option<String>
+    )
+    {
+        self.emit(Event::Finished {
+            phase,
+            success,
+            message
+        });
+    }
+}
+
+/// An operation (see `Phase::is_operation`) that is being done by a workflow.
+/// Emits `Event::PhaseStarted` when it's created, and `Event::Finished` when
+/// it's finished.
+///
+/// Like `Progress`, dropping it without calling `Operation::finish` (like when
+/// `?` is used on an error) will finish it as a failure.
+pub struct Operation
+{
+    events: Arc<dyn EventSink>,
+    phase: Phase,
+    finished: bool
+}
+
+impl Operation
+{
+    pub fn start(
+        events: Arc<dyn EventSink>,
+        phase: Phase,
+        message: String
+    ) -> Self
+    {
+        events.phase_started(phase, message);
+        Self {
+            events,
+            phase,
+            finished: false
+        }
+    }
+
+    /// Finish the operation successfully. `message` is displayed once it's
+    /// done, like `INSTALL_FINISH_MSG`.
+    pub fn finish(
+        mut self,
+        message: Option<String>
+    )
+    {
+        self.finished = true;
+        self.events.finished(self.phase, true, message);
+    }
+}
+
+impl Drop for Operation
+{
+    fn drop(&mut self)
+    {
+        if !self.finished
+        {
+            self.events.finished(self.phase, false, None);
+        }
+    }
+}
+
+/// Progress of a phase that has a known amount of work. Emits
+/// `Event::PhaseStarted` when it's created, and `Event::Finished` when it's
+/// finished.
+///
+/// When it's dropped without calling `Progress::finish` (like when `?` is used
+/// on an error), the phase will be finished as a failure.
+pub struct Progress<'a>
+{
+    events: &'a dyn EventSink,
+    phase: Phase,
+    state: Mutex<ProgressState>
+}
+
+struct ProgressState
+{
+    current: u64,
+    total: Option<u64>,
+    last_emit: Option<Instant>,
+    finished: bool
+}
+
+impl<'a> Progress<'a>
+{
+    pub fn start(
+        events: &'a dyn EventSink,
+        phase: Phase,
+        message: String,
+        total: Option<u64>
+    ) -> Self
+    {
+        events.emit(Event::PhaseStarted {
+            phase,
+            message,
+            total
+        });
+        Self {
+            events,
+            phase,
+            state: Mutex::new(ProgressState {
+                current: 0,
+                total,
+                last_emit: None,
+                finished: false
+            })
+        }
+    }
+
+    pub fn inc(
+        &self,
+        delta: u64
+    )
+    {
+        self.update(|s| s.current += delta);
+    }
+
+    pub fn set_position(
+        &self,
+        position: u64
+    )
+    {
+        self.update(|s| s.current = position);
+    }
+
+    pub fn set_length(
+        &self,
+        total: u64
+    )
+    {
+        self.update(|s| s.total = Some(total));
+    }
+
+    pub fn file_extracted(
+        &self,
+        path: String
+    )
+    {
+        self.events.emit(Event::FileExtracted {
+            phase: self.phase,
+            path
+        });
+    }
+
+    /// Wrap `read` so this is incremented by the amount of bytes that are read
+    /// from it.
+    pub fn wrap_read<R: Read>(
+        &self,
+        read: R
+    ) -> ProgressRead<'_, 'a, R>
+    {
+        ProgressRead {
+            progress: self,
+            inner: read
+        }
+    }
+
+    /// Same as `EventSink::suspend`.
+    pub fn suspend<F: FnOnce()>(
+        &self,
+        f: F
+    )
+    {
+        let mut f = Some(f);
+        self.events.suspend(&mut || {
+            if let Some(f) = f.take()
+            {
+                f()
+            }
+        });
+    }
+
+    /// Emit the current progress, then finish the phase successfully.
+    pub fn finish(self)
+    {
+        self.end(true);
+    }
+
+    /// Finish the phase as a failure.
+    pub fn fail(self)
+    {
+        self.end(false);
+    }
+
+    fn end(
+        &self,
+        success: bool
+    )
+    {
+        let (current, total) = match self.state.lock()
+        {
+            Ok(mut s) =>
+            {
+                if s.finished
+                {
+                    return;
+                }
+                s.finished = true;
+                (s.current, s.total)
+            }
+            Err(_) => return
+        };
+        if success
+        {
+            self.events.emit(Event::Progress {
+                phase: self.phase,
+                current,
+                total
+            });
+        }
+        self.events.finished(self.phase, success, None);
+    }
+
+    /// Change the state with `f`, then emit `Event::Progress` when enough time
+    /// has passed since the last one.
+    fn update<F: FnOnce(&mut ProgressState)>(
+        &self,
+        f: F
+    )
+    {
+        let (current, total) = match self.state.lock()
+        {
+            Ok(mut s) =>
+            {
+                f(&mut s);
+                let now = Instant::now();
+                if s.last_emit
+                    .is_some_and(|t| now.duration_since(t) < PROGRESS_EMIT_INTERVAL)
+                {
+                    return;
+                }
+                s.last_emit = Some(now);
+                (s.current, s.total)
+            }
+            Err(_) => return
+        };
+        self.events.emit(Event::Progress {
+            phase: self.phase,
+            current,
+            total
+        });
+    }
+}
+
+impl Drop for Progress<'_>
+{
+    fn drop(&mut self)
+    {
+        self.end(false);
+    }
+}
+
+/// Created by `Progress::wrap_read`.
+pub struct ProgressRead<'p, 'a, R: Read>
+{
+    progress: &'p Progress<'a>,
+    inner: R
+}
+
+impl<R: Read> Read for ProgressRead<'_, '_, R>
+{
+    fn read(
+        &mut self,
+        buf: &mut [u8]
+    ) -> std::io::Result<usize>
+    {
+        let n = self.inner.read(buf)?;
+        self.progress.inc(n as u64);
+        Ok(n)
+    }
+}
+
+/// Displays events in the terminal with indicatif progress bars, and asks
+/// questions on stdin. Used by default.
+#[derive(Debug, Default)]
+pub struct TerminalSink
+{
+    bar: Mutex<Option<ProgressBar>>
+}
+
+impl TerminalSink
+{
+    fn create_progress_bar(
+        phase: Phase,
+        message: String,
+        total: u64
+    ) -> ProgressBar
+    {
+        let pb = match phase
+        {
+            Phase::Heal | Phase::Patch =>
+            {
+                let pb = ProgressBar::new(total);
+                pb.set_style(
+                    ProgressStyle::with_template(PERCENT_PROGRESS_BAR_TEMPLATE)
+                        .unwrap()
+                        .progress_chars("#>-")
+                );
+                pb
+            }
+            _ => crate::extract::create_progress_bar(total)
+        };
+        pb.set_message(message);
+        pb
+    }
+
+    /// Create a spinner for when the total amount of bytes isn't known.
+    fn create_spinner(message: String) -> ProgressBar
+    {
+        let pb = crate::extract::create_spinner();
+        pb.set_message(message);
+        pb
+    }
+
+    /// Finish the progress bar that is being displayed. It's only kept on the
+    /// screen when `phase` was successful and isn't done by butler or
+    /// `wharf::verify_signature`, which is what they did before.
+    fn finish_progress_bar(
+        &self,
+        phase: Phase,
+        success: bool
+    )
+    {
+        let pb = match self.bar.lock()
+        {
+            Ok(mut b) => b.take(),
+            Err(_) => None
+        };
+        if let Some(pb) = pb
+        {
+            match success && !matches!(phase, Phase::Check | Phase::Heal | Phase::Patch)
+            {
+                true => pb.finish(),
+                false => pb.finish_and_clear()
+            }
+        }
+    }
+
+    fn with_bar<F: FnOnce(&ProgressBar)>(
+        &self,
+        f: F
+    )
+    {
+        if let Ok(b) = self.bar.lock()
+            && let Some(pb) = b.as_ref()
+        {
+            f(pb);
+        }
+    }
+}
+
+impl EventSink for TerminalSink
+{
+    fn emit(
+        &self,
+        event: Event
+    )
+    {
+        match event
+        {
+            Event::PhaseStarted {
+                phase,
+                message,
+                total
+            } =>
+            {
+                if phase.is_operation()
+                {
+                    self.suspend(&mut || println!("{:=>60}\n{}\n{0:=>60}", "=", message));
+                    return;
+                }
+                let pb = match (total, phase)
+                {
+                    (Some(t), _) => Self::create_progress_bar(phase, message, t),
+                    // the size isn't always known before decompressing.
+                    (None, Phase::Decompress | Phase::Extract) => Self::create_spinner(message),
+                    (None, _) => return
+                };
+                self.finish_progress_bar(phase, true);
+                if let Ok(mut b) = self.bar.lock()
+                {
+                    *b = Some(pb);
+                }
+            }
+            Event::Progress {
+                current,
+                total,
+                ..
+            } => self.with_bar(|pb| {
+                if let Some(t) = total
+                {
+                    pb.set_length(t);
+                }
+                pb.set_position(current);
+            }),
+            Event::FileExtracted {
+                path, ..
+            } => self.with_bar(|pb| pb.set_message(path)),
+            Event::Message {
+                message
+            } => self.suspend(&mut || println!("{}", message)),
+            Event::Warning {
+                message
+            } => self.suspend(&mut || eprintln!("{}", message)),
+            Event::PromptRequired {
+                ..
+            } =>
+            {}
+            Event::Report {
+                data
+            } => match serde_json::to_string_pretty(&data)
+            {
+                Ok(v) => println!("{}", v),
+                Err(e) => debug!("[TerminalSink::emit] Failed to serialize report {:#?}", e)
+            },
+            Event::Finished {
+                phase,
+                success,
+                message
+            } =>
+            {
+                if !phase.is_operation()
+                {
+                    self.finish_progress_bar(phase, success);
+                }
+                if let Some(m) = message
+                {
+                    println!("{}", m);
+                }
+            }
+        }
+    }
+
+    fn confirm(
+        &self,
+        question: &str
+    ) -> bool
+    {
+        crate::helper::prompt_confirm(question)
+    }
+
+    fn input(
+        &self,
+        question: &str
+    ) -> Option<String>
+    {
+        Some(crate::helper::get_input(question))
+    }
+
+    fn suspend(
+        &self,
+        f: &mut dyn FnMut()
+    )
+    {
+        let pb = match self.bar.lock()
+        {
+            Ok(b) => b.clone(),
+            Err(_) => None
+        };
+        match pb
+        {
+            Some(pb) => pb.suspend(f),
+            None => f()
+        }
+    }
+}
+
+/// Version of the objects written by `JsonSink`. Increased when a field is
+/// removed or changes meaning, but not when one is added.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// Writes everything as objects on a single line (newline-delimited JSON) to
+/// stdout, for `--output json`. Every object has the schema version (`v`) and
+/// a `type`;
+/// - `progress`: `Event::PhaseStarted`, `Event::Progress`,
+///   `Event::FileExtracted` or `Event::Finished` (in `event`).
+/// - `log`: a log message, or `Event::Message` and `Event::Warning`.
+/// - `prompt`: `Event::PromptRequired`. `waiting` is `true` when an answer is
+///   read from stdin.
+/// - `error`: see `BeansError::to_json`.
+/// - `result`: always the last object, written with `JsonSink::result`.
+///   `Event::Report` is used as its `data`.
+///
+/// Prompts are only answered on stdin when `interactive` is `true`. Otherwise
+/// `confirm` declines, and `input` uses the default.
+pub struct JsonSink
+{
+    /// Used instead of stdout when some.
+    writer: Option<Mutex<Box<dyn Write + Send>>>,
+    interactive: bool,
+    /// Last `Event::Report`.
+    report: Mutex<Option<serde_json::Value>>
+}
+
+impl std::fmt::Debug for JsonSink
+{
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>
+    ) -> std::fmt::Result
+    {
+        f.debug_struct("JsonSink")
+            .field("interactive", &self.interactive)
+            .finish_non_exhaustive()
+    }
+}
+
+impl JsonSink
+{
+    pub fn new(interactive: bool) -> Self
+    {
+        Self {
+            writer: None,
+            interactive,
+            report: Mutex::new(None)
+        }
+    }
+
+    /// Write to `writer` instead of stdout.
+    pub fn with_writer(
+        writer: Box<dyn Write + Send>,
+        interactive: bool
+    ) -> Self
+    {
+        Self {
+            writer: Some(Mutex::new(writer)),
+            interactive,
+            report: Mutex::new(None)
+        }
+    }
+
+    /// Write `error` as an object with the `error` type.
+    pub fn error(
+        &self,
+        error: &BeansError
+    )
+    {
+        self.write("error", error.to_json());
+    }
+
+    /// Write the object that ends the output. `exit_code` is what the process
+    /// will exit with, and the last `Event::Report` is included as `data`.
+    pub fn result(
+        &self,
+        command: &str,
+        exit_code: i32
+    )
+    {
+        let data = match self.report.lock()
+        {
+            Ok(mut r) => r.take(),
+            Err(_) => None
+        };
+        self.write(
+            "result",
+            serde_json::json!({
+                "command": command,
+                "success": exit_code == 0,
+                "exit_code": exit_code,
+                "data": data
+            })
+        );
+    }
+
+    /// Write `value` with `kind` as the type. `value` must be an object.
+    fn write(
+        &self,
+        kind: &str,
+        mut value: serde_json::Value
+    )
+    {
+        if let Some(m) = value.as_object_mut()
+        {
+            m.insert(String::from("v"), serde_json::json!(JSON_SCHEMA_VERSION));
+            m.insert(String::from("type"), serde_json::json!(kind));
+        }
+        write_json_line(self.writer.as_ref(), &value);
+    }
+
+    /// Read a line from stdin when `self.interactive` is `true`.
+    fn read_answer(&self) -> Option<String>
+    {
+        if !self.interactive
+        {
+            return None;
+        }
+        let mut input = String::new();
+        if let Err(e) = std::io::stdin().lock().read_line(&mut input)
+        {
+            debug!("[JsonSink::read_answer] Failed to read stdin {:#?}", e);
+        }
+        Some(input.trim().to_string())
+    }
+}
+
+/// Write `value` as a single line to `writer` (or stdout when it's `None`).
+/// Also used by `logger` for `--output json`.
+pub fn write_json_line(
+    writer: Option<&Mutex<Box<dyn Write + Send>>>,
+    value: &serde_json::Value
+)
+{
+    let line = value.to_string();
+    let result = match writer
+    {
+        Some(w) => match w.lock()
+        {
+            Ok(mut w) => writeln!(w, "{}", line).and_then(|_| w.flush()),
+            Err(_) => return
+        },
+        None =>
+        {
+            let mut out = std::io::stdout().lock();
+            writeln!(out, "{}", line).and_then(|_| out.flush())
+        }
+    };
+    if let Err(e) = result
+    {
+        // can't log this, since the logger might be writing JSON too.
+        eprintln!("[events::write_json_line] Failed to write to stdout ({e:})");
+    }
+}
+
+impl EventSink for JsonSink
+{
+    fn emit(
+        &self,
+        event: Event
+    )
+    {
+        match event
+        {
+            Event::Message {
+                message
+            } => self.write(
+                "log",
+                serde_json::json!({ "level": "info", "message": message })
+            ),
+            Event::Warning {
+                message
+            } => self.write(
+                "log",
+                serde_json::json!({ "level": "warn", "message": message })
+            ),
+            Event::PromptRequired {
+                question,
+                kind
+            } => self.write(
+                "prompt",
+                serde_json::json!({
+                    "question": question,
+                    "kind": kind,
+                    "waiting": self.interactive
+                })
+            ),
+            Event::Report {
+                data
+            } =>
+            {
+                if let Ok(mut r) = self.report.lock()
+                {
+                    *r = Some(data);
+                }
+            }
+            e => match serde_json::to_value(&e)
+            {
+                Ok(v) => self.write("progress", v),
+                Err(err) => debug!("[JsonSink::emit] Failed to serialize {:?} {:#?}", e, err)
+            }
+        }
+    }
+
+    fn confirm(
+        &self,
+        question: &str
+    ) -> bool
+    {
+        self.emit(Event::PromptRequired {
+            question: question.to_string(),
+            kind: PromptKind::Confirm
+        });
+        match self.read_answer()
+        {
+            Some(v) => parse_confirm(&v),
+            None =>
+            {
+                self.warning(format!(
+                    "Declined \"{}\" since prompts aren't answered with --output json. Use --confirm or --interactive.",
+                    question
+                ));
+                false
+            }
+        }
+    }
+
+    fn input(
+        &self,
+        question: &str
+    ) -> Option<String>
+    {
+        self.emit(Event::PromptRequired {
+            question: question.to_string(),
+            kind: PromptKind::Text
+        });
+        self.read_answer()
+    }
+}
+
+/// Parse the answer to `EventSink::confirm`. Anything other than `n` or `no`
+/// is yes, since it's the default.
+pub(crate) fn parse_confirm(answer: &str) -> bool
+{
+    !matches!(answer.trim().to_lowercase().as_str(), "n" | "no")
+}
+
+/// Sends every event to a channel, so it can be received on a different
+/// thread (like a GUI). Questions wait for `ChannelHandle::answer` or
+/// `ChannelHandle::answer_text`.
+#[derive(Debug)]
+pub struct ChannelSink
+{
+    sender: mpsc::Sender<Event>,
+    answers: Mutex<mpsc::Receiver<String>>
+}
+
+/// Other end of a `ChannelSink`.
+#[derive(Debug)]
+pub struct ChannelHandle
+{
+    pub events: mpsc::Receiver<Event>,
+    answers: mpsc::Sender<String>
+}
+
+impl ChannelSink
+{
+    pub fn new() -> (Self, ChannelHandle)
+    {
+        let (sender, events) = mpsc::channel();
+        let (answers_tx, answers) = mpsc::channel();
+        (
+            Self {
+                sender,
+                answers: Mutex::new(answers)
+            },
+            ChannelHandle {
+                events,
+                answers: answers_tx
+            }
+        )
+    }
+
+    fn wait_for_answer(&self) -> Option<String>
+    {
+        match self.answers.lock()
+        {
+            Ok(r) => r.recv().ok(),
+            Err(_) => None
+        }
+    }
+}
+
+impl ChannelHandle
+{
+    /// Answer the question from the last `Event::PromptRequired` with
+    /// `PromptKind::Confirm`.
+    pub fn answer(
+        &self,
+        value: bool
+    )
+    {
+        self.answer_text(String::from(match value
+        {
+            true => "y",
+            false => "n"
+        }));
+    }
+
+    /// Answer the question from the last `Event::PromptRequired`.
+    pub fn answer_text(
+        &self,
+        value: String
+    )
+    {
+        if self.answers.send(value).is_err()
+        {
+            debug!("[ChannelHandle::answer_text] Sink was dropped");
+        }
+    }
+}
+
+impl EventSink for ChannelSink
+{
+    fn emit(
+        &self,
+        event: Event
+    )
+    {
+        // nothing is listening anymore, which is fine.
+        let _ = self.sender.send(event);
+    }
+
+    /// Declines when the `ChannelHandle` was dropped, since nobody can answer.
+    fn confirm(
+        &self,
+        question: &str
+    ) -> bool
+    {
+        self.emit(Event::PromptRequired {
+            question: question.to_string(),
+            kind: PromptKind::Confirm
+        });
+        self.wait_for_answer().is_some_and(|v| parse_confirm(&v))
+    }
+
+    fn input(
+        &self,
+        question: &str
+    ) -> Option<String>
+    {
+        self.emit(Event::PromptRequired {
+            question: question.to_string(),
+            kind: PromptKind::Text
+        });
+        self.wait_for_answer()
+    }
+}
@@ -1,15 +1,21 @@
 use std::{backtrace::Backtrace,
-          fs::File};
+          fs::File,
+          io::Read};
 
+use flate2::read::MultiGzDecoder;
 use indicatif::{ProgressBar,
                 ProgressStyle};
 use log::{debug,
           error,
           info,
           warn};
+use xz2::read::XzDecoder;
 use zstd::stream::read::Decoder as ZstdDecoder;
 
-use crate::{helper::join_path,
+use crate::{events::{Phase,
+                     Progress,
+                     TerminalSink},
+            helper::join_path,
             BeansError};
 
 fn unpack_tarball_getfile(
@@ -35,7 +41,7 @@ pub fn unpack_tarball(
     show_progress: bool
 ) -> Result<(), BeansError>
 {
-    let mut tarball = unpack_tarball_getfile(tarball_location.clone(), output_directory.clone())?;
+    let tarball = unpack_tarball_getfile(tarball_location.clone(), output_directory.clone())?;
     let mut archive = tar::Archive::new(&tarball);
 
     if !show_progress
@@ -52,7 +58,36 @@ pub fn unpack_tarball(
         return Ok(());
     };
 
-    let archive_entries = match archive.entries()
+    let tarball_length = tarball.metadata()?.len();
+    let events = TerminalSink::default();
+    let pb = Progress::start(
+        &events,
+        Phase::Extract,
+        String::from("Extracting files"),
+        Some(tarball_length)
+    );
+    unpack_tarball_with_progress(tarball_location, output_directory, &pb)?;
+    pb.finish();
+    Ok(())
+}
+
+/// Unpack the tarball at `tarball_location` into `output_directory`, while
+/// incrementing `pb` by the amount of bytes that have been read from the
+/// tarball. `Progress::file_extracted` is used for every file that was
+/// unpacked.
+///
+/// `pb` will not be finished when this function is successful, so the caller
+/// can decide when the phase is done.
+pub fn unpack_tarball_with_progress(
+    tarball_location: String,
+    output_directory: String,
+    pb: &Progress
+) -> Result<(), BeansError>
+{
+    let mut tarball = unpack_tarball_getfile(tarball_location.clone(), output_directory.clone())?;
+    let mut archive = tar::Archive::new(&tarball);
+
+    let archive_entries = match archive.entries_with_seek()
     {
         Ok(v) => v,
         Err(e) =>
@@ -69,23 +104,15 @@ pub fn unpack_tarball(
     info!("Extracting {} files", archive_entry_count);
 
     tarball = unpack_tarball_getfile(tarball_location.clone(), output_directory.clone())?;
-    archive = tar::Archive::new(&tarball);
+    let mut archive = tar::Archive::new(pb.wrap_read(&tarball));
     archive.set_preserve_permissions(false);
     archive.set_preserve_ownerships(false);
 
-    let pb = ProgressBar::new(archive_entry_count);
-    pb.set_style(ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta})")
-            .unwrap()
-            .with_key("eta", |state: &indicatif::ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-            .progress_chars("#>-"));
-    pb.set_message("Extracting files");
-
     let entries = match archive.entries()
     {
         Ok(a) => a,
         Err(error) =>
         {
-            pb.finish_and_clear();
             return Err(BeansError::TarExtractFailure {
                 src_file: tarball_location,
                 target_dir: output_directory,
@@ -102,14 +129,12 @@ pub fn unpack_tarball(
             Ok(mut x) =>
             {
                 x.set_preserve_permissions(false);
-                pb.set_message("Extracting files");
                 let mut filename = String::new();
 
                 if let Ok(Some(p)) = x.link_name()
                 {
                     if let Some(s) = p.to_str()
                     {
-                        pb.set_message(s.to_string());
                         filename = String::from(s);
                     }
                 }
@@ -120,8 +145,7 @@ pub fn unpack_tarball(
                     {
                         if let Some(ep_str) = entry_path.to_str()
                         {
-                            let ep = ep_str.to_string();
-                            filename = ep;
+                            filename = ep_str.to_string();
                         }
                     }
                 }
@@ -134,18 +158,12 @@ pub fn unpack_tarball(
                     debug!("entry.size={:#?}", x.size());
                     debug!("size={size:}");
 
-                    let error_str = format!("{:#?}", error);
-                    if error_str.contains("io: Custom {")
-                        && error_str.contains("error: TarError {")
-                        && error_str.contains("kind: PermissionDenied")
-                        && error_str.contains("io: Os {")
-                        && error_str.contains("code: 5")
+                    if is_readonly_error(&error)
                     {
                         warn!("Failed to unpack file {filename} (Permission Denied, might be read-only)")
                     }
                     else
                     {
-                        pb.finish_and_clear();
                         error!(
                             "[extract::unpack_tarball] Failed to unpack file {filename} ({error:})"
                         );
@@ -163,22 +181,13 @@ pub fn unpack_tarball(
                 {
                     if let Some(ep_str) = entry_path.to_str()
                     {
-                        let ep = ep_str.to_string();
-                        let target_path = join_path(output_directory.clone(), ep);
-                        if crate::helper::file_exists(target_path.clone())
-                        {
-                            if let Err(e) = crate::helper::unmark_readonly(target_path.clone())
-                            {
-                                debug!("Failed to unmark read-only on file: {target_path:} {e:#?}");
-                            }
-                        }
+                        unmark_readonly_entry(output_directory.clone(), ep_str.to_string());
                     }
                 }
-                pb.inc(1);
+                pb.file_extracted(filename);
             }
             Err(error) =>
             {
-                pb.finish_and_clear();
                 debug!("[extract::unpack_tarball] size={size:}, error={:#?}", error);
                 error!("[extract::unpack_tarball] Failed to unpack entry ({error:})");
                 return Err(BeansError::TarExtractFailure {
@@ -190,7 +199,6 @@ pub fn unpack_tarball(
             }
         }
     }
-    pb.finish();
     Ok(())
 }
 
@@ -200,29 +208,873 @@ pub fn decompress_zstd(
     show_progress: bool
 ) -> Result<(), BeansError>
 {
-    let zstd_file = File::open(&zstd_location)?;
-    let zstd_file_length = &zstd_file.metadata()?.len();
-    let mut tar_tmp_file = File::create_new(&output_file)?;
     if show_progress
     {
-        let decoder = ZstdDecoder::new(zstd_file)?;
-        // estimate extracted size as x2 since idk how to get the decompressed size with
-        // zstd
-        let pb_decompress = ProgressBar::new(*zstd_file_length * 2);
-        pb_decompress
-            .set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-            .unwrap()
-            .with_key("eta", |state: &indicatif::ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-            .progress_chars("#>-"));
-
-        std::io::copy(&mut pb_decompress.wrap_read(decoder), &mut tar_tmp_file)
-            .expect("Failed to decompress file");
-        pb_decompress.finish();
+        // `None` when the frame doesn't have the content size, so a spinner is
+        // shown instead.
+        let total = zstd_content_size(zstd_location.clone());
+        let events = TerminalSink::default();
+        let pb = Progress::start(
+            &events,
+            Phase::Decompress,
+            format!(
+                "Decompressing {}",
+                crate::helper::get_filename(zstd_location.clone())
+            ),
+            total
+        );
+        decompress_with_progress(zstd_location, output_file, PackageFormat::TarZstd, &pb)?;
+        pb.finish();
     }
     else
     {
+        let zstd_file = File::open(&zstd_location)?;
+        let tar_tmp_file = File::create_new(&output_file)?;
         zstd::stream::copy_decode(zstd_file, &tar_tmp_file)?;
     }
 
     Ok(())
 }
+
+/// Decompress the file at `location` to `output_file` with the decoder for
+/// `format`, while incrementing `pb` by the amount of bytes that have been
+/// written to `output_file`.
+///
+/// Returns the amount of bytes that were written to `output_file`. Like
+/// [`unpack_tarball_with_progress`], `pb` will not be finished.
+pub fn decompress_with_progress(
+    location: String,
+    output_file: String,
+    format: PackageFormat,
+    pb: &Progress
+) -> Result<u64, BeansError>
+{
+    let decoder = open_decoder(location.clone(), format)?;
+    let mut tar_tmp_file = File::create_new(&output_file)?;
+
+    match std::io::copy(&mut pb.wrap_read(decoder), &mut tar_tmp_file)
+    {
+        Ok(v) => Ok(v),
+        Err(e) =>
+        {
+            debug!("[extract::decompress_with_progress] {:#?}", e);
+            error!(
+                "[extract::decompress_with_progress] Failed to decompress {} ({:})",
+                location, e
+            );
+            Err(e.into())
+        }
+    }
+}
+
+/// Open `location` with the decompressor for `format`. Only works with
+/// compressed tarballs (see [`PackageFormat::is_compressed_tar`])
+fn open_decoder(
+    location: String,
+    format: PackageFormat
+) -> Result<Box<dyn Read>, BeansError>
+{
+    let file = File::open(&location)?;
+    let decoder: Box<dyn Read> = match format
+    {
+        PackageFormat::TarZstd => Box::new(ZstdDecoder::new(file)?),
+        PackageFormat::TarXz => Box::new(XzDecoder::new_multi_decoder(file)),
+        PackageFormat::TarGzip => Box::new(MultiGzDecoder::new(file)),
+        _ =>
+        {
+            return Err(BeansError::UnsupportedPackageFormat {
+                location,
+                format: format.to_string()
+            });
+        }
+    };
+    Ok(decoder)
+}
+
+/// Get the location of every file in the package at `location`, without
+/// extracting it. Directories are not included, and any leading `./` is
+/// removed.
+pub fn list_package_files(
+    location: String,
+    format: PackageFormat
+) -> Result<Vec<String>, BeansError>
+{
+    let mut result: Vec<String> = Vec::new();
+    match format
+    {
+        PackageFormat::Zip =>
+        {
+            let file = File::open(&location)?;
+            let map_err = |error| BeansError::ZipExtractFailure {
+                src_file: location.clone(),
+                target_dir: String::new(),
+                error,
+                backtrace: Backtrace::capture()
+            };
+            let mut archive = zip::ZipArchive::new(file).map_err(map_err)?;
+            for i in 0..archive.len()
+            {
+                let entry = archive.by_index_raw(i).map_err(map_err)?;
+                if !entry.is_dir()
+                    && let Some(p) = entry.enclosed_name()
+                {
+                    result.push(p.to_string_lossy().replace('\\', "/"));
+                }
+            }
+        }
+        PackageFormat::Tar =>
+        {
+            let file = File::open(&location)?;
+            list_tar_files(tar::Archive::new(file), &mut result)?;
+        }
+        f if f.is_compressed_tar() =>
+        {
+            let decoder = open_decoder(location, f)?;
+            list_tar_files(tar::Archive::new(decoder), &mut result)?;
+        }
+        f =>
+        {
+            return Err(BeansError::UnsupportedPackageFormat {
+                location,
+                format: f.to_string()
+            });
+        }
+    }
+    Ok(result)
+}
+
+fn list_tar_files<R: Read>(
+    mut archive: tar::Archive<R>,
+    result: &mut Vec<String>
+) -> Result<(), BeansError>
+{
+    for entry in archive.entries()?
+    {
+        let entry = entry?;
+        if entry.header().entry_type().is_dir()
+        {
+            continue;
+        }
+        let p = entry.path()?.to_string_lossy().replace('\\', "/");
+        result.push(p.trim_start_matches("./").to_string());
+    }
+    Ok(())
+}
+
+/// Extract the zip file at `zip_location` into `output_directory`, while
+/// incrementing `pb` by the uncompressed size of each file once it has been
+/// written.
+///
+/// Read-only files are handled the same way as
+/// [`unpack_tarball_with_progress`].
+pub fn unpack_zip_with_progress(
+    zip_location: String,
+    output_directory: String,
+    pb: &Progress
+) -> Result<(), BeansError>
+{
+    let file = unpack_tarball_getfile(zip_location.clone(), output_directory.clone())?;
+    let mut archive = match zip::ZipArchive::new(file)
+    {
+        Ok(v) => v,
+        Err(error) =>
+        {
+            return Err(BeansError::ZipExtractFailure {
+                src_file: zip_location,
+                target_dir: output_directory,
+                error,
+                backtrace: Backtrace::capture()
+            });
+        }
+    };
+    info!("Extracting {} files", archive.len());
+
+    for i in 0..archive.len()
+    {
+        let mut entry = match archive.by_index(i)
+        {
+            Ok(v) => v,
+            Err(error) =>
+            {
+                debug!(
+                    "[extract::unpack_zip_with_progress] index={i:}, error={:#?}",
+                    error
+                );
+                error!("[extract::unpack_zip_with_progress] Failed to read entry ({error:})");
+                return Err(BeansError::ZipExtractFailure {
+                    src_file: zip_location,
+                    target_dir: output_directory,
+                    error,
+                    backtrace: Backtrace::capture()
+                });
+            }
+        };
+        // skip anything that would be extracted outside of `output_directory`
+        let filename = match entry
+            .enclosed_name()
+            .and_then(|p| p.to_str().map(String::from))
+        {
+            Some(v) => v,
+            None =>
+            {
+                warn!(
+                    "[extract::unpack_zip_with_progress] Ignoring unsafe path {}",
+                    entry.name()
+                );
+                continue;
+            }
+        };
+        let target_path = join_path(output_directory.clone(), filename.clone());
+
+        let result = if entry.is_dir()
+        {
+            std::fs::create_dir_all(&target_path)
+        }
+        else
+        {
+            if let Some(parent) = std::path::Path::new(&target_path).parent()
+            {
+                std::fs::create_dir_all(parent)?;
+            }
+            unmark_readonly_entry(output_directory.clone(), filename.clone());
+            File::create(&target_path)
+                .and_then(|mut f| std::io::copy(&mut entry, &mut f))
+                .map(|_| ())
+        };
+        if let Err(error) = result
+        {
+            debug!("error={:#?}", error);
+            debug!("entry.name={:#?}", entry.name());
+            debug!("entry.size={:#?}", entry.size());
+            if is_readonly_error(&error)
+            {
+                warn!("Failed to unpack file {filename} (Permission Denied, might be read-only)")
+            }
+            else
+            {
+                error!(
+                    "[extract::unpack_zip_with_progress] Failed to unpack file {filename} ({error:})"
+                );
+                return Err(BeansError::TarUnpackItemFailure {
+                    src_file: zip_location,
+                    target_dir: output_directory,
+                    link_name: filename,
+                    error,
+                    backtrace: Backtrace::capture()
+                });
+            }
+        }
+        unmark_readonly_entry(output_directory.clone(), filename.clone());
+        pb.inc(entry.size());
+        pb.file_extracted(filename);
+    }
+    Ok(())
+}
+
+/// Get the sum of the uncompressed size of every file in the zip file at
+/// `zip_location`.
+pub fn zip_content_size(zip_location: String) -> Option<u64>
+{
+    let file = File::open(&zip_location).ok()?;
+    let mut archive = match zip::ZipArchive::new(file)
+    {
+        Ok(v) => v,
+        Err(e) =>
+        {
+            debug!(
+                "[extract::zip_content_size] Failed to read {} ({:})",
+                zip_location, e
+            );
+            return None;
+        }
+    };
+    let mut total = 0u64;
+    for i in 0..archive.len()
+    {
+        if let Ok(entry) = archive.by_index_raw(i)
+        {
+            total += entry.size();
+        }
+    }
+    Some(total)
+}
+
+/// Check if the error provided was caused by trying to overwrite a read-only
+/// file on Windows.
+fn is_readonly_error(error: &std::io::Error) -> bool
+{
+    crate::hint::is_access_denied(error)
+}
+
+/// Unmark the file at `entry_path` (relative to `output_directory`) as
+/// read-only when it exists.
+fn unmark_readonly_entry(
+    output_directory: String,
+    entry_path: String
+)
+{
+    let target_path = join_path(output_directory, entry_path);
+    if crate::helper::file_exists(target_path.clone())
+    {
+        if let Err(e) = crate::helper::unmark_readonly(target_path.clone())
+        {
+            debug!("Failed to unmark read-only on file: {target_path:} {e:#?}");
+        }
+    }
+}
+
+/// Get the decompressed size of the zstd file at `zstd_location` from the
+/// content size field in its frame header.
+///
+/// Will return `None` when the file couldn't be read, or when the frame
+/// doesn't include the content size.
+pub fn zstd_content_size(zstd_location: String) -> Option<u64>
+{
+    let mut header = [0u8; ZSTD_FRAME_HEADER_SIZE_MAX];
+    let length = match File::open(&zstd_location).and_then(|mut f| f.read(&mut header))
+    {
+        Ok(v) => v,
+        Err(e) =>
+        {
+            debug!(
+                "[extract::zstd_content_size] Failed to read header of {} {:#?}",
+                zstd_location, e
+            );
+            return None;
+        }
+    };
+    match zstd::zstd_safe::get_frame_content_size(&header[..length])
+    {
+        Ok(v) => v,
+        Err(e) =>
+        {
+            debug!(
+                "[extract::zstd_content_size] Failed to get content size of {} ({:})",
+                zstd_location, e
+            );
+            None
+        }
+    }
+}
+
+/// indicatif template for [`create_progress_bar`].
+const PROGRESS_BAR_TEMPLATE: &str = "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})";
+/// indicatif template for [`create_spinner`].
+const SPINNER_TEMPLATE: &str =
+    "{msg}\n{spinner:.green} [{elapsed_precise}] {bytes} ({bytes_per_sec})";
+
+/// Create the progress bar that is used when decompressing or extracting
+/// files. `total` is measured in bytes.
+pub fn create_progress_bar(total: u64) -> ProgressBar
+{
+    let pb = ProgressBar::new(total);
+    pb.set_style(
+        ProgressStyle::with_template(PROGRESS_BAR_TEMPLATE)
+            .unwrap()
+            .with_key(
+                "eta",
+                |state: &indicatif::ProgressState, w: &mut dyn std::fmt::Write| {
+                    write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
+                }
+            )
+            .progress_chars("#>-")
+    );
+    pb
+}
+
+/// Create a spinner that shows the amount of bytes written, for when the
+/// total amount isn't known before decompressing.
+pub fn create_spinner() -> ProgressBar
+{
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(ProgressStyle::with_template(SPINNER_TEMPLATE).unwrap());
+    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    pb
+}
+
+/// Maximum size of a zstd frame header. Used by [`zstd_content_size`].
+const ZSTD_FRAME_HEADER_SIZE_MAX: usize = 18;
+
+/// Container and compression of a package, detected from the magic bytes at
+/// the start of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackageFormat
+{
+    /// `.tar.zst`, which is what the remote server provides.
+    TarZstd,
+    TarXz,
+    TarGzip,
+    /// Plain `.tar`, doesn't need to be decompressed.
+    Tar,
+    Zip,
+    /// Formats that are known, but can't be installed. Value is the name of
+    /// the format.
+    Unsupported(&'static str),
+    /// Value is the first few bytes of the file formatted as hex.
+    Unknown(String)
+}
+
+impl PackageFormat
+{
+    /// Read the magic bytes of the file at `location` and detect its format.
+    pub fn detect(location: String) -> Result<PackageFormat, BeansError>
+    {
+        let mut header = [0u8; 512];
+        let mut file = match File::open(&location)
+        {
+            Ok(v) => v,
+            Err(error) =>
+            {
+                return Err(BeansError::FileOpenFailure {
+                    location,
+                    error
+                });
+            }
+        };
+        let mut length = 0;
+        while length < header.len()
+        {
+            match file.read(&mut header[length..])?
+            {
+                0 => break,
+                n => length += n
+            }
+        }
+        let format = Self::from_magic(&header[..length]);
+        debug!("[PackageFormat::detect] {} is {:?}", location, format);
+        Ok(format)
+    }
+
+    /// Detect the format from the start of a file. `header` should be at least
+    /// 262 bytes long so plain tarballs can be detected.
+    pub fn from_magic(header: &[u8]) -> PackageFormat
+    {
+        let starts = |magic: &[u8]| header.starts_with(magic);
+        if starts(&[0x28, 0xB5, 0x2F, 0xFD])
+        {
+            PackageFormat::TarZstd
+        }
+        else if starts(&[0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00])
+        {
+            PackageFormat::TarXz
+        }
+        else if starts(&[0x1F, 0x8B])
+        {
+            PackageFormat::TarGzip
+        }
+        else if starts(&[0x50, 0x4B, 0x03, 0x04]) || starts(&[0x50, 0x4B, 0x05, 0x06])
+        {
+            PackageFormat::Zip
+        }
+        else if header.len() >= 262 && &header[257..262] == b"ustar"
+        {
+            PackageFormat::Tar
+        }
+        else if starts(b"BZh")
+        {
+            PackageFormat::Unsupported("bzip2")
+        }
+        else if starts(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C])
+        {
+            PackageFormat::Unsupported("7-Zip")
+        }
+        else if starts(b"Rar!")
+        {
+            PackageFormat::Unsupported("RAR")
+        }
+        else if starts(&[0x04, 0x22, 0x4D, 0x18])
+        {
+            PackageFormat::Unsupported("lz4")
+        }
+        else
+        {
+            let hex: Vec<String> = header
+                .iter()
+                .take(8)
+                .map(|b| format!("{:02X}", b))
+                .collect();
+            PackageFormat::Unknown(hex.join(" "))
+        }
+    }
+
+    /// Is this a tarball that must be decompressed before it can be
+    /// extracted?
+    pub fn is_compressed_tar(&self) -> bool
+    {
+        matches!(
+            self,
+            PackageFormat::TarZstd | PackageFormat::TarXz | PackageFormat::TarGzip
+        )
+    }
+}
+
+impl std::fmt::Display for PackageFormat
+{
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>
+    ) -> std::fmt::Result
+    {
+        match self
+        {
+            PackageFormat::TarZstd => write!(f, "tar.zst"),
+            PackageFormat::TarXz => write!(f, "tar.xz"),
+            PackageFormat::TarGzip => write!(f, "tar.gz"),
+            PackageFormat::Tar => write!(f, "tar"),
+            PackageFormat::Zip => write!(f, "zip"),
+            PackageFormat::Unsupported(name) => write!(f, "{}", name),
+            PackageFormat::Unknown(hex) => write!(f, "unknown (starts with {})", hex)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{io::Write,
+              path::{Path,
+                     PathBuf}};
+
+    use super::*;
+    use crate::{config::{Config,
+                         ConfigValues},
+                events::{ChannelSink,
+                         Event},
+                RunnerContext};
+
+    /// Files in the packages that are created by [`create_tar`] and
+    /// [`create_zip`].
+    const PACKAGE_FILES: &[(&str, &[u8])] = &[
+        ("open_fortress/gameinfo.txt", b"\"GameInfo\"\n{\n}\n"),
+        ("open_fortress/cfg/config.cfg", b"name beans\n"),
+        ("open_fortress/maps/empty.bsp", b"")
+    ];
+
+    fn create_test_dir(name: &str) -> PathBuf
+    {
+        let root =
+            std::env::temp_dir().join(format!("beans-extract-{}-{}", name, std::process::id()));
+        if root.exists()
+        {
+            std::fs::remove_dir_all(&root).unwrap();
+        }
+        std::fs::create_dir_all(root.join("out")).unwrap();
+        root
+    }
+
+    /// Create a tarball with [`PACKAGE_FILES`], and an entry for each path in
+    /// `unsafe_paths` that isn't checked by `tar::Builder`.
+    fn create_tar(unsafe_paths: &[&str]) -> Vec<u8>
+    {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content) in PACKAGE_FILES.iter()
+        {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *content).unwrap();
+        }
+        for path in unsafe_paths.iter()
+        {
+            let mut header = tar::Header::new_gnu();
+            header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(4);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, &b"evil"[..]).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn create_zip(unsafe_paths: &[&str]) -> Vec<u8>
+    {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        writer.add_directory("open_fortress/", options).unwrap();
+        for (path, content) in PACKAGE_FILES.iter()
+        {
+            writer.start_file(*path, options).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        for path in unsafe_paths.iter()
+        {
+            writer.start_file(*path, options).unwrap();
+            writer.write_all(b"evil").unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Write `content` to `package` in `root`, then extract it to `root/out`.
+    /// Returns the events that were emitted.
+    fn extract(
+        root: &Path,
+        package: &str,
+        content: &[u8]
+    ) -> Result<Vec<Event>, BeansError>
+    {
+        let location = root.join(package);
+        std::fs::write(&location, content).unwrap();
+        let (events, handle) = ChannelSink::new();
+        // so the decompressed tarball is written to `root`.
+        let config = Config::new(
+            ConfigValues {
+                tmp_dir: Some(root.to_string_lossy().to_string()),
+                ..Default::default()
+            },
+            ConfigValues::default(),
+            ConfigValues::default()
+        );
+        RunnerContext::extract_package(
+            location.to_string_lossy().to_string(),
+            root.join("out").to_string_lossy().to_string(),
+            None,
+            &config,
+            &events
+        )?;
+        Ok(handle.events.try_iter().collect())
+    }
+
+    fn assert_extracted(root: &Path)
+    {
+        for (path, content) in PACKAGE_FILES.iter()
+        {
+            assert_eq!(
+                std::fs::read(root.join("out").join(path)).unwrap(),
+                *content,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn extract_package_round_trip()
+    {
+        let tar = create_tar(&[]);
+        let packages: Vec<(&str, Vec<u8>)> = vec![
+            ("package.tar.zst", zstd::encode_all(&tar[..], 3).unwrap()),
+            ("package.tar.xz", {
+                let mut e = xz2::write::XzEncoder::new(Vec::new(), 6);
+                e.write_all(&tar).unwrap();
+                e.finish().unwrap()
+            }),
+            ("package.tar.gz", {
+                let mut e =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                e.write_all(&tar).unwrap();
+                e.finish().unwrap()
+            }),
+            ("package.tar", tar.clone()),
+            ("package.zip", create_zip(&[])),
+        ];
+        for (name, content) in packages.iter()
+        {
+            let root = create_test_dir(name);
+            let events = match extract(&root, name, content)
+            {
+                Ok(v) => v,
+                Err(e) => panic!("failed to extract {}: {:#?}", name, e)
+            };
+            assert_extracted(&root);
+
+            // decompressing and extracting share one progress bar, which
+            // ends at it's total.
+            let started: Vec<&Phase> = events
+                .iter()
+                .filter_map(|e| match e
+                {
+                    Event::PhaseStarted {
+                        phase, ..
+                    } => Some(phase),
+                    _ => None
+                })
+                .collect();
+            assert_eq!(started, vec![&Phase::Extract], "{}", name);
+            if name.starts_with("package.tar.")
+            {
+                match events.iter().rev().find_map(|e| match e
+                {
+                    Event::Progress {
+                        current,
+                        total,
+                        ..
+                    } => Some((*current, *total)),
+                    _ => None
+                })
+                {
+                    Some((current, Some(total))) =>
+                    {
+                        assert_eq!(current, total, "{}", name);
+                        assert_eq!(total, tar.len() as u64 * 2, "{}", name);
+                    }
+                    v => panic!("{}: unexpected progress {:?}", name, v)
+                }
+            }
+            std::fs::remove_dir_all(&root).unwrap();
+        }
+    }
+
+    #[test]
+    fn extract_package_skips_unsafe_paths()
+    {
+        let unsafe_paths = ["../outside.txt", "open_fortress/../../outside2.txt"];
+        let packages = [
+            ("unsafe.tar", create_tar(&unsafe_paths)),
+            ("unsafe.zip", create_zip(&unsafe_paths))
+        ];
+        for (name, content) in packages.iter()
+        {
+            let root = create_test_dir(name);
+            extract(&root, name, content).unwrap();
+            assert_extracted(&root);
+            assert!(!root.join("outside.txt").exists(), "{}", name);
+            assert!(!root.join("outside2.txt").exists(), "{}", name);
+            std::fs::remove_dir_all(&root).unwrap();
+        }
+    }
+
+    /// Errors other than a read-only file on Windows stop the extraction.
+    #[test]
+    fn extract_package_fails_when_file_cant_be_written()
+    {
+        let packages = [
+            ("blocked.tar", create_tar(&[])),
+            ("blocked.zip", create_zip(&[]))
+        ];
+        for (name, content) in packages.iter()
+        {
+            let root = create_test_dir(name);
+            // a directory where a file should be extracted can't be replaced.
+            std::fs::create_dir_all(root.join("out/open_fortress/cfg/config.cfg/keep")).unwrap();
+            match extract(&root, name, content)
+            {
+                Err(BeansError::TarUnpackItemFailure {
+                    link_name, ..
+                }) =>
+                {
+                    assert!(
+                        link_name.ends_with("cfg/config.cfg"),
+                        "{} {}",
+                        name,
+                        link_name
+                    )
+                }
+                v => panic!("{} {:#?}", name, v)
+            }
+            std::fs::remove_dir_all(&root).unwrap();
+        }
+    }
+
+    #[test]
+    fn readonly_error_is_only_access_denied_on_windows()
+    {
+        assert_eq!(
+            is_readonly_error(&std::io::Error::from_raw_os_error(5)),
+            cfg!(target_os = "windows")
+        );
+        assert!(!is_readonly_error(&std::io::Error::from(
+            std::io::ErrorKind::PermissionDenied
+        )));
+        assert!(!is_readonly_error(&std::io::Error::from(
+            std::io::ErrorKind::NotFound
+        )));
+    }
+
+    /// Read-only files (like `gameinfo.txt`) are replaced on Windows.
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn extract_package_replaces_readonly_files()
+    {
+        let packages = [
+            ("readonly.tar", create_tar(&[])),
+            ("readonly.zip", create_zip(&[]))
+        ];
+        for (name, content) in packages.iter()
+        {
+            let root = create_test_dir(name);
+            let gameinfo = root.join("out/open_fortress/gameinfo.txt");
+            std::fs::create_dir_all(gameinfo.parent().unwrap()).unwrap();
+            std::fs::write(&gameinfo, b"old").unwrap();
+            let mut perms = std::fs::metadata(&gameinfo).unwrap().permissions();
+            perms.set_readonly(true);
+            std::fs::set_permissions(&gameinfo, perms).unwrap();
+
+            extract(&root, name, content).unwrap();
+            assert_extracted(&root);
+            std::fs::remove_dir_all(&root).unwrap();
+        }
+    }
+
+    /// `header` padded to 512 bytes, with `ustar` at offset 257 when `tar` is
+    /// `true`.
+    fn padded(
+        header: &[u8],
+        tar: bool
+    ) -> Vec<u8>
+    {
+        let mut result = header.to_vec();
+        result.resize(512, 0);
+        if tar
+        {
+            result[257..262].copy_from_slice(b"ustar");
+        }
+        result
+    }
+
+    #[test]
+    fn package_format_from_magic()
+    {
+        let cases: &[(&[u8], bool, PackageFormat)] = &[
+            (&[0x28, 0xB5, 0x2F, 0xFD], false, PackageFormat::TarZstd),
+            (
+                &[0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00],
+                false,
+                PackageFormat::TarXz
+            ),
+            (&[0x1F, 0x8B, 0x08], false, PackageFormat::TarGzip),
+            (&[0x50, 0x4B, 0x03, 0x04], false, PackageFormat::Zip),
+            // empty zip
+            (&[0x50, 0x4B, 0x05, 0x06], false, PackageFormat::Zip),
+            (b"open_fortress/gameinfo.txt", true, PackageFormat::Tar),
+            // magic bytes at the start are used before `ustar`.
+            (&[0x28, 0xB5, 0x2F, 0xFD], true, PackageFormat::TarZstd),
+            (b"BZh91AY", false, PackageFormat::Unsupported("bzip2")),
+            (
+                &[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C],
+                false,
+                PackageFormat::Unsupported("7-Zip")
+            ),
+            (b"Rar!\x1A\x07", false, PackageFormat::Unsupported("RAR")),
+            (
+                &[0x04, 0x22, 0x4D, 0x18],
+                false,
+                PackageFormat::Unsupported("lz4")
+            ),
+            (
+                b"hello world",
+                false,
+                PackageFormat::Unknown(String::from("68 65 6C 6C 6F 20 77 6F"))
+            )
+        ];
+        for (header, tar, expected) in cases.iter()
+        {
+            assert_eq!(
+                &PackageFormat::from_magic(&padded(header, *tar)),
+                expected,
+                "header {:02X?}",
+                header
+            );
+        }
+
+        // too short to have `ustar`, and the start doesn't match anything.
+        assert_eq!(
+            PackageFormat::from_magic(&[0x00, 0x01]),
+            PackageFormat::Unknown(String::from("00 01"))
+        );
+        assert_eq!(
+            PackageFormat::from_magic(&[]),
+            PackageFormat::Unknown(String::new())
+        );
+    }
+}
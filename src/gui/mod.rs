@@ -118,9 +118,6 @@ pub fn wait_for_quit(
     {
         if let Some(GUIAppStatus::Quit) = receive_action.recv()
         {
-            unsafe {
-                crate::PAUSE_ONCE_DONE = false;
-            }
             app.quit();
         }
     }
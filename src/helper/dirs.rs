@@ -0,0 +1,428 @@
+//! Per-user directories that beans stores it's files in.
+//!
+//! | Directory | Linux                               | Windows                           |
+//! |-----------|-------------------------------------|-----------------------------------|
+//! | config    | `$XDG_CONFIG_HOME/beans-rs`         | `%APPDATA%\beans-rs`              |
+//! | cache     | `$XDG_CACHE_HOME/beans-rs`          | `%LOCALAPPDATA%\beans-rs\cache`   |
+//! | tmp       | `tmp` in the cache directory        | `%TEMP%\beans-rs`                 |
+//!
+//! When the XDG variables aren't set, `~/.config` and `~/.cache` are used.
+//! Embedded binaries are extracted to `bin`, and downloads are written to
+//! `downloads` in the cache directory (see `get_bin_dir` for when the cache is
+//! mounted with `noexec`).
+//!
+//! Before these existed, everything was written to one shared directory
+//! (`/var/tmp/beans-rs` on Linux). Files in there are moved out by
+//! `migrate_legacy_dirs`.
+use std::{path::Path,
+          sync::OnceLock};
+
+use log::{debug,
+          error,
+          info,
+          trace,
+          warn};
+
+use super::{dir_exists,
+            format_directory_path,
+            generate_rand_str,
+            is_noexec,
+            is_steamdeck,
+            join_path,
+            use_custom_tmpdir};
+use crate::config::Config;
+
+const DIR_NAME: &str = "beans-rs";
+
+#[derive(Debug, Clone, Copy)]
+enum UserDir
+{
+    Config,
+    Cache
+}
+
+impl UserDir
+{
+    fn name(&self) -> &'static str
+    {
+        match self
+        {
+            UserDir::Config => "config",
+            UserDir::Cache => "cache"
+        }
+    }
+}
+
+/// Home directory of the current user, from `HOME` (or `USERPROFILE` on
+/// Windows). `None` when it isn't set, or isn't an absolute path.
+pub fn home_dir() -> Option<String>
+{
+    let key = match cfg!(target_os = "windows")
+    {
+        true => "USERPROFILE",
+        false => "HOME"
+    };
+    let home = super::try_get_env_var(key.to_string())?;
+    match Path::new(&home).is_absolute()
+    {
+        true => Some(format_directory_path(home)),
+        false => None
+    }
+}
+
+/// `$XDG_DATA_HOME`, or `~/.local/share` when it isn't set (or is relative).
+#[cfg(not(target_os = "windows"))]
+pub fn data_dir() -> Option<String>
+{
+    match super::try_get_env_var(String::from("XDG_DATA_HOME"))
+    {
+        Some(x) if Path::new(&x).is_absolute() => Some(x),
+        _ => Some(join_path(home_dir()?, String::from(".local/share")))
+    }
+}
+
+/// Location of `kind`, following the XDG Base Directory spec. Relative paths
+/// in the `XDG_*` variables are ignored, like the spec says.
+#[cfg(not(target_os = "windows"))]
+fn find_user_dir(kind: UserDir) -> Option<String>
+{
+    let (key, fallback) = match kind
+    {
+        UserDir::Config => ("XDG_CONFIG_HOME", ".config"),
+        UserDir::Cache => ("XDG_CACHE_HOME", ".cache")
+    };
+    let base = match super::try_get_env_var(key.to_string())
+    {
+        Some(x) if Path::new(&x).is_absolute() => x,
+        _ => join_path(home_dir()?, fallback.to_string())
+    };
+    Some(join_path(base, DIR_NAME.to_string()))
+}
+
+#[cfg(target_os = "windows")]
+fn find_user_dir(kind: UserDir) -> Option<String>
+{
+    let key = match kind
+    {
+        UserDir::Config => "APPDATA",
+        UserDir::Cache => "LOCALAPPDATA"
+    };
+    let base = super::try_get_env_var(key.to_string()).filter(|v| !v.is_empty())?;
+    let dir = join_path(base, DIR_NAME.to_string());
+    Some(match kind
+    {
+        UserDir::Config => dir,
+        _ => join_path(dir, kind.name().to_string())
+    })
+}
+
+/// Get the location of `kind`. When it can't be found (like when there is no
+/// home directory), a directory in `std::env::temp_dir()` is used instead.
+fn user_dir_location(kind: UserDir) -> String
+{
+    match find_user_dir(kind)
+    {
+        Some(v) => format_directory_path(v),
+        None =>
+        {
+            let tmp = std::env::temp_dir().to_str().unwrap_or("").to_string();
+            let v = join_path(tmp, format!("{}-{}", DIR_NAME, kind.name()));
+            warn!(
+                "[helper::user_dir_location] Couldn't find the {} directory, using {} instead",
+                kind.name(),
+                v
+            );
+            format_directory_path(v)
+        }
+    }
+}
+
+/// Same as `user_dir_location`, but the directory is created.
+fn get_user_dir(kind: UserDir) -> String
+{
+    create_user_dir(user_dir_location(kind))
+}
+
+/// Create `dir` (and it's parents) when it doesn't exist.
+fn create_user_dir(dir: String) -> String
+{
+    let dir = format_directory_path(dir);
+    if !dir_exists(dir.clone())
+    {
+        if let Err(e) = std::fs::create_dir_all(&dir)
+        {
+            trace!("[helper::create_user_dir] {:#?}", e);
+            warn!(
+                "[helper::create_user_dir] failed to make directory at {} ({:})",
+                dir, e
+            );
+            sentry::capture_error(&e);
+        }
+        else
+        {
+            trace!("[helper::create_user_dir] created directory {}", dir);
+        }
+    }
+    dir
+}
+
+/// Directory for configuration files.
+pub fn get_config_dir() -> String
+{
+    get_user_dir(UserDir::Config)
+}
+
+/// Directory for files that can be deleted without losing anything, like
+/// downloads and the embedded binaries.
+///
+/// Will be `beans-rs` in `tmp_dir` from the config (`ADASTRAL_TMPDIR`) when
+/// it's set.
+pub fn get_cache_dir(config: &Config) -> String
+{
+    create_user_dir(find_cache_dir(config))
+}
+
+/// Location of `get_cache_dir`, without creating it.
+pub fn find_cache_dir(config: &Config) -> String
+{
+    if let Some(x) = use_custom_tmpdir(config)
+    {
+        return format_directory_path(join_path(x, DIR_NAME.to_string()));
+    }
+    user_dir_location(UserDir::Cache)
+}
+
+/// Directory that the embedded binaries (butler, aria2c) are extracted to.
+///
+/// This is `bin` in `get_cache_dir`, unless it's on a filesystem that is
+/// mounted with `noexec` (like `/tmp` on hardened systems), since butler
+/// couldn't be run from there. Then the first of these that isn't `noexec` is
+/// used;
+/// - `$XDG_DATA_HOME/beans-rs/bin` (or `~/.local/share/beans-rs/bin`)
+/// - `$XDG_RUNTIME_DIR/beans-rs/bin`
+///
+/// The directory is only picked once, so it's the same for the rest of the
+/// process.
+pub fn get_bin_dir(config: &Config) -> String
+{
+    static BIN_DIR: OnceLock<String> = OnceLock::new();
+    create_user_dir(BIN_DIR.get_or_init(|| find_bin_dir(config)).clone())
+}
+
+fn find_bin_dir(config: &Config) -> String
+{
+    let default = create_user_dir(join_path(get_cache_dir(config), String::from("bin")));
+    if !is_noexec(default.clone())
+    {
+        return default;
+    }
+
+    let mut candidates: Vec<String> = Vec::new();
+    if let Some(x) = data_dir()
+    {
+        candidates.push(x);
+    }
+    if let Some(x) = super::try_get_env_var(String::from("XDG_RUNTIME_DIR"))
+        && Path::new(&x).is_absolute()
+    {
+        candidates.push(x);
+    }
+    for c in candidates
+    {
+        let dir = create_user_dir(join_path(c, format!("{DIR_NAME}/bin")));
+        if dir_exists(dir.clone()) && !is_noexec(dir.clone())
+        {
+            warn!("[helper::get_bin_dir] {default} is on a filesystem that is mounted with noexec, so {dir} will be used for butler instead");
+            return dir;
+        }
+    }
+    error!("[helper::get_bin_dir] Programs can't be run from {default}, since it's on a filesystem that is mounted with noexec, and there isn't anywhere else that butler can be run from. Set tmp_dir in the config (or ADASTRAL_TMPDIR) to a directory that isn't mounted with noexec, or set butler_path (or BEANS_BUTLER_PATH) to the location of butler.");
+    default
+}
+
+/// Directory that packages and patches are downloaded to.
+pub fn get_download_dir(config: &Config) -> String
+{
+    create_user_dir(join_path(get_cache_dir(config), String::from("downloads")))
+}
+
+/// Generate a full file location for a download, in `get_download_dir`.
+pub fn get_download_file(
+    config: &Config,
+    filename: String
+) -> String
+{
+    let head = format!("{}_{}", generate_rand_str(8), filename);
+    join_path(get_download_dir(config), head)
+}
+
+/// Directory for temporary files (like packages that are being extracted).
+///
+/// - `tmp` in `get_cache_dir` (which honours `tmp_dir` in the config)
+/// - `~/.tmp/beans-rs` on the Steam Deck, when `tmp_dir` isn't set
+/// - `%TEMP%\beans-rs` on Windows
+///
+/// `/tmp` isn't used on Linux since it's usually in memory, and too small for
+/// a package.
+pub fn get_tmp_dir(config: &Config) -> String
+{
+    create_user_dir(find_tmp_dir(config))
+}
+
+/// Location of `get_tmp_dir`, without creating it.
+pub fn find_tmp_dir(config: &Config) -> String
+{
+    if use_custom_tmpdir(config).is_none()
+    {
+        if is_steamdeck()
+        {
+            trace!("[helper::find_tmp_dir] Detected that we are running on a steam deck. Using ~/.tmp/beans-rs");
+            if let Some(home) = home_dir()
+            {
+                return format_directory_path(join_path(home, format!(".tmp/{DIR_NAME}")));
+            }
+            trace!("[helper::find_tmp_dir] Failed to get home directory.");
+        }
+        if cfg!(target_os = "windows")
+        {
+            let tmp = std::env::temp_dir().to_str().unwrap_or("").to_string();
+            return format_directory_path(join_path(tmp, DIR_NAME.to_string()));
+        }
+    }
+    format_directory_path(join_path(find_cache_dir(config), String::from("tmp")))
+}
+
+/// Location of the shared directory that was used for everything (embedded
+/// binaries, downloads, and temporary files) by older versions of beans.
+fn get_legacy_dir(config: &Config) -> String
+{
+    let dir = if let Some(x) = use_custom_tmpdir(config)
+    {
+        x
+    }
+    else if is_steamdeck()
+        && let Some(home) = home_dir()
+    {
+        join_path(home, String::from(".tmp"))
+    }
+    else if cfg!(target_os = "android")
+    {
+        String::from("/data/var/tmp")
+    }
+    else if cfg!(not(target_os = "windows"))
+    {
+        String::from("/var/tmp")
+    }
+    else
+    {
+        std::env::temp_dir().to_str().unwrap_or("").to_string()
+    };
+    format_directory_path(join_path(dir, DIR_NAME.to_string()))
+}
+
+/// Check if `location` belongs to the user that is running beans. Always
+/// true on Windows, since the old directory is in `%TEMP%` there.
+#[cfg(not(target_os = "windows"))]
+fn is_owned_by_current_user(location: &str) -> bool
+{
+    use std::os::unix::fs::MetadataExt;
+    match std::fs::metadata(location)
+    {
+        Ok(m) => m.uid() == unsafe { libc::getuid() },
+        Err(_) => false
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn is_owned_by_current_user(_location: &str) -> bool
+{
+    true
+}
+
+/// Move files out of the shared directory that older versions of beans used
+/// (see `get_legacy_dir`). Files named in `binaries` are deleted, since
+/// they're extracted again to `get_bin_dir`. Everything else is moved to
+/// `get_tmp_dir`, and the old directory is removed once it's empty.
+///
+/// Does nothing when the old directory doesn't exist, or when it belongs to
+/// another user.
+pub fn migrate_legacy_dirs(
+    config: &Config,
+    binaries: &[&str]
+)
+{
+    let legacy_dir = get_legacy_dir(config);
+    if !dir_exists(legacy_dir.clone())
+    {
+        return;
+    }
+    if !is_owned_by_current_user(&legacy_dir)
+    {
+        debug!(
+            "[helper::migrate_legacy_dirs] {legacy_dir} belongs to another user, leaving it alone"
+        );
+        return;
+    }
+    let entries = match std::fs::read_dir(&legacy_dir)
+    {
+        Ok(v) => v,
+        Err(e) =>
+        {
+            debug!("[helper::migrate_legacy_dirs] Failed to read {legacy_dir} ({e:})");
+            return;
+        }
+    };
+    let tmp_dir = get_tmp_dir(config);
+    // on Windows, the Steam Deck, or with ADASTRAL_TMPDIR, the old directory
+    // is also the new temporary (or cache) directory.
+    let same_dir = Path::new(&legacy_dir) == Path::new(&tmp_dir);
+    let keep_dir = same_dir || Path::new(&legacy_dir) == Path::new(&get_cache_dir(config));
+
+    let mut moved: usize = 0;
+    for entry in entries.flatten()
+    {
+        if !entry.file_type().is_ok_and(|t| t.is_file())
+        {
+            continue;
+        }
+        let location = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if binaries.contains(&name.as_str())
+        {
+            match std::fs::remove_file(&location)
+            {
+                Ok(_) => debug!(
+                    "[helper::migrate_legacy_dirs] Removed {}",
+                    location.display()
+                ),
+                Err(e) => debug!(
+                    "[helper::migrate_legacy_dirs] Failed to remove {} ({e:})",
+                    location.display()
+                )
+            }
+            continue;
+        }
+        if same_dir
+        {
+            continue;
+        }
+        let target = Path::new(&tmp_dir).join(&name);
+        match std::fs::rename(&location, &target)
+        {
+            Ok(_) => moved += 1,
+            Err(e) => warn!(
+                "[helper::migrate_legacy_dirs] Failed to move {} to {} ({e:}), it can be deleted",
+                location.display(),
+                target.display()
+            )
+        }
+    }
+    if moved > 0
+    {
+        info!("[helper::migrate_legacy_dirs] Moved {moved} file(s) from {legacy_dir} to {tmp_dir}");
+    }
+    if !keep_dir && std::fs::remove_dir(&legacy_dir).is_ok()
+    {
+        info!("[helper::migrate_legacy_dirs] Removed {legacy_dir}");
+    }
+}
@@ -1,4 +1,7 @@
-use std::fs::read_to_string;
+use std::{fs::read_to_string,
+          os::unix::fs::{DirBuilderExt,
+                         MetadataExt,
+                         PermissionsExt}};
 
 use log::{debug,
           error};
@@ -54,42 +57,394 @@ pub fn find_sourcemod_path() -> Result<String, BeansError>
 /// returns the first item in STEAM_POSSIBLE_DIR that exists. otherwise None
 fn find_steam_reg_path() -> Result<String, BeansError>
 {
+    let home = match crate::helper::home_dir()
+    {
+        Some(v) => v,
+        None =>
+        {
+            debug!("[helper::find_steam_reg_path] helper::home_dir() returned None!");
+            return Err(BeansError::SteamNotFound);
+        }
+    };
     for x in STEAM_POSSIBLE_DIR.iter()
     {
-        match simple_home_dir::home_dir()
+        let reg_loc = x.replace("~", home.as_str());
+        if crate::helper::file_exists(reg_loc.clone())
+        {
+            return Ok(reg_loc.clone());
+        }
+    }
+    error!("Couldn't find any of the locations in STEAM_POSSIBLE_DIR");
+    Err(BeansError::SteamNotFound)
+}
+
+pub fn unmark_readonly(location: String) -> Result<(), BeansError>
+{
+    // does nothing since this function only
+    // matters for windows
+    // -kate, 13th mar 2025
+    Ok(())
+}
+
+/// Move `location` into the freedesktop.org trash for the current user. See
+/// `find_trash_dir` for which trash directory is used.
+///
+/// Returns the location of where `location` was moved to.
+pub fn move_to_trash(location: String) -> Result<String, BeansError>
+{
+    let trash_dir = match find_trash_dir(location.clone())
+    {
+        Some(v) => v,
+        None =>
+        {
+            return Err(BeansError::TrashFailure {
+                location,
+                reason: String::from("Couldn't find a trash directory on the same filesystem")
+            });
+        }
+    };
+    let files_dir = crate::helper::join_path(trash_dir.clone(), String::from("files"));
+    let info_dir = crate::helper::join_path(trash_dir.clone(), String::from("info"));
+    for d in [&files_dir, &info_dir]
+    {
+        // the trash (and everything in it) should only be readable by the user.
+        if let Err(e) = std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(d)
+        {
+            return Err(BeansError::TrashFailure {
+                location,
+                reason: format!("Failed to create {d} ({e:})")
+            });
+        }
+    }
+
+    let absolute_location = match std::path::absolute(&location)
+    {
+        Ok(v) => v.to_string_lossy().to_string(),
+        Err(_) => location.clone()
+    };
+    let name = crate::helper::get_filename(absolute_location.clone());
+    let trashinfo = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        trash_encode_path(&absolute_location),
+        chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+    );
+
+    // the .trashinfo file has to be created first (with O_EXCL) so we know that the
+    // name isn't being used by something else.
+    let mut index = 1;
+    let mut trash_name = name.clone();
+    let info_location = loop
+    {
+        let info_location =
+            crate::helper::join_path(info_dir.clone(), format!("{trash_name}.trashinfo"));
+        let in_use = crate::helper::join_path(files_dir.clone(), trash_name.clone());
+        if std::fs::symlink_metadata(&in_use).is_err()
         {
-            Some(v) => match v.to_str()
+            match std::fs::File::create_new(&info_location)
             {
-                Some(k) =>
+                Ok(mut f) =>
                 {
-                    let h = format_directory_path(k.to_string());
-                    let reg_loc = x.replace("~", h.as_str());
-                    if crate::helper::file_exists(reg_loc.clone())
+                    use std::io::Write;
+                    if let Err(e) = f.write_all(trashinfo.as_bytes())
                     {
-                        return Ok(reg_loc.clone());
+                        let _ = std::fs::remove_file(&info_location);
+                        return Err(BeansError::TrashFailure {
+                            location,
+                            reason: format!("Failed to write {info_location} ({e:})")
+                        });
                     }
+                    break info_location;
                 }
-                None =>
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists =>
+                {}
+                Err(e) =>
                 {
-                    debug!("[helper::find_steam_reg_path] simple_home_dir::home_dir().to_str() returned None!");
-                    return Err(BeansError::SteamNotFound);
+                    return Err(BeansError::TrashFailure {
+                        location,
+                        reason: format!("Failed to create {info_location} ({e:})")
+                    });
                 }
-            },
-            None =>
-            {
-                debug!("[helper::find_steam_reg_path] simple_home_dir::home_dir() returned None!");
-                return Err(BeansError::SteamNotFound);
             }
         }
+        index += 1;
+        trash_name = format!("{name}.{index}");
+    };
+
+    let target = crate::helper::join_path(files_dir, trash_name);
+    debug!("[helper::move_to_trash] {absolute_location} -> {target}");
+    if let Err(e) = std::fs::rename(&absolute_location, &target)
+    {
+        let _ = std::fs::remove_file(&info_location);
+        let reason = if e.raw_os_error() == Some(18)
+        {
+            format!("{location} is on a different filesystem to {trash_dir}")
+        }
+        else
+        {
+            format!("{e:}")
+        };
+        return Err(BeansError::TrashFailure {
+            location,
+            reason
+        });
     }
-    error!("Couldn't find any of the locations in STEAM_POSSIBLE_DIR");
-    Err(BeansError::SteamNotFound)
+    Ok(target)
 }
 
-pub fn unmark_readonly(location: String) -> Result<(), BeansError>
+/// Find the trash directory that `move_to_trash` will use for `location`.
+/// Nothing is created.
+///
+/// Files can only be moved into a trash on the same filesystem, so this is the
+/// home trash (`$XDG_DATA_HOME/Trash`, or `~/.local/share/Trash`) when
+/// `location` is on the same filesystem as it. Otherwise, the trash at the top
+/// of the filesystem that `location` is on is used (like a second Steam
+/// library drive). That is `$topdir/.Trash/$uid` when `$topdir/.Trash` is a
+/// directory with the sticky bit set, otherwise `$topdir/.Trash-$uid`.
+///
+/// Returns `None` when the home directory or the top of the filesystem
+/// couldn't be found.
+pub fn find_trash_dir(location: String) -> Option<String>
 {
-    // does nothing since this function only
-    // matters for windows
-    // -kate, 13th mar 2025
-    Ok(())
+    let path = std::path::absolute(&location).ok()?;
+    let device = std::fs::symlink_metadata(&path).ok()?.dev();
+
+    if let Some(home_trash) = find_home_trash_dir()
+    {
+        // the home trash might not exist yet, so check the closest parent that
+        // does.
+        let home_device = std::path::Path::new(&home_trash)
+            .ancestors()
+            .find_map(|p| std::fs::metadata(p).ok())
+            .map(|m| m.dev());
+        if home_device == Some(device)
+        {
+            return Some(home_trash);
+        }
+    }
+
+    let topdir = path
+        .ancestors()
+        .take_while(|p| std::fs::symlink_metadata(p).is_ok_and(|m| m.dev() == device))
+        .last()?
+        .to_path_buf();
+    let uid = unsafe { libc::getuid() };
+    Some(find_topdir_trash_dir(&topdir, uid))
+}
+
+/// Get the trash directory for `uid` at the top of a filesystem (`topdir`).
+/// See `find_trash_dir`.
+fn find_topdir_trash_dir(
+    topdir: &std::path::Path,
+    uid: u32
+) -> String
+{
+    let shared_trash = topdir.join(".Trash");
+    // the shared trash must not be used when it's a symlink, or when the sticky
+    // bit isn't set, since other users could mess with it.
+    let shared_trash_valid = std::fs::symlink_metadata(&shared_trash)
+        .is_ok_and(|m| m.is_dir() && m.permissions().mode() & 0o1000 != 0);
+    let trash_dir = match shared_trash_valid
+    {
+        true => shared_trash.join(uid.to_string()),
+        false => topdir.join(format!(".Trash-{uid}"))
+    };
+    trash_dir.to_string_lossy().to_string()
+}
+
+/// Get the location of the home trash directory. Doesn't check if it exists.
+fn find_home_trash_dir() -> Option<String>
+{
+    Some(crate::helper::join_path(
+        crate::helper::data_dir()?,
+        String::from("Trash")
+    ))
+}
+
+/// Percent-encode `location` for the `Path` key in a `.trashinfo` file.
+fn trash_encode_path(location: &str) -> String
+{
+    let mut result = String::new();
+    for b in location.bytes()
+    {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b)
+        {
+            result.push(b as char);
+        }
+        else
+        {
+            result.push_str(&format!("%{:02X}", b));
+        }
+    }
+    result
+}
+
+/// Check if `location` is on a filesystem that is mounted with `noexec` (from
+/// `/proc/self/mountinfo`), which means that programs in it can't be run.
+///
+/// Returns `false` when the mount options couldn't be found (like on macOS).
+pub fn is_noexec(location: String) -> bool
+{
+    let content = match read_to_string("/proc/self/mountinfo")
+    {
+        Ok(v) => v,
+        Err(e) =>
+        {
+            debug!("[helper::is_noexec] Failed to read /proc/self/mountinfo ({e:})");
+            return false;
+        }
+    };
+    let path = std::fs::canonicalize(&location).unwrap_or_else(|_| location.clone().into());
+    mountinfo_is_noexec(&content, &path)
+}
+
+/// Check if `path` is on a mount that has `noexec` in it's mount options,
+/// with the content of `/proc/self/mountinfo`. Only the per-mount options
+/// (the 6th field) are used, not the super options after the ` - `.
+fn mountinfo_is_noexec(
+    mountinfo: &str,
+    path: &std::path::Path
+) -> bool
+{
+    // the last mount point that contains `path` is the one that is used, since
+    // mounts on the same point are listed in the order they were mounted.
+    let mut best: Option<(usize, bool)> = None;
+    for line in mountinfo.lines()
+    {
+        let fields: Vec<&str> = line.split(' ').collect();
+        if fields.len() < 6
+        {
+            continue;
+        }
+        let mount_point = mountinfo_unescape(fields[4]);
+        if !path.starts_with(&mount_point)
+        {
+            continue;
+        }
+        let depth = std::path::Path::new(&mount_point).components().count();
+        if best.is_none_or(|(d, _)| depth >= d)
+        {
+            let noexec = fields[5].split(',').any(|o| o == "noexec");
+            best = Some((depth, noexec));
+        }
+    }
+    best.is_some_and(|(_, noexec)| noexec)
+}
+
+/// Decode the octal escapes (like `\040` for a space) in a field from
+/// `/proc/self/mountinfo`.
+fn mountinfo_unescape(field: &str) -> String
+{
+    let bytes = field.as_bytes();
+    let mut result: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len()
+    {
+        if bytes[i] == b'\\'
+            && let Some(octal) = bytes.get(i + 1..i + 4)
+            && let Ok(octal) = std::str::from_utf8(octal)
+            && let Ok(v) = u8::from_str_radix(octal, 8)
+        {
+            result.push(v);
+            i += 4;
+            continue;
+        }
+        result.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&result).to_string()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn topdir_trash_needs_sticky_bit()
+    {
+        let topdir = std::env::temp_dir().join(format!("beans-trash-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&topdir);
+        std::fs::create_dir_all(&topdir).unwrap();
+        let shared_trash = topdir.join(".Trash");
+        let fallback = topdir.join(".Trash-1000").to_string_lossy().to_string();
+
+        // doesn't exist
+        assert_eq!(find_topdir_trash_dir(&topdir, 1000), fallback);
+
+        // a symlink to a sticky directory isn't used.
+        let target = topdir.join("sticky");
+        std::fs::create_dir(&target).unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o1777)).unwrap();
+        std::os::unix::fs::symlink(&target, &shared_trash).unwrap();
+        assert_eq!(find_topdir_trash_dir(&topdir, 1000), fallback);
+        std::fs::remove_file(&shared_trash).unwrap();
+
+        // no sticky bit
+        std::fs::create_dir(&shared_trash).unwrap();
+        std::fs::set_permissions(&shared_trash, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert_eq!(find_topdir_trash_dir(&topdir, 1000), fallback);
+
+        std::fs::set_permissions(&shared_trash, std::fs::Permissions::from_mode(0o1777)).unwrap();
+        assert_eq!(
+            find_topdir_trash_dir(&topdir, 1000),
+            shared_trash.join("1000").to_string_lossy().to_string()
+        );
+
+        std::fs::remove_dir_all(&topdir).unwrap();
+    }
+
+    const MOUNTINFO: &str = "\
+22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw
+35 22 0:31 / /tmp rw,nosuid,nodev,noexec shared:14 - tmpfs tmpfs rw,size=4096k
+36 22 8:3 / /home rw,nosuid,nodev,noexec,relatime shared:15 - ext4 /dev/sda3 rw
+37 36 8:4 / /home/kate/games rw,relatime shared:16 - ext4 /dev/sda4 rw
+38 22 8:5 / /mnt/steam\\040library rw,noexec,relatime shared:17 - ext4 /dev/sda5 rw
+39 22 8:6 / /mnt/steam rw,relatime shared:18 - ext4 /dev/sda6 rw
+40 22 0:40 / /var/tmp rw,relatime shared:19 - tmpfs tmpfs rw,noexec
+41 22 8:7 / /media rw,relatime shared:20 - vfat /dev/sda7 rw
+42 41 8:7 / /media rw,noexec,relatime shared:21 - vfat /dev/sda7 rw
+";
+
+    fn noexec(path: &str) -> bool
+    {
+        mountinfo_is_noexec(MOUNTINFO, std::path::Path::new(path))
+    }
+
+    #[test]
+    fn mountinfo_escaped_mount_point()
+    {
+        assert_eq!(
+            mountinfo_unescape("/mnt/steam\\040library"),
+            "/mnt/steam library"
+        );
+        assert!(noexec("/mnt/steam library/steamapps/sourcemods"));
+        // not a prefix of "/mnt/steam library", since only whole components
+        // are matched.
+        assert!(!noexec("/mnt/steam/steamapps/sourcemods"));
+        assert!(!noexec("/mnt/steamlibrary"));
+    }
+
+    #[test]
+    fn mountinfo_longest_prefix()
+    {
+        assert!(!noexec("/usr/bin"));
+        assert!(noexec("/tmp/beans-rs"));
+        assert!(noexec("/home/kate/.cache/beans-rs/bin"));
+        // mounted inside the noexec /home
+        assert!(!noexec("/home/kate/games/beans-rs"));
+        assert!(!noexec("/home/kate/games"));
+        // the last mount on the same mount point is used
+        assert!(noexec("/media/usb"));
+    }
+
+    #[test]
+    fn mountinfo_super_options()
+    {
+        // noexec in the super options (after " - ") isn't a mount option.
+        assert!(!noexec("/var/tmp/beans-rs"));
+        assert!(noexec("/tmp"));
+    }
 }
@@ -1,8 +1,10 @@
+mod dirs;
 #[cfg(not(target_os = "windows"))]
 mod linux;
 
 use std::backtrace::Backtrace;
 
+pub use dirs::*;
 #[cfg(not(target_os = "windows"))]
 pub use linux::*;
 
@@ -14,8 +16,6 @@ use std::{collections::HashMap,
           path::PathBuf};
 
 use futures::StreamExt;
-use indicatif::{ProgressBar,
-                ProgressStyle};
 use log::{debug,
           error,
           trace,
@@ -23,10 +23,15 @@ use log::{debug,
 use rand::{distr::Alphanumeric,
            Rng};
 use reqwest::header::USER_AGENT;
+use tokio_util::sync::CancellationToken;
 #[cfg(target_os = "windows")]
 pub use windows::*;
 
 use crate::{appvar::AppVarData,
+            config::Config,
+            events::{EventSink,
+                     Phase,
+                     Progress},
             BeansError,
             DownloadFailureReason,
             GameinfoBackupCreateDirectoryFail,
@@ -35,7 +40,7 @@ use crate::{appvar::AppVarData,
             GameinfoBackupWriteFail,
             RunnerContext};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize)]
 pub enum InstallType
 {
     /// when steam/sourcemods/open_fortress/ doesn't exist
@@ -94,8 +99,11 @@ impl PartialEq for InstallType
     }
 }
 
-/// get the current type of installation.
-pub fn install_state(sourcemods_location: Option<String>) -> InstallType
+/// get the current type of installation of the mod in `appvar`.
+pub fn install_state(
+    sourcemods_location: Option<String>,
+    appvar: &AppVarData
+) -> InstallType
 {
     let mut smp_x = match sourcemods_location
     {
@@ -120,7 +128,7 @@ pub fn install_state(sourcemods_location: Option<String>) -> InstallType
         smp_x.pop();
     }
 
-    let data_dir = join_path(smp_x, crate::data_dir());
+    let data_dir = join_path(smp_x, appvar.data_dir());
 
     if file_exists(format!("{}.adastral", data_dir))
     {
@@ -153,6 +161,28 @@ pub fn get_input(prompt: &str) -> String
     input.trim().to_string()
 }
 
+/// Ask the user a yes/no question. Yes is the default option.
+///
+/// Always waits for input, use `RunnerContext::prompt_confirm` so
+/// `RunnerOptions.confirm` is respected.
+pub fn prompt_confirm(question: &str) -> bool
+{
+    println!("{}", question);
+    println!("Yes/Y (default)");
+    println!("No/N");
+    let user_input = get_input("-- Enter option below --");
+    match user_input.to_lowercase().as_str()
+    {
+        "y" | "yes" | "" => true,
+        "n" | "no" => false,
+        _ =>
+        {
+            println!("Unknown option \"{}\"", user_input.to_lowercase());
+            prompt_confirm(question)
+        }
+    }
+}
+
 /// check if a file exists
 pub fn file_exists(location: String) -> bool
 {
@@ -319,6 +349,103 @@ pub fn parse_location(location: String) -> String
     };
     real_location
 }
+/// Get the location of every file in `directory` (and it's sub-directories),
+/// relative to `directory`. Separators are always `/`.
+///
+/// Symlinks are included as files, but they won't be followed.
+pub fn list_files_recursive(directory: String) -> Result<Vec<String>, BeansError>
+{
+    let mut result: Vec<String> = Vec::new();
+    list_files_recursive_inner(PathBuf::from(&directory), String::new(), &mut result)?;
+    result.sort();
+    Ok(result)
+}
+fn list_files_recursive_inner(
+    directory: PathBuf,
+    prefix: String,
+    result: &mut Vec<String>
+) -> Result<(), BeansError>
+{
+    for entry in std::fs::read_dir(&directory)?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = format!("{}{}", prefix, name);
+        if entry.file_type()?.is_dir()
+        {
+            list_files_recursive_inner(entry.path(), format!("{}/", relative), result)?;
+        }
+        else
+        {
+            result.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// Get the SHA-256 hash of the file at `location` as a lowercase hex string.
+pub fn sha256_file(location: String) -> Result<String, BeansError>
+{
+    use sha2::Digest;
+    let mut file = match std::fs::File::open(&location)
+    {
+        Ok(v) => v,
+        Err(e) =>
+        {
+            return Err(BeansError::FileOpenFailure {
+                location,
+                error: e
+            });
+        }
+    };
+    let mut hasher = sha2::Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    let mut result = String::new();
+    for b in hasher.finalize().iter()
+    {
+        result.push_str(&format!("{:02x}", b));
+    }
+    Ok(result)
+}
+
+/// Get the sum of the size of every file in `location` (and it's
+/// sub-directories).
+pub fn get_directory_size(location: String) -> Result<u64, BeansError>
+{
+    let mut size = 0u64;
+    for f in list_files_recursive(location.clone())?
+    {
+        if let Ok(m) = std::fs::symlink_metadata(join_path(location.clone(), f))
+        {
+            size += m.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Write `content` to `location` by writing it to a temporary file in the
+/// same directory, then renaming it. This means that `location` will either
+/// have the old content or the new content, and never be half-written.
+pub fn write_atomic(
+    location: String,
+    content: &[u8]
+) -> Result<(), std::io::Error>
+{
+    let tmp_location = format!("{}.{}.tmp", location, generate_rand_str(8));
+    let result = std::fs::File::create_new(&tmp_location).and_then(|mut f| {
+        f.write_all(content)?;
+        f.sync_all()
+    });
+    let result = result.and_then(|_| std::fs::rename(&tmp_location, &location));
+    if let Err(e) = result
+    {
+        debug!("[helper::write_atomic] Failed to write {location} ({e:})");
+        let _ = std::fs::remove_file(&tmp_location);
+        return Err(e);
+    }
+    Ok(())
+}
+
 /// Check if a process is running
 ///
 /// name: Executable name (from `Process.name(&self)`)
@@ -461,31 +588,61 @@ pub fn has_free_space(
     Ok((size as u64) < get_free_space(location)?)
 }
 
+/// Run `future` until it's finished, or until `cancel` is cancelled. When it's
+/// cancelled first, `future` is dropped and `BeansError::Cancelled` is
+/// returned.
+///
+/// NOTE `future` is only dropped while it's waiting, so blocking work (like
+/// aria2c) is finished first.
+pub async fn cancellable<T, F>(
+    cancel: &CancellationToken,
+    future: F
+) -> Result<T, BeansError>
+where
+    F: std::future::Future<Output = Result<T, BeansError>>
+{
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(BeansError::Cancelled),
+        r = future => r
+    }
+}
+
+/// Download the file at `url` to `out_location`, with aria2c when it can be
+/// used. Progress is reported to `events` as `Phase::Download`.
 pub async fn download_with_progress(
     url: String,
-    out_location: String
+    out_location: String,
+    config: &Config,
+    events: &dyn EventSink
 ) -> Result<(), BeansError>
 {
     debug!(
         "[helper::download_with_progress] url: {}, out_location: {}",
         url, out_location
     );
-    if crate::aria2::can_use_aria2() && !crate::env_disable_aria2c()
+    if crate::aria2::can_use_aria2(config) && !config.disable_aria2c
     {
         debug!("[helper::download_with_progress] using aria2c");
-        crate::aria2::download_file(url, out_location).await?;
+        // aria2c displays it's own progress, so only the start and end of
+        // the download is reported.
+        events.phase_started(Phase::Download, format!("Downloading {}", &url));
+        let result = crate::aria2::download_file(url, out_location, config).await;
+        events.finished(Phase::Download, result.is_ok(), None);
+        result?;
     }
     else
     {
-        download_with_progress_reqwest(url, out_location).await?;
+        download_with_progress_reqwest(url, out_location, events).await?;
     }
     Ok(())
 }
 /// Download file at the URL provided to the output location provided
-/// This function will also show a progress bar with indicatif.
+/// Progress is reported to `events` as the amount of bytes downloaded.
 async fn download_with_progress_reqwest(
     url: String,
-    out_location: String
+    out_location: String,
+    events: &dyn EventSink
 ) -> Result<(), BeansError>
 {
     let res = match reqwest::Client::new().get(&url).send().await
@@ -508,12 +665,12 @@ async fn download_with_progress_reqwest(
         .content_length()
         .expect("Failed to get length of data to download");
 
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-        .unwrap()
-        .with_key("eta", |state: &indicatif::ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-        .progress_chars("#>-"));
-    pb.set_message(format!("Downloading {}", &url));
+    let pb = Progress::start(
+        events,
+        Phase::Download,
+        format!("Downloading {}", &url),
+        Some(total_size)
+    );
 
     // download chunks
     let mut file = match std::fs::File::create(out_location.clone())
@@ -606,15 +763,15 @@ pub fn format_size(i: usize) -> String
     format!("{}{}", whole, dec_x)
 }
 
-/// Check if we should use the custom temporary directory, which is stored in
-/// the environment variable defined in `CUSTOM_TMPDIR_NAME`.
+/// Check if we should use the custom temporary directory, which is `tmp_dir`
+/// in the config (or the `ADASTRAL_TMPDIR` environment variable).
 ///
 /// ## Return
-/// `Some` when the environment variable is set, and the directory exist.
+/// `Some` when `tmp_dir` is set, and the directory exist.
 /// Otherwise `None` is returned.
-pub fn use_custom_tmpdir() -> Option<String>
+pub fn use_custom_tmpdir(config: &Config) -> Option<String>
 {
-    if let Some(x) = crate::env_custom_tmpdir()
+    if let Some(x) = config.tmp_dir.clone()
     {
         let s = x.to_string();
         if dir_exists(s.clone())
@@ -632,80 +789,6 @@ pub fn use_custom_tmpdir() -> Option<String>
     None
 }
 
-/// Create directory in temp directory with name of "beans-rs"
-pub fn get_tmp_dir() -> String
-{
-    let mut dir = std::env::temp_dir().to_str().unwrap_or("").to_string();
-    if let Some(x) = use_custom_tmpdir()
-    {
-        dir = x;
-    }
-    else if is_steamdeck()
-    {
-        trace!("[helper::get_tmp_dir] Detected that we are running on a steam deck. Using ~/.tmp/beans-rs");
-        match simple_home_dir::home_dir()
-        {
-            Some(v) => match v.to_str()
-            {
-                Some(k) =>
-                {
-                    dir = format_directory_path(k.to_string());
-                    dir = join_path(dir, String::from(".tmp"));
-                }
-                None =>
-                {
-                    trace!("[helper::get_tmp_dir] Failed to convert PathBuf to &str");
-                }
-            },
-            None =>
-            {
-                trace!("[helper::get_tmp_dir] Failed to get home directory.");
-            }
-        };
-    }
-    else if cfg!(target_os = "android")
-    {
-        dir = String::from("/data/var/tmp");
-    }
-    else if cfg!(not(target_os = "windows"))
-    {
-        dir = String::from("/var/tmp");
-    }
-    dir = format_directory_path(dir);
-    if !dir_exists(dir.clone())
-    {
-        if let Err(e) = std::fs::create_dir(&dir)
-        {
-            trace!("[helper::get_tmp_dir] {:#?}", e);
-            warn!(
-                "[helper::get_tmp_dir] failed to make tmp directory at {} ({:})",
-                dir, e
-            );
-        }
-    }
-    dir = join_path(dir, String::from("beans-rs"));
-    dir = format_directory_path(dir);
-
-    if !dir_exists(dir.clone())
-    {
-        if let Err(e) = std::fs::create_dir(&dir)
-        {
-            trace!("[helper::get_tmp_dir] {:#?}", e);
-            warn!(
-                "[helper::get_tmp_dir] failed to make tmp directory at {} ({:})",
-                dir, e
-            );
-            sentry::capture_error(&e);
-        }
-        else
-        {
-            trace!("[helper::get_tmp_dir] created directory {}", dir);
-        }
-    }
-
-    dir
-}
-
 /// Check if the content of `uname -r` contains `valve` (Linux Only)
 ///
 /// ## Returns
@@ -764,10 +847,13 @@ pub fn is_steamdeck() -> bool
 }
 
 /// Generate a full file location for a temporary file.
-pub fn get_tmp_file(filename: String) -> String
+pub fn get_tmp_file(
+    config: &Config,
+    filename: String
+) -> String
 {
     let head = format!("{}_{}", generate_rand_str(8), filename);
-    join_path(get_tmp_dir(), head)
+    join_path(get_tmp_dir(config), head)
 }
 
 /// Check if there is an update available. When the latest release doesn't match
@@ -858,8 +944,10 @@ pub fn restore_gameinfo(
 
 pub fn backup_gameinfo(ctx: &mut RunnerContext) -> Result<(), BeansError>
 {
-    let av = AppVarData::get();
-    let gamedir = join_path(ctx.clone().sourcemod_path, av.mod_info.sourcemod_name);
+    let gamedir = join_path(
+        ctx.sourcemod_path.clone(),
+        ctx.appvar.mod_info.sourcemod_name.clone()
+    );
     let backupdir = join_path(gamedir.clone(), String::from(GAMEINFO_BACKUP_DIRNAME));
 
     let current_time = chrono::Local::now();
@@ -951,12 +1039,15 @@ pub fn backup_gameinfo(ctx: &mut RunnerContext) -> Result<(), BeansError>
         });
     }
 
-    println!("[backup_gameinfo] Created backup at {}", output_location);
+    ctx.events().message(format!(
+        "Created backup of gameinfo.txt at {}",
+        output_location
+    ));
 
     Ok(())
 }
 
-const GAMEINFO_BACKUP_DIRNAME: &str = "gameinfo_backup";
+pub const GAMEINFO_BACKUP_DIRNAME: &str = "gameinfo_backup";
 const GITHUB_RELEASES_URL: &str = "https://api.github.com/repositories/805393469/releases/latest";
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
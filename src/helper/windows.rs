@@ -155,3 +155,24 @@ bitflags! {
         const NonContentIndexed = 8192;
     }
 }
+
+/// Moving to the trash (recycle bin) is only supported on Linux.
+pub fn move_to_trash(location: String) -> Result<String, BeansError>
+{
+    Err(BeansError::TrashFailure {
+        location,
+        reason: String::from("Moving to the trash is only supported on Linux")
+    })
+}
+
+/// Moving to the trash (recycle bin) is only supported on Linux.
+pub fn find_trash_dir(_location: String) -> Option<String>
+{
+    None
+}
+
+/// Mount options don't exist on Windows, so this is always `false`.
+pub fn is_noexec(_location: String) -> bool
+{
+    false
+}
@@ -0,0 +1,199 @@
+use std::io::ErrorKind;
+
+use crate::{config::{Config,
+                     ConfigValues},
+            Aria2cExitCodeReason,
+            BeansError,
+            DownloadFailureReason};
+
+/// `ENOSPC` on Linux
+const ERRNO_NO_SPACE: i32 = 28;
+/// `EROFS` on Linux
+const ERRNO_READ_ONLY_FS: i32 = 30;
+/// `ERROR_ACCESS_DENIED` on Windows
+const WIN_ERROR_ACCESS_DENIED: i32 = 5;
+/// `ERROR_DISK_FULL` on Windows
+const WIN_ERROR_DISK_FULL: i32 = 112;
+
+const HINT_NETWORK: &str = "Check your internet connection. If it's working, the server might be down, so try again later.";
+const HINT_STEAM: &str = "Make sure that Steam is installed, and that it has been started at least once. Otherwise, provide the sourcemods directory with --location (it's usually steamapps/sourcemods in the Steam directory).";
+const HINT_CORRUPT_PACKAGE: &str =
+    "The package might be corrupt or incomplete. Delete it and download it again.";
+
+/// Settings that are used for hints. butler_path and tmp_dir can't be set
+/// with arguments, so only the environment variables and config file are
+/// needed.
+fn load_config() -> Config
+{
+    Config::load(ConfigValues::default())
+}
+
+/// Get a plain-language explanation of `error`, and what the user can do to
+/// fix it. `None` when there isn't anything more useful to say than the
+/// error message.
+pub fn get_hint(error: &BeansError) -> Option<String>
+{
+    let hint = match error
+    {
+        BeansError::FreeSpaceCheckFailure { .. } => "Free up space on the drive, or use a different sourcemods directory with --location (an existing installation can be moved with the move command).",
+        BeansError::SourceModLocationNotFound
+        | BeansError::SteamNotFound
+        | BeansError::RegistryKeyFailure { .. } => HINT_STEAM,
+        BeansError::FileOpenFailure { error, .. }
+        | BeansError::FileWriteFailure { error, .. }
+        | BeansError::FileDeleteFailure { error, .. }
+        | BeansError::DirectoryCreateFailure { error, .. }
+        | BeansError::DirectoryDeleteFailure { error, .. }
+        | BeansError::UserDataExportFailure { error, .. }
+        | BeansError::IO { error, .. }
+        | BeansError::CleanTempFailure { error, .. }
+        | BeansError::VersionFileMigrationFailure { error, .. }
+        | BeansError::VersionFileMigrationDeleteFailure { error, .. }
+        | BeansError::VersionFileReadFailure { error, .. }
+        | BeansError::GameInfoFileReadFail { error, .. }
+        | BeansError::ReadFileAttributesError { error, .. } => io_error_hint(error)?,
+        BeansError::TrashFailure { .. } => "Uninstall without --trash to delete the files instead.",
+        BeansError::MoveTargetExists { .. } => "Remove or rename the existing directory, or use a different sourcemods directory with --to.",
+        BeansError::MoveVerifyFailure { .. } => "Run the verify command to repair the installation, then try to move it again.",
+        BeansError::TarExtractFailure { error, .. } => io_error_hint(error).unwrap_or(HINT_CORRUPT_PACKAGE),
+        BeansError::TarUnpackItemFailure { error, .. } =>
+        {
+            if is_access_denied(error)
+            {
+                "A file in the mod directory couldn't be replaced. Close the game, and anything else that might be using the mod directory (such as Hammer or an antivirus scan), then try again."
+            }
+            else
+            {
+                io_error_hint(error).unwrap_or(HINT_CORRUPT_PACKAGE)
+            }
+        }
+        BeansError::ZipExtractFailure { error, .. } => match error
+        {
+            zip::result::ZipError::Io(e) => io_error_hint(e).unwrap_or(HINT_CORRUPT_PACKAGE),
+            _ => HINT_CORRUPT_PACKAGE
+        },
+        BeansError::Reqwest { .. } => HINT_NETWORK,
+        BeansError::LatestVersionAlreadyInstalled { .. } => "Nothing needs to be done. To repair the installation, use the verify command.",
+        BeansError::DownloadFailure { reason, .. } => match reason
+        {
+            DownloadFailureReason::Reqwest { .. } => HINT_NETWORK,
+            DownloadFailureReason::MissingAria2cExecutable
+            | DownloadFailureReason::Aria2cSpawnError { .. } => "aria2c couldn't be started. Make sure that beans can write to its temporary directory, then run it again so aria2c is extracted again.",
+            DownloadFailureReason::FileNotFound { .. } => "The download didn't finish. Check that there is enough free space, then try again."
+        },
+        BeansError::TargetSourcemodNotInstalled { .. } => "Install it first with the install command (or the wizard).",
+        BeansError::ButlerVerifyFailure { .. }
+        | BeansError::ButlerPatchFailure { .. }
+            if load_config().butler_path.is_some() => "butler couldn't be started. Make sure that butler_path in the config (or BEANS_BUTLER_PATH) is the location of the butler executable, or unset it to use the version that comes with beans.",
+        BeansError::ButlerVerifyFailure { error, .. }
+        | BeansError::ButlerPatchFailure { error, .. }
+            if error.kind() == ErrorKind::PermissionDenied && crate::helper::is_noexec(crate::depends::get_butler_location(&load_config())) => "butler is on a filesystem that is mounted with noexec, so it can't be run. Set tmp_dir in the config (or ADASTRAL_TMPDIR) to a directory that isn't mounted with noexec, or set butler_path (or BEANS_BUTLER_PATH) to the location of butler.",
+        BeansError::ButlerVerifyFailure { error, .. }
+        | BeansError::ButlerPatchFailure { error, .. } => io_error_hint(error).unwrap_or("butler couldn't be started. Make sure that beans can write to its temporary directory, and that your antivirus isn't blocking butler."),
+        BeansError::ButlerNetworkFailure { .. } => HINT_NETWORK,
+        BeansError::ButlerPatchCorrupt { .. } => "The patch was corrupt. Use the clean-tmp command so it's downloaded again, then try again.",
+        BeansError::ButlerDiskFull { .. } => "The drive is full. Free up some space, then try again.",
+        BeansError::ButlerSignatureMismatch { .. } => "Some files couldn't be repaired. Use the verify command to try again, or reinstall with install --clean.",
+        BeansError::ButlerExitFailure { .. } => "Use the verify command to repair the installation, then try again.",
+        BeansError::WharfSignatureInvalid { .. } => "The signature might not have downloaded correctly. Try again later.",
+        BeansError::ConfigInvalid { .. } => "Fix the config file (\"config path\" shows where it is), or delete it to use the defaults.",
+        BeansError::ConfigKeyInvalid { .. } => "Use \"config list\" to see every key, and what type of value it takes.",
+        BeansError::JsonOutputUnsupported { .. } => "Use a different command (like install, update or verify), or remove --output json.",
+        BeansError::WharfPatchInvalid { .. } => "Use the verify command to repair the installation, then try again. If it still fails, use the clean-tmp command so the patch is downloaded again.",
+        BeansError::CleanInstallRefused { .. } => "Nothing was removed. Make sure that the package is for this mod (its files must be in a directory with the same name as the mod directory), or install without --clean. If the mod has been restructured, check what would be removed with --dry-run --force, then use --force.",
+        BeansError::RemoteVersionNotFound { .. } => "Use \"versions list\" to see which versions are available.",
+        BeansError::InvalidVersionArgument { .. } => "Provide the version as a number, like 18. Use \"versions list\" to see which versions are available.",
+        BeansError::VersionFileCorrupt { .. } => "Use the adopt command to find which version is installed, and repair the version file.",
+        BeansError::GameInfoPermissionSetFail { error, .. } => io_error_hint(error).unwrap_or("Make sure that gameinfo.txt isn't open in another program, and that your user owns the mod directory."),
+        BeansError::GameinfoBackupFailure { .. } => "Make sure that your user can write to the mod directory.",
+        BeansError::GameStillRunning { .. } => "Close the game (check that it isn't still running in the background), then run beans again.",
+        BeansError::Aria2cExitCode { reason, .. } => aria2c_hint(reason)?,
+        BeansError::WindowsSetFileAttributeError { .. } => "Try running beans as an administrator.",
+        BeansError::SerdeJson { .. }
+        | BeansError::UnsupportedPackageFormat { .. }
+        | BeansError::FileNotFound { .. }
+        | BeansError::VersionFileSerialize { .. }
+        | BeansError::VersionFileParseFailure { .. }
+        | BeansError::AppVarDataSerializeFailure { .. }
+        | BeansError::Cancelled => return None
+    };
+    Some(hint.to_string())
+}
+
+/// Get a hint for an IO error from what kind of error it is. `None` when
+/// there isn't anything useful to say about it.
+pub fn io_error_hint(error: &std::io::Error) -> Option<&'static str>
+{
+    let code = error.raw_os_error();
+    if error.kind() == ErrorKind::StorageFull
+        || (cfg!(not(target_os = "windows")) && code == Some(ERRNO_NO_SPACE))
+        || (cfg!(target_os = "windows") && code == Some(WIN_ERROR_DISK_FULL))
+    {
+        return Some("The drive is full. Free up some space, then try again.");
+    }
+    if error.kind() == ErrorKind::ReadOnlyFilesystem
+        || (cfg!(not(target_os = "windows")) && code == Some(ERRNO_READ_ONLY_FS))
+    {
+        return Some("The drive is mounted as read-only. Remount it as read-write (or use a different sourcemods directory with --location), then try again.");
+    }
+    match error.kind()
+    {
+        ErrorKind::PermissionDenied if is_access_denied(error) => Some("Access was denied. Close the game, and anything else that might be using the mod directory, then try again. If it still fails, the file might be marked as read-only."),
+        ErrorKind::PermissionDenied => Some("Your user doesn't have permission to change this file. Make sure that you own the sourcemods directory (for example, that it wasn't created with sudo)."),
+        ErrorKind::NotFound => Some("A file or directory that beans needs is missing. If it's in the mod directory, use the verify command to repair the installation."),
+        ErrorKind::CrossesDevices => Some("The source and target are on different drives."),
+        ErrorKind::TimedOut
+        | ErrorKind::ConnectionRefused
+        | ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionAborted => Some(HINT_NETWORK),
+        _ => None
+    }
+}
+
+/// Check if `error` is `ERROR_ACCESS_DENIED` on Windows, which happens when
+/// trying to overwrite a read-only file or a file that is being used.
+pub fn is_access_denied(error: &std::io::Error) -> bool
+{
+    cfg!(target_os = "windows")
+        && error.kind() == ErrorKind::PermissionDenied
+        && error.raw_os_error() == Some(WIN_ERROR_ACCESS_DENIED)
+}
+
+fn aria2c_hint(reason: &Aria2cExitCodeReason) -> Option<&'static str>
+{
+    let hint = match reason
+    {
+        Aria2cExitCodeReason::Timeout
+        | Aria2cExitCodeReason::NetworkProblem
+        | Aria2cExitCodeReason::NameResolutionFailure
+        | Aria2cExitCodeReason::AbortedDueToSlowDownloadSpeed
+        | Aria2cExitCodeReason::BadHttpResponseHeader
+        | Aria2cExitCodeReason::TooManyHttpRedirects
+        | Aria2cExitCodeReason::RemoteServerFailureDueToOverloadingOrMaintenance => HINT_NETWORK,
+        Aria2cExitCodeReason::ResourceNotFound
+        | Aria2cExitCodeReason::ResourceNotFoundLimitReached => "The file couldn't be found on the server. Try again later, since the server might still be updating.",
+        Aria2cExitCodeReason::NotEnoughDiskSpace => "The drive is full. Free up some space, then try again.",
+        Aria2cExitCodeReason::OutputFileAlreadyExists
+        | Aria2cExitCodeReason::AlreadyDownloadingFile
+        | Aria2cExitCodeReason::AlreadyDownloadingFileWithSameTorrentHash => "Another download is already running. Close any other instances of beans, or use the clean-tmp command, then try again.",
+        Aria2cExitCodeReason::OutputFileRenameFailure
+        | Aria2cExitCodeReason::CouldNotOpenOutputFile
+        | Aria2cExitCodeReason::CouldNotCreateOutputFile
+        | Aria2cExitCodeReason::CouldNotCreateDirectory
+        | Aria2cExitCodeReason::IOError => "The download couldn't be saved. Make sure that beans can write to its temporary directory, and that there is enough free space.",
+        Aria2cExitCodeReason::ChecksumValidationFailure
+        | Aria2cExitCodeReason::PieceLengthMismatch
+        | Aria2cExitCodeReason::CorruptTorrentFile => "The download was corrupt. Use the clean-tmp command, then try again.",
+        _ => return None
+    };
+    Some(hint)
+}
+
+impl BeansError
+{
+    /// See `hint::get_hint`
+    pub fn hint(&self) -> Option<String>
+    {
+        get_hint(self)
+    }
+}
@@ -20,14 +20,17 @@ mod error;
 
 pub use error::*;
 
-use crate::appvar::AppVarData;
-
 pub mod appvar;
 pub mod butler;
+pub mod config;
+pub mod events;
 pub mod extract;
 pub mod flags;
 pub mod gui;
+pub mod hint;
 pub mod logger;
+pub mod rpc;
+pub mod wharf;
 
 pub mod aria2;
 
@@ -37,14 +40,6 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const SENTRY_URL: &str = "https://9df80170f0a4411bb9c834ac54734380@sentry.kate.pet/1";
 /// content to display when showing a message box on panic.
 pub const PANIC_MSG_CONTENT: &str = include_str!("text/msgbox_panic_text.txt");
-/// once everything is done, do we wait for the user to press enter before
-/// exiting?
-///
-/// just like the `pause` thing in batch.
-pub static mut PAUSE_ONCE_DONE: bool = false;
-/// When `true`, everything that prompts the user for Y/N should use the default
-/// option.
-pub static mut PROMPT_DO_WHATEVER: bool = false;
 
 // ------------------------------------------------------------------------
 // please dont change consts below unless you know what you're doing <3
@@ -56,120 +51,7 @@ pub const PATH_SEP: &str = "/";
 #[cfg(target_os = "windows")]
 pub const PATH_SEP: &str = "\\";
 
-pub fn data_dir() -> String
-{
-    let av = AppVarData::get();
-    format!("{}{}{}", PATH_SEP, av.mod_info.sourcemod_name, PATH_SEP)
-}
-
-/// Temporary directory which is specified by `ADASTRAL_TMPDIR`.
-///
-/// Will return `None` when the environment variable couldn't be found, or it's
-/// an empty string.
-pub fn env_custom_tmpdir() -> Option<String>
-{
-    let s = helper::try_get_env_var(String::from("ADASTRAL_TMPDIR"));
-    match s
-    {
-        Some(x) => match x.trim().is_empty()
-        {
-            true => None,
-            false => Some(x)
-        },
-        None => s
-    }
-}
-/// Return `true` when the environment variable `BEANS_DEBUG` or
-/// `ADASTRAL_DEBUG` exists and equals `1` or `true`.
-pub fn env_debug() -> bool
-{
-    check_env_bool("BEANS_DEBUG") || check_env_bool("ADASTRAL_DEBUG")
-}
-/// Return `true` when the environment variable `BEANS_HEADLESS` or
-/// `ADASTRAL_HEADLESS` exists and equals `1` or `true`.
-pub fn env_headless() -> bool
-{
-    check_env_bool("BEANS_HEADLESS") || check_env_bool("ADASTRAL_HEADLESS")
-}
-
-/// Return `true` when the environment variable `BEANS_DISABLE_ARIA2C` or
-/// `ADASTRAL_DISABLE_ARIA2C` exists and equals `1` or `true`.
-pub fn env_disable_aria2c() -> bool
-{
-    check_env_bool("BEANS_DISABLE_ARIA2C") || check_env_bool("ADASTRAL_DISABLE_ARIA2C")
-}
-
-/// Will return the content of either of the following environment variables
-/// if they exist and there is at least 1 character in it;
-/// - `BEANS_ARIA2C_ARGS_OVERRIDE`
-/// - `ADASTRAL_ARIA2C_ARGS_OVERRIDE`
-///
-/// This string will be used as the launch arguments for aria2c. It'll replace
-/// the following content with;
-///
-/// | Look For | Replace With |
-/// | -------- | ------------ |
-/// | `%OUT_DIR%` | Output directory (argument `-d` with aria2c) |
-/// | `%OUT_FILENAME%` | Output Filename (argument `--out=` with aria2c) |
-/// | `%USER_AGENT%` | Used for the `--user-agent=` aria2c argument|
-/// | `%URL%` | URL to download from
-pub fn env_aria2c_override_args() -> Option<String>
-{
-    if let Some(val) = helper::try_get_env_var("BEANS_ARIA2C_ARGS_OVERRIDE".to_string())
-    {
-        if val.len() > 0
-        {
-            return Some(val);
-        }
-    }
-    if let Some(val) = helper::try_get_env_var("ADASTRAL_ARIA2C_ARGS_OVERRIDE".to_string())
-    {
-        if val.len() > 0
-        {
-            return Some(val);
-        }
-    }
-    return None;
-}
-
-/// Will return the content of either of the following environment variables
-/// if they exist and there is at least 1 character in it;
-/// - `BEANS_ARIA2C_ARGS`
-/// - `ADASTRAL_ARIA2C_ARGS`
-///
-/// This string will be put in the launch argument for starting the aria2c
-/// instance.
-pub fn env_aria2c_extra_args() -> Option<String>
-{
-    if let Some(val) = helper::try_get_env_var("BEANS_ARIA2C_ARGS".to_string())
-    {
-        if val.len() > 0
-        {
-            return Some(val);
-        }
-    }
-    if let Some(val) = helper::try_get_env_var("ADASTRAL_ARIA2C_ARGS".to_string())
-    {
-        if val.len() > 0
-        {
-            return Some(val);
-        }
-    }
-    return None;
-}
-
-/// Return `true` when the environment variable exists, and it's value equals
-/// `1` or `true (when trimmed and made lowercase).
-fn check_env_bool<K: AsRef<std::ffi::OsStr>>(key: K) -> bool
-{
-    std::env::var(key).is_ok_and(|x| {
-        let y = x.trim().to_lowercase();
-        y == "1" || y == "true"
-    })
-}
-
-/// Check if we have GUI support enabled. Will always return `false` when
-/// `PAUSE_ONCE_DONE` is `false`.
+/// Check if we have GUI support enabled.
 ///
 /// Will return `true` when
 /// - Running on Windows
@@ -178,14 +60,9 @@ fn check_env_bool<K: AsRef<std::ffi::OsStr>>(key: K) -> bool
 ///   variables are set.
 pub fn has_gui_support() -> bool
 {
-    unsafe {
-        if !PAUSE_ONCE_DONE
-        {
-            return false;
-        }
-    }
-
-    if env_headless()
+    // there's no argument for headless, so it's only from the environment
+    // variables and config file.
+    if config::Config::load(config::ConfigValues::default()).headless
     {
         return true;
     }
@@ -243,3 +120,13 @@ flate!(pub static BUTLER_LIB_2: [u8] from "Binaries/c7zip.dll");
 flate!(pub static BUTLER_LIB_2: [u8] from "Binaries/libc7zip.so");
 #[cfg(target_os = "windows")]
 flate!(pub static ARIA2C_BINARY: [u8] from "Binaries/aria2c.exe");
+
+/// SHA-256 hash of `BUTLER_BINARY`, from `build.rs`
+pub const BUTLER_BINARY_SHA256: &str = env!("BEANS_BUTLER_SHA256");
+/// SHA-256 hash of `BUTLER_LIB_1`, from `build.rs`
+pub const BUTLER_LIB_1_SHA256: &str = env!("BEANS_BUTLER_LIB_1_SHA256");
+/// SHA-256 hash of `BUTLER_LIB_2`, from `build.rs`
+pub const BUTLER_LIB_2_SHA256: &str = env!("BEANS_BUTLER_LIB_2_SHA256");
+/// SHA-256 hash of `ARIA2C_BINARY`, from `build.rs`
+#[cfg(target_os = "windows")]
+pub const ARIA2C_BINARY_SHA256: &str = env!("BEANS_ARIA2C_SHA256");
@@ -1,6 +1,7 @@
 use std::{io,
           io::Write,
-          sync::Mutex,
+          sync::{Mutex,
+                 RwLock},
           time::Instant};
 
 use lazy_static::lazy_static;
@@ -11,13 +12,41 @@ use log::{LevelFilter,
 
 lazy_static! {
     static ref LOGGER: CustomLogger = CustomLogger {
-        inner: Mutex::new(None)
+        inner: Mutex::new(None),
+        settings: RwLock::new(LogSettings::default())
     };
 }
 
 struct CustomLogger
 {
-    inner: Mutex<Option<CustomLoggerInner>>
+    inner: Mutex<Option<CustomLoggerInner>>,
+    settings: RwLock<LogSettings>
+}
+
+/// How log messages are filtered and formatted. There is only one logger for
+/// the whole process, so these are shared by everything that is running.
+#[derive(Debug, Clone, Copy)]
+struct LogSettings
+{
+    filter: LevelFilter,
+    format: &'static str,
+    color: bool,
+    /// Write a line of JSON for every message instead of using `format` (see
+    /// `events::JsonSink`).
+    json: bool
+}
+
+impl Default for LogSettings
+{
+    fn default() -> Self
+    {
+        Self {
+            filter: LevelFilter::Trace,
+            format: LOG_FORMAT_DEFAULT,
+            color: true,
+            json: false
+        }
+    }
 }
 
 impl CustomLogger
@@ -61,9 +90,10 @@ impl Log for CustomLogger
             return;
         }
 
+        let settings = *self.settings.read().unwrap();
         if let Some(ref mut inner) = *self.inner.lock().unwrap()
         {
-            inner.log(record);
+            inner.log(record, &settings);
         }
     }
 
@@ -90,18 +120,26 @@ impl CustomLoggerInner
 {
     fn log(
         &mut self,
-        record: &Record
+        record: &Record,
+        settings: &LogSettings
     )
     {
-        let mut do_print = true;
-        unsafe {
-            if LOG_FILTER < record.level()
+        if settings.filter >= record.level()
+        {
+            if settings.json
             {
-                do_print = false;
+                let value = serde_json::json!({
+                    "v": crate::events::JSON_SCHEMA_VERSION,
+                    "type": "log",
+                    "level": record.level().as_str().to_lowercase(),
+                    "message": format!("{}", record.args())
+                });
+                let _ = writeln!(self.sink, "{}", value);
+                let _ = self.sink.flush();
+                self.sentry.log(record);
+                return;
             }
-        }
-        if do_print
-        {
+
             let now = self.start.elapsed();
             let seconds = now.as_secs();
             let hours = seconds / 3600;
@@ -109,12 +147,8 @@ impl CustomLoggerInner
             let seconds = seconds % 60;
             let milliseconds = now.subsec_millis();
 
-            #[allow(unused_assignments)]
-            let mut data = String::new();
-            unsafe {
-                data = LOG_FORMAT.to_string();
-            }
-            data = data
+            let mut data = settings
+                .format
                 .replace("#HOURS", &format!("{:02}", hours))
                 .replace("#MINUTES", &format!("{:02}", minutes))
                 .replace("#SECONDS", &format!("{:02}", seconds))
@@ -123,19 +157,17 @@ impl CustomLoggerInner
                 .replace("#LEVEL", &format!("{:6}", record.level()))
                 .replace("#CONTENT", &format!("{}", record.args()));
 
-            unsafe {
-                if LOG_COLOR
+            if settings.color
+            {
+                data = match record.level()
                 {
-                    data = match record.level()
-                    {
-                        log::Level::Error => data.red(),
-                        log::Level::Warn => data.yellow(),
-                        log::Level::Info => data.normal(),
-                        log::Level::Debug => data.green(),
-                        log::Level::Trace => data.blue()
-                    }
-                    .to_string()
+                    log::Level::Error => data.red(),
+                    log::Level::Warn => data.yellow(),
+                    log::Level::Info => data.normal(),
+                    log::Level::Debug => data.green(),
+                    log::Level::Trace => data.blue()
                 }
+                .to_string()
             }
 
             let _ = writeln!(self.sink, "{}", data);
@@ -143,15 +175,26 @@ impl CustomLoggerInner
         self.sentry.log(record);
     }
 }
+/// Only print messages that are at or above `filter`.
 pub fn set_filter(filter: LevelFilter)
 {
-    unsafe {
-        LOG_FILTER = filter;
-    }
+    LOGGER.settings.write().unwrap().filter = filter;
+}
+/// Set the format of printed messages (like `LOG_FORMAT_DEFAULT`).
+pub fn set_format(format: &'static str)
+{
+    LOGGER.settings.write().unwrap().format = format;
+}
+/// Print messages in a color that matches their level.
+pub fn set_color(color: bool)
+{
+    LOGGER.settings.write().unwrap().color = color;
+}
+/// Write every message as a line of JSON (for `--output json`).
+pub fn set_json(json: bool)
+{
+    LOGGER.settings.write().unwrap().json = json;
 }
-static mut LOG_FILTER: LevelFilter = LevelFilter::Trace;
-pub static mut LOG_FORMAT: &str = LOG_FORMAT_DEFAULT;
-pub static mut LOG_COLOR: bool = true;
 pub const LOG_FORMAT_DEFAULT: &str =
     "[#HOURS:#MINUTES:#SECONDS.#MILLISECONDS] (#THREAD) #LEVEL #CONTENT";
 pub const LOG_FORMAT_MINIMAL: &str = "#LEVEL #CONTENT";
@@ -167,3 +210,8 @@ pub fn log_to_stdout()
 {
     log_to(io::stdout());
 }
+/// Used when stdout is being used for something else (like JSON output).
+pub fn log_to_stderr()
+{
+    log_to(io::stderr());
+}
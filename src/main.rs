@@ -1,18 +1,37 @@
-use std::str::FromStr;
+use std::{backtrace::Backtrace,
+          str::FromStr,
+          sync::{atomic::{AtomicBool,
+                          Ordering},
+                 Arc}};
 
-use beans_rs::{flags,
+use beans_rs::{appvar::AppVarData,
+               config::{Config,
+                        ConfigValues},
+               events::{JsonSink,
+                        TerminalSink},
                flags::LaunchFlag,
                gui::DialogIconKind,
                helper,
                helper::parse_location,
+               rpc::RpcServer,
                wizard,
-               workflows::{CleanWorkflow,
+               workflows::{AdoptWorkflow,
+                           CheckUpdateWorkflow,
+                           CleanInstallOptions,
+                           CleanWorkflow,
+                           ConfigWorkflow,
+                           HistoryWorkflow,
                            InstallWorkflow,
+                           RelocateWorkflow,
+                           StatusWorkflow,
+                           UninstallOptions,
                            UninstallWorkflow,
                            UpdateWorkflow,
-                           VerifyWorkflow},
+                           VerifyWorkflow,
+                           VersionsWorkflow},
                BeansError,
                RunnerContext,
+               RunnerOptions,
                SourceModDirectoryParam,
                PANIC_MSG_CONTENT};
 use clap::{Arg,
@@ -25,6 +44,7 @@ use log::{debug,
           trace,
           warn,
           LevelFilter};
+use tokio_util::sync::CancellationToken;
 
 pub const DEFAULT_LOG_LEVEL_RELEASE: LevelFilter = LevelFilter::Info;
 #[cfg(debug_assertions)]
@@ -32,15 +52,22 @@ pub const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Trace;
 #[cfg(not(debug_assertions))]
 pub const DEFAULT_LOG_LEVEL: LevelFilter = DEFAULT_LOG_LEVEL_RELEASE;
 
+/// Once everything is done, wait for the user to press enter before exiting.
+/// Kept here (as well as in `Launcher.options`) since the panic handler can't
+/// get to the `Launcher`. Set with `Launcher::set_pause_once_done`.
+static PAUSE_ONCE_DONE: AtomicBool = AtomicBool::new(false);
+/// Print full errors and other debug messages. Set with `set_debug_mode`.
+static DEBUG_MODE: AtomicBool = AtomicBool::new(false);
+
 fn main()
 {
     init_console();
-    init_flags();
+    let config = init_flags();
     // initialize sentry and custom panic handler for msgbox
     #[cfg(not(debug_assertions))]
     let _guard = sentry::init((beans_rs::SENTRY_URL, sentry::ClientOptions {
         release: sentry::release_name!(),
-        debug: flags::has_flag(LaunchFlag::DEBUG_MODE),
+        debug: DEBUG_MODE.load(Ordering::Relaxed),
         max_breadcrumbs: 100,
         auto_session_tracking: true,
         attach_stacktrace: true,
@@ -53,7 +80,7 @@ fn main()
         .build()
         .unwrap()
         .block_on(async {
-            Launcher::run().await;
+            Launcher::run(config).await;
         });
 }
 
@@ -87,18 +114,26 @@ fn init_console()
     // do nothing
 }
 
-fn init_flags()
+/// Load the config (without the arguments, since they haven't been parsed
+/// yet), and set up logging with it.
+fn init_flags() -> Config
 {
-    flags::remove_flag(LaunchFlag::DEBUG_MODE);
-    #[cfg(debug_assertions)]
-    flags::add_flag(LaunchFlag::DEBUG_MODE);
-    if beans_rs::env_debug()
-    {
-        flags::add_flag(LaunchFlag::DEBUG_MODE);
-    }
-    flags::add_flag(LaunchFlag::STANDALONE_APP);
+    let config = Config::load(ConfigValues::default());
+    set_debug_mode(cfg!(debug_assertions) || config.debug);
     beans_rs::logger::set_filter(DEFAULT_LOG_LEVEL);
     beans_rs::logger::log_to_stdout();
+    config
+}
+
+/// Set `DEBUG_MODE`, and use the full log format when it's enabled.
+fn set_debug_mode(enabled: bool)
+{
+    DEBUG_MODE.store(enabled, Ordering::Relaxed);
+    beans_rs::logger::set_format(match enabled
+    {
+        true => beans_rs::logger::LOG_FORMAT_DEFAULT,
+        false => beans_rs::logger::LOG_FORMAT_MINIMAL
+    });
 }
 
 fn init_panic_handle()
@@ -110,7 +145,7 @@ fn init_panic_handle()
         custom_panic_handle(msg);
         debug!("[panic::set_hook] calling sentry_panic::panic_handler");
         sentry::integrations::panic::panic_handler(info);
-        if flags::has_flag(LaunchFlag::DEBUG_MODE)
+        if DEBUG_MODE.load(Ordering::Relaxed)
         {
             eprintln!("{:#?}", info);
         }
@@ -120,11 +155,9 @@ fn init_panic_handle()
 
 fn custom_panic_handle(msg: String)
 {
-    unsafe {
-        if !beans_rs::PAUSE_ONCE_DONE
-        {
-            return;
-        }
+    if !PAUSE_ONCE_DONE.load(Ordering::Relaxed)
+    {
+        return;
     }
     let txt = PANIC_MSG_CONTENT
         .to_string()
@@ -136,17 +169,26 @@ fn custom_panic_handle(msg: String)
         .with_icon(DialogIconKind::Error)
         .with_content(txt)
         .run();
+    dialog_closed();
 }
 
 /// should called once the logic flow is done!
 /// will call `helper::get_input` when `PAUSE_ONCE_DONE` is `true`.
 fn logic_done()
 {
-    unsafe {
-        if beans_rs::PAUSE_ONCE_DONE
-        {
-            let _ = helper::get_input("Press enter/return to exit");
-        }
+    if PAUSE_ONCE_DONE.load(Ordering::Relaxed)
+    {
+        let _ = helper::get_input("Press enter/return to exit");
+    }
+}
+
+/// Should be called once a dialog from `beans_rs::gui` has been closed. There's
+/// no need to wait for the user to press enter after they closed a window.
+fn dialog_closed()
+{
+    if beans_rs::has_gui_support()
+    {
+        PAUSE_ONCE_DONE.store(false, Ordering::Relaxed);
     }
 }
 
@@ -156,7 +198,11 @@ pub struct Launcher
     /// used.
     pub to_location: Option<String>,
     /// Output of `Command.matches()`
-    pub root_matches: ArgMatches
+    pub root_matches: ArgMatches,
+    /// Passed to every `RunnerContext` that is created.
+    pub options: RunnerOptions,
+    /// Set when `--output json` is used. Also used as `options.events`.
+    pub json_output: Option<Arc<JsonSink>>
 }
 
 impl Launcher
@@ -170,6 +216,14 @@ impl Launcher
             .help("Manually specify sourcemods directory. When not provided, beans-rs will automatically detect the sourcemods directory.")
             .required(false)
     }
+    fn create_json_arg() -> Arg
+    {
+        Arg::new("json")
+            .long("json")
+            .help("Print the output as JSON. Log messages are written to stderr instead of stdout.")
+            .required(false)
+            .action(ArgAction::SetTrue)
+    }
     fn create_confirm_arg() -> Arg
     {
         Arg::new("confirm")
@@ -178,11 +232,16 @@ impl Launcher
             .required(false)
             .action(ArgAction::SetTrue)
     }
-    pub async fn run()
+    pub async fn run(config: Config)
     {
         let cmd = Command::new("beans-rs")
             .version(clap::crate_version!())
             .bin_name(clap::crate_name!())
+            .after_help(format!(
+                "Exit codes:\n  0   Success\n  {:<4}Unexpected failure\n{}",
+                beans_rs::GENERAL_FAILURE_EXIT_CODE,
+                BeansError::code_table()
+            ))
             .subcommand(Command::new("wizard")
                 .about("Use the wizard to install. (Default subcommand)")
                 .arg(Launcher::create_location_arg()))
@@ -192,12 +251,31 @@ impl Launcher
                     Launcher::create_location_arg(),
                     Arg::new("from")
                         .long("from")
-                        .help("Location to where the .tar.zstd file is that you want to install from.")
+                        .help("Location to where the package is that you want to install from. Can be a .tar.zst, .tar.xz, .tar.gz, .tar or .zip file.")
                         .required(false),
                     Arg::new("target-version")
                         .long("target-version")
                         .help("Specify the version to install. Ignored when [--from] is used.")
                         .required(false),
+                    Arg::new("clean")
+                        .long("clean")
+                        .help("Remove files from the mod directory that aren't in the package being installed. A list of the files that will be removed is shown before anything is deleted.")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("preserve")
+                        .long("preserve")
+                        .help("File or directory (ending with /) relative to the mod directory that [--clean] should never remove. Can be used multiple times. Defaults to cfg/config.cfg, cfg/autoexec.cfg, custom/, screenshots/, demos/ and gameinfo_backup/")
+                        .requires("clean")
+                        .action(ArgAction::Append),
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("List the files that [--clean] would remove, without installing or removing anything.")
+                        .requires("clean")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("force")
+                        .long("force")
+                        .help("Let [--clean] remove files even when most of the installation isn't in the package, like when the mod has been restructured.")
+                        .requires("clean")
+                        .action(ArgAction::SetTrue),
                     Self::create_confirm_arg()]))
             .subcommand(Command::new("verify")
                 .about("Verify your current installation")
@@ -205,11 +283,103 @@ impl Launcher
             .subcommand(Command::new("update")
                 .about("Update your installation")
                 .arg(Launcher::create_location_arg()))
+            .subcommand(Command::new("status")
+                .about("Show a summary of the installation, and what beans would do.")
+                .args([
+                    Launcher::create_location_arg(),
+                    Launcher::create_json_arg()]))
+            .subcommand(Command::new("check-update")
+                .about("Check if an update is available, without changing anything. Exits with 0 when up to date, 10 when an update is available, and 11 when not installed. When the check fails, it exits with the code of the error, which is always 20 or higher (listed in beans-rs --help).")
+                .args([
+                    Launcher::create_location_arg(),
+                    Launcher::create_json_arg()]))
+            .subcommand(Command::new("versions")
+                .about("Show the versions and patches that are available.")
+                .subcommand_required(true)
+                .subcommand(Command::new("list")
+                    .about("List every version, and which one is installed.")
+                    .args([
+                        Launcher::create_location_arg(),
+                        Launcher::create_json_arg()]))
+                .subcommand(Command::new("patches")
+                    .about("List every patch, and the versions that they go from and to.")
+                    .args([
+                        Launcher::create_location_arg(),
+                        Launcher::create_json_arg()]))
+                .subcommand(Command::new("show")
+                    .about("Show the details of a version.")
+                    .args([
+                        Arg::new("version")
+                            .help("Version to show")
+                            .required(true),
+                        Launcher::create_location_arg(),
+                        Launcher::create_json_arg()])))
+            .subcommand(Command::new("history")
+                .about("Show details about the installation, and everything that beans has done to it.")
+                .arg(Launcher::create_location_arg()))
+            .subcommand(Command::new("move")
+                .about("Move your installation to a different sourcemods directory (or drive)")
+                .args([
+                    Launcher::create_location_arg(),
+                    Arg::new("to")
+                        .long("to")
+                        .help("sourcemods directory to move the installation to")
+                        .required(true)]))
+            .subcommand(Command::new("adopt")
+                .about("Find which version an installation that wasn't installed by beans is, so it can be updated and verified.")
+                .args([
+                    Launcher::create_location_arg(),
+                    Self::create_confirm_arg()]))
+            .subcommand(Command::new("rpc")
+                .about("Read JSON-RPC 2.0 requests from stdin (one on each line), and write the responses and progress to stdout. Used by launchers that keep beans running in the background. Log messages are written to stderr.")
+                .arg(Launcher::create_location_arg()))
             .subcommand(Command::new("clean-tmp")
                 .about("Clean up temporary files used by beans"))
+            .subcommand(Command::new("config")
+                .about("Show or change the settings in the config file. Command-line arguments and environment variables are used instead of the config file when they're set.")
+                .subcommand_required(true)
+                .subcommand(Command::new("get")
+                    .about("Print the value of a setting.")
+                    .arg(Arg::new("key")
+                        .help("Name of the setting")
+                        .required(true)))
+                .subcommand(Command::new("set")
+                    .about("Change a setting in the config file.")
+                    .args([
+                        Arg::new("key")
+                            .help("Name of the setting")
+                            .required(true),
+                        Arg::new("value")
+                            .help("New value for the setting")
+                            .required(true)
+                    ]))
+                .subcommand(Command::new("unset")
+                    .about("Remove a setting from the config file, so the default is used.")
+                    .arg(Arg::new("key")
+                        .help("Name of the setting")
+                        .required(true)))
+                .subcommand(Command::new("list")
+                    .about("List every setting, it's value, and where the value came from.")
+                    .arg(Self::create_json_arg()))
+                .subcommand(Command::new("path")
+                    .about("Print the location of the config file.")))
             .subcommand(Command::new("uninstall")
                 .about("Uninstall the target Source Mod.")
-                .args([Launcher::create_location_arg()]))
+                .args([
+                    Launcher::create_location_arg(),
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("List what would be removed (and the total size) without removing anything.")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("keep-user-data")
+                        .long("keep-user-data")
+                        .help("Save cfg/, custom/ and demos/ to a .tar.gz file in the sourcemods directory before uninstalling.")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("trash")
+                        .long("trash")
+                        .help("Move the mod directory to the trash instead of deleting it. Only supported on Linux.")
+                        .action(ArgAction::SetTrue),
+                    Self::create_confirm_arg()]))
             .args([
                 Arg::new("debug")
                     .long("debug")
@@ -219,6 +389,22 @@ impl Launcher
                     .long("no-debug")
                     .help("Disable mode. Mainly used for debug builds to not spew into the console.")
                     .action(ArgAction::SetTrue),
+                Arg::new("json-errors")
+                    .long("json-errors")
+                    .help("When something fails, write the error to stderr as a JSON object with the code, id and message.")
+                    .global(true)
+                    .action(ArgAction::SetTrue),
+                Arg::new("output")
+                    .long("output")
+                    .help("Format of everything that is written to stdout. With json, a JSON object is written on each line (progress, log, prompt, error and result), ending with a result object. Prompts are declined unless [--interactive] is used.")
+                    .value_parser(["text", "json"])
+                    .default_value("text")
+                    .global(true),
+                Arg::new("interactive")
+                    .long("interactive")
+                    .help("With [--output json], wait for the answer to a prompt on stdin (one line) instead of declining it.")
+                    .global(true)
+                    .action(ArgAction::SetTrue),
                 Arg::new("no-pause")
                     .long("no-pause")
                     .help("When provided, beans-rs will not wait for user input before exiting. It is suggested that server owners use this for any of their scripts.")
@@ -227,7 +413,7 @@ impl Launcher
                 Self::create_confirm_arg()
             ]);
 
-        let mut i = Self::new(&cmd.get_matches());
+        let mut i = Self::new(&cmd.get_matches(), config);
         if let Ok(Some(v)) = helper::beans_has_update().await
         {
             info!("A new version of beans-rs is available!");
@@ -235,48 +421,176 @@ impl Launcher
         }
         i.subcommand_processor().await;
     }
-    pub fn new(matches: &ArgMatches) -> Self
+    pub fn new(
+        matches: &ArgMatches,
+        config: Config
+    ) -> Self
     {
+        let mut flags = LaunchFlag::STANDALONE_APP;
+        flags.set(LaunchFlag::DEBUG_MODE, DEBUG_MODE.load(Ordering::Relaxed));
         let mut i = Self {
             to_location: None,
-            root_matches: matches.clone()
+            root_matches: matches.clone(),
+            options: RunnerOptions {
+                flags,
+                confirm: false,
+                pause_once_done: false,
+                events: Arc::new(TerminalSink::default()),
+                config,
+                cancel: CancellationToken::new()
+            },
+            json_output: None
         };
+        i.set_output_format();
+        if i.root_matches.subcommand_name() == Some("rpc")
+        {
+            // stdout is only used for responses.
+            beans_rs::logger::log_to_stderr();
+        }
+        i.set_config_arguments();
         i.set_debug();
         i.set_no_pause();
         i.set_prompt_do_whatever();
-        i.to_location = Launcher::find_arg_sourcemods_location(&i.root_matches);
+        i.to_location = i.find_arg_sourcemods_location(&i.root_matches);
 
         i
     }
 
-    /// add `LaunchFlag::DEBUG_MODE` to `flags` when the `--debug` parameter
-    /// flag is used.
+    /// Use the values of `--debug`, `--no-debug` and `--no-pause` instead of
+    /// the config file and environment variables.
+    pub fn set_config_arguments(&mut self)
+    {
+        let mut arguments = ConfigValues::default();
+        if self.root_matches.get_flag("no-debug")
+        {
+            arguments.debug = Some(false);
+        }
+        else if self.root_matches.get_flag("debug")
+        {
+            arguments.debug = Some(true);
+        }
+        if self.root_matches.get_flag("no-pause")
+            || self.json_output.is_some()
+            || self.root_matches.subcommand_name() == Some("rpc")
+        {
+            arguments.pause_once_done = Some(false);
+        }
+        let config = &self.options.config;
+        self.options.config = Config::new(arguments, config.env.clone(), config.file.clone());
+    }
+
+    /// When `--output json` is provided, write everything as JSON to stdout
+    /// (see `JsonSink`) and don't wait for user input once done.
+    pub fn set_output_format(&mut self)
+    {
+        if self
+            .root_matches
+            .get_one::<String>("output")
+            .is_none_or(|v| v != "json")
+        {
+            return;
+        }
+        beans_rs::logger::set_json(true);
+        let sink = Arc::new(JsonSink::new(self.root_matches.get_flag("interactive")));
+        self.options.events = sink.clone();
+        self.json_output = Some(sink);
+    }
+
+    /// Name of the subcommand that is being run, including the nested
+    /// subcommand (like `versions list`).
+    fn command_name(&self) -> String
+    {
+        match self.root_matches.subcommand()
+        {
+            Some((name, matches)) => match matches.subcommand_name()
+            {
+                Some(sub) => format!("{name} {sub}"),
+                None => name.to_string()
+            },
+            None => String::from("wizard")
+        }
+    }
+
+    /// Write the result object when `--output json` is used. Must be the last
+    /// thing that is written to stdout.
+    fn write_result(
+        &self,
+        exit_code: i32
+    )
+    {
+        if let Some(sink) = &self.json_output
+        {
+            sink.result(&self.command_name(), exit_code);
+        }
+    }
+
+    /// add `LaunchFlag::DEBUG_MODE` to `self.options.flags` when the `--debug`
+    /// parameter flag is used.
     pub fn set_debug(&mut self)
     {
         if self.root_matches.get_flag("no-debug")
         {
-            flags::remove_flag(LaunchFlag::DEBUG_MODE);
+            set_debug_mode(false);
+            self.options.flags.remove(LaunchFlag::DEBUG_MODE);
             beans_rs::logger::set_filter(DEFAULT_LOG_LEVEL_RELEASE);
             info!("Disabled Debug Mode");
         }
         else if self.root_matches.get_flag("debug")
         {
-            flags::add_flag(LaunchFlag::DEBUG_MODE);
+            set_debug_mode(true);
+            self.options.flags.insert(LaunchFlag::DEBUG_MODE);
             beans_rs::logger::set_filter(LevelFilter::max());
             trace!("Debug mode enabled");
         }
     }
-    /// Set `PAUSE_ONCE_DONE` to `false` when `--no-pause` is provided.
-    /// Otherwise, set it to `true`.
+    /// Set `pause_once_done` to the value from the config, which is `false`
+    /// when `--no-pause` is provided.
     pub fn set_no_pause(&mut self)
     {
-        unsafe {
-            beans_rs::PAUSE_ONCE_DONE = !self.root_matches.get_flag("no-pause");
+        self.set_pause_once_done(self.options.config.pause_once_done);
+    }
+
+    /// Set `self.options.pause_once_done` and `PAUSE_ONCE_DONE` to `value`.
+    pub fn set_pause_once_done(
+        &mut self,
+        value: bool
+    )
+    {
+        PAUSE_ONCE_DONE.store(value, Ordering::Relaxed);
+        self.options.pause_once_done = value;
+    }
+
+    /// When `--json` is provided, write log messages to stderr (so stdout only
+    /// has JSON) and don't wait for user input once done.
+    ///
+    /// Returns `true` when `--json` or `--output json` is provided.
+    pub fn set_json_output(
+        &mut self,
+        matches: &ArgMatches
+    ) -> bool
+    {
+        if self.json_output.is_some()
+        {
+            return true;
+        }
+        let json = matches.get_flag("json");
+        if json
+        {
+            beans_rs::logger::log_to_stderr();
+            self.set_pause_once_done(false);
         }
+        json
     }
 
-    /// Set `self.to_location` when provided in the arguments.
-    pub fn find_arg_sourcemods_location(matches: &ArgMatches) -> Option<String>
+    /// Set `self.to_location` when provided in the arguments. Otherwise,
+    /// `sourcemods_location` from the config is used (when it exists).
+    ///
+    /// NOTE this function exits the process when the directory provided in
+    /// the arguments doesn't exist, and can't be created.
+    pub fn find_arg_sourcemods_location(
+        &self,
+        matches: &ArgMatches
+    ) -> Option<String>
     {
         let mut sml_dir_manual: Option<String> = None;
         if let Some(x) = matches.get_one::<String>("location")
@@ -285,9 +599,14 @@ impl Launcher
             {
                 if let Err(e) = std::fs::create_dir(x)
                 {
-                    debug!("{:#?}", e);
-                    error!("[Launcher::find_arg_sourcemods_location] Failed to create directory {x:?} ({e:})");
-                    panic!("[Launcher::find_arg_sourcemods_location] Failed to create directory {x:?}\n\n{e:#?}")
+                    self.exit_with_error(
+                        "Launcher::find_arg_sourcemods_location",
+                        BeansError::DirectoryCreateFailure {
+                            location: x.clone(),
+                            error: e,
+                            backtrace: Backtrace::capture()
+                        }
+                    );
                 }
             }
             sml_dir_manual = Some(parse_location(x.to_string()));
@@ -296,6 +615,18 @@ impl Launcher
                 x
             );
         }
+        else if let Some(x) = self.options.config.sourcemods_location.clone()
+        {
+            if helper::dir_exists(x.clone())
+            {
+                info!("[Launcher::find_arg_sourcemods_location] Using sourcemods_location from the config {x}");
+                sml_dir_manual = Some(parse_location(x));
+            }
+            else
+            {
+                warn!("[Launcher::find_arg_sourcemods_location] sourcemods_location in the config ({x}) doesn't exist, so it's ignored");
+            }
+        }
         sml_dir_manual
     }
 
@@ -320,29 +651,61 @@ impl Launcher
             {
                 self.task_uninstall(ui_matches).await;
             }
+            Some(("adopt", ad_matches)) =>
+            {
+                self.task_adopt(ad_matches).await;
+            }
+            Some(("status", s_matches)) =>
+            {
+                self.task_status(s_matches).await;
+            }
+            Some(("check-update", cu_matches)) =>
+            {
+                self.task_check_update(cu_matches).await;
+            }
+            Some(("versions", vs_matches)) =>
+            {
+                self.task_versions(vs_matches).await;
+            }
+            Some(("history", h_matches)) =>
+            {
+                self.task_history(h_matches).await;
+            }
+            Some(("move", mv_matches)) =>
+            {
+                self.task_move(mv_matches).await;
+            }
             Some(("wizard", wz_matches)) =>
             {
-                self.to_location = Launcher::find_arg_sourcemods_location(wz_matches);
+                self.to_location = self.find_arg_sourcemods_location(wz_matches);
                 self.task_wizard().await;
             }
             Some(("clean-tmp", _)) =>
             {
                 self.task_clean_tmp().await;
             }
+            Some(("config", cf_matches)) =>
+            {
+                self.task_config(cf_matches);
+            }
+            Some(("rpc", rpc_matches)) =>
+            {
+                self.task_rpc(rpc_matches);
+            }
             _ =>
             {
                 self.task_wizard().await;
             }
         }
+        self.write_result(0);
     }
 
+    /// Set `self.options.confirm` when `--confirm` is provided.
     pub fn set_prompt_do_whatever(&mut self)
     {
         if self.root_matches.get_flag("confirm")
         {
-            unsafe {
-                beans_rs::PROMPT_DO_WHATEVER = true;
-            }
+            self.options.confirm = true;
         }
     }
 
@@ -360,10 +723,16 @@ impl Launcher
     /// handler for the `wizard` subcommand. it's also the default subcommand.
     pub async fn task_wizard(&mut self)
     {
+        if self.json_output.is_some()
+        {
+            self.exit_with_error("Launcher::task_wizard", BeansError::JsonOutputUnsupported {
+                command: String::from("wizard")
+            });
+        }
         let x = self.try_get_smdp();
-        if let Err(e) = wizard::WizardContext::run(x).await
+        if let Err(e) = wizard::WizardContext::run(x, self.options.clone()).await
         {
-            panic!("Failed to run WizardContext {:#?}", e);
+            self.exit_with_error("WizardContext", e);
         }
         else
         {
@@ -373,22 +742,21 @@ impl Launcher
 
     /// handler for the `install` subcommand
     ///
-    /// NOTE this function uses `panic!` when `InstallWorkflow::wizard` fails.
-    /// panics are handled and are reported via sentry.
+    /// NOTE this function exits the process when `InstallWorkflow::wizard`
+    /// fails. See `Launcher::exit_with_error`
     pub async fn task_install(
         &mut self,
         matches: &ArgMatches
     )
     {
-        self.to_location = Launcher::find_arg_sourcemods_location(matches);
+        self.to_location = self.find_arg_sourcemods_location(matches);
         if matches.get_flag("confirm")
         {
-            unsafe {
-                beans_rs::PROMPT_DO_WHATEVER = true;
-            }
+            self.options.confirm = true;
         }
 
-        let mut ctx = self.try_create_context().await;
+        let ctx = self.try_create_context().await;
+        let clean = Launcher::find_arg_clean_install(matches);
 
         // call install_version when target-version is found.
         // we do this since target-version overrides the `from` parameter.
@@ -397,7 +765,8 @@ impl Launcher
         // so a return isn't required.
         if let Some(x) = matches.get_one::<String>("target-version")
         {
-            self.task_install_version_specific(ctx, x.clone()).await;
+            self.task_install_version_specific(ctx, x.clone(), clean)
+                .await;
         }
         // manually install from specific `.tar.zstd` file when the
         // --from parameter is provided. otherwise we install/reinstall
@@ -410,35 +779,64 @@ impl Launcher
                 ctx.sourcemod_path.clone()
             );
             if let Err(e) =
-                InstallWorkflow::install_from(x.clone(), ctx.sourcemod_path.clone(), None).await
+                InstallWorkflow::install_from(&ctx, x.clone(), None, None, clean, None).await
             {
-                error!("Failed to run InstallWorkflow::install_from");
-                sentry::capture_error(&e);
-                panic!("{:#?}", e);
+                self.exit_with_error("InstallWorkflow::install_from", e);
             }
             else
             {
                 logic_done();
             }
         }
-        else if let Err(e) = InstallWorkflow::wizard(&mut ctx).await
+        else
         {
-            panic!("Failed to run InstallWorkflow {:#?}", e);
+            let mut wf = InstallWorkflow {
+                context: ctx,
+                clean
+            };
+            if let Err(e) = wf.install_latest().await
+            {
+                self.exit_with_error("InstallWorkflow", e);
+            }
+            else
+            {
+                logic_done();
+            }
         }
-        else
+    }
+
+    /// create `CleanInstallOptions` from the `--clean`, `--preserve`,
+    /// `--dry-run` and `--force` arguments. will be `None` when `--clean`
+    /// wasn't provided.
+    fn find_arg_clean_install(matches: &ArgMatches) -> Option<CleanInstallOptions>
+    {
+        if !matches.get_flag("clean")
         {
-            logic_done();
+            return None;
         }
+        let mut options = CleanInstallOptions {
+            dry_run: matches.get_flag("dry-run"),
+            force: matches.get_flag("force"),
+            ..CleanInstallOptions::default()
+        };
+        if let Some(x) = matches.get_many::<String>("preserve")
+        {
+            options.preserve = x.cloned().collect();
+        }
+        Some(options)
     }
+
     /// handler for the `install` subcommand where the `--target-version`
     /// parameter is provided.
     ///
-    /// NOTE this function uses `expect` on `InstallWorkflow::install_version`.
-    /// panics are handled and are reported via sentry.
+    /// NOTE this function exits the process when
+    /// `InstallWorkflow::install_version` fails.
+    /// See `Launcher::exit_with_error`
     pub async fn task_install_version_specific(
         &mut self,
         ctx: RunnerContext,
-        version_str: String
+        version_str: String,
+        clean: Option<CleanInstallOptions>
     )
     {
         let version = match usize::from_str(&version_str)
@@ -446,23 +844,22 @@ impl Launcher
             Ok(v) => v,
             Err(e) =>
             {
-                sentry::capture_error(&e);
-                error!(
-                    "Failed to parse version argument \"{version_str}\": {:#?}",
-                    e
+                debug!("[Launcher::task_install_version_specific] {e:#?}");
+                self.exit_with_error(
+                    "Launcher::task_install_version_specific",
+                    BeansError::InvalidVersionArgument {
+                        version: version_str
+                    }
                 );
-                logic_done();
-                return;
             }
         };
         let mut wf = InstallWorkflow {
-            context: ctx
+            context: ctx,
+            clean
         };
         if let Err(e) = wf.install_version(version).await
         {
-            error!("Failed to run InstallWorkflow::install_version");
-            sentry::capture_error(&e);
-            panic!("{:#?}", e);
+            self.exit_with_error("InstallWorkflow::install_version", e);
         }
         else
         {
@@ -472,19 +869,19 @@ impl Launcher
 
     /// handler for the `verify` subcommand
     ///
-    /// NOTE this function uses `panic!` when `VerifyWorkflow::wizard` fails.
-    /// panics are handled and are reported via sentry.
+    /// NOTE this function exits the process when `VerifyWorkflow::wizard`
+    /// fails. See `Launcher::exit_with_error`
     pub async fn task_verify(
         &mut self,
         matches: &ArgMatches
     )
     {
-        self.to_location = Launcher::find_arg_sourcemods_location(matches);
+        self.to_location = self.find_arg_sourcemods_location(matches);
         let mut ctx = self.try_create_context().await;
 
         if let Err(e) = VerifyWorkflow::wizard(&mut ctx).await
         {
-            panic!("Failed to run VerifyWorkflow {:#?}", e);
+            self.exit_with_error("VerifyWorkflow", e);
         }
         else
         {
@@ -494,19 +891,19 @@ impl Launcher
 
     /// handler for the `update` subcommand
     ///
-    /// NOTE this function uses `panic!` when `UpdateWorkflow::wizard` fails.
-    /// panics are handled and are reported via sentry.
+    /// NOTE this function exits the process when `UpdateWorkflow::wizard`
+    /// fails. See `Launcher::exit_with_error`
     pub async fn task_update(
         &mut self,
         matches: &ArgMatches
     )
     {
-        self.to_location = Launcher::find_arg_sourcemods_location(matches);
+        self.to_location = self.find_arg_sourcemods_location(matches);
         let mut ctx = self.try_create_context().await;
 
         if let Err(e) = UpdateWorkflow::wizard(&mut ctx).await
         {
-            panic!("Failed to run UpdateWorkflow {:#?}", e);
+            self.exit_with_error("UpdateWorkflow", e);
         }
         else
         {
@@ -516,14 +913,14 @@ impl Launcher
 
     /// Handler for the `clean-tmp` subcommand.
     ///
-    /// NOTE this function uses `panic!` when `CleanWorkflow::wizard` fails.
-    /// panics are handled and are reported via sentry.
+    /// NOTE this function exits the process when `CleanWorkflow::wizard` fails.
+    /// See `Launcher::exit_with_error`
     pub async fn task_clean_tmp(&mut self)
     {
         let mut ctx = self.try_create_context().await;
         if let Err(e) = CleanWorkflow::wizard(&mut ctx)
         {
-            panic!("Failed to run CleanWorkflow {:#?}", e);
+            self.exit_with_error("CleanWorkflow", e);
         }
         else
         {
@@ -531,21 +928,129 @@ impl Launcher
         }
     }
 
+    /// handler for the `config` subcommand
+    ///
+    /// NOTE this function exits the process when `ConfigWorkflow` fails.
+    /// See `Launcher::exit_with_error`
+    pub fn task_config(
+        &mut self,
+        matches: &ArgMatches
+    )
+    {
+        self.set_pause_once_done(false);
+        let (name, sub_matches) = match matches.subcommand()
+        {
+            Some(v) => v,
+            None => return
+        };
+        let json = match name
+        {
+            "list" => self.set_json_output(sub_matches),
+            _ => self.json_output.is_some()
+        };
+        let get_arg = |id: &str| {
+            sub_matches
+                .get_one::<String>(id)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let wf = ConfigWorkflow {
+            json,
+            events: self.options.events.clone(),
+            config: self.options.config.clone()
+        };
+        let result = match name
+        {
+            "get" => wf.get(get_arg("key")),
+            "set" => wf.set(get_arg("key"), get_arg("value")),
+            "unset" => wf.unset(get_arg("key")),
+            "list" => wf.list(),
+            "path" => wf.path(),
+            _ => Ok(())
+        };
+        match result
+        {
+            Err(e) => self.exit_with_error("ConfigWorkflow", e),
+            Ok(_) => logic_done()
+        }
+    }
+
+    /// handler for the `rpc` subcommand. Returns once stdin is closed, and
+    /// every request has finished.
+    pub fn task_rpc(
+        &mut self,
+        matches: &ArgMatches
+    )
+    {
+        if self.json_output.is_some()
+        {
+            self.exit_with_error("Launcher::task_rpc", BeansError::JsonOutputUnsupported {
+                command: String::from("rpc")
+            });
+        }
+        self.to_location = self.find_arg_sourcemods_location(matches);
+        let server = RpcServer::new(
+            tokio::runtime::Handle::current(),
+            self.options.clone(),
+            self.to_location.clone()
+        );
+        server.serve(std::io::stdin().lock());
+    }
+
     /// handler for the `uninstall` subcommand
     ///
-    /// NOTE this function uses `panic!` when `UninstallWorkflow::wizard` fails.
-    /// panics are handled and are reported via sentry.
+    /// NOTE this function exits the process when `UninstallWorkflow::wizard`
+    /// fails. See `Launcher::exit_with_error`
     pub async fn task_uninstall(
         &mut self,
         matches: &ArgMatches
     )
     {
-        self.to_location = Launcher::find_arg_sourcemods_location(matches);
+        self.to_location = self.find_arg_sourcemods_location(matches);
+        if matches.get_flag("confirm")
+        {
+            self.options.confirm = true;
+        }
+        let ctx = self.try_create_context().await;
+
+        let mut wf = UninstallWorkflow {
+            context: ctx,
+            options: UninstallOptions {
+                dry_run: matches.get_flag("dry-run"),
+                keep_user_data: matches.get_flag("keep-user-data"),
+                trash: matches.get_flag("trash")
+            }
+        };
+        if let Err(e) = wf.run().await
+        {
+            self.exit_with_error("UninstallWorkflow", e);
+        }
+        else
+        {
+            logic_done();
+        }
+    }
+
+    /// handler for the `adopt` subcommand
+    ///
+    /// NOTE this function exits the process when `AdoptWorkflow::wizard` fails.
+    /// See `Launcher::exit_with_error`
+    pub async fn task_adopt(
+        &mut self,
+        matches: &ArgMatches
+    )
+    {
+        self.to_location = self.find_arg_sourcemods_location(matches);
+        if matches.get_flag("confirm")
+        {
+            self.options.confirm = true;
+        }
         let mut ctx = self.try_create_context().await;
 
-        if let Err(e) = UninstallWorkflow::wizard(&mut ctx).await
+        if let Err(e) = AdoptWorkflow::wizard(&mut ctx).await
         {
-            panic!("Failed to run UninstallWorkflow {:#?}", e);
+            self.exit_with_error("AdoptWorkflow", e);
         }
         else
         {
@@ -553,47 +1058,280 @@ impl Launcher
         }
     }
 
-    /// try and create an instance of `RunnerContext` via the `create_auto`
-    /// method while setting the `sml_via` parameter to the output of
-    /// `self.try_get_smdp()`
+    /// handler for the `status` subcommand
     ///
-    /// on failure, `panic!` is called. but that's okay because a dialog is
-    /// shown (in `init_panic_handle`) and the error is reported via sentry.
-    async fn try_create_context(&mut self) -> RunnerContext
+    /// NOTE this function exits the process when `StatusWorkflow::run` fails.
+    /// See `Launcher::exit_with_error`
+    pub async fn task_status(
+        &mut self,
+        matches: &ArgMatches
+    )
     {
-        match RunnerContext::create_auto(self.try_get_smdp()).await
+        let json = self.set_json_output(matches);
+        // `find_arg_sourcemods_location` isn't used since it creates the
+        // directory, and the config is checked by `StatusWorkflow`.
+        let sourcemods_location = matches
+            .get_one::<String>("location")
+            .or(self.root_matches.get_one::<String>("location"))
+            .cloned();
+        let wf = StatusWorkflow {
+            sourcemods_location,
+            appvar: AppVarData::get(),
+            json,
+            events: self.options.events.clone(),
+            config: self.options.config.clone()
+        };
+        if let Err(e) = wf.run().await
         {
-            Ok(v) => v,
+            self.exit_with_error("StatusWorkflow", e);
+        }
+        else
+        {
+            logic_done();
+        }
+    }
+
+    /// handler for the `check-update` subcommand
+    ///
+    /// NOTE this function always exits the process, with the exit code from
+    /// `UpdateCheckStatus::exit_code` (or `BeansError::code` when it
+    /// fails).
+    pub async fn task_check_update(
+        &mut self,
+        matches: &ArgMatches
+    )
+    {
+        let json = self.set_json_output(matches);
+        self.set_pause_once_done(false);
+        // `find_arg_sourcemods_location` isn't used since it creates the directory.
+        let sourcemods_location = matches
+            .get_one::<String>("location")
+            .cloned()
+            .or(self.to_location.clone());
+        let wf = CheckUpdateWorkflow {
+            sourcemods_location,
+            appvar: AppVarData::get(),
+            json,
+            events: self.options.events.clone()
+        };
+        match wf.run().await
+        {
+            Ok(status) =>
+            {
+                self.write_result(status.exit_code());
+                std::process::exit(status.exit_code());
+            }
             Err(e) =>
             {
-                error!("[try_create_context] {:}", e);
-                trace!("======== Full Error ========");
-                trace!("{:#?}", &e);
-                show_msgbox_error(format!("{:}", &e));
-
-                let do_report = !matches!(
-                    e,
-                    BeansError::GameStillRunning { .. }
-                        | BeansError::LatestVersionAlreadyInstalled { .. }
-                        | BeansError::FreeSpaceCheckFailure { .. }
-                );
+                error!("[Launcher::task_check_update] {e:}");
+                if let Some(hint) = e.hint()
+                {
+                    warn!("Hint: {}", hint);
+                }
+                trace!("{:#?}", e);
+                if self.root_matches.get_flag("json-errors")
+                {
+                    eprintln!("{}", e.to_json());
+                }
+                if let Some(sink) = &self.json_output
+                {
+                    sink.error(&e);
+                    self.write_result(e.code());
+                }
+                else if json
+                {
+                    // same as `--json-errors`, with a status like the report
+                    // has when the check worked.
+                    let mut value = e.to_json();
+                    value["status"] = serde_json::json!("error");
+                    println!("{}", value);
+                }
+                std::process::exit(e.code());
+            }
+        }
+    }
 
-                if do_report
+    /// handler for the `versions` subcommand
+    ///
+    /// NOTE this function exits the process when `VersionsWorkflow` fails.
+    /// See `Launcher::exit_with_error`
+    pub async fn task_versions(
+        &mut self,
+        matches: &ArgMatches
+    )
+    {
+        let (name, sub_matches) = match matches.subcommand()
+        {
+            Some(v) => v,
+            None => return
+        };
+        let json = self.set_json_output(sub_matches);
+        // `find_arg_sourcemods_location` isn't used since it creates the directory.
+        let sourcemods_location = sub_matches
+            .get_one::<String>("location")
+            .cloned()
+            .or(self.to_location.clone());
+        let wf = VersionsWorkflow {
+            sourcemods_location,
+            appvar: AppVarData::get(),
+            json,
+            events: self.options.events.clone()
+        };
+        let result = match name
+        {
+            "list" => wf.list().await,
+            "patches" => wf.patches().await,
+            "show" =>
+            {
+                let version_str = sub_matches
+                    .get_one::<String>("version")
+                    .cloned()
+                    .unwrap_or_default();
+                match usize::from_str(version_str.trim_start_matches('v'))
                 {
-                    sentry::capture_error(&e);
+                    Ok(v) => wf.show(v).await,
+                    Err(e) =>
+                    {
+                        debug!("[Launcher::task_versions] {e:#?}");
+                        Err(BeansError::InvalidVersionArgument {
+                            version: version_str
+                        })
+                    }
                 }
-                logic_done();
-                std::process::exit(1);
             }
+            _ => Ok(())
+        };
+        match result
+        {
+            Err(e) => self.exit_with_error("VersionsWorkflow", e),
+            Ok(_) => logic_done()
+        }
+    }
+
+    /// handler for the `history` subcommand
+    ///
+    /// NOTE this function exits the process when `HistoryWorkflow::wizard`
+    /// fails. See `Launcher::exit_with_error`
+    pub async fn task_history(
+        &mut self,
+        matches: &ArgMatches
+    )
+    {
+        self.to_location = self.find_arg_sourcemods_location(matches);
+        let mut ctx = self.try_create_context().await;
+
+        if let Err(e) = HistoryWorkflow::wizard(&mut ctx)
+        {
+            self.exit_with_error("HistoryWorkflow", e);
+        }
+        else
+        {
+            logic_done();
         }
     }
+
+    /// handler for the `move` subcommand
+    ///
+    /// NOTE this function exits the process when `RelocateWorkflow::run` fails.
+    /// See `Launcher::exit_with_error`
+    pub async fn task_move(
+        &mut self,
+        matches: &ArgMatches
+    )
+    {
+        self.to_location = self.find_arg_sourcemods_location(matches);
+        let ctx = self.try_create_context().await;
+        let target_sourcemods = matches.get_one::<String>("to").cloned().unwrap_or_default();
+
+        let mut wf = RelocateWorkflow {
+            context: ctx,
+            target_sourcemods
+        };
+        if let Err(e) = wf.run().await
+        {
+            self.exit_with_error("RelocateWorkflow", e);
+        }
+        else
+        {
+            logic_done();
+        }
+    }
+
+    /// try and create an instance of `RunnerContext` via the `create` method
+    /// while setting the `sml_via` parameter to the output of
+    /// `self.try_get_smdp()`, and the options to `self.options`
+    ///
+    /// on failure, the process exits with `Launcher::exit_with_error`
+    async fn try_create_context(&mut self) -> RunnerContext
+    {
+        let sml_via = self.try_get_smdp();
+        match RunnerContext::create(sml_via, AppVarData::get(), self.options.clone()).await
+        {
+            Ok(v) => v,
+            Err(e) => self.exit_with_error("try_create_context", e)
+        }
+    }
+
+    /// Log `e`, show it in a dialog (unless `--no-pause` is used), report it
+    /// via sentry, then exit with the code from `BeansError::code`.
+    ///
+    /// When `--json-errors` is provided, `BeansError::to_json` is written to
+    /// stderr. With `--output json`, it's also written to stdout (followed by
+    /// the result object).
+    fn exit_with_error(
+        &self,
+        source: &str,
+        e: BeansError
+    ) -> !
+    {
+        error!("[{}] {:}", source, e);
+        trace!("======== Full Error ========");
+        trace!("{:#?}", &e);
+        if let Some(hint) = e.hint()
+        {
+            warn!("Hint: {}", hint);
+        }
+        if self.root_matches.get_flag("json-errors")
+        {
+            eprintln!("{}", e.to_json());
+        }
+        if PAUSE_ONCE_DONE.load(Ordering::Relaxed)
+        {
+            show_msgbox_error(&e);
+        }
+        if let Some(sink) = &self.json_output
+        {
+            sink.error(&e);
+            self.write_result(e.code());
+        }
+
+        let do_report = !matches!(
+            e,
+            BeansError::GameStillRunning { .. }
+                | BeansError::LatestVersionAlreadyInstalled { .. }
+                | BeansError::FreeSpaceCheckFailure { .. }
+        );
+        if do_report
+        {
+            sentry::capture_error(&e);
+        }
+        logic_done();
+        std::process::exit(e.code());
+    }
 }
 
-fn show_msgbox_error(text: String)
+/// Show `error` (and its hint, when there is one) in a dialog.
+fn show_msgbox_error(error: &BeansError)
 {
+    let mut text = format!("{:}", error);
+    if let Some(hint) = error.hint()
+    {
+        text.push_str(&format!("\n\n{}", hint));
+    }
     beans_rs::gui::DialogBuilder::new()
         .with_title(String::from("beans - Fatal Error!"))
         .with_icon(DialogIconKind::Error)
         .with_content(text.replace("\\n", "\n"))
         .run();
+    dialog_closed();
 }
@@ -0,0 +1,793 @@
+//! [JSON-RPC 2.0](https://www.jsonrpc.org/specification) server over stdin and
+//! stdout, so a launcher can keep beans running as a child process (see the
+//! `rpc` command).
+//!
+//! Every request and response is a single line. Requests for a workflow (see
+//! `WORKFLOW_METHODS`) run on their own thread, so `cancel` and `answer` can be
+//! used while they're running. Everything the workflow reports is sent as an
+//! `event` notification, with the `id` of the request and the fields of
+//! `events::Event`. The result is the `events::Event::Report` from the
+//! workflow (like `StatusReport`), or `null` when there isn't one.
+//!
+//! Only one of the workflows that change files runs at a time. The others wait
+//! for it to finish.
+//!
+//! `cancel` stops a request at the next step of it's workflow (butler is
+//! killed when it's running), which cleans up like it does for any other
+//! error. The request gets a `REQUEST_CANCELLED` error once that's done.
+use std::{collections::HashMap,
+          io::{BufRead,
+               Write},
+          sync::{mpsc,
+                 Arc,
+                 Mutex}};
+
+use futures::future::LocalBoxFuture;
+use log::{debug,
+          warn};
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+
+use crate::{appvar::AppVarData,
+            config::Config,
+            events::{parse_confirm,
+                     write_json_line,
+                     Event,
+                     EventSink,
+                     PromptKind},
+            helper,
+            workflows::{CleanInstallOptions,
+                        InstallWorkflow,
+                        StatusWorkflow,
+                        UninstallOptions,
+                        UninstallWorkflow,
+                        UpdateWorkflow,
+                        VerifyWorkflow,
+                        VersionsWorkflow},
+            BeansError,
+            RunnerContext,
+            RunnerOptions,
+            SourceModDirectoryParam};
+
+#[cfg(test)]
+mod tests;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+/// The request isn't valid JSON.
+pub const PARSE_ERROR: i64 = -32700;
+/// The request isn't a JSON-RPC 2.0 request object.
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Response to a request that was stopped with the `cancel` method. Same as
+/// the code used by the Language Server Protocol.
+pub const REQUEST_CANCELLED: i64 = -32800;
+
+/// Methods that run a workflow. Every other method is answered straight away.
+///
+/// `status` and `versions` only read files, so they never wait for the others.
+pub const WORKFLOW_METHODS: &[&str] = &[
+    "status",
+    "versions",
+    "install",
+    "update",
+    "verify",
+    "uninstall",
+    "switch"
+];
+
+type SharedWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+/// Runs a request from `WORKFLOW_METHODS`, see `call`.
+type CallFn = fn(
+    String,
+    Value,
+    RunnerOptions,
+    Option<String>
+) -> LocalBoxFuture<'static, Result<(), RpcError>>;
+
+/// Request object. `id` is `None` for notifications, which never get a
+/// response.
+#[derive(Debug, serde::Deserialize)]
+struct Request
+{
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value
+}
+
+/// Error object in a response. When it was caused by a `BeansError`, the code
+/// is from `BEANS_ERROR_CODES` and `data` is `BeansError::to_json`.
+#[derive(Debug, Clone)]
+pub struct RpcError
+{
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>
+}
+
+impl RpcError
+{
+    pub fn new(
+        code: i64,
+        message: String
+    ) -> Self
+    {
+        Self {
+            code,
+            message,
+            data: None
+        }
+    }
+
+    fn invalid_params(error: serde_json::Error) -> Self
+    {
+        Self::new(INVALID_PARAMS, format!("Invalid params ({error:})"))
+    }
+
+    fn to_json(&self) -> Value
+    {
+        let mut value = serde_json::json!({
+            "code": self.code,
+            "message": self.message
+        });
+        if let Some(data) = &self.data
+        {
+            value["data"] = data.clone();
+        }
+        value
+    }
+}
+
+impl From<BeansError> for RpcError
+{
+    fn from(error: BeansError) -> Self
+    {
+        Self {
+            code: error.code() as i64,
+            message: format!("{error:}"),
+            data: Some(error.to_json())
+        }
+    }
+}
+
+/// Params that every workflow method accepts.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct CommonParams
+{
+    /// sourcemods directory. Same as `--location`.
+    location: Option<String>,
+    /// Use the default answer for every prompt instead of sending it to the
+    /// client. Same as `--confirm`.
+    confirm: bool
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct InstallParams
+{
+    /// Latest version when `None`.
+    version: Option<usize>,
+    /// Same as `install --clean`.
+    clean: bool,
+    /// Same as `install --preserve`. The defaults are used when it's empty.
+    preserve: Vec<String>,
+    /// Same as `install --dry-run`.
+    dry_run: bool,
+    /// Same as `install --force`.
+    force: bool
+}
+
+impl InstallParams
+{
+    fn clean_options(&self) -> Option<CleanInstallOptions>
+    {
+        if !self.clean
+        {
+            return None;
+        }
+        let mut options = CleanInstallOptions {
+            dry_run: self.dry_run,
+            force: self.force,
+            ..CleanInstallOptions::default()
+        };
+        if !self.preserve.is_empty()
+        {
+            options.preserve = self.preserve.clone();
+        }
+        Some(options)
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct UninstallParams
+{
+    dry_run: bool,
+    keep_user_data: bool,
+    trash: bool
+}
+
+/// Params for `cancel` and `answer`.
+#[derive(Debug, serde::Deserialize)]
+struct TargetParams
+{
+    /// `id` of the request that is running.
+    id: Value,
+    /// Only used by `answer`. `true`/`false` for `PromptKind::Confirm`, or a
+    /// string for `PromptKind::Text`.
+    #[serde(default)]
+    value: Value
+}
+
+/// Request that is running on it's own thread.
+struct RunningRequest
+{
+    cancel: CancellationToken,
+    /// `None` once stdin is closed, so questions are declined instead of
+    /// waiting forever.
+    answers: Option<mpsc::Sender<String>>
+}
+
+/// Reads requests from stdin, and writes responses and notifications to
+/// stdout. See the module documentation.
+pub struct RpcServer
+{
+    writer: SharedWriter,
+    /// Used to run the workflows, since each request has it's own thread.
+    runtime: tokio::runtime::Handle,
+    /// Used for every `RunnerContext`, except for `events` and `confirm`.
+    options: RunnerOptions,
+    /// Used when a request doesn't have the `location` param.
+    sourcemods_location: Option<String>,
+    /// Key is the `id` of the request as JSON.
+    running: Arc<Mutex<HashMap<String, RunningRequest>>>,
+    /// Held while a workflow that changes files is running.
+    busy: Arc<Mutex<()>>,
+    /// Always `call`, except for tests.
+    call: CallFn
+}
+
+impl RpcServer
+{
+    /// Create a server that writes to stdout.
+    pub fn new(
+        runtime: tokio::runtime::Handle,
+        options: RunnerOptions,
+        sourcemods_location: Option<String>
+    ) -> Self
+    {
+        Self::with_writer(
+            Box::new(std::io::stdout()),
+            runtime,
+            options,
+            sourcemods_location
+        )
+    }
+
+    pub fn with_writer(
+        writer: Box<dyn Write + Send>,
+        runtime: tokio::runtime::Handle,
+        options: RunnerOptions,
+        sourcemods_location: Option<String>
+    ) -> Self
+    {
+        Self {
+            writer: Arc::new(Mutex::new(writer)),
+            runtime,
+            options,
+            sourcemods_location,
+            running: Arc::new(Mutex::new(HashMap::new())),
+            busy: Arc::new(Mutex::new(())),
+            call: |method, params, options, location| {
+                Box::pin(call(method, params, options, location))
+            }
+        }
+    }
+
+    /// Handle every line from `input` until it's closed, then wait for the
+    /// requests that are still running. Questions that are asked after `input`
+    /// is closed are declined.
+    ///
+    /// NOTE this blocks the current thread, so it must not be called from a
+    /// tokio worker thread.
+    pub fn serve<R: BufRead>(
+        &self,
+        input: R
+    )
+    {
+        let mut threads: Vec<std::thread::JoinHandle<()>> = Vec::new();
+        for line in input.lines()
+        {
+            let line = match line
+            {
+                Ok(v) => v,
+                Err(e) =>
+                {
+                    warn!("[RpcServer::serve] Failed to read request ({e:})");
+                    break;
+                }
+            };
+            if line.trim().is_empty()
+            {
+                continue;
+            }
+            if let Some(t) = self.handle_line(&line)
+            {
+                threads.push(t);
+            }
+            threads.retain(|t| !t.is_finished());
+        }
+
+        debug!(
+            "[RpcServer::serve] input closed, waiting for {} request(s)",
+            threads.len()
+        );
+        for r in lock(&self.running).values_mut()
+        {
+            r.answers = None;
+        }
+        for t in threads
+        {
+            let _ = t.join();
+        }
+    }
+
+    /// Handle a single request. Returns the thread that it's running on when
+    /// it's for a workflow.
+    fn handle_line(
+        &self,
+        line: &str
+    ) -> Option<std::thread::JoinHandle<()>>
+    {
+        let value: Value = match serde_json::from_str(line)
+        {
+            Ok(v) => v,
+            Err(e) =>
+            {
+                self.respond(
+                    Some(Value::Null),
+                    Err(RpcError::new(PARSE_ERROR, format!("Parse error ({e:})")))
+                );
+                return None;
+            }
+        };
+        let request = match serde_json::from_value::<Request>(value.clone())
+        {
+            Ok(v) if v.jsonrpc == JSONRPC_VERSION => v,
+            _ =>
+            {
+                let id = value.get("id").cloned().unwrap_or(Value::Null);
+                self.respond(
+                    Some(id),
+                    Err(RpcError::new(
+                        INVALID_REQUEST,
+                        String::from("Invalid request (batches aren't supported)")
+                    ))
+                );
+                return None;
+            }
+        };
+
+        match request.method.as_str()
+        {
+            "cancel" =>
+            {
+                let result = self.cancel(request.params);
+                self.respond(request.id, result);
+                None
+            }
+            "answer" =>
+            {
+                let result = self.answer(request.params);
+                self.respond(request.id, result);
+                None
+            }
+            m if WORKFLOW_METHODS.contains(&m) => self.spawn(request),
+            m =>
+            {
+                let message = format!("Method not found ({m})");
+                self.respond(request.id, Err(RpcError::new(METHOD_NOT_FOUND, message)));
+                None
+            }
+        }
+    }
+
+    /// Stop the request with the `id` from `params` at the next step. It's
+    /// question is declined when it's waiting for `answer`.
+    ///
+    /// The request is responded to with `REQUEST_CANCELLED` once the workflow
+    /// has stopped (and cleaned up), which is after the response to `cancel`.
+    fn cancel(
+        &self,
+        params: Value
+    ) -> Result<Value, RpcError>
+    {
+        let params: TargetParams =
+            serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+        let cancelled = match lock(&self.running).get_mut(&params.id.to_string())
+        {
+            Some(r) =>
+            {
+                r.cancel.cancel();
+                r.answers = None;
+                true
+            }
+            None => false
+        };
+        Ok(serde_json::json!({ "cancelled": cancelled }))
+    }
+
+    /// Answer the last `Event::PromptRequired` from the request with the `id`
+    /// from `params`.
+    fn answer(
+        &self,
+        params: Value
+    ) -> Result<Value, RpcError>
+    {
+        let params: TargetParams =
+            serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+        let value = match params.value
+        {
+            Value::Bool(true) => String::from("y"),
+            Value::Bool(false) => String::from("n"),
+            Value::String(v) => v,
+            _ =>
+            {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    String::from("Invalid params (value must be a boolean or a string)")
+                ));
+            }
+        };
+        let sender = lock(&self.running)
+            .get(&params.id.to_string())
+            .and_then(|r| r.answers.clone());
+        match sender.map(|s| s.send(value))
+        {
+            Some(Ok(_)) => Ok(Value::Null),
+            _ => Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("Request {} isn't running", params.id)
+            ))
+        }
+    }
+
+    /// Run a request from `WORKFLOW_METHODS` on a new thread.
+    fn spawn(
+        &self,
+        request: Request
+    ) -> Option<std::thread::JoinHandle<()>>
+    {
+        let cancel = CancellationToken::new();
+        let (answers_tx, answers_rx) = mpsc::channel();
+        let key = request.id.as_ref().map(|v| v.to_string());
+        if let Some(k) = &key
+        {
+            let mut running = lock(&self.running);
+            if running.contains_key(k)
+            {
+                drop(running);
+                self.respond(
+                    request.id,
+                    Err(RpcError::new(
+                        INVALID_REQUEST,
+                        String::from("Invalid request (a request with this id is already running)")
+                    ))
+                );
+                return None;
+            }
+            running.insert(k.clone(), RunningRequest {
+                cancel: cancel.clone(),
+                answers: Some(answers_tx)
+            });
+        }
+
+        let sink = Arc::new(RpcSink {
+            id: request.id.clone().unwrap_or(Value::Null),
+            writer: self.writer.clone(),
+            answers: Mutex::new(answers_rx),
+            report: Mutex::new(None)
+        });
+        let mut options = self.options.clone();
+        options.events = sink.clone();
+        options.cancel = cancel.clone();
+        let call = self.call;
+        let location = self.sourcemods_location.clone();
+        let runtime = self.runtime.clone();
+        let running = self.running.clone();
+        let busy = self.busy.clone();
+        let writer = self.writer.clone();
+        Some(std::thread::spawn(move || {
+            let _busy = match request.method.as_str()
+            {
+                "status" | "versions" => None,
+                _ => Some(lock(&busy))
+            };
+            // might've been cancelled while it was waiting for `busy`.
+            let result = match cancel.is_cancelled()
+            {
+                true => Err(RpcError::from(BeansError::Cancelled)),
+                false => runtime.block_on(call(
+                    request.method.clone(),
+                    request.params,
+                    options,
+                    location
+                ))
+            };
+            if let Some(k) = key
+            {
+                lock(&running).remove(&k);
+            }
+            let result = match result
+            {
+                Ok(_) => Ok(sink.take_report().unwrap_or(Value::Null)),
+                Err(_) if cancel.is_cancelled() => Err(RpcError::new(
+                    REQUEST_CANCELLED,
+                    String::from("Request cancelled")
+                )),
+                Err(e) => Err(e)
+            };
+            write_response(&writer, request.id, result);
+        }))
+    }
+
+    fn respond(
+        &self,
+        id: Option<Value>,
+        result: Result<Value, RpcError>
+    )
+    {
+        write_response(&self.writer, id, result);
+    }
+}
+
+/// Run `method` with a new `RunnerContext`.
+async fn call(
+    method: String,
+    params: Value,
+    mut options: RunnerOptions,
+    default_location: Option<String>
+) -> Result<(), RpcError>
+{
+    let common: CommonParams =
+        serde_json::from_value(params.clone()).map_err(RpcError::invalid_params)?;
+    let install: InstallParams =
+        serde_json::from_value(params.clone()).map_err(RpcError::invalid_params)?;
+    let uninstall: UninstallParams =
+        serde_json::from_value(params).map_err(RpcError::invalid_params)?;
+    if method == "switch" && install.version.is_none()
+    {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            String::from("Invalid params (version is required)")
+        ));
+    }
+    options.confirm = common.confirm;
+    let location = common.location.or(default_location);
+    // these don't need a `RunnerContext`, see `StatusWorkflow` and
+    // `VersionsWorkflow`
+    if method == "status"
+    {
+        StatusWorkflow {
+            sourcemods_location: location,
+            appvar: AppVarData::get(),
+            json: true,
+            events: options.events.clone(),
+            config: options.config.clone()
+        }
+        .run()
+        .await?;
+        return Ok(());
+    }
+    if method == "versions"
+    {
+        VersionsWorkflow {
+            sourcemods_location: location,
+            appvar: AppVarData::get(),
+            json: true,
+            events: options.events.clone()
+        }
+        .list()
+        .await?;
+        return Ok(());
+    }
+    let mut ctx = RunnerContext::create(
+        sourcemods_param(location, &options.config),
+        AppVarData::get(),
+        options
+    )
+    .await?;
+
+    match method.as_str()
+    {
+        "install" | "switch" =>
+        {
+            let mut wf = InstallWorkflow {
+                context: ctx,
+                clean: install.clean_options()
+            };
+            match install.version
+            {
+                Some(v) => wf.install_version(v).await?,
+                None => wf.install_latest().await?
+            }
+        }
+        "update" => UpdateWorkflow::wizard(&mut ctx).await?,
+        "verify" => VerifyWorkflow::wizard(&mut ctx).await?,
+        "uninstall" =>
+        {
+            UninstallWorkflow {
+                context: ctx,
+                options: UninstallOptions {
+                    dry_run: uninstall.dry_run,
+                    keep_user_data: uninstall.keep_user_data,
+                    trash: uninstall.trash
+                }
+            }
+            .run()
+            .await?
+        }
+        m =>
+        {
+            return Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Method not found ({m})")
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Use `location` when provided, otherwise `sourcemods_location` from the
+/// config (when it exists).
+fn sourcemods_param(
+    location: Option<String>,
+    config: &Config
+) -> SourceModDirectoryParam
+{
+    match location.or(config.sourcemods_location.clone())
+    {
+        Some(x) if helper::dir_exists(x.clone()) =>
+        {
+            SourceModDirectoryParam::WithLocation(helper::parse_location(x))
+        }
+        _ => SourceModDirectoryParam::AutoDetect
+    }
+}
+
+fn write_response(
+    writer: &SharedWriter,
+    id: Option<Value>,
+    result: Result<Value, RpcError>
+)
+{
+    // notifications never get a response, even when they fail.
+    let Some(id) = id
+    else
+    {
+        return;
+    };
+    let value = match result
+    {
+        Ok(v) => serde_json::json!({
+            "jsonrpc": JSONRPC_VERSION,
+            "id": id,
+            "result": v
+        }),
+        Err(e) => serde_json::json!({
+            "jsonrpc": JSONRPC_VERSION,
+            "id": id,
+            "error": e.to_json()
+        })
+    };
+    write_json_line(Some(writer.as_ref()), &value);
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T>
+{
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Sends every event from a request as an `event` notification, and waits for
+/// `answer` when a question is asked.
+struct RpcSink
+{
+    /// `id` of the request.
+    id: Value,
+    writer: SharedWriter,
+    answers: Mutex<mpsc::Receiver<String>>,
+    /// Last `Event::Report`, which is used as the result.
+    report: Mutex<Option<Value>>
+}
+
+impl std::fmt::Debug for RpcSink
+{
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>
+    ) -> std::fmt::Result
+    {
+        f.debug_struct("RpcSink")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RpcSink
+{
+    fn take_report(&self) -> Option<Value>
+    {
+        lock(&self.report).take()
+    }
+
+    /// Wait for `RpcServer::answer`. `None` when the request was cancelled, or
+    /// stdin was closed.
+    fn wait_for_answer(&self) -> Option<String>
+    {
+        lock(&self.answers).recv().ok()
+    }
+}
+
+impl EventSink for RpcSink
+{
+    fn emit(
+        &self,
+        event: Event
+    )
+    {
+        if let Event::Report {
+            data
+        } = event
+        {
+            *lock(&self.report) = Some(data);
+            return;
+        }
+        let mut params = match serde_json::to_value(&event)
+        {
+            Ok(v) => v,
+            Err(e) =>
+            {
+                debug!("[RpcSink::emit] Failed to serialize {:?} {:#?}", event, e);
+                return;
+            }
+        };
+        params["id"] = self.id.clone();
+        write_json_line(
+            Some(self.writer.as_ref()),
+            &serde_json::json!({
+                "jsonrpc": JSONRPC_VERSION,
+                "method": "event",
+                "params": params
+            })
+        );
+    }
+
+    /// Declines when the request was cancelled, or stdin was closed.
+    fn confirm(
+        &self,
+        question: &str
+    ) -> bool
+    {
+        self.emit(Event::PromptRequired {
+            question: question.to_string(),
+            kind: PromptKind::Confirm
+        });
+        self.wait_for_answer().is_some_and(|v| parse_confirm(&v))
+    }
+
+    fn input(
+        &self,
+        question: &str
+    ) -> Option<String>
+    {
+        self.emit(Event::PromptRequired {
+            question: question.to_string(),
+            kind: PromptKind::Text
+        });
+        self.wait_for_answer()
+    }
+}
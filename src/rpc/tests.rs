@@ -0,0 +1,327 @@
+use std::{io::{Cursor,
+               Read,
+               Write},
+          sync::{Arc,
+                 Mutex},
+          time::{Duration,
+                 Instant}};
+
+use super::*;
+use crate::events::{Phase,
+                    Progress};
+
+/// Writer for `RpcServer::with_writer` that can be read after it's used.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer
+{
+    fn write(
+        &mut self,
+        buf: &[u8]
+    ) -> std::io::Result<usize>
+    {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>
+    {
+        Ok(())
+    }
+}
+
+/// Run the server until every line in `input` is handled, and get everything
+/// that it wrote.
+fn serve(input: &str) -> Vec<Value>
+{
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let buffer = SharedBuffer::default();
+    let server = RpcServer::with_writer(
+        Box::new(buffer.clone()),
+        runtime.handle().clone(),
+        RunnerOptions::default(),
+        None
+    );
+    server.serve(input.as_bytes());
+    buffer.lines()
+}
+
+impl SharedBuffer
+{
+    /// Every line that was written, as JSON.
+    fn lines(&self) -> Vec<Value>
+    {
+        let output = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        output
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+}
+
+/// Input for `RpcServer::serve` that is written to while the server is
+/// running. Closed once the sender is dropped.
+struct ChannelInput
+{
+    lines: mpsc::Receiver<String>,
+    buffer: Cursor<Vec<u8>>
+}
+
+impl Read for ChannelInput
+{
+    fn read(
+        &mut self,
+        buf: &mut [u8]
+    ) -> std::io::Result<usize>
+    {
+        if self.buffer.position() as usize >= self.buffer.get_ref().len()
+        {
+            match self.lines.recv()
+            {
+                Ok(l) => self.buffer = Cursor::new(format!("{l}\n").into_bytes()),
+                Err(_) => return Ok(0)
+            }
+        }
+        self.buffer.read(buf)
+    }
+}
+
+/// Server that is running on it's own thread, with `call` instead of the
+/// workflows (which need the network).
+struct TestServer
+{
+    input: mpsc::Sender<String>,
+    output: SharedBuffer,
+    thread: std::thread::JoinHandle<()>
+}
+
+impl TestServer
+{
+    fn start(call: CallFn) -> Self
+    {
+        let (input, lines) = mpsc::channel();
+        let output = SharedBuffer::default();
+        let writer = output.clone();
+        let thread = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let mut server = RpcServer::with_writer(
+                Box::new(writer),
+                runtime.handle().clone(),
+                RunnerOptions::default(),
+                None
+            );
+            server.call = call;
+            server.serve(std::io::BufReader::new(ChannelInput {
+                lines,
+                buffer: Cursor::new(Vec::new())
+            }));
+        });
+        Self {
+            input,
+            output,
+            thread
+        }
+    }
+
+    fn send(
+        &self,
+        line: &str
+    )
+    {
+        self.input.send(line.to_string()).unwrap();
+    }
+
+    /// Wait until the server writes a line that matches `predicate`.
+    fn wait_for<F: Fn(&Value) -> bool>(
+        &self,
+        predicate: F
+    )
+    {
+        let started = Instant::now();
+        while !self.output.lines().iter().any(&predicate)
+        {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "{:#?}",
+                self.output.lines()
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Close the input, and get everything that was written once every
+    /// request is finished.
+    fn finish(self) -> Vec<Value>
+    {
+        drop(self.input);
+        self.thread.join().unwrap();
+        self.output.lines()
+    }
+}
+
+/// `event` notification with `event` as the type.
+fn is_event(
+    value: &Value,
+    event: &str
+) -> bool
+{
+    value["method"] == "event" && value["params"]["event"] == event
+}
+
+/// Position of the response to the request with `id`.
+fn response_index(
+    output: &[Value],
+    id: i64
+) -> usize
+{
+    let responses: Vec<usize> = output
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| v.get("method").is_none() && v["id"] == id)
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(responses.len(), 1, "{output:#?}");
+    responses[0]
+}
+
+#[test]
+fn invalid_requests_get_an_error()
+{
+    let output = serve(concat!(
+        "not json\n",
+        "{\"jsonrpc\":\"1.0\",\"id\":1,\"method\":\"status\"}\n",
+        "{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"nope\"}\n",
+        "{\"jsonrpc\":\"2.0\",\"method\":\"nope\"}\n",
+        "{\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"switch\",\"params\":{\"clean\":true}}\n"
+    ));
+    // the notification doesn't get a response.
+    assert_eq!(output.len(), 4);
+    assert!(output.iter().all(|r| r["jsonrpc"] == JSONRPC_VERSION));
+    assert_eq!(output[0]["id"], Value::Null);
+    assert_eq!(output[0]["error"]["code"], PARSE_ERROR);
+    assert_eq!(output[1]["id"], 1);
+    assert_eq!(output[1]["error"]["code"], INVALID_REQUEST);
+    assert_eq!(output[2]["id"], 2);
+    assert_eq!(output[2]["error"]["code"], METHOD_NOT_FOUND);
+    // checked before anything is downloaded.
+    assert_eq!(output[3]["id"], 3);
+    assert_eq!(output[3]["error"]["code"], INVALID_PARAMS);
+}
+
+#[test]
+fn cancel_and_answer_need_a_running_request()
+{
+    let output = serve(concat!(
+        "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"cancel\",\"params\":{\"id\":7}}\n",
+        "{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"answer\",\"params\":{\"id\":7,\"value\":true}}\n",
+        "{\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"answer\",\"params\":{\"id\":7,\"value\":4}}\n",
+        "{\"jsonrpc\":\"2.0\",\"id\":4,\"method\":\"cancel\"}\n"
+    ));
+    assert_eq!(output.len(), 4);
+    assert_eq!(output[0]["result"]["cancelled"], false);
+    assert_eq!(output[1]["error"]["code"], INVALID_PARAMS);
+    assert_eq!(output[2]["error"]["code"], INVALID_PARAMS);
+    assert_eq!(output[3]["error"]["code"], INVALID_PARAMS);
+}
+
+#[test]
+fn progress_is_sent_as_notifications()
+{
+    let server = TestServer::start(|method, _, options, _| {
+        Box::pin(async move {
+            let pb = Progress::start(
+                options.events.as_ref(),
+                Phase::Download,
+                String::from("Downloading"),
+                Some(10)
+            );
+            pb.set_position(4);
+            pb.finish();
+            options.events.emit(Event::Report {
+                data: serde_json::json!({ "method": method })
+            });
+            Ok(())
+        })
+    });
+    server.send(r#"{"jsonrpc":"2.0","id":1,"method":"install"}"#);
+    let output = server.finish();
+
+    let response = response_index(&output, 1);
+    assert_eq!(response, output.len() - 1);
+    assert_eq!(output[response]["result"]["method"], "install");
+    let events = &output[..response];
+    assert!(events.iter().all(|e| e["params"]["id"] == 1));
+    assert!(is_event(&events[0], "phase_started"));
+    assert_eq!(events[0]["params"]["phase"], "download");
+    assert_eq!(events[0]["params"]["total"], 10);
+    assert!(events.iter().any(|e| is_event(e, "progress")
+        && e["params"]["current"] == 4
+        && e["params"]["total"] == 10));
+    let last = events.last().unwrap();
+    assert!(is_event(last, "finished"));
+    assert_eq!(last["params"]["success"], true);
+}
+
+#[test]
+fn cancel_waits_for_the_workflow_to_stop()
+{
+    let server = TestServer::start(|_, _, options, _| {
+        Box::pin(async move {
+            options.events.message(String::from("started"));
+            options.cancel.cancelled().await;
+            // the response must wait for this, even though it takes a while.
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            options.events.message(String::from("cleaned up"));
+            Err(RpcError::from(BeansError::Cancelled))
+        })
+    });
+    server.send(r#"{"jsonrpc":"2.0","id":1,"method":"update"}"#);
+    server.wait_for(|v| v["params"]["message"] == "started");
+    server.send(r#"{"jsonrpc":"2.0","id":2,"method":"cancel","params":{"id":1}}"#);
+    let output = server.finish();
+
+    let cancel = response_index(&output, 2);
+    assert_eq!(output[cancel]["result"]["cancelled"], true);
+    let cancelled = response_index(&output, 1);
+    assert_eq!(output[cancelled]["error"]["code"], REQUEST_CANCELLED);
+    let cleaned_up = output
+        .iter()
+        .position(|v| v["params"]["message"] == "cleaned up")
+        .unwrap();
+    assert!(cancel < cleaned_up);
+    assert!(cleaned_up < cancelled);
+}
+
+#[test]
+fn answer_resolves_a_prompt()
+{
+    let server = TestServer::start(|_, _, options, _| {
+        Box::pin(async move {
+            let confirmed = options.events.confirm("Continue?");
+            let name = options.events.input("Name?");
+            options.events.emit(Event::Report {
+                data: serde_json::json!({ "confirmed": confirmed, "name": name })
+            });
+            Ok(())
+        })
+    });
+    server.send(r#"{"jsonrpc":"2.0","id":1,"method":"install"}"#);
+    server.wait_for(|v| is_event(v, "prompt_required") && v["params"]["kind"] == "confirm");
+    server.send(r#"{"jsonrpc":"2.0","id":2,"method":"answer","params":{"id":1,"value":true}}"#);
+    server.wait_for(|v| is_event(v, "prompt_required") && v["params"]["kind"] == "text");
+    server.send(r#"{"jsonrpc":"2.0","id":3,"method":"answer","params":{"id":1,"value":"beans"}}"#);
+    let output = server.finish();
+
+    assert_eq!(output[response_index(&output, 2)]["result"], Value::Null);
+    assert_eq!(output[response_index(&output, 3)]["result"], Value::Null);
+    let result = &output[response_index(&output, 1)]["result"];
+    assert_eq!(result["confirmed"], true);
+    assert_eq!(result["name"], "beans");
+}
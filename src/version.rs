@@ -1,11 +1,12 @@
 use std::{backtrace::Backtrace,
           collections::HashMap,
-          fs::read_to_string,
-          io::Write};
+          fs::read_to_string};
 
 use log::{debug,
           error,
-          trace};
+          info,
+          trace,
+          warn};
 
 use crate::{appvar::AppVarData,
             helper,
@@ -15,40 +16,181 @@ use crate::{appvar::AppVarData,
 
 /// get the current version installed via the .adastral file in the sourcemod
 /// mod folder. will parse the value of `version` as usize.
-pub fn get_current_version(sourcemods_location: Option<String>) -> Option<usize>
+///
+/// Returns `BeansError::VersionFileCorrupt` when `.adastral` is empty,
+/// truncated or doesn't contain a valid version.
+pub fn get_current_version(
+    sourcemods_location: Option<String>,
+    appvar: &AppVarData
+) -> Result<Option<usize>, BeansError>
 {
-    let install_state = helper::install_state(sourcemods_location.clone());
+    let install_state = helper::install_state(sourcemods_location.clone(), appvar);
     if install_state != InstallType::Adastral
     {
-        return None;
+        return Ok(None);
     }
-    match get_mod_location(sourcemods_location)
+    match get_version_location(sourcemods_location, appvar)
     {
-        Some(smp_x) =>
+        Some(location) => Ok(Some(read_version_file(location)?)),
+        None => Ok(None)
+    }
+}
+
+/// Get the installed version without changing anything. Unlike
+/// `get_current_version`, a `.revision` file is read without migrating it to
+/// `.adastral`.
+pub fn read_installed_version(
+    sourcemods_location: String,
+    appvar: &AppVarData
+) -> Result<Option<usize>, BeansError>
+{
+    match helper::install_state(Some(sourcemods_location.clone()), appvar)
+    {
+        InstallType::Adastral => get_current_version(Some(sourcemods_location), appvar),
+        InstallType::OtherSource =>
+        {
+            let mod_location = helper::join_path(sourcemods_location, appvar.data_dir());
+            Ok(Some(read_revision_file(format!(
+                "{}.revision",
+                mod_location
+            ))?))
+        }
+        InstallType::OtherSourceManual =>
+        {
+            info!("[version::read_installed_version] Found an installation that wasn't installed by beans. Use the adopt command so it can be updated.");
+            Ok(None)
+        }
+        InstallType::NotInstalled => Ok(None)
+    }
+}
+
+/// Read the version from the `.adastral` file at `location`.
+pub fn read_version_file(location: String) -> Result<usize, BeansError>
+{
+    let data = parse_version_file(location.clone())?;
+    match data.version.trim().parse::<usize>()
+    {
+        Ok(v) => Ok(v),
+        Err(e) => Err(BeansError::VersionFileCorrupt {
+            location,
+            reason: format!("the version \"{}\" isn't a number ({e:})", data.version)
+        })
+    }
+}
+
+/// Read the `.adastral` file at `location`.
+pub fn parse_version_file(location: String) -> Result<AdastralVersionFile, BeansError>
+{
+    let content = match read_to_string(&location)
+    {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData =>
+        {
+            return Err(BeansError::VersionFileCorrupt {
+                location,
+                reason: String::from("it isn't a text file")
+            });
+        }
+        Err(e) =>
         {
-            // TODO generate BeansError instead of using panic
-            let location = format!("{}.adastral", smp_x);
-            let content =
-                read_to_string(&location).unwrap_or_else(|_| panic!("Failed to open {}", location));
-            let data: AdastralVersionFile = serde_json::from_str(&content)
-                .unwrap_or_else(|_| panic!("Failed to deserialize data at {}", location));
-            let parsed = data.version.parse::<usize>().unwrap_or_else(|_| {
-                panic!("Failed to convert version to usize! ({})", data.version)
+            error!("[version::parse_version_file] Failed to read {location} ({e:})");
+            return Err(BeansError::VersionFileReadFailure {
+                error: e,
+                location
             });
+        }
+    };
+    if content.trim().is_empty()
+    {
+        return Err(BeansError::VersionFileCorrupt {
+            location,
+            reason: String::from("it's empty")
+        });
+    }
+    let data: AdastralVersionFile = match serde_json::from_str(&content)
+    {
+        Ok(v) => v,
+        Err(e) =>
+        {
+            debug!("[version::parse_version_file] {:#?}", e);
+            let reason = match e.classify()
+            {
+                serde_json::error::Category::Eof => String::from("it's been cut off"),
+                _ => format!("it isn't valid ({e:})")
+            };
+            return Err(BeansError::VersionFileCorrupt {
+                location,
+                reason
+            });
+        }
+    };
+    Ok(data)
+}
 
-            Some(parsed)
+/// Read the version from the `.revision` file at `location`. This is the old
+/// version file, which only contains the version number.
+pub fn read_revision_file(location: String) -> Result<usize, BeansError>
+{
+    let content = match read_to_string(&location)
+    {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData =>
+        {
+            return Err(BeansError::VersionFileCorrupt {
+                location,
+                reason: String::from("it isn't a text file")
+            });
+        }
+        Err(e) =>
+        {
+            debug!(
+                "[version::read_revision_file] failed to read {}. {:#?}",
+                location, e
+            );
+            sentry::capture_error(&e);
+            return Err(BeansError::VersionFileReadFailure {
+                error: e,
+                location
+            });
+        }
+    };
+    if content.trim().is_empty()
+    {
+        return Err(BeansError::VersionFileCorrupt {
+            location,
+            reason: String::from("it's empty")
+        });
+    }
+    match content.trim().parse::<usize>()
+    {
+        Ok(v) => Ok(v),
+        Err(e) =>
+        {
+            debug!(
+                "[version::read_revision_file] Failed to parse content {} caused error {:}",
+                content, e
+            );
+            Err(BeansError::VersionFileCorrupt {
+                location,
+                reason: format!("\"{}\" isn't a number", content.trim())
+            })
         }
-        None => None
     }
 }
 
-fn get_version_location(sourcemods_location: Option<String>) -> Option<String>
+fn get_version_location(
+    sourcemods_location: Option<String>,
+    appvar: &AppVarData
+) -> Option<String>
 {
-    get_mod_location(sourcemods_location).map(|v| format!("{}.adastral", v))
+    get_mod_location(sourcemods_location, appvar).map(|v| format!("{}.adastral", v))
 }
 
 /// get the full location of the sourcemod mod directory.
-fn get_mod_location(sourcemods_location: Option<String>) -> Option<String>
+fn get_mod_location(
+    sourcemods_location: Option<String>,
+    appvar: &AppVarData
+) -> Option<String>
 {
     let smp_x = match sourcemods_location
     {
@@ -68,14 +210,17 @@ fn get_mod_location(sourcemods_location: Option<String>) -> Option<String>
             }
         }
     };
-    Some(helper::join_path(smp_x, crate::data_dir()))
+    Some(helper::join_path(smp_x, appvar.data_dir()))
 }
 
 /// migrate from old file (.revision) to new file (.adastral) in sourcemod mod
 /// directory.
-pub fn update_version_file(sourcemods_location: Option<String>) -> Result<(), BeansError>
+pub fn update_version_file(
+    sourcemods_location: Option<String>,
+    appvar: &AppVarData
+) -> Result<(), BeansError>
 {
-    let install_state = helper::install_state(sourcemods_location.clone());
+    let install_state = helper::install_state(sourcemods_location.clone(), appvar);
 
     match install_state
     {
@@ -97,7 +242,7 @@ pub fn update_version_file(sourcemods_location: Option<String>) -> Result<(), Be
         InstallType::OtherSourceManual =>
         {
             debug!(
-                "[version::update_version_file] install_state is {:#?}, ignoring.",
+                "[version::update_version_file] install_state is {:#?}, ignoring. (use AdoptWorkflow instead)",
                 install_state
             );
         }
@@ -122,46 +267,12 @@ pub fn update_version_file(sourcemods_location: Option<String>) -> Result<(), Be
                 }
             };
 
-            let data_dir = helper::join_path(smp_x, crate::data_dir());
+            let data_dir = helper::join_path(smp_x, appvar.data_dir());
 
             let old_version_file_location = format!("{}.revision", &data_dir);
-            let old_version_file_content = match read_to_string(&old_version_file_location)
-            {
-                Ok(v) => v,
-                Err(e) =>
-                {
-                    debug!(
-                        "[update_version_file] failed to read {}. {:#?}",
-                        old_version_file_location, e
-                    );
-                    sentry::capture_error(&e);
-                    return Err(BeansError::VersionFileReadFailure {
-                        error: e,
-                        location: old_version_file_location
-                    });
-                }
-            };
-            let old_version_idx = match old_version_file_content.parse::<usize>()
-            {
-                Ok(v) => v,
-                Err(e) =>
-                {
-                    debug!(
-                        "[update_version_file] Failed to parse content {} caused error {:}",
-                        old_version_file_content, e
-                    );
-                    sentry::capture_error(&e);
-                    return Err(BeansError::VersionFileParseFailure {
-                        error: e,
-                        old_location: old_version_file_location,
-                        old_content: old_version_file_content
-                    });
-                }
-            };
+            let old_version_idx = read_revision_file(old_version_file_location.clone())?;
 
-            let new_file_content = AdastralVersionFile {
-                version: old_version_idx.to_string()
-            };
+            let new_file_content = AdastralVersionFile::new(old_version_idx);
 
             let new_version_file_location = format!("{}.adastral", &data_dir);
             let new_version_file_content = match serde_json::to_string(&new_file_content)
@@ -177,8 +288,10 @@ pub fn update_version_file(sourcemods_location: Option<String>) -> Result<(), Be
                 }
             };
 
-            if let Err(e) =
-                std::fs::write(new_version_file_location.clone(), new_version_file_content)
+            if let Err(e) = helper::write_atomic(
+                new_version_file_location.clone(),
+                new_version_file_content.as_bytes()
+            )
             {
                 sentry::capture_error(&e);
                 return Err(BeansError::VersionFileMigrationFailure {
@@ -199,11 +312,10 @@ pub fn update_version_file(sourcemods_location: Option<String>) -> Result<(), Be
     Ok(())
 }
 
-/// fetch the version list from `{crate::SOURCE_URL}versions.json`
-pub async fn get_version_list() -> Result<RemoteVersionResponse, BeansError>
+/// fetch the version list from `appvar.remote_info.versions_url`
+pub async fn get_version_list(appvar: &AppVarData) -> Result<RemoteVersionResponse, BeansError>
 {
-    let av = AppVarData::get();
-    let response = match reqwest::get(&av.remote_info.versions_url).await
+    let response = match reqwest::get(&appvar.remote_info.versions_url).await
     {
         Ok(v) => v,
         Err(e) =>
@@ -229,54 +341,287 @@ pub async fn get_version_list() -> Result<RemoteVersionResponse, BeansError>
     Ok(data)
 }
 
+/// Current value for `AdastralVersionFile.schema`.
+pub const ADASTRAL_SCHEMA_VERSION: u32 = 2;
+/// Maximum amount of items in `AdastralVersionFile.history`. The oldest items
+/// are removed first.
+pub const ADASTRAL_HISTORY_LIMIT: usize = 50;
+/// Release channel that versions from `versions.json` are from. There is only
+/// one right now.
+pub const RELEASE_CHANNEL: &str = "stable";
+
 /// Version file that is used as `.adastral` in the sourcemod mod folder.
+///
+/// Everything except `version` was added in schema v2, and is optional so v1
+/// files (`{"version":"18"}`) can still be read. Older versions of beans will
+/// ignore the new properties.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct AdastralVersionFile
 {
-    pub version: String
+    pub version: String,
+    /// Will be `1` when reading a file that doesn't have it.
+    #[serde(default = "AdastralVersionFile::schema_v1")]
+    pub schema: u32,
+    /// When the current version was installed (RFC 3339)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<String>,
+    /// Version of beans that installed the current version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub beans_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// Where the package was installed from. Either the mirror that it was
+    /// downloaded from, or the location of the file (when `install --from`
+    /// is used).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// SHA-256 hash of the package that was installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package_checksum: Option<String>,
+    /// Everything that beans has done to this installation, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<AdastralHistoryEntry>
 }
 
 impl AdastralVersionFile
 {
+    pub fn new(version: usize) -> Self
+    {
+        Self {
+            version: version.to_string(),
+            schema: ADASTRAL_SCHEMA_VERSION,
+            installed_at: None,
+            beans_version: Some(crate::VERSION.to_string()),
+            channel: Some(RELEASE_CHANNEL.to_string()),
+            source: None,
+            package_checksum: None,
+            history: Vec::new()
+        }
+    }
+    fn schema_v1() -> u32
+    {
+        1
+    }
+
+    /// Read `.adastral` from the directory of the mod in `appvar` in
+    /// `sourcemods_location`. Returns `None` when it doesn't exist.
+    pub fn read(
+        sourcemods_location: Option<String>,
+        appvar: &AppVarData
+    ) -> Result<Option<Self>, BeansError>
+    {
+        match get_version_location(sourcemods_location, appvar)
+        {
+            Some(location) if helper::file_exists(location.clone()) =>
+            {
+                Ok(Some(parse_version_file(location)?))
+            }
+            _ => Ok(None)
+        }
+    }
+
+    /// Add `entry` to the history, and remove the oldest items when there are
+    /// more than `ADASTRAL_HISTORY_LIMIT`.
+    pub fn push_history(
+        &mut self,
+        entry: AdastralHistoryEntry
+    )
+    {
+        self.history.push(entry);
+        if self.history.len() > ADASTRAL_HISTORY_LIMIT
+        {
+            let excess = self.history.len() - ADASTRAL_HISTORY_LIMIT;
+            self.history.drain(0..excess);
+        }
+    }
+
     pub fn write(
         &self,
-        sourcemods_location: Option<String>
+        sourcemods_location: Option<String>,
+        appvar: &AppVarData
     ) -> Result<(), BeansError>
     {
-        match get_version_location(sourcemods_location)
+        let mut data = self.clone();
+        data.schema = ADASTRAL_SCHEMA_VERSION;
+        match get_version_location(sourcemods_location, appvar)
         {
             Some(vl) =>
             {
-                let f = match helper::file_exists(vl.clone())
+                let ser = serde_json::to_string(&data)?;
+                if let Err(e) = helper::write_atomic(vl.clone(), ser.as_bytes())
                 {
-                    true => std::fs::File::create(vl.clone()),
-                    false => std::fs::File::create_new(vl.clone())
-                };
-                match f
-                {
-                    Ok(mut file) => match serde_json::to_string(self)
-                    {
-                        Ok(ser) => match file.write_all(ser.as_bytes())
-                        {
-                            Ok(_) => Ok(()),
-                            Err(e) => Err(BeansError::FileWriteFailure {
-                                location: vl,
-                                error: e
-                            })
-                        },
-                        Err(e) => Err(e.into())
-                    },
-                    Err(e) => Err(BeansError::FileOpenFailure {
+                    return Err(BeansError::FileWriteFailure {
                         location: vl,
                         error: e
-                    })
+                    });
                 }
+                Ok(())
             }
             None => Err(BeansError::SourceModLocationNotFound)
         }
     }
 }
 
+/// Something that beans did to an installation. Stored in
+/// `AdastralVersionFile.history`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AdastralHistoryEntry
+{
+    pub operation: AdastralOperation,
+    /// When the operation finished (RFC 3339)
+    pub timestamp: String,
+    pub beans_version: String,
+    #[serde(default)]
+    pub from_version: Option<usize>,
+    #[serde(default)]
+    pub to_version: Option<usize>,
+    pub outcome: AdastralOperationOutcome,
+    /// Error message when `outcome` is `Failure`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Files that were repaired or patched by butler (relative to the mod
+    /// directory).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    /// Files that were removed by a patch (relative to the mod directory).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_files: Vec<String>
+}
+
+impl AdastralHistoryEntry
+{
+    /// Create a history entry for an operation that has just finished, with
+    /// the outcome based on `result`.
+    pub fn new<T>(
+        operation: AdastralOperation,
+        from_version: Option<usize>,
+        to_version: Option<usize>,
+        result: &Result<T, BeansError>
+    ) -> Self
+    {
+        let (outcome, message) = match result
+        {
+            Ok(_) => (AdastralOperationOutcome::Success, None),
+            Err(e) => (AdastralOperationOutcome::Failure, Some(format!("{e:}")))
+        };
+        Self {
+            operation,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            beans_version: crate::VERSION.to_string(),
+            from_version,
+            to_version,
+            outcome,
+            message,
+            files: Vec::new(),
+            removed_files: Vec::new()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdastralOperation
+{
+    Install,
+    Update,
+    Verify,
+    /// Installing a different version to the one that was installed.
+    Switch,
+    Adopt,
+    Move
+}
+
+impl std::fmt::Display for AdastralOperation
+{
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>
+    ) -> std::fmt::Result
+    {
+        let s = match self
+        {
+            AdastralOperation::Install => "install",
+            AdastralOperation::Update => "update",
+            AdastralOperation::Verify => "verify",
+            AdastralOperation::Switch => "switch",
+            AdastralOperation::Adopt => "adopt",
+            AdastralOperation::Move => "move"
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdastralOperationOutcome
+{
+    Success,
+    Failure
+}
+
+impl std::fmt::Display for AdastralOperationOutcome
+{
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>
+    ) -> std::fmt::Result
+    {
+        match self
+        {
+            AdastralOperationOutcome::Success => write!(f, "success"),
+            AdastralOperationOutcome::Failure => write!(f, "failure")
+        }
+    }
+}
+
+/// Add `entry` to the history in `.adastral`. When `entry` is successful and
+/// has a `to_version`, the version in `.adastral` is set to it.
+///
+/// Errors are logged instead of returned, since failing to record history
+/// shouldn't make the operation fail.
+pub fn record_history(
+    sourcemods_location: Option<String>,
+    appvar: &AppVarData,
+    entry: AdastralHistoryEntry
+)
+{
+    let mut data = match AdastralVersionFile::read(sourcemods_location.clone(), appvar)
+    {
+        Ok(Some(v)) => v,
+        Ok(None) =>
+        {
+            match entry.to_version
+            {
+                Some(v) if entry.outcome == AdastralOperationOutcome::Success =>
+                {
+                    AdastralVersionFile::new(v)
+                }
+                _ =>
+                {
+                    debug!("[version::record_history] .adastral doesn't exist, not recording {entry:?}");
+                    return;
+                }
+            }
+        }
+        Err(e) =>
+        {
+            warn!("[version::record_history] Not recording {} since .adastral couldn't be read ({e:})", entry.operation);
+            return;
+        }
+    };
+    if entry.outcome == AdastralOperationOutcome::Success
+        && let Some(v) = entry.to_version
+    {
+        data.version = v.to_string();
+    }
+    data.push_history(entry);
+    if let Err(e) = data.write(sourcemods_location, appvar)
+    {
+        warn!("[version::record_history] Failed to write .adastral ({e:})");
+        debug!("{:#?}", e);
+    }
+}
+
 /// Value of the `versions` property in `RemoteVersionResponse`
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct RemoteVersion
@@ -301,6 +646,67 @@ pub struct RemoteVersionResponse
     pub patches: HashMap<usize, RemotePatch>
 }
 
+impl RemoteVersionResponse
+{
+    /// Get the version that each patch goes from and to (see
+    /// `RemotePatch::versions`), sorted by the version it goes from. Patches
+    /// with an unknown filename format are ignored.
+    pub fn patch_graph(
+        &self,
+        short_name: &str
+    ) -> Vec<(usize, usize, RemotePatch)>
+    {
+        let mut result: Vec<(usize, usize, RemotePatch)> = self
+            .patches
+            .values()
+            .filter_map(|p| p.versions(short_name).map(|(f, t)| (f, t, p.clone())))
+            .collect();
+        result.sort_by_key(|(f, t, _)| (*f, *t));
+        result
+    }
+
+    /// Find the patches that need to be applied (in order) to go from
+    /// `from_version` to `to_version`, using as few patches as possible.
+    ///
+    /// Returns `None` when there isn't a way to get to `to_version` with
+    /// patches.
+    pub fn find_patch_chain(
+        &self,
+        short_name: &str,
+        from_version: usize,
+        to_version: usize
+    ) -> Option<Vec<RemotePatch>>
+    {
+        let graph = self.patch_graph(short_name);
+        let mut previous: HashMap<usize, (usize, RemotePatch)> = HashMap::new();
+        let mut queue = std::collections::VecDeque::from([from_version]);
+        while let Some(current) = queue.pop_front()
+        {
+            if current == to_version
+            {
+                let mut chain: Vec<RemotePatch> = Vec::new();
+                let mut v = current;
+                while let Some((p, patch)) = previous.get(&v)
+                {
+                    chain.push(patch.clone());
+                    v = *p;
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+            for (f, t, patch) in graph.iter()
+            {
+                if *f == current && *t != from_version && !previous.contains_key(t)
+                {
+                    previous.insert(*t, (current, patch.clone()));
+                    queue.push_back(*t);
+                }
+            }
+        }
+        None
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct RemotePatch
 {
@@ -310,3 +716,105 @@ pub struct RemotePatch
     /// in bytes.
     pub tempreq: usize
 }
+
+impl RemotePatch
+{
+    /// Get the version that this patch goes from and to, from the filename
+    /// (`{short_name}-{from}to{to}.pwr`)
+    pub fn versions(
+        &self,
+        short_name: &str
+    ) -> Option<(usize, usize)>
+    {
+        let name = self
+            .file
+            .strip_prefix(&format!("{}-", short_name))?
+            .strip_suffix(".pwr")?;
+        let (from, to) = name.split_once("to")?;
+        Some((from.parse().ok()?, to.parse().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// Write `content` to a `.adastral` file in the temp directory, and return
+    /// its location.
+    fn write_test_file(
+        name: &str,
+        content: &[u8]
+    ) -> String
+    {
+        let location = std::env::temp_dir().join(format!(
+            "beans-version-{}-{}.adastral",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&location, content).unwrap();
+        location.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn v1_version_file()
+    {
+        let location = write_test_file("v1", br#"{"version":"18"}"#);
+        let data = parse_version_file(location.clone()).unwrap();
+        assert_eq!(data.version, "18");
+        assert_eq!(data.schema, 1);
+        assert!(data.installed_at.is_none());
+        assert!(data.channel.is_none());
+        assert!(data.history.is_empty());
+        assert_eq!(read_version_file(location.clone()).unwrap(), 18);
+        std::fs::remove_file(location).unwrap();
+    }
+
+    #[test]
+    fn history_is_trimmed()
+    {
+        let mut data = AdastralVersionFile::new(1);
+        for i in 0..ADASTRAL_HISTORY_LIMIT + 5
+        {
+            data.push_history(AdastralHistoryEntry::new::<()>(
+                AdastralOperation::Update,
+                Some(i),
+                Some(i + 1),
+                &Ok(())
+            ));
+        }
+        assert_eq!(data.history.len(), ADASTRAL_HISTORY_LIMIT);
+        // the oldest items are removed first
+        assert_eq!(data.history[0].from_version, Some(5));
+        assert_eq!(
+            data.history.last().unwrap().to_version,
+            Some(ADASTRAL_HISTORY_LIMIT + 5)
+        );
+    }
+
+    #[test]
+    fn corrupt_version_file()
+    {
+        let cases: &[(&str, &[u8])] = &[
+            ("empty", b""),
+            ("whitespace", b" \n"),
+            ("truncated", br#"{"version":"18","sch"#),
+            ("garbage", b"\x28\xB5\x2F\xFD\x00\xFF\xFE"),
+            ("not-json", b"version=18"),
+            ("not-a-number", br#"{"version":"latest"}"#)
+        ];
+        for (name, content) in cases
+        {
+            let location = write_test_file(name, content);
+            let result = read_version_file(location.clone());
+            std::fs::remove_file(&location).unwrap();
+            match result
+            {
+                Err(BeansError::VersionFileCorrupt {
+                    location: l, ..
+                }) => assert_eq!(l, location, "{name}"),
+                v => panic!("{name}: expected VersionFileCorrupt, got {v:?}")
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+#!/bin/sh
+# Creates the patches and the signature that are used by the tests in
+# src/wharf/tests.rs with butler (https://itch.io/docs/butler/). The trees
+# are only created in a temporary directory; the tests build the same trees
+# themselves (see `fixture_tree`), so keep both in sync.
+#
+# - rsync.pwr: v1 -> v2-moved. Only files that were renamed or added, so
+#   every file uses rsync operations.
+# - bsdiff.pwr: v1 -> v2. Files that were changed in place use bsdiff.
+# - zstd.pwr, brotli.pwr: same as bsdiff.pwr, compressed with zstd and brotli.
+# - v2.pws: signature of v2, from butler sign.
+#
+# The trees are created with awk and printf, so they are the same every time.
+# The flags are from butler 15; check `butler diff --help` if they changed.
+# Usage: ./generate.sh [path to butler]
+set -e
+BUTLER="${1:-butler}"
+case "$BUTLER" in
+    */*) BUTLER="$(cd "$(dirname "$BUTLER")" && pwd)/$(basename "$BUTLER")" ;;
+esac
+cd "$(dirname "$0")"
+if ! command -v "$BUTLER" > /dev/null
+then
+    echo "$BUTLER wasn't found." >&2
+    exit 1
+fi
+
+OUT="$(pwd)"
+TREES="$(mktemp -d)"
+trap 'rm -rf "$TREES"' EXIT
+cd "$TREES"
+mkdir -p v1/cfg v1/maps v2/cfg v2/maps v2-moved/cfg v2-moved/maps
+
+# maps/big.bsp is 240000 bytes (4 blocks), so there are whole blocks that can
+# be copied, and a short last block.
+big()
+{
+    awk -v changed="$1" 'BEGIN {
+        for (i = 0; i < 20000; i++)
+        {
+            if (i == changed) printf "BLOCK %05d\n", i;
+            else printf "block %05d\n", i;
+        }
+    }'
+}
+
+printf 'GameInfo\n{\n\tgame "beans test"\n}\n' > v1/gameinfo.txt
+printf 'name beans\n' > v1/cfg/config.cfg
+printf 'This file is removed in v2.\n' > v1/readme.txt
+big -1 > v1/maps/big.bsp
+
+printf 'GameInfo\n{\n\tgame "beans test 2"\n}\n' > v2/gameinfo.txt
+printf 'name beans\n' > v2/cfg/config.cfg
+printf 'exec config.cfg\n' > v2/cfg/autoexec.cfg
+big 9000 > v2/maps/big.bsp
+
+cp v1/gameinfo.txt v2-moved/gameinfo.txt
+cp v1/cfg/config.cfg v2-moved/cfg/config.cfg
+cp v1/readme.txt v2-moved/readme.txt
+printf 'exec config.cfg\n' > v2-moved/cfg/autoexec.cfg
+cp v1/maps/big.bsp v2-moved/maps/renamed.bsp
+
+"$BUTLER" diff --compression=none v1 v2-moved "$OUT/rsync.pwr"
+"$BUTLER" diff --compression=none v1 v2 "$OUT/bsdiff.pwr"
+"$BUTLER" diff --compression=zstd v1 v2 "$OUT/zstd.pwr"
+"$BUTLER" diff --compression=brotli v1 v2 "$OUT/brotli.pwr"
+"$BUTLER" sign v2 "$OUT/v2.pws"
@@ -0,0 +1,510 @@
+//! Native implementation of the parts of [wharf](https://github.com/itchio/wharf)
+//! that beans uses, so patches can be applied without butler.
+//!
+//! wharf files start with a magic number (little-endian `i32`), followed by
+//! a header message. Everything after the header is compressed with the
+//! algorithm from the header, and contains more messages. Every message is
+//! protobuf, prefixed with it's length as a uvarint.
+//!
+//! Only the fields that beans needs are decoded. Field numbers are from
+//! `pwr.proto`, `tlc.proto` and `bsdiff.proto` in wharf.
+use std::{collections::HashSet,
+          io::Read,
+          path::{Component,
+                 Path,
+                 PathBuf}};
+
+mod patch;
+mod signature;
+pub use patch::*;
+pub use signature::*;
+
+#[cfg(test)]
+mod tests;
+
+/// Magic number at the start of a `.pwr` file
+pub const PATCH_MAGIC: i32 = 0x0FEF_5F00;
+/// Magic number at the start of a `.pws` file
+pub const SIGNATURE_MAGIC: i32 = PATCH_MAGIC + 1;
+/// Size of the blocks that files are split into by wharf.
+pub const BLOCK_SIZE: u64 = 64 * 1024;
+/// Messages larger than this are treated as corrupt, so a bad length doesn't
+/// cause a huge allocation.
+const MAX_MESSAGE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Amount of blocks that a file with `size` bytes is split into.
+pub fn block_count(size: u64) -> u64
+{
+    size.div_ceil(BLOCK_SIZE)
+}
+
+/// Value of a field in a protobuf message.
+pub(crate) enum FieldValue<'a>
+{
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed64,
+    Fixed32
+}
+
+impl FieldValue<'_>
+{
+    fn as_u64(&self) -> u64
+    {
+        match self
+        {
+            FieldValue::Varint(v) => *v,
+            _ => 0
+        }
+    }
+    fn as_i64(&self) -> i64
+    {
+        self.as_u64() as i64
+    }
+    fn as_bytes(&self) -> &[u8]
+    {
+        match self
+        {
+            FieldValue::Bytes(v) => v,
+            _ => &[]
+        }
+    }
+    fn as_string(&self) -> String
+    {
+        String::from_utf8_lossy(self.as_bytes()).to_string()
+    }
+}
+
+/// Message that can be read with `WireReader::read_message`. Fields that
+/// aren't handled by `merge_field` are ignored.
+pub(crate) trait WireMessage: Default
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>;
+
+    fn decode(data: &[u8]) -> Result<Self, String>
+    {
+        let mut result = Self::default();
+        let mut pos = 0;
+        while pos < data.len()
+        {
+            let key = read_varint(data, &mut pos)?;
+            let field = (key >> 3) as u32;
+            let value = match key & 0x7
+            {
+                0 => FieldValue::Varint(read_varint(data, &mut pos)?),
+                1 =>
+                {
+                    take(data, &mut pos, 8)?;
+                    FieldValue::Fixed64
+                }
+                2 =>
+                {
+                    let length = read_varint(data, &mut pos)? as usize;
+                    FieldValue::Bytes(take(data, &mut pos, length)?)
+                }
+                5 =>
+                {
+                    take(data, &mut pos, 4)?;
+                    FieldValue::Fixed32
+                }
+                t => return Err(format!("unsupported wire type {t} for field {field}"))
+            };
+            result.merge_field(field, value)?;
+        }
+        Ok(result)
+    }
+}
+
+fn read_varint(
+    data: &[u8],
+    pos: &mut usize
+) -> Result<u64, String>
+{
+    let mut result: u64 = 0;
+    for shift in (0..64).step_by(7)
+    {
+        let byte = *take(data, pos, 1)?.first().unwrap_or(&0);
+        result |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0
+        {
+            return Ok(result);
+        }
+    }
+    Err(String::from("varint is too long"))
+}
+
+fn take<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    length: usize
+) -> Result<&'a [u8], String>
+{
+    let end = pos
+        .checked_add(length)
+        .filter(|e| *e <= data.len())
+        .ok_or_else(|| String::from("message is truncated"))?;
+    let result = &data[*pos..end];
+    *pos = end;
+    Ok(result)
+}
+
+/// Reads the magic number and length-prefixed messages from a wharf file.
+pub(crate) struct WireReader<R: Read>
+{
+    reader: R,
+    buffer: Vec<u8>
+}
+
+impl<R: Read> WireReader<R>
+{
+    pub fn new(reader: R) -> Self
+    {
+        Self {
+            reader,
+            buffer: Vec::new()
+        }
+    }
+    pub fn into_inner(self) -> R
+    {
+        self.reader
+    }
+
+    pub fn read_magic(&mut self) -> Result<i32, String>
+    {
+        let mut data = [0u8; 4];
+        self.reader
+            .read_exact(&mut data)
+            .map_err(|e| format!("failed to read magic number ({e:})"))?;
+        Ok(i32::from_le_bytes(data))
+    }
+
+    pub fn read_message<M: WireMessage>(&mut self) -> Result<M, String>
+    {
+        match self.try_read_message()?
+        {
+            Some(v) => Ok(v),
+            None => Err(String::from("unexpected end of file"))
+        }
+    }
+
+    /// Same as `read_message`, but returns `None` when the end of the file
+    /// has been reached before the message.
+    pub fn try_read_message<M: WireMessage>(&mut self) -> Result<Option<M>, String>
+    {
+        let mut length: u64 = 0;
+        let mut shift = 0;
+        loop
+        {
+            let mut byte = [0u8; 1];
+            if shift == 0
+            {
+                let count = loop
+                {
+                    match self.reader.read(&mut byte)
+                    {
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                        v => break v
+                    }
+                }
+                .map_err(|e| format!("failed to read message length ({e:})"))?;
+                if count == 0
+                {
+                    return Ok(None);
+                }
+            }
+            else
+            {
+                self.reader
+                    .read_exact(&mut byte)
+                    .map_err(|e| format!("failed to read message length ({e:})"))?;
+            }
+            length |= ((byte[0] & 0x7F) as u64) << shift;
+            if byte[0] & 0x80 == 0
+            {
+                break;
+            }
+            shift += 7;
+            if shift >= 64
+            {
+                return Err(String::from("message length is too long"));
+            }
+        }
+        if length > MAX_MESSAGE_SIZE
+        {
+            return Err(format!("message is too large ({length} bytes)"));
+        }
+        self.buffer.resize(length as usize, 0);
+        self.reader
+            .read_exact(&mut self.buffer)
+            .map_err(|e| format!("failed to read message ({e:})"))?;
+        M::decode(&self.buffer).map(Some)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompressionAlgorithm
+{
+    #[default]
+    None,
+    Brotli,
+    Gzip,
+    Zstd,
+    Unknown(u64)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompressionSettings
+{
+    pub algorithm: CompressionAlgorithm,
+    pub quality: i32
+}
+
+impl WireMessage for CompressionSettings
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        match field
+        {
+            1 =>
+            {
+                self.algorithm = match value.as_u64()
+                {
+                    0 => CompressionAlgorithm::None,
+                    1 => CompressionAlgorithm::Brotli,
+                    2 => CompressionAlgorithm::Gzip,
+                    3 => CompressionAlgorithm::Zstd,
+                    v => CompressionAlgorithm::Unknown(v)
+                }
+            }
+            2 => self.quality = value.as_i64() as i32,
+            _ =>
+            {}
+        }
+        Ok(())
+    }
+}
+
+/// Header of a `.pwr` or `.pws` file (`PatchHeader` and `SignatureHeader`)
+#[derive(Debug, Clone, Default)]
+pub struct Header
+{
+    pub compression: CompressionSettings
+}
+
+impl WireMessage for Header
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        if field == 1
+        {
+            self.compression = CompressionSettings::decode(value.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Wrap `reader` with a decoder for `compression`.
+pub(crate) fn decompress<'a, R: Read + 'a>(
+    reader: R,
+    compression: &CompressionSettings
+) -> Result<Box<dyn Read + 'a>, String>
+{
+    Ok(match compression.algorithm
+    {
+        CompressionAlgorithm::None => Box::new(reader),
+        CompressionAlgorithm::Brotli =>
+        {
+            Box::new(brotli_decompressor::Decompressor::new(reader, 64 * 1024))
+        }
+        CompressionAlgorithm::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+        CompressionAlgorithm::Zstd => Box::new(
+            zstd::stream::read::Decoder::new(reader)
+                .map_err(|e| format!("failed to create zstd decoder ({e:})"))?
+        ),
+        CompressionAlgorithm::Unknown(v) =>
+        {
+            return Err(format!("unsupported compression algorithm {v}"))
+        }
+    })
+}
+
+/// List of files, directories, and symlinks (`tlc.Container`). Paths always
+/// use `/` as the separator.
+#[derive(Debug, Clone, Default)]
+pub struct Container
+{
+    pub files: Vec<ContainerFile>,
+    pub dirs: Vec<ContainerDir>,
+    pub symlinks: Vec<ContainerSymlink>,
+    pub size: i64
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContainerFile
+{
+    pub path: String,
+    pub mode: u32,
+    pub size: u64
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContainerDir
+{
+    pub path: String,
+    pub mode: u32
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContainerSymlink
+{
+    pub path: String,
+    pub mode: u32,
+    pub dest: String
+}
+
+impl WireMessage for Container
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        match field
+        {
+            1 => self.files.push(ContainerFile::decode(value.as_bytes())?),
+            2 => self.dirs.push(ContainerDir::decode(value.as_bytes())?),
+            3 => self
+                .symlinks
+                .push(ContainerSymlink::decode(value.as_bytes())?),
+            16 => self.size = value.as_i64(),
+            _ =>
+            {}
+        }
+        Ok(())
+    }
+}
+
+impl WireMessage for ContainerFile
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        match field
+        {
+            1 => self.path = value.as_string(),
+            2 => self.mode = value.as_u64() as u32,
+            3 => self.size = value.as_u64(),
+            _ =>
+            {}
+        }
+        Ok(())
+    }
+}
+
+impl WireMessage for ContainerDir
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        match field
+        {
+            1 => self.path = value.as_string(),
+            2 => self.mode = value.as_u64() as u32,
+            _ =>
+            {}
+        }
+        Ok(())
+    }
+}
+
+impl WireMessage for ContainerSymlink
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        match field
+        {
+            1 => self.path = value.as_string(),
+            2 => self.mode = value.as_u64() as u32,
+            3 => self.dest = value.as_string(),
+            _ =>
+            {}
+        }
+        Ok(())
+    }
+}
+
+impl Container
+{
+    /// Path of every file, directory and symlink.
+    pub fn paths(&self) -> HashSet<&str>
+    {
+        self.files
+            .iter()
+            .map(|f| f.path.as_str())
+            .chain(self.symlinks.iter().map(|s| s.path.as_str()))
+            .chain(self.dirs.iter().map(|d| d.path.as_str()))
+            .collect()
+    }
+
+    /// Check that every path is relative, and doesn't go outside of the
+    /// directory that it's in.
+    pub fn validate(&self) -> Result<(), String>
+    {
+        let paths = self
+            .files
+            .iter()
+            .map(|f| &f.path)
+            .chain(self.dirs.iter().map(|d| &d.path))
+            .chain(self.symlinks.iter().map(|s| &s.path));
+        for p in paths
+        {
+            resolve_path(Path::new("."), p)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the location of `path` (from a `Container`) in `root`. Fails when
+/// `path` is absolute or has `..`, since a patch shouldn't be able to write
+/// outside of the mod directory.
+pub fn resolve_path(
+    root: &Path,
+    path: &str
+) -> Result<PathBuf, String>
+{
+    let mut result = root.to_path_buf();
+    for component in Path::new(path).components()
+    {
+        match component
+        {
+            Component::Normal(c) => result.push(c),
+            Component::CurDir =>
+            {}
+            _ => return Err(format!("path {path} isn't allowed"))
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,859 @@
+use std::{collections::VecDeque,
+          fs::File,
+          io::{BufReader,
+               BufWriter,
+               Read,
+               Seek,
+               SeekFrom,
+               Write},
+          path::{Path,
+                 PathBuf}};
+
+use log::{debug,
+          info,
+          warn};
+
+use super::{block_count,
+            decompress,
+            resolve_path,
+            Container,
+            FieldValue,
+            Header,
+            WireMessage,
+            WireReader,
+            BLOCK_SIZE,
+            PATCH_MAGIC};
+use crate::{butler::ButlerReport,
+            BeansError};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SyncHeaderKind
+{
+    #[default]
+    Rsync,
+    Bsdiff,
+    Unknown(u64)
+}
+
+/// Start of the operations for a file in the new container.
+#[derive(Debug, Clone, Default)]
+struct SyncHeader
+{
+    kind: SyncHeaderKind,
+    file_index: i64
+}
+
+impl WireMessage for SyncHeader
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        match field
+        {
+            1 =>
+            {
+                self.kind = match value.as_u64()
+                {
+                    0 => SyncHeaderKind::Rsync,
+                    1 => SyncHeaderKind::Bsdiff,
+                    v => SyncHeaderKind::Unknown(v)
+                }
+            }
+            16 => self.file_index = value.as_i64(),
+            _ =>
+            {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SyncOpKind
+{
+    /// Copy blocks from a file in the old container.
+    #[default]
+    BlockRange,
+    /// Write the data in the operation.
+    Data,
+    /// Last operation for a file.
+    HeyYouDidIt,
+    Unknown(u64)
+}
+
+#[derive(Debug, Clone, Default)]
+struct SyncOp
+{
+    kind: SyncOpKind,
+    file_index: i64,
+    block_index: i64,
+    block_span: i64,
+    data: Vec<u8>
+}
+
+impl WireMessage for SyncOp
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        match field
+        {
+            1 =>
+            {
+                self.kind = match value.as_u64()
+                {
+                    0 => SyncOpKind::BlockRange,
+                    1 => SyncOpKind::Data,
+                    2049 => SyncOpKind::HeyYouDidIt,
+                    v => SyncOpKind::Unknown(v)
+                }
+            }
+            2 => self.file_index = value.as_i64(),
+            3 => self.block_index = value.as_i64(),
+            4 => self.block_span = value.as_i64(),
+            5 => self.data = value.as_bytes().to_vec(),
+            _ =>
+            {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct BsdiffHeader
+{
+    target_index: i64
+}
+
+impl WireMessage for BsdiffHeader
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        if field == 1
+        {
+            self.target_index = value.as_i64();
+        }
+        Ok(())
+    }
+}
+
+/// bsdiff control message. `add` is added to the old file, then `copy` is
+/// written as-is, then the position in the old file is moved by `seek`.
+#[derive(Debug, Clone, Default)]
+struct BsdiffControl
+{
+    add: Vec<u8>,
+    copy: Vec<u8>,
+    seek: i64,
+    eof: bool
+}
+
+impl WireMessage for BsdiffControl
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        match field
+        {
+            1 => self.add = value.as_bytes().to_vec(),
+            2 => self.copy = value.as_bytes().to_vec(),
+            3 => self.seek = value.as_i64(),
+            4 => self.eof = value.as_u64() != 0,
+            _ =>
+            {}
+        }
+        Ok(())
+    }
+}
+
+/// Apply the wharf patch at `patchfile_location` to `gamedir`, without
+/// butler.
+///
+/// Works like `butler apply --staging-dir`; new and changed files are written
+/// to `staging_dir` first, and `gamedir` is only changed once every file has
+/// been patched. Then files that aren't in the new version are deleted, and
+/// `staging_dir` is removed.
+pub fn patch(
+    patchfile_location: String,
+    staging_dir: String,
+    gamedir: String
+) -> Result<ButlerReport, BeansError>
+{
+    info!(
+        "[wharf::patch] Applying {} to {}",
+        patchfile_location, gamedir
+    );
+    let file = match File::open(&patchfile_location)
+    {
+        Ok(v) => v,
+        Err(error) =>
+        {
+            return Err(BeansError::FileOpenFailure {
+                location: patchfile_location,
+                error
+            });
+        }
+    };
+    let (mut wire, target, source) = open_patch(file, patchfile_location.clone())?;
+    debug!(
+        "[wharf::patch] old version has {} files, new version has {} files",
+        target.files.len(),
+        source.files.len()
+    );
+
+    if crate::helper::dir_exists(staging_dir.clone())
+    {
+        if let Err(error) = std::fs::remove_dir_all(&staging_dir)
+        {
+            return Err(BeansError::DirectoryDeleteFailure {
+                location: staging_dir,
+                error
+            });
+        }
+    }
+    let mut ctx = PatchContext {
+        location: patchfile_location.clone(),
+        gamedir: PathBuf::from(&gamedir),
+        staging_dir: PathBuf::from(&staging_dir),
+        target,
+        source,
+        old_file: None
+    };
+    let result = ctx.stage(&mut wire).and_then(|staged| ctx.commit(&staged));
+    if crate::helper::dir_exists(staging_dir.clone())
+    {
+        if let Err(e) = std::fs::remove_dir_all(&staging_dir)
+        {
+            warn!("[wharf::patch] Failed to remove {staging_dir} ({e:})");
+        }
+    }
+    let report = result?;
+    info!(
+        "[wharf::patch] Patched {} files and removed {} files in {}",
+        report.changed_files.len(),
+        report.removed_files.len(),
+        gamedir
+    );
+    Ok(report)
+}
+
+/// Get the files that the patch at `patchfile_location` changes and removes,
+/// from the list of files in the old and new version, without applying it.
+/// Used when the patch is applied by butler, since it only reports how many
+/// files it changed.
+///
+/// Files in the new version are listed as changed when they're new, or their
+/// size has changed. Files that changed without changing size aren't listed,
+/// since that can only be found by reading every operation.
+pub fn patch_changes(patchfile_location: String) -> Result<ButlerReport, BeansError>
+{
+    let file = match File::open(&patchfile_location)
+    {
+        Ok(v) => v,
+        Err(error) =>
+        {
+            return Err(BeansError::FileOpenFailure {
+                location: patchfile_location,
+                error
+            });
+        }
+    };
+    let (_, target, source) = open_patch(file, patchfile_location)?;
+    let mut changed_files: Vec<String> = source
+        .files
+        .iter()
+        .filter(|f| {
+            !target
+                .files
+                .iter()
+                .any(|t| t.path == f.path && t.size == f.size)
+        })
+        .map(|f| f.path.clone())
+        .collect();
+    let mut removed_files: Vec<String> = removed_paths(&target, &source)
+        .into_iter()
+        .cloned()
+        .collect();
+    changed_files.sort();
+    removed_files.sort();
+    Ok(ButlerReport {
+        changed_files,
+        removed_files
+    })
+}
+
+/// Reader for the rest of a patch, then the containers for the old and new
+/// version.
+type OpenPatch = (WireReader<Box<dyn Read>>, Container, Container);
+
+/// Read the header of the patch in `file`, then the containers for the old
+/// (`target`) and new (`source`) version. Returns the reader for the rest of
+/// the patch, then the containers.
+fn open_patch(
+    file: File,
+    patchfile_location: String
+) -> Result<OpenPatch, BeansError>
+{
+    let invalid = |reason: String| BeansError::WharfPatchInvalid {
+        location: patchfile_location.clone(),
+        reason
+    };
+
+    let mut raw = WireReader::new(BufReader::new(file));
+    let magic = raw.read_magic().map_err(invalid)?;
+    if magic != PATCH_MAGIC
+    {
+        return Err(invalid(format!("wrong magic number {magic:#x}")));
+    }
+    let header: Header = raw.read_message().map_err(invalid)?;
+    debug!("[wharf::open_patch] {:?}", header);
+    let mut wire =
+        WireReader::new(decompress(raw.into_inner(), &header.compression).map_err(invalid)?);
+    let target: Container = wire.read_message().map_err(invalid)?;
+    let source: Container = wire.read_message().map_err(invalid)?;
+    target.validate().map_err(invalid)?;
+    source.validate().map_err(invalid)?;
+    Ok((wire, target, source))
+}
+
+/// Files and symlinks in `target` that aren't in `source`, so they are
+/// removed by the patch.
+fn removed_paths<'a>(
+    target: &'a Container,
+    source: &Container
+) -> Vec<&'a String>
+{
+    let keep = source.paths();
+    target
+        .files
+        .iter()
+        .map(|f| &f.path)
+        .chain(target.symlinks.iter().map(|s| &s.path))
+        .filter(|p| !keep.contains(p.as_str()))
+        .collect()
+}
+
+struct PatchContext
+{
+    /// Location of the patch, used for errors.
+    location: String,
+    gamedir: PathBuf,
+    staging_dir: PathBuf,
+    /// Files that are installed
+    target: Container,
+    /// Files after the patch has been applied
+    source: Container,
+    /// File in `target` that blocks were last copied from, so it isn't
+    /// opened again for every operation.
+    old_file: Option<(usize, File)>
+}
+
+impl PatchContext
+{
+    fn invalid(
+        &self,
+        reason: String
+    ) -> BeansError
+    {
+        BeansError::WharfPatchInvalid {
+            location: self.location.clone(),
+            reason
+        }
+    }
+
+    /// Write every file that was changed by the patch to `staging_dir`.
+    /// Returns the index of every file that was written.
+    fn stage<R: Read>(
+        &mut self,
+        wire: &mut WireReader<R>
+    ) -> Result<Vec<usize>, BeansError>
+    {
+        let mut staged: Vec<usize> = Vec::new();
+        for index in 0..self.source.files.len()
+        {
+            let header: SyncHeader = wire.read_message().map_err(|e| self.invalid(e))?;
+            if header.file_index != index as i64
+            {
+                return Err(self.invalid(format!(
+                    "expected file {index}, but found file {}",
+                    header.file_index
+                )));
+            }
+            let changed = match header.kind
+            {
+                SyncHeaderKind::Rsync => self.apply_rsync(wire, index)?,
+                SyncHeaderKind::Bsdiff => self.apply_bsdiff(wire, index)?,
+                SyncHeaderKind::Unknown(v) =>
+                {
+                    return Err(self.invalid(format!("unknown sync header type {v}")));
+                }
+            };
+            if changed
+            {
+                staged.push(index);
+            }
+        }
+        Ok(staged)
+    }
+
+    /// Apply the rsync operations for the file at `index` in `source`.
+    /// Returns `false` when the file hasn't changed, so it wasn't written.
+    fn apply_rsync<R: Read>(
+        &mut self,
+        wire: &mut WireReader<R>,
+        index: usize
+    ) -> Result<bool, BeansError>
+    {
+        let mut queue: VecDeque<SyncOp> = VecDeque::new();
+        let first: SyncOp = wire.read_message().map_err(|e| self.invalid(e))?;
+        let unchanged = self.is_unchanged(&first, index);
+        queue.push_back(first);
+        if unchanged
+        {
+            let next: SyncOp = wire.read_message().map_err(|e| self.invalid(e))?;
+            if next.kind == SyncOpKind::HeyYouDidIt
+            {
+                return Ok(false);
+            }
+            queue.push_back(next);
+        }
+
+        let (location, mut writer) = self.create_staged_file(index)?;
+        let mut written: u64 = 0;
+        loop
+        {
+            let op = match queue.pop_front()
+            {
+                Some(v) => v,
+                None => wire.read_message().map_err(|e| self.invalid(e))?
+            };
+            match op.kind
+            {
+                SyncOpKind::BlockRange =>
+                {
+                    written += self.copy_blocks(&op, &mut writer, &location)?;
+                }
+                SyncOpKind::Data =>
+                {
+                    write_staged(&mut writer, &op.data, &location)?;
+                    written += op.data.len() as u64;
+                }
+                SyncOpKind::HeyYouDidIt => break,
+                SyncOpKind::Unknown(v) =>
+                {
+                    return Err(self.invalid(format!("unknown sync operation {v}")));
+                }
+            }
+        }
+        self.finish_staged_file(index, writer, written, &location)?;
+        Ok(true)
+    }
+
+    /// Apply the bsdiff operations for the file at `index` in `source`.
+    fn apply_bsdiff<R: Read>(
+        &mut self,
+        wire: &mut WireReader<R>,
+        index: usize
+    ) -> Result<bool, BeansError>
+    {
+        let header: BsdiffHeader = wire.read_message().map_err(|e| self.invalid(e))?;
+        let old_index = self.old_file_index(header.target_index)?;
+        let mut old_file = self.open_old_file(old_index)?;
+        let (location, mut writer) = self.create_staged_file(index)?;
+        let mut old_pos: i64 = 0;
+        let mut written: u64 = 0;
+        loop
+        {
+            let ctrl: BsdiffControl = wire.read_message().map_err(|e| self.invalid(e))?;
+            if ctrl.eof
+            {
+                break;
+            }
+            if !ctrl.add.is_empty()
+            {
+                let mut data = vec![0u8; ctrl.add.len()];
+                let read = u64::try_from(old_pos)
+                    .ok()
+                    .and_then(|p| old_file.seek(SeekFrom::Start(p)).ok())
+                    .and_then(|_| old_file.read_exact(&mut data).ok());
+                if read.is_none()
+                {
+                    return Err(self.invalid(format!(
+                        "{} is smaller than expected. Verify the installation, then try again.",
+                        self.target.files[old_index].path
+                    )));
+                }
+                for (d, a) in data.iter_mut().zip(ctrl.add.iter())
+                {
+                    *d = d.wrapping_add(*a);
+                }
+                write_staged(&mut writer, &data, &location)?;
+                old_pos += data.len() as i64;
+                written += data.len() as u64;
+            }
+            write_staged(&mut writer, &ctrl.copy, &location)?;
+            written += ctrl.copy.len() as u64;
+            old_pos += ctrl.seek;
+        }
+        let last: SyncOp = wire.read_message().map_err(|e| self.invalid(e))?;
+        if last.kind != SyncOpKind::HeyYouDidIt
+        {
+            return Err(self.invalid(format!(
+                "expected the end of file {index} after bsdiff operations"
+            )));
+        }
+        self.finish_staged_file(index, writer, written, &location)?;
+        Ok(true)
+    }
+
+    /// Check if `op` is the only operation that is needed for the file at
+    /// `index`, because it copies the whole file from the same path.
+    fn is_unchanged(
+        &self,
+        op: &SyncOp,
+        index: usize
+    ) -> bool
+    {
+        let file = &self.source.files[index];
+        let old = match usize::try_from(op.file_index)
+            .ok()
+            .and_then(|i| self.target.files.get(i))
+        {
+            Some(v) => v,
+            None => return false
+        };
+        op.kind == SyncOpKind::BlockRange
+            && op.block_index == 0
+            && op.block_span as u64 == block_count(file.size)
+            && old.path == file.path
+            && old.size == file.size
+    }
+
+    /// Copy the blocks from `op` to `writer`. Returns the amount of bytes
+    /// that were copied.
+    fn copy_blocks(
+        &mut self,
+        op: &SyncOp,
+        writer: &mut BufWriter<File>,
+        location: &Path
+    ) -> Result<u64, BeansError>
+    {
+        let index = self.old_file_index(op.file_index)?;
+        let mut old_file = match self.old_file.take()
+        {
+            Some((i, f)) if i == index => f,
+            _ => self.open_old_file(index)?
+        };
+        let old_size = old_file.metadata().map(|m| m.len()).unwrap_or(0);
+        let start = (op.block_index.max(0) as u64).saturating_mul(BLOCK_SIZE);
+        let length = (op.block_span.max(0) as u64)
+            .saturating_mul(BLOCK_SIZE)
+            .min(old_size.saturating_sub(start));
+        if let Err(error) = old_file.seek(SeekFrom::Start(start))
+        {
+            return Err(BeansError::IO {
+                error,
+                backtrace: std::backtrace::Backtrace::capture()
+            });
+        }
+        let result = std::io::copy(&mut Read::by_ref(&mut old_file).take(length), writer);
+        self.old_file = Some((index, old_file));
+        match result
+        {
+            Ok(v) => Ok(v),
+            Err(error) => Err(BeansError::FileWriteFailure {
+                location: location.to_string_lossy().to_string(),
+                error
+            })
+        }
+    }
+
+    /// Check that `index` is a file in `target`.
+    fn old_file_index(
+        &self,
+        index: i64
+    ) -> Result<usize, BeansError>
+    {
+        match usize::try_from(index)
+            .ok()
+            .filter(|i| *i < self.target.files.len())
+        {
+            Some(v) => Ok(v),
+            None => Err(self.invalid(format!("old file {index} doesn't exist")))
+        }
+    }
+
+    /// Open the file at `index` in `target`, from `gamedir`.
+    fn open_old_file(
+        &self,
+        index: usize
+    ) -> Result<File, BeansError>
+    {
+        let location = resolve_path(&self.gamedir, &self.target.files[index].path)
+            .map_err(|e| self.invalid(e))?;
+        match File::open(&location)
+        {
+            Ok(f) => Ok(f),
+            Err(error) => Err(BeansError::FileOpenFailure {
+                location: location.to_string_lossy().to_string(),
+                error
+            })
+        }
+    }
+
+    fn create_staged_file(
+        &self,
+        index: usize
+    ) -> Result<(PathBuf, BufWriter<File>), BeansError>
+    {
+        let location = resolve_path(&self.staging_dir, &self.source.files[index].path)
+            .map_err(|e| self.invalid(e))?;
+        if let Some(parent) = location.parent()
+        {
+            if let Err(error) = std::fs::create_dir_all(parent)
+            {
+                return Err(BeansError::DirectoryCreateFailure {
+                    location: parent.to_string_lossy().to_string(),
+                    error,
+                    backtrace: std::backtrace::Backtrace::capture()
+                });
+            }
+        }
+        match File::create(&location)
+        {
+            Ok(f) => Ok((location, BufWriter::new(f))),
+            Err(error) => Err(BeansError::FileWriteFailure {
+                location: location.to_string_lossy().to_string(),
+                error
+            })
+        }
+    }
+
+    /// Flush `writer`, and check that the right amount of data was written.
+    fn finish_staged_file(
+        &self,
+        index: usize,
+        mut writer: BufWriter<File>,
+        written: u64,
+        location: &Path
+    ) -> Result<(), BeansError>
+    {
+        if let Err(error) = writer.flush()
+        {
+            return Err(BeansError::FileWriteFailure {
+                location: location.to_string_lossy().to_string(),
+                error
+            });
+        }
+        let file = &self.source.files[index];
+        if written != file.size
+        {
+            return Err(self.invalid(format!(
+                "{} should be {} bytes, but {} bytes were written",
+                file.path, file.size, written
+            )));
+        }
+        Ok(())
+    }
+
+    /// Move the files in `staged` from `staging_dir` to `gamedir`, then
+    /// create symlinks and remove everything that isn't in `source`.
+    ///
+    /// Returns the path of every file that was moved, and every file that was
+    /// removed (both sorted).
+    fn commit(
+        &self,
+        staged: &[usize]
+    ) -> Result<ButlerReport, BeansError>
+    {
+        for dir in self.source.dirs.iter()
+        {
+            let location = resolve_path(&self.gamedir, &dir.path).map_err(|e| self.invalid(e))?;
+            create_dir(&location)?;
+        }
+
+        let mut changed_files: Vec<String> = Vec::new();
+        for index in staged.iter()
+        {
+            let file = &self.source.files[*index];
+            let from = resolve_path(&self.staging_dir, &file.path).map_err(|e| self.invalid(e))?;
+            let to = resolve_path(&self.gamedir, &file.path).map_err(|e| self.invalid(e))?;
+            if let Some(parent) = to.parent()
+            {
+                create_dir(parent)?;
+            }
+            if let Err(e) = std::fs::rename(&from, &to)
+            {
+                debug!("[wharf::commit] Failed to rename {from:?}, copying instead ({e:})");
+                if let Err(error) = std::fs::copy(&from, &to)
+                {
+                    return Err(BeansError::FileWriteFailure {
+                        location: to.to_string_lossy().to_string(),
+                        error
+                    });
+                }
+            }
+            set_mode(&to, file.mode);
+            changed_files.push(file.path.clone());
+        }
+
+        for symlink in self.source.symlinks.iter()
+        {
+            let location =
+                resolve_path(&self.gamedir, &symlink.path).map_err(|e| self.invalid(e))?;
+            create_symlink(&symlink.dest, &location)?;
+        }
+
+        // remove things that were deleted in the new version.
+        let mut removed_files: Vec<String> = Vec::new();
+        for p in removed_paths(&self.target, &self.source)
+        {
+            let location = resolve_path(&self.gamedir, p).map_err(|e| self.invalid(e))?;
+            debug!("[wharf::commit] Removing {location:?}");
+            match std::fs::remove_file(&location)
+            {
+                Ok(_) => removed_files.push(p.clone()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound =>
+                {}
+                Err(error) =>
+                {
+                    return Err(BeansError::FileDeleteFailure {
+                        location: location.to_string_lossy().to_string(),
+                        error
+                    });
+                }
+            }
+        }
+        // deepest first, so parents are empty when they are removed.
+        let keep = self.source.paths();
+        let mut removed_dirs: Vec<&String> = self
+            .target
+            .dirs
+            .iter()
+            .map(|d| &d.path)
+            .filter(|p| !keep.contains(p.as_str()))
+            .collect();
+        removed_dirs.sort_by_key(|p| std::cmp::Reverse(p.matches('/').count()));
+        for p in removed_dirs
+        {
+            let location = resolve_path(&self.gamedir, p).map_err(|e| self.invalid(e))?;
+            if let Err(e) = std::fs::remove_dir(&location)
+            {
+                // might have files that weren't installed by us.
+                debug!("[wharf::commit] Didn't remove {location:?} ({e:})");
+            }
+        }
+
+        changed_files.sort();
+        removed_files.sort();
+        Ok(ButlerReport {
+            changed_files,
+            removed_files
+        })
+    }
+}
+
+fn write_staged(
+    writer: &mut BufWriter<File>,
+    data: &[u8],
+    location: &Path
+) -> Result<(), BeansError>
+{
+    match writer.write_all(data)
+    {
+        Ok(_) => Ok(()),
+        Err(error) => Err(BeansError::FileWriteFailure {
+            location: location.to_string_lossy().to_string(),
+            error
+        })
+    }
+}
+
+fn create_dir(location: &Path) -> Result<(), BeansError>
+{
+    match std::fs::create_dir_all(location)
+    {
+        Ok(_) => Ok(()),
+        Err(error) => Err(BeansError::DirectoryCreateFailure {
+            location: location.to_string_lossy().to_string(),
+            error,
+            backtrace: std::backtrace::Backtrace::capture()
+        })
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn set_mode(
+    location: &Path,
+    mode: u32
+)
+{
+    use std::os::unix::fs::PermissionsExt;
+    if mode == 0
+    {
+        return;
+    }
+    if let Err(e) =
+        std::fs::set_permissions(location, std::fs::Permissions::from_mode(mode & 0o777))
+    {
+        warn!("[wharf::set_mode] Failed to set permissions on {location:?} ({e:})");
+    }
+}
+#[cfg(target_os = "windows")]
+fn set_mode(
+    _location: &Path,
+    _mode: u32
+)
+{
+}
+
+#[cfg(not(target_os = "windows"))]
+fn create_symlink(
+    dest: &str,
+    location: &Path
+) -> Result<(), BeansError>
+{
+    if let Some(parent) = location.parent()
+    {
+        create_dir(parent)?;
+    }
+    if std::fs::symlink_metadata(location).is_ok()
+    {
+        if let Err(error) = std::fs::remove_file(location)
+        {
+            return Err(BeansError::FileDeleteFailure {
+                location: location.to_string_lossy().to_string(),
+                error
+            });
+        }
+    }
+    match std::os::unix::fs::symlink(dest, location)
+    {
+        Ok(_) => Ok(()),
+        Err(error) => Err(BeansError::FileWriteFailure {
+            location: location.to_string_lossy().to_string(),
+            error
+        })
+    }
+}
+#[cfg(target_os = "windows")]
+fn create_symlink(
+    dest: &str,
+    location: &Path
+) -> Result<(), BeansError>
+{
+    warn!("[wharf::create_symlink] Symlinks aren't supported on Windows, so {location:?} (to {dest}) wasn't created");
+    Ok(())
+}
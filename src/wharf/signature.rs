@@ -0,0 +1,481 @@
+use std::{fs::File,
+          io::{BufReader,
+               Read},
+          path::PathBuf,
+          sync::{atomic::{AtomicU64,
+                          AtomicUsize,
+                          Ordering},
+                 Mutex}};
+
+use log::{debug,
+          info,
+          warn};
+use md5::{Digest,
+          Md5};
+
+use super::{block_count,
+            decompress,
+            resolve_path,
+            Container,
+            FieldValue,
+            Header,
+            WireMessage,
+            WireReader,
+            BLOCK_SIZE,
+            SIGNATURE_MAGIC};
+use crate::{config::Config,
+            events::{EventSink,
+                     Phase,
+                     Progress},
+            helper,
+            BeansError};
+
+/// Maximum amount of files listed by `print_damaged_files`
+const DAMAGED_FILES_DISPLAY_LIMIT: usize = 20;
+
+/// Hash of a block in a file. `strong_hash` is MD5.
+#[derive(Debug, Clone, Default)]
+pub struct BlockHash
+{
+    pub weak_hash: u32,
+    pub strong_hash: Vec<u8>
+}
+
+impl WireMessage for BlockHash
+{
+    fn merge_field(
+        &mut self,
+        field: u32,
+        value: FieldValue
+    ) -> Result<(), String>
+    {
+        match field
+        {
+            1 => self.weak_hash = value.as_u64() as u32,
+            2 => self.strong_hash = value.as_bytes().to_vec(),
+            _ =>
+            {}
+        }
+        Ok(())
+    }
+}
+
+/// Contents of a `.pws` file.
+#[derive(Debug, Clone)]
+pub struct Signature
+{
+    pub container: Container,
+    /// Hashes for every file in `container.files`, in the same order.
+    pub hashes: Vec<Vec<BlockHash>>
+}
+
+impl Signature
+{
+    /// Read the signature file at `location`.
+    pub fn read(location: String) -> Result<Self, BeansError>
+    {
+        let file = match File::open(&location)
+        {
+            Ok(v) => v,
+            Err(error) =>
+            {
+                return Err(BeansError::FileOpenFailure {
+                    location,
+                    error
+                });
+            }
+        };
+        let invalid = |reason: String| BeansError::WharfSignatureInvalid {
+            location: location.clone(),
+            reason
+        };
+
+        let mut raw = WireReader::new(BufReader::new(file));
+        let magic = raw.read_magic().map_err(invalid)?;
+        if magic != SIGNATURE_MAGIC
+        {
+            return Err(invalid(format!("wrong magic number {magic:#x}")));
+        }
+        let header: Header = raw.read_message().map_err(invalid)?;
+        let mut wire =
+            WireReader::new(decompress(raw.into_inner(), &header.compression).map_err(invalid)?);
+        let container: Container = wire.read_message().map_err(invalid)?;
+        container.validate().map_err(invalid)?;
+
+        let mut all_hashes: Vec<BlockHash> = Vec::new();
+        while let Some(hash) = wire.try_read_message::<BlockHash>().map_err(invalid)?
+        {
+            all_hashes.push(hash);
+        }
+
+        // empty files might have a hash for a 0-length block, depending on
+        // the version of wharf that created the signature.
+        let expected: u64 = container.files.iter().map(|f| block_count(f.size)).sum();
+        let empty_files = container.files.iter().filter(|f| f.size == 0).count() as u64;
+        let total = all_hashes.len() as u64;
+        let empty_has_hash = if total == expected
+        {
+            false
+        }
+        else if total == expected + empty_files
+        {
+            true
+        }
+        else
+        {
+            return Err(invalid(format!(
+                "it has {total} block hashes, but {expected} were expected"
+            )));
+        };
+
+        let mut hashes: Vec<Vec<BlockHash>> = Vec::with_capacity(container.files.len());
+        let mut remaining = all_hashes.into_iter();
+        for f in container.files.iter()
+        {
+            let mut count = block_count(f.size) as usize;
+            if f.size == 0 && empty_has_hash
+            {
+                count = 1;
+            }
+            hashes.push(remaining.by_ref().take(count).collect());
+        }
+        debug!(
+            "[Signature::read] {} files, {} blocks",
+            container.files.len(),
+            total
+        );
+        Ok(Self {
+            container,
+            hashes
+        })
+    }
+}
+
+/// Why a file doesn't match the signature.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "damage", rename_all = "snake_case")]
+pub enum Damage
+{
+    Missing,
+    WrongSize
+    {
+        expected: u64,
+        actual: u64
+    },
+    /// Index of every block that has the wrong hash.
+    Blocks
+    {
+        blocks: Vec<u64>
+    },
+    Unreadable
+    {
+        error: String
+    }
+}
+
+impl Damage
+{
+    /// Amount of bytes in a file of `size` bytes that don't match. Only the
+    /// damaged blocks are counted for `Damage::Blocks`, otherwise it's the
+    /// whole file.
+    pub fn damaged_bytes(
+        &self,
+        size: u64
+    ) -> u64
+    {
+        match self
+        {
+            Damage::Blocks {
+                blocks
+            } => blocks
+                .iter()
+                .map(|b| (size - (b * BLOCK_SIZE).min(size)).min(BLOCK_SIZE))
+                .sum(),
+            _ => size
+        }
+    }
+}
+
+impl std::fmt::Display for Damage
+{
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>
+    ) -> std::fmt::Result
+    {
+        match self
+        {
+            Damage::Missing => write!(f, "missing"),
+            Damage::WrongSize {
+                expected,
+                actual
+            } =>
+            {
+                write!(f, "{actual} bytes, but it should be {expected} bytes")
+            }
+            Damage::Blocks {
+                blocks
+            } => write!(f, "{} damaged block(s)", blocks.len()),
+            Damage::Unreadable {
+                error
+            } => write!(f, "couldn't be read ({error})")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct DamagedFile
+{
+    pub path: String,
+    #[serde(flatten)]
+    pub damage: Damage
+}
+
+/// Output of `verify_signature`
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SignatureReport
+{
+    pub files_checked: usize,
+    pub bytes_checked: u64,
+    /// Sum of `Damage::damaged_bytes` for every file in `damaged`.
+    pub bytes_damaged: u64,
+    /// Sorted by path.
+    pub damaged: Vec<DamagedFile>
+}
+
+impl SignatureReport
+{
+    /// `true` when every file matches the signature.
+    pub fn is_valid(&self) -> bool
+    {
+        self.damaged.is_empty()
+    }
+}
+
+/// Check every file in `gamedir` against the signature at
+/// `signature_location`, without butler. Files are checked in parallel, and
+/// nothing in `gamedir` is changed.
+///
+/// Files that aren't in the signature are ignored. Progress is reported to
+/// `events` as `Phase::Check`.
+pub fn verify_signature(
+    signature_location: String,
+    gamedir: String,
+    events: &dyn EventSink
+) -> Result<SignatureReport, BeansError>
+{
+    let signature = Signature::read(signature_location)?;
+    let files = &signature.container.files;
+    let total_size: u64 = files.iter().map(|f| f.size).sum();
+    info!(
+        "[wharf::verify_signature] Checking {} files in {}",
+        files.len(),
+        gamedir
+    );
+
+    let pb = Progress::start(
+        events,
+        Phase::Check,
+        String::from("Checking files"),
+        Some(total_size)
+    );
+    let next = AtomicUsize::new(0);
+    let damaged: Mutex<Vec<DamagedFile>> = Mutex::new(Vec::new());
+    let bytes_damaged = AtomicU64::new(0);
+    let threads = std::thread::available_parallelism()
+        .map(|v| v.get())
+        .unwrap_or(1)
+        .clamp(1, files.len().max(1));
+    let root = PathBuf::from(&gamedir);
+    std::thread::scope(|scope| {
+        for _ in 0..threads
+        {
+            scope.spawn(|| {
+                loop
+                {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= files.len()
+                    {
+                        break;
+                    }
+                    let file = &files[index];
+                    let result = check_file(
+                        &root,
+                        &file.path,
+                        file.size,
+                        &signature.hashes[index],
+                        |n| pb.inc(n)
+                    );
+                    if let Some(damage) = result
+                    {
+                        bytes_damaged.fetch_add(damage.damaged_bytes(file.size), Ordering::Relaxed);
+                        if let Ok(mut d) = damaged.lock()
+                        {
+                            d.push(DamagedFile {
+                                path: file.path.clone(),
+                                damage
+                            });
+                        }
+                    }
+                }
+            });
+        }
+    });
+    pb.finish();
+
+    let mut damaged = damaged.into_inner().unwrap_or_default();
+    for dir in signature.container.dirs.iter()
+    {
+        if let Ok(location) = resolve_path(&root, &dir.path)
+            && !location.is_dir()
+        {
+            damaged.push(DamagedFile {
+                path: dir.path.clone(),
+                damage: Damage::Missing
+            });
+        }
+    }
+    damaged.sort_by(|a, b| a.path.cmp(&b.path));
+    info!(
+        "[wharf::verify_signature] {} of {} files don't match the signature",
+        damaged.len(),
+        files.len()
+    );
+    Ok(SignatureReport {
+        files_checked: files.len(),
+        bytes_checked: total_size,
+        bytes_damaged: bytes_damaged.into_inner(),
+        damaged
+    })
+}
+
+/// Download the signature at `signature_url`, then check `gamedir` against it
+/// with `verify_signature`.
+pub async fn verify_signature_url(
+    signature_url: String,
+    gamedir: String,
+    config: &Config,
+    events: &dyn EventSink
+) -> Result<SignatureReport, BeansError>
+{
+    let location = helper::get_download_file(config, String::from("signature.pws"));
+    info!("[wharf::verify_signature_url] downloading {signature_url} to {location}");
+    helper::download_with_progress(signature_url, location.clone(), config, events).await?;
+    let result = verify_signature(location.clone(), gamedir, events);
+    if let Err(e) = std::fs::remove_file(&location)
+    {
+        warn!("[wharf::verify_signature_url] Failed to delete {location} ({e:})");
+    }
+    result
+}
+
+/// Emit the files in `report` that don't match the signature as a message.
+/// Only the first `DAMAGED_FILES_DISPLAY_LIMIT` are listed.
+pub fn print_damaged_files(
+    report: &SignatureReport,
+    events: &dyn EventSink
+)
+{
+    if report.is_valid()
+    {
+        events.message(format!(
+            "All {} files match the signature.",
+            report.files_checked
+        ));
+        return;
+    }
+    let mut message = format!(
+        "{} of {} files don't match the signature;",
+        report.damaged.len(),
+        report.files_checked
+    );
+    for f in report.damaged.iter().take(DAMAGED_FILES_DISPLAY_LIMIT)
+    {
+        message.push_str(&format!("\n    {} ({})", f.path, f.damage));
+    }
+    if report.damaged.len() > DAMAGED_FILES_DISPLAY_LIMIT
+    {
+        message.push_str(&format!(
+            "\n    ...and {} more",
+            report.damaged.len() - DAMAGED_FILES_DISPLAY_LIMIT
+        ));
+    }
+    events.message(message);
+}
+
+/// Check the file at `path` in `root` against `hashes`. `progress` is called
+/// with the amount of bytes that were checked.
+///
+/// Returns `None` when the file matches.
+fn check_file<F>(
+    root: &std::path::Path,
+    path: &str,
+    size: u64,
+    hashes: &[BlockHash],
+    progress: F
+) -> Option<Damage>
+where
+    F: Fn(u64)
+{
+    let location = match resolve_path(root, path)
+    {
+        Ok(v) => v,
+        Err(error) =>
+        {
+            return Some(Damage::Unreadable {
+                error
+            })
+        }
+    };
+    let mut file = match File::open(&location)
+    {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound =>
+        {
+            progress(size);
+            return Some(Damage::Missing);
+        }
+        Err(e) =>
+        {
+            progress(size);
+            return Some(Damage::Unreadable {
+                error: format!("{e:}")
+            });
+        }
+    };
+    let actual = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if actual != size
+    {
+        progress(size);
+        return Some(Damage::WrongSize {
+            expected: size,
+            actual
+        });
+    }
+
+    let mut blocks: Vec<u64> = Vec::new();
+    let mut buffer = vec![0u8; BLOCK_SIZE as usize];
+    for (index, hash) in hashes.iter().enumerate()
+    {
+        let length = (size - (index as u64 * BLOCK_SIZE).min(size)).min(BLOCK_SIZE) as usize;
+        if let Err(e) = file.read_exact(&mut buffer[..length])
+        {
+            progress(size.saturating_sub(index as u64 * BLOCK_SIZE));
+            return Some(Damage::Unreadable {
+                error: format!("{e:}")
+            });
+        }
+        if Md5::digest(&buffer[..length]).as_slice() != hash.strong_hash.as_slice()
+        {
+            blocks.push(index as u64);
+        }
+        progress(length as u64);
+    }
+    match blocks.is_empty()
+    {
+        true => None,
+        false => Some(Damage::Blocks {
+            blocks
+        })
+    }
+}
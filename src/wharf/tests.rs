@@ -0,0 +1,749 @@
+//! Patches are written with the same framing as butler (magic, header, then
+//! compressed length-prefixed protobuf messages), so they can be read by
+//! `wharf::patch` in the same way as a patch that was created with
+//! `butler diff`.
+use std::{io::Write,
+          path::{Path,
+                 PathBuf}};
+
+use super::*;
+use crate::BeansError;
+
+const RSYNC: u64 = 0;
+const BSDIFF: u64 = 1;
+const OP_BLOCK_RANGE: u64 = 0;
+const OP_DATA: u64 = 1;
+const OP_HEY_YOU_DID_IT: u64 = 2049;
+const COMPRESSION_NONE: u64 = 0;
+const COMPRESSION_GZIP: u64 = 2;
+const COMPRESSION_ZSTD: u64 = 3;
+
+fn put_varint(
+    out: &mut Vec<u8>,
+    mut value: u64
+)
+{
+    while value >= 0x80
+    {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+fn put_varint_field(
+    out: &mut Vec<u8>,
+    field: u32,
+    value: u64
+)
+{
+    put_varint(out, (field as u64) << 3);
+    put_varint(out, value);
+}
+fn put_bytes_field(
+    out: &mut Vec<u8>,
+    field: u32,
+    value: &[u8]
+)
+{
+    put_varint(out, ((field as u64) << 3) | 2);
+    put_varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+/// Write `body` as a length-prefixed message.
+fn put_message(
+    out: &mut Vec<u8>,
+    body: &[u8]
+)
+{
+    put_varint(out, body.len() as u64);
+    out.extend_from_slice(body);
+}
+
+fn container(
+    files: &[(&str, &[u8])],
+    dirs: &[&str]
+) -> Vec<u8>
+{
+    let mut out = Vec::new();
+    for (path, content) in files
+    {
+        let mut file = Vec::new();
+        put_bytes_field(&mut file, 1, path.as_bytes());
+        put_varint_field(&mut file, 2, 0o644);
+        put_varint_field(&mut file, 3, content.len() as u64);
+        put_bytes_field(&mut out, 1, &file);
+    }
+    for path in dirs
+    {
+        let mut dir = Vec::new();
+        put_bytes_field(&mut dir, 1, path.as_bytes());
+        put_varint_field(&mut dir, 2, 0o755);
+        put_bytes_field(&mut out, 2, &dir);
+    }
+    out
+}
+fn sync_header(
+    out: &mut Vec<u8>,
+    kind: u64,
+    file_index: u64
+)
+{
+    let mut body = Vec::new();
+    put_varint_field(&mut body, 1, kind);
+    put_varint_field(&mut body, 16, file_index);
+    put_message(out, &body);
+}
+fn block_range(
+    out: &mut Vec<u8>,
+    file_index: u64,
+    block_index: u64,
+    block_span: u64
+)
+{
+    let mut body = Vec::new();
+    put_varint_field(&mut body, 1, OP_BLOCK_RANGE);
+    put_varint_field(&mut body, 2, file_index);
+    put_varint_field(&mut body, 3, block_index);
+    put_varint_field(&mut body, 4, block_span);
+    put_message(out, &body);
+}
+fn data(
+    out: &mut Vec<u8>,
+    content: &[u8]
+)
+{
+    let mut body = Vec::new();
+    put_varint_field(&mut body, 1, OP_DATA);
+    put_bytes_field(&mut body, 5, content);
+    put_message(out, &body);
+}
+fn hey_you_did_it(out: &mut Vec<u8>)
+{
+    let mut body = Vec::new();
+    put_varint_field(&mut body, 1, OP_HEY_YOU_DID_IT);
+    put_message(out, &body);
+}
+fn bsdiff_control(
+    out: &mut Vec<u8>,
+    add: &[u8],
+    copy: &[u8],
+    seek: i64
+)
+{
+    let mut body = Vec::new();
+    put_bytes_field(&mut body, 1, add);
+    put_bytes_field(&mut body, 2, copy);
+    put_varint_field(&mut body, 3, seek as u64);
+    put_message(out, &body);
+}
+fn bsdiff_eof(out: &mut Vec<u8>)
+{
+    let mut body = Vec::new();
+    put_varint_field(&mut body, 4, 1);
+    put_message(out, &body);
+}
+
+/// Create a `.pwr` file from the messages in `body`, compressed with
+/// `algorithm`.
+fn write_patch(
+    location: &Path,
+    algorithm: u64,
+    body: &[u8]
+)
+{
+    let mut out = Vec::new();
+    out.extend_from_slice(&PATCH_MAGIC.to_le_bytes());
+    let mut compression = Vec::new();
+    put_varint_field(&mut compression, 1, algorithm);
+    let mut header = Vec::new();
+    put_bytes_field(&mut header, 1, &compression);
+    put_message(&mut out, &header);
+    match algorithm
+    {
+        COMPRESSION_NONE => out.extend_from_slice(body),
+        COMPRESSION_GZIP =>
+        {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(body).unwrap();
+            out.extend(encoder.finish().unwrap());
+        }
+        COMPRESSION_ZSTD => out.extend(zstd::encode_all(body, 0).unwrap()),
+        _ => unreachable!()
+    }
+    std::fs::write(location, out).unwrap();
+}
+
+/// Create an empty directory for a test, with `files` in `game/`.
+fn create_test_dir(
+    name: &str,
+    files: &[(&str, &[u8])]
+) -> PathBuf
+{
+    let root = std::env::temp_dir().join(format!("beans-wharf-{}-{}", name, std::process::id()));
+    if root.exists()
+    {
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    std::fs::create_dir_all(root.join("game")).unwrap();
+    for (path, content) in files
+    {
+        let location = root.join("game").join(path);
+        std::fs::create_dir_all(location.parent().unwrap()).unwrap();
+        std::fs::write(location, content).unwrap();
+    }
+    root
+}
+
+fn run_patch(root: &Path) -> Result<crate::butler::ButlerReport, BeansError>
+{
+    patch(
+        root.join("patch.pwr").to_string_lossy().to_string(),
+        root.join("staging").to_string_lossy().to_string(),
+        root.join("game").to_string_lossy().to_string()
+    )
+}
+
+fn test_data(
+    size: usize,
+    seed: u8
+) -> Vec<u8>
+{
+    (0..size)
+        .map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed))
+        .collect()
+}
+
+#[test]
+fn rsync_patch()
+{
+    let unchanged = test_data(150_000, 1);
+    let old_modified = test_data(200_000, 2);
+    let mut new_modified = old_modified.clone();
+    let new_block = test_data(BLOCK_SIZE as usize, 3);
+    new_modified[BLOCK_SIZE as usize..(BLOCK_SIZE * 2) as usize].copy_from_slice(&new_block);
+
+    let old_files: &[(&str, &[u8])] = &[
+        ("unchanged.bin", &unchanged),
+        ("modified.bin", &old_modified),
+        ("a.txt", b"hello\n"),
+        ("sub/s.txt", b"same\n"),
+        ("gone.txt", b"bye\n"),
+        ("olddir/x.txt", b"x")
+    ];
+    let new_files: &[(&str, &[u8])] = &[
+        ("unchanged.bin", &unchanged),
+        ("modified.bin", &new_modified),
+        ("a.txt", b"hello world\n"),
+        ("sub/renamed.txt", b"same\n"),
+        ("new/n.txt", b"new\n"),
+        ("empty", b"")
+    ];
+    let root = create_test_dir("rsync", old_files);
+
+    let mut body = Vec::new();
+    put_message(&mut body, &container(old_files, &["sub", "olddir"]));
+    put_message(&mut body, &container(new_files, &["sub", "new"]));
+    // unchanged.bin
+    sync_header(&mut body, RSYNC, 0);
+    block_range(&mut body, 0, 0, 3);
+    hey_you_did_it(&mut body);
+    // modified.bin, second block replaced
+    sync_header(&mut body, RSYNC, 1);
+    block_range(&mut body, 1, 0, 1);
+    data(&mut body, &new_block);
+    block_range(&mut body, 1, 2, 2);
+    hey_you_did_it(&mut body);
+    // a.txt
+    sync_header(&mut body, RSYNC, 2);
+    data(&mut body, b"hello world\n");
+    hey_you_did_it(&mut body);
+    // sub/renamed.txt, from sub/s.txt
+    sync_header(&mut body, RSYNC, 3);
+    block_range(&mut body, 3, 0, 1);
+    hey_you_did_it(&mut body);
+    // new/n.txt
+    sync_header(&mut body, RSYNC, 4);
+    data(&mut body, b"new\n");
+    hey_you_did_it(&mut body);
+    // empty
+    sync_header(&mut body, RSYNC, 5);
+    hey_you_did_it(&mut body);
+    write_patch(&root.join("patch.pwr"), COMPRESSION_ZSTD, &body);
+    let removed = vec!["gone.txt", "olddir/x.txt", "sub/s.txt"];
+
+    // modified.bin is the same size, so it's not found without the operations.
+    let changes = patch_changes(root.join("patch.pwr").to_string_lossy().to_string()).unwrap();
+    assert_eq!(changes.changed_files, vec![
+        "a.txt",
+        "empty",
+        "new/n.txt",
+        "sub/renamed.txt"
+    ]);
+    assert_eq!(changes.removed_files, removed);
+
+    let report = run_patch(&root).unwrap();
+    assert_eq!(report.changed_files, vec![
+        "a.txt",
+        "empty",
+        "modified.bin",
+        "new/n.txt",
+        "sub/renamed.txt"
+    ]);
+    assert_eq!(report.removed_files, removed);
+    let game = root.join("game");
+    for (path, content) in new_files
+    {
+        assert_eq!(&std::fs::read(game.join(path)).unwrap(), content, "{path}");
+    }
+    assert!(!game.join("gone.txt").exists());
+    assert!(!game.join("sub/s.txt").exists());
+    assert!(!game.join("olddir").exists());
+    assert!(!root.join("staging").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn bsdiff_patch()
+{
+    let old = test_data(1000, 4);
+    let mut add = vec![0u8; 500];
+    for item in add.iter_mut().take(110).skip(100)
+    {
+        *item = 1;
+    }
+    // first 500 bytes with 100..110 changed, then go back 100 bytes and add
+    // the rest of the old file, then "tail".
+    let mut new: Vec<u8> = old[..500]
+        .iter()
+        .zip(add.iter())
+        .map(|(o, a)| o.wrapping_add(*a))
+        .collect();
+    new.extend_from_slice(&old[400..]);
+    new.extend_from_slice(b"tail");
+
+    let root = create_test_dir("bsdiff", &[("c.bin", &old)]);
+    let mut body = Vec::new();
+    put_message(&mut body, &container(&[("c.bin", &old)], &[]));
+    put_message(&mut body, &container(&[("c.bin", &new)], &[]));
+    sync_header(&mut body, BSDIFF, 0);
+    let mut bsdiff_header = Vec::new();
+    put_varint_field(&mut bsdiff_header, 1, 0);
+    put_message(&mut body, &bsdiff_header);
+    bsdiff_control(&mut body, &add, b"", -100);
+    bsdiff_control(&mut body, &[0u8; 600], b"tail", 0);
+    bsdiff_eof(&mut body);
+    hey_you_did_it(&mut body);
+    write_patch(&root.join("patch.pwr"), COMPRESSION_GZIP, &body);
+
+    let report = run_patch(&root).unwrap();
+    assert_eq!(report.changed_files, vec!["c.bin"]);
+    assert_eq!(std::fs::read(root.join("game/c.bin")).unwrap(), new);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn wrong_magic()
+{
+    let root = create_test_dir("magic", &[("a.txt", b"hello\n")]);
+    std::fs::write(root.join("patch.pwr"), b"not a patch").unwrap();
+    let result = run_patch(&root);
+    assert!(matches!(result, Err(BeansError::WharfPatchInvalid { .. })));
+    assert_eq!(std::fs::read(root.join("game/a.txt")).unwrap(), b"hello\n");
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+/// Nothing in the mod directory should change when the patch ends early.
+#[test]
+fn truncated_patch()
+{
+    let root = create_test_dir("truncated", &[("a.txt", b"hello\n"), ("b.txt", b"b")]);
+    let mut body = Vec::new();
+    put_message(
+        &mut body,
+        &container(&[("a.txt", b"hello\n"), ("b.txt", b"b")], &[])
+    );
+    put_message(
+        &mut body,
+        &container(&[("a.txt", b"changed\n"), ("b.txt", b"c")], &[])
+    );
+    sync_header(&mut body, RSYNC, 0);
+    data(&mut body, b"changed\n");
+    hey_you_did_it(&mut body);
+    sync_header(&mut body, RSYNC, 1);
+    write_patch(&root.join("patch.pwr"), COMPRESSION_NONE, &body);
+
+    let result = run_patch(&root);
+    assert!(matches!(result, Err(BeansError::WharfPatchInvalid { .. })));
+    assert_eq!(std::fs::read(root.join("game/a.txt")).unwrap(), b"hello\n");
+    assert!(!root.join("staging").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn path_outside_gamedir()
+{
+    let root = create_test_dir("outside", &[]);
+    let mut body = Vec::new();
+    put_message(&mut body, &container(&[], &[]));
+    put_message(&mut body, &container(&[("../evil.txt", b"evil")], &[]));
+    sync_header(&mut body, RSYNC, 0);
+    data(&mut body, b"evil");
+    hey_you_did_it(&mut body);
+    write_patch(&root.join("patch.pwr"), COMPRESSION_NONE, &body);
+
+    let result = run_patch(&root);
+    assert!(matches!(result, Err(BeansError::WharfPatchInvalid { .. })));
+    assert!(!root.join("evil.txt").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+/// Create a `.pws` file for `files`. When `empty_hash` is true, empty files
+/// have a hash for a 0-length block.
+fn write_signature(
+    location: &Path,
+    files: &[(&str, &[u8])],
+    empty_hash: bool
+)
+{
+    use md5::{Digest,
+              Md5};
+    let mut body = Vec::new();
+    put_message(&mut body, &container(files, &[]));
+    for (_, content) in files
+    {
+        let mut blocks: Vec<&[u8]> = content.chunks(BLOCK_SIZE as usize).collect();
+        if content.is_empty() && empty_hash
+        {
+            blocks.push(&[]);
+        }
+        for block in blocks
+        {
+            let mut hash = Vec::new();
+            put_varint_field(&mut hash, 1, 0);
+            put_bytes_field(&mut hash, 2, &Md5::digest(block));
+            put_message(&mut body, &hash);
+        }
+    }
+    let mut out = Vec::new();
+    out.extend_from_slice(&SIGNATURE_MAGIC.to_le_bytes());
+    let mut compression = Vec::new();
+    put_varint_field(&mut compression, 1, COMPRESSION_ZSTD);
+    let mut header = Vec::new();
+    put_bytes_field(&mut header, 1, &compression);
+    put_message(&mut out, &header);
+    out.extend(zstd::encode_all(body.as_slice(), 0).unwrap());
+    std::fs::write(location, out).unwrap();
+}
+
+fn run_verify(root: &Path) -> Result<SignatureReport, BeansError>
+{
+    verify_signature(
+        root.join("signature.pws").to_string_lossy().to_string(),
+        root.join("game").to_string_lossy().to_string(),
+        &crate::events::TerminalSink::default()
+    )
+}
+
+#[test]
+fn signature_valid()
+{
+    let big = test_data(300_000, 5);
+    let files: &[(&str, &[u8])] = &[("big.bin", &big), ("sub/a.txt", b"hello\n"), ("empty", b"")];
+    for empty_hash in [false, true]
+    {
+        let root = create_test_dir("signature-valid", files);
+        write_signature(&root.join("signature.pws"), files, empty_hash);
+        let report = run_verify(&root).unwrap();
+        assert!(report.is_valid(), "{:?}", report.damaged);
+        assert_eq!(report.files_checked, 3);
+        assert_eq!(report.bytes_checked, 300_006);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
+
+#[test]
+fn signature_damaged()
+{
+    let big = test_data(300_000, 6);
+    let files: &[(&str, &[u8])] = &[
+        ("big.bin", &big),
+        ("missing.txt", b"missing"),
+        ("size.txt", b"size"),
+        ("ok.txt", b"ok")
+    ];
+    let root = create_test_dir("signature-damaged", files);
+    write_signature(&root.join("signature.pws"), files, false);
+    let mut damaged = big.clone();
+    damaged[BLOCK_SIZE as usize + 10] ^= 0xFF;
+    damaged[299_999] ^= 0xFF;
+    std::fs::write(root.join("game/big.bin"), damaged).unwrap();
+    std::fs::remove_file(root.join("game/missing.txt")).unwrap();
+    std::fs::write(root.join("game/size.txt"), b"wrong size").unwrap();
+
+    let report = run_verify(&root).unwrap();
+    assert_eq!(report.damaged, vec![
+        DamagedFile {
+            path: String::from("big.bin"),
+            damage: Damage::Blocks {
+                blocks: vec![1, 4]
+            }
+        },
+        DamagedFile {
+            path: String::from("missing.txt"),
+            damage: Damage::Missing
+        },
+        DamagedFile {
+            path: String::from("size.txt"),
+            damage: Damage::WrongSize {
+                expected: 4,
+                actual: 10
+            }
+        }
+    ]);
+    // block 4 is the last one, so it's shorter.
+    assert_eq!(
+        report.bytes_damaged,
+        BLOCK_SIZE + (300_000 - 4 * BLOCK_SIZE) + 7 + 4
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn signature_wrong_hash_count()
+{
+    let root = create_test_dir("signature-count", &[("a.txt", b"a")]);
+    // 3 hashes for a file that only has 1 block.
+    let files: &[(&str, &[u8])] = &[("a.txt", b"a")];
+    let mut body = Vec::new();
+    put_message(&mut body, &container(files, &[]));
+    for _ in 0..3
+    {
+        let mut hash = Vec::new();
+        put_bytes_field(&mut hash, 2, &[0u8; 16]);
+        put_message(&mut body, &hash);
+    }
+    let mut out = Vec::new();
+    out.extend_from_slice(&SIGNATURE_MAGIC.to_le_bytes());
+    put_message(&mut out, &[]);
+    out.extend(body);
+    std::fs::write(root.join("signature.pws"), out).unwrap();
+
+    let result = run_verify(&root);
+    assert!(matches!(
+        result,
+        Err(BeansError::WharfSignatureInvalid { .. })
+    ));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+// Everything below uses patches and a signature that were created by butler,
+// instead of the encoder above. The trees are built here, the same way as
+// `fixtures/generate.sh` builds them. Run that script, then
+// `cargo test -- --ignored`
+
+fn fixture(name: &str) -> PathBuf
+{
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/wharf/fixtures")
+        .join(name)
+}
+
+/// Content of maps/big.bsp: 20000 lines of `block %05d`, where line
+/// `changed` is upper case.
+fn big_bsp(changed: Option<usize>) -> Vec<u8>
+{
+    let mut content = Vec::with_capacity(240_000);
+    for i in 0..20000
+    {
+        let word = if Some(i) == changed { "BLOCK" } else { "block" };
+        content.extend_from_slice(format!("{word} {i:05}\n").as_bytes());
+    }
+    content
+}
+
+/// Path and content of every file in the fixture tree `tree`.
+fn fixture_tree(tree: &str) -> Vec<(String, Vec<u8>)>
+{
+    let gameinfo = b"GameInfo\n{\n\tgame \"beans test\"\n}\n".to_vec();
+    let config = b"name beans\n".to_vec();
+    let readme = b"This file is removed in v2.\n".to_vec();
+    let autoexec = b"exec config.cfg\n".to_vec();
+    let mut files = match tree
+    {
+        "v1" => vec![
+            ("cfg/config.cfg", config),
+            ("gameinfo.txt", gameinfo),
+            ("maps/big.bsp", big_bsp(None)),
+            ("readme.txt", readme),
+        ],
+        "v2" => vec![
+            ("cfg/autoexec.cfg", autoexec),
+            ("cfg/config.cfg", config),
+            (
+                "gameinfo.txt",
+                b"GameInfo\n{\n\tgame \"beans test 2\"\n}\n".to_vec()
+            ),
+            ("maps/big.bsp", big_bsp(Some(9000))),
+        ],
+        "v2-moved" => vec![
+            ("cfg/autoexec.cfg", autoexec),
+            ("cfg/config.cfg", config),
+            ("gameinfo.txt", gameinfo),
+            ("maps/renamed.bsp", big_bsp(None)),
+            ("readme.txt", readme),
+        ],
+        _ => panic!("unknown fixture tree {tree}")
+    }
+    .into_iter()
+    .map(|(path, content)| (path.to_string(), content))
+    .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+/// Path and content of every file in `root`.
+fn read_tree(root: &Path) -> Vec<(String, Vec<u8>)>
+{
+    let mut files = crate::helper::list_files_recursive(root.to_string_lossy().to_string())
+        .unwrap()
+        .into_iter()
+        .map(|f| {
+            let content = std::fs::read(root.join(&f)).unwrap();
+            (f.replace('\\', "/"), content)
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+/// Create a test directory where `game` contains the fixture tree `tree`.
+fn create_fixture_dir(
+    name: &str,
+    tree: &str
+) -> PathBuf
+{
+    let root = create_test_dir(name, &[]);
+    for (path, content) in fixture_tree(tree)
+    {
+        let location = root.join("game").join(path);
+        std::fs::create_dir_all(location.parent().unwrap()).unwrap();
+        std::fs::write(location, content).unwrap();
+    }
+    root
+}
+
+/// Apply the butler patch `patch_name` to a copy of `v1`, and check that
+/// the result is the same as `expected`.
+fn check_butler_patch(
+    patch_name: &str,
+    expected: &str,
+    compression: CompressionAlgorithm
+)
+{
+    let location = fixture(patch_name);
+    let file = std::fs::File::open(&location)
+        .unwrap_or_else(|e| panic!("{location:?} ({e:}). Run fixtures/generate.sh first."));
+    let mut reader = WireReader::new(file);
+    assert_eq!(reader.read_magic().unwrap(), PATCH_MAGIC);
+    let header: Header = reader.read_message().unwrap();
+    assert_eq!(header.compression.algorithm, compression);
+
+    let root = create_fixture_dir(&format!("butler-{patch_name}"), "v1");
+    std::fs::copy(&location, root.join("patch.pwr")).unwrap();
+    run_patch(&root).unwrap();
+    assert_eq!(read_tree(&root.join("game")), fixture_tree(expected));
+    assert!(!root.join("staging").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+#[ignore = "needs the .pwr files from src/wharf/fixtures/generate.sh, which requires butler"]
+fn butler_rsync_patch()
+{
+    check_butler_patch("rsync.pwr", "v2-moved", CompressionAlgorithm::None);
+}
+
+#[test]
+#[ignore = "needs the .pwr files from src/wharf/fixtures/generate.sh, which requires butler"]
+fn butler_bsdiff_patch()
+{
+    check_butler_patch("bsdiff.pwr", "v2", CompressionAlgorithm::None);
+}
+
+#[test]
+#[ignore = "needs the .pwr files from src/wharf/fixtures/generate.sh, which requires butler"]
+fn butler_zstd_patch()
+{
+    check_butler_patch("zstd.pwr", "v2", CompressionAlgorithm::Zstd);
+}
+
+#[test]
+#[ignore = "needs the .pwr files from src/wharf/fixtures/generate.sh, which requires butler"]
+fn butler_brotli_patch()
+{
+    check_butler_patch("brotli.pwr", "v2", CompressionAlgorithm::Brotli);
+}
+
+/// Verify the `v2` tree in `root` against `root/signature.pws`, then damage
+/// it and check that the right blocks are reported.
+fn check_fixture_signature(root: &Path)
+{
+    let report = run_verify(&root).unwrap();
+    assert!(report.is_valid(), "{:?}", report.damaged);
+    assert_eq!(report.files_checked, 4);
+    assert_eq!(report.bytes_checked, 240_000 + 16 + 11 + 34);
+
+    // change one byte in the last (short) block of big.bsp, and the only
+    // block of config.cfg.
+    let big = root.join("game/maps/big.bsp");
+    let mut content = std::fs::read(&big).unwrap();
+    content[3 * BLOCK_SIZE as usize + 5] ^= 0xFF;
+    std::fs::write(&big, content).unwrap();
+    std::fs::write(root.join("game/cfg/config.cfg"), b"name Beans\n").unwrap();
+
+    let report = run_verify(&root).unwrap();
+    assert_eq!(report.damaged, vec![
+        DamagedFile {
+            path: String::from("cfg/config.cfg"),
+            damage: Damage::Blocks {
+                blocks: vec![0]
+            }
+        },
+        DamagedFile {
+            path: String::from("maps/big.bsp"),
+            damage: Damage::Blocks {
+                blocks: vec![3]
+            }
+        }
+    ]);
+    assert_eq!(report.bytes_damaged, (240_000 - 3 * BLOCK_SIZE) + 11);
+}
+
+#[test]
+fn fixture_signature()
+{
+    // same checks as butler_signature, with a signature from the encoder
+    // above, so they also run without butler.
+    let root = create_fixture_dir("fixture-signature", "v2");
+    let tree = fixture_tree("v2");
+    let files = tree
+        .iter()
+        .map(|(path, content)| (path.as_str(), content.as_slice()))
+        .collect::<Vec<_>>();
+    write_signature(&root.join("signature.pws"), &files, false);
+    check_fixture_signature(&root);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+#[ignore = "needs v2.pws from src/wharf/fixtures/generate.sh, which requires butler"]
+fn butler_signature()
+{
+    let root = create_fixture_dir("butler-signature", "v2");
+    let location = fixture("v2.pws");
+    std::fs::copy(&location, root.join("signature.pws"))
+        .unwrap_or_else(|e| panic!("{location:?} ({e:}). Run fixtures/generate.sh first."));
+    check_fixture_signature(&root);
+    std::fs::remove_dir_all(&root).unwrap();
+}
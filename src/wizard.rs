@@ -4,23 +4,26 @@ use async_recursion::async_recursion;
 use log::{debug,
           error,
           info,
-          trace};
+          trace,
+          warn};
 
 use crate::{appvar::AppVarData,
+            config::Config,
             depends,
-            flags,
             flags::LaunchFlag,
             helper,
             helper::{find_sourcemod_path,
                      parse_location,
                      InstallType},
-            workflows::{CleanWorkflow,
+            workflows::{AdoptWorkflow,
+                        CleanWorkflow,
                         InstallWorkflow,
                         UninstallWorkflow,
                         UpdateWorkflow,
                         VerifyWorkflow},
             BeansError,
             RunnerContext,
+            RunnerOptions,
             SourceModDirectoryParam};
 
 #[derive(Debug, Clone)]
@@ -32,15 +35,23 @@ pub struct WizardContext
 
 impl WizardContext
 {
-    /// run the wizard!
-    pub async fn run(sml_via: SourceModDirectoryParam) -> Result<(), BeansError>
+    /// run the wizard for the mod that was compiled in (see
+    /// `AppVarData::get`)!
+    pub async fn run(
+        sml_via: SourceModDirectoryParam,
+        mut options: RunnerOptions
+    ) -> Result<(), BeansError>
     {
-        depends::try_write_deps();
-        WizardContext::check_aria();
-        if let Err(e) = depends::try_install_vcredist().await
+        let appvar = AppVarData::get();
+        depends::try_write_deps(&options.config);
+        WizardContext::check_aria(&options.config);
+        if let Err(e) =
+            depends::try_install_vcredist(&options.config, options.events.as_ref()).await
         {
             sentry::capture_error(&e);
-            println!("Failed to install vcredist! {:}", e);
+            options
+                .events
+                .warning(format!("Failed to install vcredist! {:}", e));
             debug!("[WizardContext::run] {:#?}", e);
         }
         let sourcemod_path = parse_location(match sml_via
@@ -48,7 +59,7 @@ impl WizardContext
             SourceModDirectoryParam::AutoDetect =>
             {
                 debug!("[WizardContext::run] Auto-detecting sourcemods directory");
-                get_path()
+                get_path(&mut options.config)
             }
             SourceModDirectoryParam::WithLocation(loc) =>
             {
@@ -56,7 +67,7 @@ impl WizardContext
                 loc
             }
         });
-        let version_list = match crate::version::get_version_list().await
+        let version_list = match crate::version::get_version_list(&appvar).await
         {
             Ok(v) => v,
             Err(e) =>
@@ -68,17 +79,15 @@ impl WizardContext
             }
         };
 
-        if helper::install_state(Some(sourcemod_path.clone())) == InstallType::OtherSource
-        {
-            crate::version::update_version_file(Some(sourcemod_path.clone()))?;
-        }
-
-        let ctx = RunnerContext {
+        let mut ctx = RunnerContext {
             sourcemod_path: sourcemod_path.clone(),
             remote_version_list: version_list,
-            current_version: crate::version::get_current_version(Some(sourcemod_path)),
-            appvar: AppVarData::get()
+            current_version: None,
+            version_file_corrupt: false,
+            appvar,
+            options
         };
+        ctx.load_current_version().await?;
 
         let mut i = Self {
             context: ctx,
@@ -88,17 +97,18 @@ impl WizardContext
         Ok(())
     }
 
-    fn check_aria()
+    fn check_aria(config: &Config)
     {
-        if !crate::env_disable_aria2c()
+        let disable_aria2c = config.disable_aria2c;
+        if !disable_aria2c
         {
-            if crate::aria2::get_executable_location().is_none()
+            if crate::aria2::get_executable_location(config).is_none()
             {
                 info!("Could not find aria2c!\nFor faster downloads, install it with your package manager (usually called \"aria2\")");
             }
         }
 
-        if crate::env_disable_aria2c() && crate::aria2::get_executable_location().is_some()
+        if disable_aria2c && crate::aria2::get_executable_location(config).is_some()
         {
             info!("== aria2c support disabled, even though it's available ==");
         }
@@ -109,7 +119,7 @@ impl WizardContext
     #[async_recursion]
     pub async fn menu<'a>(&'a mut self)
     {
-        let av = crate::appvar::AppVarData::get();
+        let av = self.context.appvar.clone();
         if self.menu_trigger_count == 0
         {
             if let Some(cv) = self.context.current_version
@@ -130,6 +140,14 @@ impl WizardContext
         println!("1 - Install or reinstall the game");
         println!("2 - Check for and apply any available updates");
         println!("3 - Verify and repair game files");
+        if helper::install_state(Some(self.context.sourcemod_path.clone()), &av)
+            == InstallType::OtherSourceManual
+        {
+            println!(
+                "a - Adopt the existing installation of {} (so it can be updated and verified)",
+                av.mod_info.name_stylized
+            );
+        }
         println!("c - Clean up temporary files used by beans.");
         println!("u - Uninstall {}", av.mod_info.name_stylized);
         println!();
@@ -140,6 +158,7 @@ impl WizardContext
             "1" | "install" => WizardContext::menu_error_catch(self.task_install().await),
             "2" | "update" => WizardContext::menu_error_catch(self.task_update().await),
             "3" | "verify" => WizardContext::menu_error_catch(self.task_verify().await),
+            "a" | "adopt" => WizardContext::menu_error_catch(self.task_adopt().await),
             "c" | "clean" => Self::menu_error_catch(CleanWorkflow::wizard(&mut self.context)),
             "u" | "uninstall" =>
             {
@@ -147,7 +166,9 @@ impl WizardContext
             }
             "d" | "debug" =>
             {
-                flags::add_flag(LaunchFlag::DEBUG_MODE);
+                self.context.options.flags.insert(LaunchFlag::DEBUG_MODE);
+                crate::logger::set_format(crate::logger::LOG_FORMAT_DEFAULT);
+                crate::logger::set_filter(log::LevelFilter::max());
                 info!("Debug mode enabled!");
                 self.menu().await;
             }
@@ -185,6 +206,12 @@ impl WizardContext
     {
         UpdateWorkflow::wizard(&mut self.context).await
     }
+    /// Figure out what version an installation that wasn't installed by beans
+    /// is, so it can be managed by beans.
+    pub async fn task_adopt(&mut self) -> Result<(), BeansError>
+    {
+        AdoptWorkflow::wizard(&mut self.context).await
+    }
     /// Verify the current data for the target sourcemod.
     pub async fn task_verify(&mut self) -> Result<(), BeansError>
     {
@@ -192,15 +219,42 @@ impl WizardContext
     }
 }
 
-fn get_path() -> String
+fn get_path(config: &mut Config) -> String
 {
     find_sourcemod_path().unwrap_or_else(|e| {
         error!("[get_path] Failed to automatically detect sourcemods folder!");
         debug!("{:#?}", e);
-        prompt_sourcemod_location()
+        let location = prompt_sourcemod_location();
+        remember_sourcemod_location(config, location.clone());
+        location
     })
 }
 
+/// Save `location` as `sourcemods_location` in the config, so it doesn't have
+/// to be entered again.
+fn remember_sourcemod_location(
+    config: &mut Config,
+    location: String
+)
+{
+    match config.save_value(
+        "sourcemods_location",
+        Some(toml::Value::String(location.clone()))
+    )
+    {
+        Ok(v) =>
+        {
+            *config = v;
+            info!("Saved {location} as the sourcemods folder. Use \"config unset sourcemods_location\" to detect it again.");
+        }
+        Err(e) =>
+        {
+            warn!("[remember_sourcemod_location] Failed to save the sourcemods folder to the config ({e:})");
+            debug!("{:#?}", e);
+        }
+    }
+}
+
 fn prompt_sourcemod_location() -> String
 {
     let res = helper::get_input("Please provide your sourcemods folder, then press enter.");
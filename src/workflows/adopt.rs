@@ -0,0 +1,349 @@
+use log::{debug,
+          info,
+          warn};
+
+use crate::{events::{Operation,
+                     Phase},
+            helper,
+            helper::InstallType,
+            version::{AdastralHistoryEntry,
+                      AdastralOperation,
+                      AdastralVersionFile},
+            wharf,
+            wharf::SignatureReport,
+            workflows::{CleanInstallOptions,
+                        VerifyWorkflow},
+            BeansError,
+            RunnerContext};
+
+/// Take over an installation that wasn't installed by beans (where there is a
+/// `gameinfo.txt`, but no `.adastral` or `.revision` file), by figuring out
+/// which version it is. Also used to repair corrupt version files.
+#[derive(Debug, Clone)]
+pub struct AdoptWorkflow
+{
+    pub context: RunnerContext
+}
+
+/// Maximum amount of versions that will be checked against their signature.
+/// They are checked in order of how close their `post_sz` is to the size of
+/// the installation, and the one with the fewest damaged files is used when
+/// none of them match.
+pub const ADOPT_MAX_CANDIDATES: usize = 3;
+
+impl AdoptWorkflow
+{
+    pub async fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let av = ctx.appvar.clone();
+        match helper::install_state(Some(ctx.sourcemod_path.clone()), &ctx.appvar)
+        {
+            InstallType::OtherSourceManual =>
+            {}
+            InstallType::NotInstalled =>
+            {
+                info!("{} is not installed.", av.mod_info.name_stylized);
+                return Ok(());
+            }
+            _ =>
+            {
+                if let Err(e) = Self::check_version_file(ctx)
+                {
+                    warn!("[AdoptWorkflow] {e:}");
+                    return Self::repair(ctx).await;
+                }
+                info!(
+                    "[AdoptWorkflow] {} is already managed by beans, nothing to do.",
+                    av.mod_info.name_stylized
+                );
+                return Ok(());
+            }
+        }
+        Self::fingerprint(ctx).await
+    }
+
+    /// Repair a corrupt `.adastral` or `.revision` file. The user is asked
+    /// which version is installed, and when they don't know (or
+    /// `ctx.options.confirm` is set) the version is found with
+    /// `AdoptWorkflow::fingerprint`.
+    pub async fn repair(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let av = ctx.appvar.clone();
+        let mod_location = ctx.get_mod_location();
+        let mut version_id: Option<usize> = None;
+        if !ctx.options.confirm
+        {
+            loop
+            {
+                let user_input = match ctx.events().input(&format!(
+                    "Enter the version of {} that is installed, or leave it empty to detect it automatically.",
+                    av.mod_info.name_stylized
+                ))
+                {
+                    Some(v) if !v.is_empty() => v,
+                    _ => break
+                };
+                match user_input.trim_start_matches('v').parse::<usize>()
+                {
+                    Ok(v) if ctx.remote_version_list.versions.contains_key(&v) =>
+                    {
+                        version_id = Some(v);
+                        break;
+                    }
+                    Ok(v) => ctx
+                        .events()
+                        .warning(format!("v{v} doesn't exist. Try again.")),
+                    Err(_) => ctx
+                        .events()
+                        .warning(format!("\"{user_input}\" isn't a number. Try again."))
+                }
+            }
+        }
+
+        let revision_location = format!("{}.revision", mod_location);
+        if helper::file_exists(revision_location.clone())
+        {
+            debug!("[AdoptWorkflow::repair] removing {revision_location}");
+            if let Err(e) = std::fs::remove_file(&revision_location)
+            {
+                return Err(BeansError::FileDeleteFailure {
+                    location: revision_location,
+                    error: e
+                });
+            }
+        }
+        match version_id
+        {
+            Some(v) =>
+            {
+                AdastralVersionFile::new(v).write(Some(ctx.sourcemod_path.clone()), &ctx.appvar)?;
+                ctx.current_version = Some(v);
+                ctx.version_file_corrupt = false;
+                info!("[AdoptWorkflow] Set the installed version to v{v}");
+                Ok(())
+            }
+            None => Self::fingerprint(ctx).await
+        }
+    }
+
+    /// Check that the version file (`.adastral` or `.revision`) can be read.
+    fn check_version_file(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        match helper::install_state(Some(ctx.sourcemod_path.clone()), &ctx.appvar)
+        {
+            InstallType::OtherSource =>
+            {
+                crate::version::read_revision_file(format!("{}.revision", ctx.get_mod_location()))?;
+            }
+            _ =>
+            {
+                crate::version::get_current_version(Some(ctx.sourcemod_path.clone()), &ctx.appvar)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the version that the installation matches (or is closest to), write
+    /// it to `.adastral`, then offer to repair the files that don't match.
+    pub async fn fingerprint(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let av = ctx.appvar.clone();
+        let mod_location = ctx.get_mod_location();
+        if let Some(pid) = helper::is_game_running(mod_location.clone())
+        {
+            info!(
+                "{} (pid: {:}) is running! Can't adopt since the game files are being used.",
+                av.mod_info.name_stylized, pid
+            );
+            return Err(BeansError::GameStillRunning {
+                name: av.mod_info.name_stylized.clone(),
+                pid: format!("{:}", pid)
+            });
+        }
+
+        let candidates = Self::find_candidates(ctx, mod_location.clone())?;
+        let closest = match candidates.first()
+        {
+            Some(v) => *v,
+            None =>
+            {
+                warn!("[AdoptWorkflow] There aren't any versions available to compare against.");
+                return Ok(());
+            }
+        };
+
+        let operation = Operation::start(
+            ctx.options.events.clone(),
+            Phase::Adopt,
+            format!(
+                "Finding the version of {} that is installed",
+                av.mod_info.name_stylized
+            )
+        );
+        // the version with the fewest damaged files (then bytes) is the closest.
+        let mut closest_report: Option<(usize, SignatureReport)> = None;
+        for version_id in candidates.iter().take(ADOPT_MAX_CANDIDATES)
+        {
+            let signature_url = match ctx
+                .remote_version_list
+                .versions
+                .get(version_id)
+                .and_then(|v| v.signature_url.clone())
+            {
+                Some(v) => v,
+                None =>
+                {
+                    debug!("[AdoptWorkflow] v{version_id} has no signature, skipping");
+                    continue;
+                }
+            };
+            info!("[AdoptWorkflow] Checking if the installation matches v{version_id}");
+            let report = wharf::verify_signature_url(
+                format!("{}{}", &av.remote_info.base_url, signature_url),
+                mod_location.clone(),
+                ctx.config(),
+                ctx.events()
+            )
+            .await?;
+            debug!(
+                "[AdoptWorkflow] v{version_id} has {} damaged files ({} bytes)",
+                report.damaged.len(),
+                report.bytes_damaged
+            );
+            let is_closer = closest_report.as_ref().is_none_or(|(_, r)| {
+                (report.damaged.len(), report.bytes_damaged) < (r.damaged.len(), r.bytes_damaged)
+            });
+            if is_closer
+            {
+                closest_report = Some((*version_id, report));
+            }
+            if closest_report.as_ref().is_some_and(|(_, r)| r.is_valid())
+            {
+                break;
+            }
+        }
+
+        let (version_id, report) = match closest_report
+        {
+            Some(v) => v,
+            None =>
+            {
+                // only the size can be compared when none of the candidates have
+                // a signature, which isn't enough to be sure.
+                operation.finish(Some(format!(
+                    "None of the versions have a signature, so the installed version can only be guessed from its size. The closest match in size is v{}.",
+                    closest
+                )));
+                if !ctx.prompt_confirm(&format!(
+                    "Do you want to mark the installation as v{}?",
+                    closest
+                ))
+                {
+                    info!("[AdoptWorkflow] Not adopting, since the installed version couldn't be confirmed.");
+                    return Ok(());
+                }
+                return Self::write_version_file(
+                    ctx,
+                    closest,
+                    Some(format!(
+                        "closest match in size was v{closest}, since there are no signatures"
+                    ))
+                );
+            }
+        };
+        let matched = report.is_valid();
+        let closest_message = format!(
+            "closest match was v{} ({} files, {} don't match)",
+            version_id,
+            report.damaged.len(),
+            helper::format_size(report.bytes_damaged as usize)
+        );
+        Self::write_version_file(ctx, version_id, (!matched).then(|| closest_message.clone()))?;
+
+        if matched
+        {
+            operation.finish(Some(format!(
+                "Installation matches v{}. It can now be updated and verified with beans.",
+                version_id
+            )));
+            return Ok(());
+        }
+
+        operation.finish(Some(format!(
+            "Couldn't find a version that exactly matches the installation. The {}.",
+            closest_message
+        )));
+        if ctx.prompt_confirm(&format!(
+            "Do you want to repair the files that don't match v{}?",
+            version_id
+        ))
+        {
+            // boxed, since verifying can repair the version file with
+            // `AdoptWorkflow::repair`.
+            Box::pin(VerifyWorkflow::wizard(ctx)).await?;
+        }
+        else
+        {
+            info!("[AdoptWorkflow] Not repairing. Use the verify command to do it later.");
+        }
+        Ok(())
+    }
+
+    /// Write a version file that says `version_id` is installed, with an
+    /// `Adopt` history entry. `message` is used for the entry when the version
+    /// wasn't an exact match.
+    fn write_version_file(
+        ctx: &mut RunnerContext,
+        version_id: usize,
+        message: Option<String>
+    ) -> Result<(), BeansError>
+    {
+        let mut version_file = AdastralVersionFile::new(version_id);
+        let mut entry = AdastralHistoryEntry::new(
+            AdastralOperation::Adopt,
+            None,
+            Some(version_id),
+            &Ok::<(), BeansError>(())
+        );
+        entry.message = message;
+        version_file.push_history(entry);
+        version_file.write(Some(ctx.sourcemod_path.clone()), &ctx.appvar)?;
+        ctx.current_version = Some(version_id);
+        ctx.version_file_corrupt = false;
+        Ok(())
+    }
+
+    /// Get all the version IDs that have a `post_sz`, sorted by how close it is
+    /// to the size of the installation (closest first). Files that are kept by
+    /// `install --clean` are ignored, since they're user data.
+    fn find_candidates(
+        ctx: &RunnerContext,
+        mod_location: String
+    ) -> Result<Vec<usize>, BeansError>
+    {
+        let preserve = CleanInstallOptions::default();
+        let size: usize = helper::list_files_recursive(mod_location.clone())?
+            .into_iter()
+            .filter(|f| !preserve.is_preserved(f))
+            .map(|f| {
+                std::fs::symlink_metadata(helper::join_path(mod_location.clone(), f))
+                    .map(|m| m.len() as usize)
+                    .unwrap_or(0)
+            })
+            .sum();
+        debug!(
+            "[AdoptWorkflow::find_candidates] installation size is {}",
+            size
+        );
+
+        let mut candidates: Vec<(usize, usize)> = ctx
+            .remote_version_list
+            .versions
+            .iter()
+            .filter_map(|(id, v)| v.post_sz.map(|sz| (*id, sz.abs_diff(size))))
+            .collect();
+        // sort by difference, then newest first.
+        candidates.sort_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
+        Ok(candidates.into_iter().map(|(id, _)| id).collect())
+    }
+}
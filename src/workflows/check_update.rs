@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use log::debug;
+
+use crate::{appvar::AppVarData,
+            events::EventSink,
+            helper,
+            helper::InstallType,
+            version,
+            BeansError};
+
+/// Check if an update is available, without prompting or changing any files.
+///
+/// `RunnerContext::create_auto` isn't used since it writes the dependencies
+/// and migrates `.revision` files.
+#[derive(Debug, Clone)]
+pub struct CheckUpdateWorkflow
+{
+    /// sourcemods directory. When `None`, it's found with
+    /// `helper::find_sourcemod_path`
+    pub sourcemods_location: Option<String>,
+    /// Mod to check for updates.
+    pub appvar: AppVarData,
+    /// Print the result as JSON instead of text.
+    pub json: bool,
+    /// Where the result is sent to when `json` is `true`.
+    pub events: Arc<dyn EventSink>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateCheckStatus
+{
+    UpToDate,
+    UpdateAvailable,
+    /// Not installed, or not installed by beans (see `AdoptWorkflow`)
+    NotInstalled
+}
+
+impl UpdateCheckStatus
+{
+    /// Process exit code for this status. When the check fails, the code from
+    /// `BeansError::code` is used instead, which is always 20 or higher.
+    pub fn exit_code(&self) -> i32
+    {
+        match self
+        {
+            UpdateCheckStatus::UpToDate => 0,
+            UpdateCheckStatus::UpdateAvailable => 10,
+            UpdateCheckStatus::NotInstalled => 11
+        }
+    }
+}
+
+/// Output of `CheckUpdateWorkflow`. This is what is printed when `--json` is
+/// used.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UpdateCheckReport
+{
+    pub status: UpdateCheckStatus,
+    pub install_type: InstallType,
+    pub installed_version: Option<usize>,
+    pub latest_version: usize,
+    /// Patches that would be applied to update to `latest_version`, in order.
+    /// Empty when there isn't a way to update with patches.
+    pub patches: Vec<String>,
+    /// Sum of `tempreq` for everything in `patches`.
+    pub patch_size: Option<usize>,
+    /// `true` when there is a single patch from `installed_version` to
+    /// `latest_version`.
+    pub direct_patch: bool
+}
+
+impl CheckUpdateWorkflow
+{
+    pub async fn run(&self) -> Result<UpdateCheckStatus, BeansError>
+    {
+        let report = self.check().await?;
+        if self.json
+        {
+            self.events.report(serde_json::to_value(&report)?);
+        }
+        else
+        {
+            self.print_report(&report);
+        }
+        Ok(report.status)
+    }
+
+    /// Compare the installed version with the latest version from
+    /// `versions.json`, and find the patches required to update.
+    pub async fn check(&self) -> Result<UpdateCheckReport, BeansError>
+    {
+        let av = &self.appvar;
+        let sourcemods = helper::parse_location(match self.sourcemods_location.clone()
+        {
+            Some(v) => v,
+            None => helper::find_sourcemod_path()?
+        });
+        let remote = version::get_version_list(av).await?;
+        let latest_version = match remote.versions.keys().max()
+        {
+            Some(v) => *v,
+            None =>
+            {
+                return Err(BeansError::RemoteVersionNotFound {
+                    version: None
+                });
+            }
+        };
+
+        let install_type = helper::install_state(Some(sourcemods.clone()), av);
+        let installed_version = version::read_installed_version(sourcemods, av)?;
+        debug!(
+            "[CheckUpdateWorkflow::check] installed: {:?}, latest: {}",
+            installed_version, latest_version
+        );
+
+        let mut report = UpdateCheckReport {
+            status: UpdateCheckStatus::NotInstalled,
+            install_type,
+            installed_version,
+            latest_version,
+            patches: Vec::new(),
+            patch_size: None,
+            direct_patch: false
+        };
+        match installed_version
+        {
+            Some(cv) if cv < latest_version =>
+            {
+                report.status = UpdateCheckStatus::UpdateAvailable;
+                if let Some(chain) =
+                    remote.find_patch_chain(&av.mod_info.short_name, cv, latest_version)
+                {
+                    report.direct_patch = chain.len() == 1;
+                    report.patch_size = Some(chain.iter().map(|p| p.tempreq).sum());
+                    report.patches = chain.into_iter().map(|p| p.file).collect();
+                }
+            }
+            Some(_) => report.status = UpdateCheckStatus::UpToDate,
+            None =>
+            {}
+        }
+        Ok(report)
+    }
+
+    fn print_report(
+        &self,
+        report: &UpdateCheckReport
+    )
+    {
+        let av = &self.appvar;
+        match (report.status, report.installed_version)
+        {
+            (UpdateCheckStatus::UpdateAvailable, Some(cv)) => match report.patch_size
+            {
+                Some(size) => println!(
+                    "Update available: v{} -> v{} with {} ({})",
+                    cv,
+                    report.latest_version,
+                    report.patches.join(", "),
+                    helper::format_size(size)
+                ),
+                None => println!(
+                    "Update available: v{} -> v{}, but there are no patches (a reinstall is required)",
+                    cv, report.latest_version
+                )
+            },
+            (UpdateCheckStatus::UpToDate, Some(cv)) => println!("Up to date (v{cv})"),
+            _ => println!("{} is not installed", av.mod_info.name_stylized)
+        }
+    }
+}
@@ -14,10 +14,23 @@ pub struct CleanWorkflow
 
 impl CleanWorkflow
 {
-    pub fn wizard(_ctx: &mut RunnerContext) -> Result<(), BeansError>
+    /// Delete everything in the temporary and download directories.
+    pub fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
     {
-        let target_directory = helper::get_tmp_dir();
+        for target_directory in [
+            helper::get_tmp_dir(ctx.config()),
+            helper::get_download_dir(ctx.config())
+        ]
+        {
+            Self::clean_directory(target_directory)?;
+        }
 
+        info!("[CleanWorkflow] Done!");
+        Ok(())
+    }
+
+    fn clean_directory(target_directory: String) -> Result<(), BeansError>
+    {
         info!("[CleanWorkflow] Cleaning up {}", target_directory);
         if !helper::file_exists(target_directory.clone())
         {
@@ -27,7 +40,7 @@ impl CleanWorkflow
         // delete directory and it's contents (and error handling)
         if let Err(e) = std::fs::remove_dir_all(&target_directory)
         {
-            debug!("[CleanWorkflow::wizard] remove_dir_all {:#?}", e);
+            debug!("[CleanWorkflow::clean_directory] remove_dir_all {:#?}", e);
             return Err(BeansError::CleanTempFailure {
                 location: target_directory,
                 error: e,
@@ -38,15 +51,13 @@ impl CleanWorkflow
         // re-creating the temporary directory (and error handling)
         if let Err(e) = std::fs::create_dir(&target_directory)
         {
-            debug!("[CleanWorkflow::wizard] create_dir {:#?}", e);
+            debug!("[CleanWorkflow::clean_directory] create_dir {:#?}", e);
             return Err(BeansError::DirectoryCreateFailure {
                 location: target_directory,
                 error: e,
                 backtrace: std::backtrace::Backtrace::capture()
             });
         }
-
-        info!("[CleanWorkflow] Done!");
         Ok(())
     }
 }
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use log::{info,
+          warn};
+
+use crate::{config::{find_key,
+                     format_value,
+                     parse_value,
+                     Config,
+                     ConfigValueType,
+                     CONFIG_KEYS},
+            events::EventSink,
+            BeansError};
+
+/// Show and change the settings in the config file (see `crate::config`).
+#[derive(Debug, Clone)]
+pub struct ConfigWorkflow
+{
+    /// Print as JSON instead of text.
+    pub json: bool,
+    /// Where the output is sent to when `json` is `true`.
+    pub events: Arc<dyn EventSink>,
+    pub config: Config
+}
+
+/// Item in the output of `ConfigWorkflow::list`
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConfigSummary
+{
+    pub key: String,
+    pub value_type: ConfigValueType,
+    /// `None` when it's not set.
+    pub value: Option<serde_json::Value>,
+    /// Where `value` came from (like `config file` or `default`).
+    pub source: Option<String>,
+    pub env: Vec<String>,
+    pub description: String
+}
+
+impl ConfigWorkflow
+{
+    /// Print the value of `key`. Nothing is printed when it's not set (or
+    /// `null` when `self.json` is `true`).
+    pub fn get(
+        &self,
+        key: String
+    ) -> Result<(), BeansError>
+    {
+        let key = find_key(&key)?;
+        let value = self.config.get_value(key.name);
+        if self.json
+        {
+            let value = match value
+            {
+                Some((v, _)) => serde_json::to_value(v)?,
+                None => serde_json::Value::Null
+            };
+            self.events.report(value);
+            return Ok(());
+        }
+        if let Some((value, _)) = value
+        {
+            println!("{}", format_value(&value));
+        }
+        Ok(())
+    }
+
+    /// Set `key` to `value` in the config file.
+    pub fn set(
+        &self,
+        key: String,
+        value: String
+    ) -> Result<(), BeansError>
+    {
+        let key = find_key(&key)?;
+        let value = parse_value(key, &value)?;
+        let config = self.config.save_value(key.name, Some(value.clone()))?;
+        info!("Set {} to {}", key.name, format_value(&value));
+        Self::warn_overridden(&config, key.name);
+        Ok(())
+    }
+
+    /// Remove `key` from the config file, so the default value is used.
+    pub fn unset(
+        &self,
+        key: String
+    ) -> Result<(), BeansError>
+    {
+        let key = find_key(&key)?;
+        let config = self.config.save_value(key.name, None)?;
+        info!("Removed {} from the config file", key.name);
+        Self::warn_overridden(&config, key.name);
+        Ok(())
+    }
+
+    /// Print every setting, it's value, and where the value came from.
+    pub fn list(&self) -> Result<(), BeansError>
+    {
+        let config = &self.config;
+        let items: Vec<ConfigSummary> = CONFIG_KEYS
+            .iter()
+            .map(|k| {
+                let value = config.get_value(k.name);
+                ConfigSummary {
+                    key: k.name.to_string(),
+                    value_type: k.value_type,
+                    value: value
+                        .as_ref()
+                        .and_then(|(v, _)| serde_json::to_value(v).ok()),
+                    source: value.map(|(_, s)| s.to_string()),
+                    env: k.env.iter().map(|e| e.to_string()).collect(),
+                    description: k.description.to_string()
+                }
+            })
+            .collect();
+
+        if self.json
+        {
+            self.events.report(serde_json::to_value(&items)?);
+            return Ok(());
+        }
+        for (item, key) in items.iter().zip(CONFIG_KEYS.iter())
+        {
+            match config.get_value(key.name)
+            {
+                Some((value, source)) =>
+                {
+                    println!("{} = {} ({})", item.key, format_value(&value), source)
+                }
+                None => println!("{} is not set", item.key)
+            }
+            println!("    {}", item.description);
+        }
+        Ok(())
+    }
+
+    /// Print the location of the config file.
+    pub fn path(&self) -> Result<(), BeansError>
+    {
+        if self.json
+        {
+            self.events.report(serde_json::json!(Config::location()));
+            return Ok(());
+        }
+        println!("{}", Config::location());
+        Ok(())
+    }
+
+    /// Warn when the value in the config file for `name` isn't used, since
+    /// an environment variable is set.
+    fn warn_overridden(
+        config: &Config,
+        name: &str
+    )
+    {
+        if let Some((value, source)) = config.get_value(name)
+            && config.file.get(name) != Some(value.clone())
+            && !matches!(source, crate::config::ConfigSource::Default)
+        {
+            warn!(
+                "{} is {} from the {}, which is used instead of the config file",
+                name,
+                format_value(&value),
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use log::info;
+
+use crate::{version::{AdastralVersionFile,
+                      ADASTRAL_SCHEMA_VERSION},
+            BeansError,
+            RunnerContext};
+
+/// Show what is in `.adastral`, and everything that beans has done to the
+/// installation.
+#[derive(Debug, Clone)]
+pub struct HistoryWorkflow
+{
+    pub context: RunnerContext
+}
+
+impl HistoryWorkflow
+{
+    pub fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let av = ctx.appvar.clone();
+        let data = match AdastralVersionFile::read(Some(ctx.sourcemod_path.clone()), &ctx.appvar)?
+        {
+            Some(v) => v,
+            None =>
+            {
+                info!(
+                    "{} is not installed (or wasn't installed by beans).",
+                    av.mod_info.name_stylized
+                );
+                return Ok(());
+            }
+        };
+
+        let unknown = String::from("unknown");
+        let mut lines: Vec<String> = vec![
+            format!("Location:         {}", ctx.get_mod_location()),
+            format!("Version:          {}", data.version),
+            format!(
+                "Installed at:     {}",
+                data.installed_at.as_ref().unwrap_or(&unknown)
+            ),
+            format!(
+                "Installed by:     beans {}",
+                data.beans_version.as_ref().unwrap_or(&unknown)
+            ),
+            format!(
+                "Channel:          {}",
+                data.channel.as_ref().unwrap_or(&unknown)
+            ),
+            format!(
+                "Source:           {}",
+                data.source.as_ref().unwrap_or(&unknown)
+            ),
+            format!(
+                "Package checksum: {}",
+                data.package_checksum.as_ref().unwrap_or(&unknown)
+            ),
+        ];
+        if data.schema < ADASTRAL_SCHEMA_VERSION
+        {
+            lines.push(String::from("(.adastral was written by an older version of beans, so some details aren't available)"));
+        }
+        lines.push(String::new());
+
+        if data.history.is_empty()
+        {
+            lines.push(String::from("No history has been recorded yet."));
+            ctx.events().message(lines.join("\n"));
+            return Ok(());
+        }
+        lines.push(String::from("History (newest first);"));
+        for item in data.history.iter().rev()
+        {
+            let versions = match (item.from_version, item.to_version)
+            {
+                (Some(f), Some(t)) if f != t => format!("v{f} -> v{t}"),
+                (_, Some(t)) => format!("v{t}"),
+                (Some(f), None) => format!("v{f}"),
+                (None, None) => String::new()
+            };
+            lines.push(format!(
+                "    {}  {:<8} {:<14} {:<8} (beans {})",
+                item.timestamp, item.operation, versions, item.outcome, item.beans_version
+            ));
+            if let Some(m) = &item.message
+            {
+                lines.push(format!("        {}", m));
+            }
+            if !item.files.is_empty()
+            {
+                lines.push(format!("        {} file(s) changed", item.files.len()));
+            }
+            if !item.removed_files.is_empty()
+            {
+                lines.push(format!(
+                    "        {} file(s) removed",
+                    item.removed_files.len()
+                ));
+            }
+        }
+        ctx.events().message(lines.join("\n"));
+        Ok(())
+    }
+}
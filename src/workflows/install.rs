@@ -1,11 +1,18 @@
+use std::collections::HashSet;
+
 use log::{debug,
           error,
           info,
           warn};
 
-use crate::{appvar::AppVarData,
+use crate::{events::{Operation,
+                     Phase},
+            extract::PackageFormat,
             helper,
-            version::{AdastralVersionFile,
+            version,
+            version::{AdastralHistoryEntry,
+                      AdastralOperation,
+                      AdastralVersionFile,
                       RemoteVersion},
             BeansError,
             DownloadFailureReason,
@@ -14,7 +21,10 @@ use crate::{appvar::AppVarData,
 #[derive(Debug, Clone)]
 pub struct InstallWorkflow
 {
-    pub context: RunnerContext
+    pub context: RunnerContext,
+    /// When some, files that aren't in the package that is being installed
+    /// will be removed from the mod directory.
+    pub clean: Option<CleanInstallOptions>
 }
 impl InstallWorkflow
 {
@@ -23,52 +33,52 @@ impl InstallWorkflow
         let (latest_remote_id, latest_remote) = ctx.latest_remote_version();
         if let Some(_cv) = ctx.current_version
         {
-            println!("[InstallWorkflow::wizard] re-installing! game files will not be touched until extraction");
+            ctx.events().message(String::from(
+                "Re-installing! Game files will not be touched until extraction"
+            ));
         }
 
-        Self::install_with_remote_version(ctx, latest_remote_id, latest_remote).await
+        Self::install_with_remote_version(ctx, latest_remote_id, latest_remote, None).await
+    }
+
+    /// Install the latest version. Same as `InstallWorkflow::wizard`, but with
+    /// `self.clean`.
+    pub async fn install_latest(&mut self) -> Result<(), BeansError>
+    {
+        let (latest_remote_id, latest_remote) = self.context.latest_remote_version();
+        if let Some(_cv) = self.context.current_version
+        {
+            self.context.events().message(String::from(
+                "Re-installing! Game files will not be touched until extraction"
+            ));
+        }
+        let mut ctx = self.context.clone();
+        Self::install_with_remote_version(
+            &mut ctx,
+            latest_remote_id,
+            latest_remote,
+            self.clean.clone()
+        )
+        .await
     }
 
-    /// Prompt the user to confirm if they want to reinstall (when parameter
-    /// `current_version` is Some)
+    /// Prompt the user to confirm if they want to reinstall (when
+    /// `ctx.current_version` is Some)
     ///
-    /// Will always return `true` when `crate::PROMPT_DO_WHATEVER` is `true`.
+    /// Will always return `true` when `ctx.options.confirm` is `true`.
     ///
     /// Returns: `true` when the installation should continue, `false` when we
     /// should silently abort.
-    pub fn prompt_confirm(current_version: Option<usize>) -> bool
+    pub fn prompt_confirm(ctx: &RunnerContext) -> bool
     {
-        unsafe {
-            if crate::PROMPT_DO_WHATEVER
-            {
-                info!(
-                    "[InstallWorkflow::prompt_confirm] skipping since PROMPT_DO_WHATEVER is true"
-                );
-                return true;
-            }
-        }
-        let av = AppVarData::get();
-        if let Some(v) = current_version
+        if let Some(v) = ctx.current_version
         {
-            println!(
-                "[InstallWorkflow::prompt_confirm] Seems like {} is already installed (v{})",
-                v, av.mod_info.name_stylized
-            );
+            ctx.events().message(format!(
+                "Seems like {} is already installed (v{})",
+                ctx.appvar.mod_info.name_stylized, v
+            ));
 
-            println!("Are you sure that you want to reinstall?");
-            println!("Yes/Y (default)");
-            println!("No/N");
-            let user_input = helper::get_input("-- Enter option below --");
-            match user_input.to_lowercase().as_str()
-            {
-                "y" | "yes" | "" => true,
-                "n" | "no" => false,
-                _ =>
-                {
-                    println!("Unknown option \"{}\"", user_input.to_lowercase());
-                    Self::prompt_confirm(current_version)
-                }
-            }
+            ctx.prompt_confirm("Are you sure that you want to reinstall?")
         }
         else
         {
@@ -94,60 +104,178 @@ impl InstallWorkflow
             }
         };
         let mut ctx = self.context.clone();
-        InstallWorkflow::install_with_remote_version(&mut ctx, version_id, target_version.clone())
-            .await
+        InstallWorkflow::install_with_remote_version(
+            &mut ctx,
+            version_id,
+            target_version.clone(),
+            self.clean.clone()
+        )
+        .await
     }
 
     /// Install with a specific remote version.
     ///
-    /// Note: Will call Self::prompt_confirm, so set `crate::PROMPT_DO_WHATEVER`
-    /// to `true` before you call       this function if you don't want to
-    /// wait for a newline from stdin.
+    /// Note: Will call Self::prompt_confirm, so set `ctx.options.confirm` to
+    /// `true` before you call this function if you don't want to wait for a
+    /// newline from stdin.
     pub async fn install_with_remote_version(
         ctx: &mut RunnerContext,
         version_id: usize,
-        version: RemoteVersion
+        version: RemoteVersion,
+        clean: Option<CleanInstallOptions>
     ) -> Result<(), BeansError>
     {
-        if !Self::prompt_confirm(ctx.current_version)
+        let dry_run = clean.as_ref().is_some_and(|c| c.dry_run);
+        if !dry_run && !ctx.repair_version_file().await?
+        {
+            ctx.events().warning(String::from(
+                "The version file will be replaced, since it wasn't repaired."
+            ));
+        }
+        if !dry_run && !Self::prompt_confirm(ctx)
         {
             info!("[InstallWorkflow] Operation aborted by user");
             return Ok(());
         }
 
-        println!(
-            "{:=>60}\nInstalling version {} to {}\n{0:=>60}",
-            "=", version_id, &ctx.sourcemod_path
+        let operation = Operation::start(
+            ctx.options.events.clone(),
+            Phase::Install,
+            format!(
+                "Installing version {} to {}",
+                version_id, &ctx.sourcemod_path
+            )
         );
-        let presz_loc = RunnerContext::download_package(version, version_id).await?;
-        Self::install_from(
+        let post_sz = version.post_sz;
+        let presz_loc = ctx.download_package(version, version_id).await?;
+        let source = Some(ctx.appvar.remote_info.base_url.clone());
+        let message = Self::finish_message(ctx, &clean);
+        Self::install_package(
+            ctx,
             presz_loc.clone(),
-            ctx.sourcemod_path.clone(),
-            Some(version_id)
-        )
-        .await?;
+            Some(version_id),
+            post_sz,
+            clean,
+            source
+        )?;
         if helper::file_exists(presz_loc.clone())
         {
             std::fs::remove_file(presz_loc)?;
         }
+        operation.finish(Some(message));
         Ok(())
     }
 
-    /// Install the `.tar.zstd` file at `package_loc` to `out_dir`
-    /// package_loc: Location to a file that is a `.tar.zstd` file.
-    /// out_dir: should be `RunnerContext.sourcemod_path`
+    /// Install the package at `package_loc` to `ctx.sourcemod_path`
+    /// package_loc: Location to a `.tar.zst`, `.tar.xz`, `.tar.gz`, `.tar` or
+    /// `.zip` file. The format is detected from the content of the file, so
+    /// the extension doesn't matter.
     /// version_id: Version that is from `package_loc`. When not specified,
-    /// `.adastral` will not be written to. Note: This function doesn't
-    /// check the extension when extracting.
+    /// `.adastral` will not be written to.
+    /// size_hint: Installed size of the package (`RemoteVersion.post_sz`),
+    /// used for the progress bar.
+    /// clean: When some, files in the mod directory that aren't in the package
+    /// will be removed once the package has been extracted.
+    /// source: Where the package came from, which is recorded in `.adastral`.
+    /// `package_loc` is used when not specified.
     pub async fn install_from(
+        ctx: &RunnerContext,
+        package_loc: String,
+        version_id: Option<usize>,
+        size_hint: Option<usize>,
+        clean: Option<CleanInstallOptions>,
+        source: Option<String>
+    ) -> Result<(), BeansError>
+    {
+        let operation = Operation::start(
+            ctx.options.events.clone(),
+            Phase::Install,
+            format!("Installing {} to {}", package_loc, &ctx.sourcemod_path)
+        );
+        let message = Self::finish_message(ctx, &clean);
+        Self::install_package(ctx, package_loc, version_id, size_hint, clean, source)?;
+        operation.finish(Some(message));
+        Ok(())
+    }
+    /// Same as `install_from`, without reporting `Phase::Install` to
+    /// `ctx.events()`, so it can be used as part of a different operation.
+    fn install_package(
+        ctx: &RunnerContext,
         package_loc: String,
-        out_dir: String,
-        version_id: Option<usize>
+        version_id: Option<usize>,
+        size_hint: Option<usize>,
+        clean: Option<CleanInstallOptions>,
+        source: Option<String>
     ) -> Result<(), BeansError>
     {
+        let out_dir = ctx.sourcemod_path.clone();
         debug!("[InstallWorkflow::install_from] package_loc={package_loc:}");
         debug!("[InstallWorkflow::install_from] out_dir={out_dir:}");
         debug!("[InstallWorkflow::install_from] version_id={version_id:?}");
+        debug!("[InstallWorkflow::install_from] size_hint={size_hint:?}");
+        if let Some(options) = clean.as_ref().filter(|c| c.dry_run)
+        {
+            // nothing is extracted, so .adastral isn't touched either.
+            Self::list_orphaned_files(ctx, package_loc, options)?;
+            return Ok(());
+        }
+        let previous = AdastralVersionFile::read(Some(out_dir.clone()), &ctx.appvar)
+            .ok()
+            .flatten();
+        let from_version = previous
+            .as_ref()
+            .and_then(|v| v.version.parse::<usize>().ok());
+        let operation = match (from_version, version_id)
+        {
+            (Some(f), Some(t)) if f != t => AdastralOperation::Switch,
+            _ => AdastralOperation::Install
+        };
+        let package_checksum = match version_id.is_some()
+            && helper::file_exists(package_loc.clone())
+        {
+            true => helper::sha256_file(package_loc.clone()).ok(),
+            false => None
+        };
+
+        let result = Self::install_from_inner(ctx, package_loc.clone(), size_hint, clean);
+        let entry = AdastralHistoryEntry::new(operation, from_version, version_id, &result);
+        match (&result, version_id)
+        {
+            (Ok(_), Some(lri)) =>
+            {
+                let mut x = previous.unwrap_or_else(|| AdastralVersionFile::new(lri));
+                x.version = lri.to_string();
+                x.installed_at = Some(entry.timestamp.clone());
+                x.beans_version = Some(crate::VERSION.to_string());
+                x.channel = Some(version::RELEASE_CHANNEL.to_string());
+                x.source = Some(source.unwrap_or(package_loc));
+                x.package_checksum = package_checksum;
+                x.push_history(entry);
+                if let Err(e) = x.write(Some(out_dir.clone()), &ctx.appvar)
+                {
+                    warn!(
+                        "[InstallWorkflow::install_from] Failed to set version to {} in .adastral",
+                        lri
+                    );
+                    debug!("{:#?}", e);
+                }
+            }
+            (Ok(_), None) =>
+            {
+                warn!("Not writing .adastral since the version wasn't provided");
+            }
+            (Err(_), _) => version::record_history(Some(out_dir.clone()), &ctx.appvar, entry)
+        }
+        result
+    }
+    fn install_from_inner(
+        ctx: &RunnerContext,
+        package_loc: String,
+        size_hint: Option<usize>,
+        clean: Option<CleanInstallOptions>
+    ) -> Result<(), BeansError>
+    {
+        let out_dir = ctx.sourcemod_path.clone();
         if !helper::file_exists(package_loc.clone())
         {
             error!("[InstallWorkflow::Wizard] Failed to find package! (location: {package_loc})");
@@ -171,37 +299,196 @@ impl InstallWorkflow
                 });
             }
         }
+        let orphaned_files = match clean
+        {
+            Some(options) => Self::prompt_orphaned_files(ctx, package_loc.clone(), &options)?,
+            None => Vec::new()
+        };
+
+        ctx.check_cancelled()?;
         info!("[InstallWorkflow::Wizard] Extracting to {out_dir}");
 
-        RunnerContext::extract_package(package_loc, out_dir.clone())?;
-        if let Some(lri) = version_id
+        RunnerContext::extract_package(
+            package_loc,
+            out_dir.clone(),
+            size_hint,
+            ctx.config(),
+            ctx.events()
+        )?;
+        Self::remove_orphaned_files(ctx, orphaned_files)?;
+        Ok(())
+    }
+    /// Print the files that `list_orphaned_files` found, then ask the user to
+    /// confirm that they should be removed.
+    ///
+    /// Returns the files that should be removed once the package is extracted,
+    /// relative to the mod directory. Will be empty when the user declines.
+    fn prompt_orphaned_files(
+        ctx: &RunnerContext,
+        package_loc: String,
+        options: &CleanInstallOptions
+    ) -> Result<Vec<String>, BeansError>
+    {
+        let orphaned_files = Self::list_orphaned_files(ctx, package_loc, options)?;
+        if orphaned_files.is_empty()
+        {
+            return Ok(orphaned_files);
+        }
+        if !ctx.prompt_confirm("Do you want to remove these files once the package is extracted?")
+        {
+            info!("[InstallWorkflow] Not removing any files");
+            return Ok(Vec::new());
+        }
+        Ok(orphaned_files)
+    }
+
+    /// Find the files in the mod directory that aren't in the package at
+    /// `package_loc`, and print them with their size. Nothing is removed.
+    ///
+    /// Returns the files relative to the mod directory.
+    fn list_orphaned_files(
+        ctx: &RunnerContext,
+        package_loc: String,
+        options: &CleanInstallOptions
+    ) -> Result<Vec<String>, BeansError>
+    {
+        let av = &ctx.appvar;
+        let mod_dir = helper::join_path(
+            ctx.sourcemod_path.clone(),
+            av.mod_info.sourcemod_name.clone()
+        );
+        if !helper::dir_exists(mod_dir.clone())
         {
-            let x = AdastralVersionFile {
-                version: lri.to_string()
+            debug!("[InstallWorkflow::prompt_orphaned_files] {mod_dir} doesn't exist, nothing to clean");
+            return Ok(Vec::new());
+        }
+
+        info!("[InstallWorkflow] Checking for files that aren't in the new package");
+        let format = PackageFormat::detect(package_loc.clone())?;
+        let package_files = crate::extract::list_package_files(package_loc.clone(), format)?;
+        let orphaned_files = options.find_orphaned_files(
+            package_loc,
+            &av.mod_info.sourcemod_name,
+            package_files,
+            helper::list_files_recursive(mod_dir.clone())?
+        )?;
+        if orphaned_files.is_empty()
+        {
+            info!("[InstallWorkflow] No files need to be removed");
+            return Ok(orphaned_files);
+        }
+
+        let mut total_size = 0usize;
+        let mut message = format!(
+            "The following {} files are not in the new package, and {} removed;",
+            orphaned_files.len(),
+            match options.dry_run
+            {
+                true => "would be",
+                false => "will be"
             }
-            .write(Some(out_dir.clone()));
-            if let Err(e) = x
+        );
+        for f in orphaned_files.iter()
+        {
+            let size = std::fs::symlink_metadata(helper::join_path(mod_dir.clone(), f.clone()))
+                .map(|m| m.len() as usize)
+                .unwrap_or(0);
+            total_size += size;
+            message.push_str(&format!("\n    {} ({})", f, helper::format_size(size)));
+        }
+        message.push_str(&format!(
+            "\nTotal: {} files ({})",
+            orphaned_files.len(),
+            helper::format_size(total_size)
+        ));
+        message.push_str(&format!(
+            "\nFiles that will be kept: {}",
+            options.preserve.join(", ")
+        ));
+        ctx.events().message(message);
+        Ok(orphaned_files)
+    }
+
+    /// Remove the files that were returned by `prompt_orphaned_files`, then
+    /// remove any directories that were left empty.
+    fn remove_orphaned_files(
+        ctx: &RunnerContext,
+        orphaned_files: Vec<String>
+    ) -> Result<(), BeansError>
+    {
+        if orphaned_files.is_empty()
+        {
+            return Ok(());
+        }
+        let mod_dir = helper::join_path(
+            ctx.sourcemod_path.clone(),
+            ctx.appvar.mod_info.sourcemod_name.clone()
+        );
+        let mut parents: HashSet<String> = HashSet::new();
+        for f in orphaned_files.iter()
+        {
+            let location = helper::join_path(mod_dir.clone(), f.clone());
+            debug!("[InstallWorkflow::remove_orphaned_files] removing {location}");
+            if let Err(e) = std::fs::remove_file(&location)
             {
-                warn!(
-                    "[InstallWorkflow::install_from] Failed to set version to {} in .adastral",
-                    lri
+                error!(
+                    "[InstallWorkflow::remove_orphaned_files] Failed to remove {location} ({e:})"
                 );
-                debug!("{:#?}", e);
+                return Err(BeansError::FileDeleteFailure {
+                    location,
+                    error: e
+                });
+            }
+            let mut parent = f.as_str();
+            while let Some((p, _)) = parent.rsplit_once('/')
+            {
+                parents.insert(p.to_string());
+                parent = p;
             }
         }
-        else
+
+        // deepest directories first, so parents are empty by the time we get to them.
+        let mut parents: Vec<String> = parents.into_iter().collect();
+        parents.sort_by_key(|p| std::cmp::Reverse(p.matches('/').count()));
+        for p in parents
         {
-            warn!("Not writing .adastral since the version wasn't provided");
+            let location = helper::join_path(mod_dir.clone(), p);
+            if std::fs::read_dir(&location).is_ok_and(|mut d| d.next().is_none())
+            {
+                debug!(
+                    "[InstallWorkflow::remove_orphaned_files] removing empty directory {location}"
+                );
+                if let Err(e) = std::fs::remove_dir(&location)
+                {
+                    warn!("[InstallWorkflow::remove_orphaned_files] Failed to remove empty directory {location} ({e:})");
+                }
+            }
         }
-        InstallWorkflow::install_from_post();
+        info!(
+            "[InstallWorkflow] Removed {} files that weren't in the new package",
+            orphaned_files.len()
+        );
         Ok(())
     }
-    fn install_from_post()
+
+    /// Message that is displayed once the install is finished. Nothing is
+    /// installed when `clean` is a dry run, so `INSTALL_FINISH_MSG` isn't used.
+    fn finish_message(
+        ctx: &RunnerContext,
+        clean: &Option<CleanInstallOptions>
+    ) -> String
     {
-        let av = AppVarData::get();
-        println!("{}", av.sub(INSTALL_FINISH_MSG.to_string()));
-        debug!("[InstallWorkflow::install_from] Displayed INSTALL_FINISH_MSG");
+        match clean.as_ref().is_some_and(|c| c.dry_run)
+        {
+            true => String::from("Nothing was changed, since --dry-run was used."),
+            false => Self::install_from_post(ctx)
+        }
+    }
 
+    /// Bring the console window to the front on Windows, then get the message
+    /// that is displayed once the install is finished.
+    fn install_from_post(ctx: &RunnerContext) -> String
+    {
         #[cfg(target_os = "windows")]
         winconsole::window::show(true);
         #[cfg(target_os = "windows")]
@@ -213,10 +500,237 @@ impl InstallWorkflow
             rate: 0,
             until_foreground: true
         });
+        ctx.appvar.sub(INSTALL_FINISH_MSG.to_string())
     }
 }
 
+/// Options for `install --clean`.
+#[derive(Debug, Clone)]
+pub struct CleanInstallOptions
+{
+    /// Files and directories in the mod directory that will never be removed.
+    /// Items that end with `/` are directories, and everything inside of them
+    /// will be kept.
+    pub preserve: Vec<String>,
+    /// Only show which files would be removed. The package won't be
+    /// extracted, and nothing in the mod directory will be touched.
+    pub dry_run: bool,
+    /// Remove the files even when more than `CLEAN_INSTALL_MAX_ORPHANED` of
+    /// the installation isn't in the package, like when the mod has been
+    /// restructured.
+    pub force: bool
+}
+
+impl Default for CleanInstallOptions
+{
+    fn default() -> Self
+    {
+        Self {
+            preserve: CLEAN_INSTALL_PRESERVE_DEFAULT
+                .iter()
+                .map(|x| x.to_string())
+                .collect(),
+            dry_run: false,
+            force: false
+        }
+    }
+}
+
+impl CleanInstallOptions
+{
+    /// Find the files in `mod_files` that aren't in `package_files` and aren't
+    /// preserved. `package_files` are relative to the sourcemods directory,
+    /// and `mod_files` are relative to the mod directory.
+    ///
+    /// Fails when nothing in the package is in the `sourcemod_name` directory,
+    /// or when more than `CLEAN_INSTALL_MAX_ORPHANED` of the installation would
+    /// be removed (unless `self.force` is set), since the package probably
+    /// isn't for this mod.
+    pub fn find_orphaned_files(
+        &self,
+        package_loc: String,
+        sourcemod_name: &str,
+        package_files: Vec<String>,
+        mod_files: Vec<String>
+    ) -> Result<Vec<String>, BeansError>
+    {
+        let package_prefix = format!("{}/", sourcemod_name);
+        let package_files: HashSet<String> = package_files
+            .into_iter()
+            .filter_map(|f| f.strip_prefix(&package_prefix).map(String::from))
+            .filter(|f| !f.is_empty())
+            .collect();
+        if package_files.is_empty()
+        {
+            return Err(BeansError::CleanInstallRefused {
+                location: package_loc,
+                reason: format!("nothing in the package is in the {package_prefix} directory")
+            });
+        }
+
+        let removable: Vec<String> = mod_files
+            .into_iter()
+            .filter(|f| !self.is_preserved(f))
+            .collect();
+        let orphaned_files: Vec<String> = removable
+            .iter()
+            .filter(|f| !package_files.contains(*f))
+            .cloned()
+            .collect();
+        if !self.force
+            && orphaned_files.len() as f64 > removable.len() as f64 * CLEAN_INSTALL_MAX_ORPHANED
+        {
+            return Err(BeansError::CleanInstallRefused {
+                location: package_loc,
+                reason: format!(
+                    "{} of the {} files in the mod directory aren't in the package",
+                    orphaned_files.len(),
+                    removable.len()
+                )
+            });
+        }
+        Ok(orphaned_files)
+    }
+
+    /// Check if `location` (relative to the mod directory, with `/` as the
+    /// separator) matches anything in `self.preserve`.
+    pub fn is_preserved(
+        &self,
+        location: &str
+    ) -> bool
+    {
+        if CLEAN_INSTALL_PRESERVE_ALWAYS.contains(&location)
+        {
+            return true;
+        }
+        self.preserve.iter().any(|p| {
+            let p = p.replace('\\', "/");
+            let p = p.trim_start_matches('/');
+            if p.ends_with('/')
+            {
+                location.starts_with(p)
+            }
+            else
+            {
+                location == p || location.starts_with(&format!("{}/", p))
+            }
+        })
+    }
+}
+
+/// Default value for `CleanInstallOptions.preserve`. Relative to the mod
+/// directory.
+pub const CLEAN_INSTALL_PRESERVE_DEFAULT: &[&str] = &[
+    "cfg/config.cfg",
+    "cfg/autoexec.cfg",
+    "custom/",
+    "screenshots/",
+    "demos/",
+    "gameinfo_backup/"
+];
+/// Highest fraction of the files in the mod directory (that aren't preserved)
+/// that `install --clean` will remove without `--force`. See
+/// `CleanInstallOptions::find_orphaned_files`.
+pub const CLEAN_INSTALL_MAX_ORPHANED: f64 = 0.5;
+/// Files that are used by beans, and will never be removed by `install
+/// --clean`.
+const CLEAN_INSTALL_PRESERVE_ALWAYS: &[&str] = &[".adastral", ".revision"];
+
 #[cfg(not(target_os = "windows"))]
 pub const INSTALL_FINISH_MSG: &str = include_str!("../text/install_complete_linux.txt");
 #[cfg(target_os = "windows")]
 pub const INSTALL_FINISH_MSG: &str = include_str!("../text/install_complete_windows.txt");
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn clean_install_refuses_packages_for_a_different_directory()
+    {
+        let options = CleanInstallOptions::default();
+        let mod_files: Vec<String> = ["gameinfo.txt", "bin/client.so", "cfg/config.cfg"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let package =
+            |files: &[&str]| -> Vec<String> { files.iter().map(|f| f.to_string()).collect() };
+
+        // not rooted at the mod directory, like a zip made from inside of it.
+        let result = options.find_orphaned_files(
+            String::from("package.zip"),
+            "open_fortress",
+            package(&["gameinfo.txt", "bin/client.so"]),
+            mod_files.clone()
+        );
+        assert!(matches!(
+            result,
+            Err(BeansError::CleanInstallRefused { .. })
+        ));
+
+        // most of the installation would be removed.
+        let result = options.find_orphaned_files(
+            String::from("package.tar.zst"),
+            "open_fortress",
+            package(&["open_fortress/", "open_fortress/maps/ctf_2fort.bsp"]),
+            mod_files.clone()
+        );
+        assert!(matches!(
+            result,
+            Err(BeansError::CleanInstallRefused { .. })
+        ));
+
+        let result = options
+            .find_orphaned_files(
+                String::from("package.tar.zst"),
+                "open_fortress",
+                package(&["open_fortress/gameinfo.txt", "open_fortress/bin/server.so"]),
+                mod_files.clone()
+            )
+            .unwrap();
+        // cfg/config.cfg is preserved by default.
+        assert_eq!(result, vec![String::from("bin/client.so")]);
+    }
+
+    #[test]
+    fn clean_install_force_allows_restructured_packages()
+    {
+        let options = CleanInstallOptions {
+            force: true,
+            ..CleanInstallOptions::default()
+        };
+        let mod_files: Vec<String> = ["gameinfo.txt", "bin/client.so", "cfg/config.cfg"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+
+        let result = options
+            .find_orphaned_files(
+                String::from("package.tar.zst"),
+                "open_fortress",
+                vec![
+                    String::from("open_fortress/"),
+                    String::from("open_fortress/maps/ctf_2fort.bsp"),
+                ],
+                mod_files.clone()
+            )
+            .unwrap();
+        assert_eq!(result, vec![
+            String::from("gameinfo.txt"),
+            String::from("bin/client.so")
+        ]);
+
+        // still refused when the package isn't for this mod at all.
+        let result = options.find_orphaned_files(
+            String::from("package.zip"),
+            "open_fortress",
+            vec![String::from("gameinfo.txt")],
+            mod_files
+        );
+        assert!(matches!(
+            result,
+            Err(BeansError::CleanInstallRefused { .. })
+        ));
+    }
+}
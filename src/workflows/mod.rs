@@ -1,11 +1,28 @@
+mod adopt;
+mod check_update;
 mod clean;
+mod config;
+mod history;
 mod install;
+mod relocate;
+mod status;
 mod uninstall;
 mod update;
 mod verify;
+mod versions;
 
+pub use adopt::*;
+pub use check_update::*;
 pub use clean::*;
+pub use config::*;
+pub use history::*;
 pub use install::*;
+pub use relocate::*;
+pub use status::*;
 pub use uninstall::*;
 pub use update::*;
 pub use verify::*;
+pub use versions::*;
+
+#[cfg(test)]
+mod tests;
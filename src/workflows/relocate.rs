@@ -0,0 +1,419 @@
+use std::path::Path;
+
+use log::{debug,
+          error,
+          info,
+          warn};
+
+use crate::{events::{Operation,
+                     Phase,
+                     Progress},
+            helper,
+            version,
+            version::{AdastralHistoryEntry,
+                      AdastralOperation},
+            wharf,
+            BeansError,
+            RunnerContext};
+
+/// Move an installation to a different sourcemods directory.
+#[derive(Debug, Clone)]
+pub struct RelocateWorkflow
+{
+    pub context: RunnerContext,
+    /// sourcemods directory to move the installation into.
+    pub target_sourcemods: String
+}
+
+impl RelocateWorkflow
+{
+    pub async fn run(&mut self) -> Result<(), BeansError>
+    {
+        let mut ctx = self.context.clone();
+        Self::relocate(&mut ctx, self.target_sourcemods.clone()).await
+    }
+
+    /// Move the mod directory in `ctx.sourcemod_path` into `target_sourcemods`.
+    ///
+    /// When both are on the same filesystem, the mod directory is renamed.
+    /// Otherwise it's copied, verified, then the original is removed.
+    pub async fn relocate(
+        ctx: &mut RunnerContext,
+        target_sourcemods: String
+    ) -> Result<(), BeansError>
+    {
+        let av = ctx.appvar.clone();
+        if ctx.current_version.is_none()
+        {
+            info!("{} is not installed.", av.mod_info.name_stylized);
+            return Ok(());
+        }
+
+        let source = ctx.get_mod_location();
+        if let Some(pid) = helper::is_game_running(source.clone())
+        {
+            info!(
+                "{} (pid: {:}) is running! Can't move since the game files are being used.",
+                av.mod_info.name_stylized, pid
+            );
+            return Err(BeansError::GameStillRunning {
+                name: av.mod_info.name_stylized.clone(),
+                pid: format!("{:}", pid)
+            });
+        }
+
+        if !helper::dir_exists(target_sourcemods.clone())
+        {
+            if let Err(e) = std::fs::create_dir_all(&target_sourcemods)
+            {
+                error!("[RelocateWorkflow] Failed to create {target_sourcemods} ({e:})");
+                return Err(BeansError::DirectoryCreateFailure {
+                    location: target_sourcemods,
+                    error: e,
+                    backtrace: std::backtrace::Backtrace::capture()
+                });
+            }
+        }
+        let target_sourcemods = helper::parse_location(target_sourcemods);
+        let target = helper::join_path(target_sourcemods.clone(), av.data_dir());
+        if helper::parse_location(source.clone()) == helper::parse_location(target.clone())
+        {
+            info!("[RelocateWorkflow] {source} is already in {target_sourcemods}");
+            return Ok(());
+        }
+        if std::fs::symlink_metadata(&target).is_ok()
+        {
+            return Err(BeansError::MoveTargetExists {
+                location: target
+            });
+        }
+
+        let operation = Operation::start(
+            ctx.options.events.clone(),
+            Phase::Relocate,
+            format!("Moving {source} to {target}")
+        );
+        let target_trimmed = target.trim_end_matches(crate::PATH_SEP).to_string();
+        let source_trimmed = source.trim_end_matches(crate::PATH_SEP).to_string();
+        match std::fs::rename(&source_trimmed, &target_trimmed)
+        {
+            Ok(_) =>
+            {
+                info!("[RelocateWorkflow] Moved {source} to {target}");
+                Self::record_move(ctx, target_sourcemods.clone());
+                Self::update_config(ctx, target_sourcemods.clone());
+                operation.finish(Some(Self::post_relocate_msg(target_sourcemods)));
+                return Ok(());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices =>
+            {
+                debug!("[RelocateWorkflow] Can't rename since {target} is on a different filesystem ({e:})");
+            }
+            Err(e) =>
+            {
+                error!("[RelocateWorkflow] Failed to rename {source} to {target} ({e:})");
+                return Err(e.into());
+            }
+        }
+
+        let (file_count, total_size) = Self::get_size(Path::new(&source_trimmed))?;
+        if !helper::has_free_space(target_sourcemods.clone(), total_size as usize)?
+        {
+            error!(
+                "[RelocateWorkflow] Not enough free space! Requires {}",
+                helper::format_size(total_size as usize)
+            );
+            return Err(BeansError::FreeSpaceCheckFailure {
+                location: target_sourcemods
+            });
+        }
+
+        info!(
+            "[RelocateWorkflow] Copying {} files ({}) to {}",
+            file_count,
+            helper::format_size(total_size as usize),
+            target
+        );
+        let pb = Progress::start(
+            ctx.events(),
+            Phase::Copy,
+            format!("Copying files to {target}"),
+            Some(total_size)
+        );
+        if let Err(e) =
+            Self::copy_recursive(Path::new(&source_trimmed), Path::new(&target_trimmed), &pb)
+        {
+            pb.fail();
+            error!("[RelocateWorkflow] Failed to copy {source} to {target} ({e:})");
+            Self::remove_copy(target_trimmed);
+            return Err(e);
+        }
+        pb.finish();
+
+        if let Err(e) = Self::verify_copy(ctx, source_trimmed.clone(), target_trimmed.clone()).await
+        {
+            error!("[RelocateWorkflow] {e:}");
+            Self::remove_copy(target_trimmed);
+            return Err(e);
+        }
+
+        info!("[RelocateWorkflow] Removing {source}");
+        if let Err(e) = std::fs::remove_dir_all(&source_trimmed)
+        {
+            error!("[RelocateWorkflow] Failed to delete {source} ({e:})");
+            return Err(BeansError::DirectoryDeleteFailure {
+                location: source,
+                error: e
+            });
+        }
+
+        info!("[RelocateWorkflow] Moved {source} to {target}");
+        Self::record_move(ctx, target_sourcemods.clone());
+        Self::update_config(ctx, target_sourcemods.clone());
+        operation.finish(Some(Self::post_relocate_msg(target_sourcemods)));
+        Ok(())
+    }
+
+    /// Make sure that every file in `source` is in `target` with the same size,
+    /// then check `target` against the signature for the current version (when
+    /// it's available).
+    async fn verify_copy(
+        ctx: &mut RunnerContext,
+        source: String,
+        target: String
+    ) -> Result<(), BeansError>
+    {
+        info!("[RelocateWorkflow] Verifying {target}");
+        for f in helper::list_files_recursive(source.clone())?
+        {
+            let src = std::fs::symlink_metadata(helper::join_path(source.clone(), f.clone()))?;
+            match std::fs::symlink_metadata(helper::join_path(target.clone(), f.clone()))
+            {
+                Ok(m) if m.len() == src.len() =>
+                {}
+                Ok(m) =>
+                {
+                    return Err(BeansError::MoveVerifyFailure {
+                        location: target,
+                        reason: format!(
+                            "{f} is {} bytes, but it should be {} bytes",
+                            m.len(),
+                            src.len()
+                        )
+                    });
+                }
+                Err(e) =>
+                {
+                    return Err(BeansError::MoveVerifyFailure {
+                        location: target,
+                        reason: format!("{f} is missing ({e:})")
+                    });
+                }
+            }
+        }
+
+        let av = ctx.appvar.clone();
+        let signature_url = match ctx.current_remote_version()
+        {
+            Ok(v) => v.signature_url,
+            Err(e) =>
+            {
+                warn!("[RelocateWorkflow::verify_copy] Couldn't get the current version ({e:})");
+                None
+            }
+        };
+        match signature_url
+        {
+            Some(url) =>
+            {
+                if !wharf::verify_signature_url(
+                    format!("{}{}", &av.remote_info.base_url, url),
+                    target.clone(),
+                    ctx.config(),
+                    ctx.events()
+                )
+                .await?
+                .is_valid()
+                {
+                    return Err(BeansError::MoveVerifyFailure {
+                        location: target,
+                        reason: String::from(
+                            "it doesn't match the signature for the installed version. Try running the verify command before moving"
+                        )
+                    });
+                }
+            }
+            None =>
+            {
+                warn!("[RelocateWorkflow::verify_copy] No signature available for the installed version, only file sizes were checked");
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the amount of files in `location`, and the sum of their size.
+    fn get_size(location: &Path) -> Result<(usize, u64), BeansError>
+    {
+        let mut count = 0usize;
+        let mut size = 0u64;
+        for entry in std::fs::read_dir(location)?
+        {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_dir()
+            {
+                let (c, s) = Self::get_size(&entry.path())?;
+                count += c;
+                size += s;
+            }
+            else
+            {
+                count += 1;
+                size += meta.len();
+            }
+        }
+        Ok((count, size))
+    }
+
+    /// Copy everything in `source` into `target`, incrementing `pb` by the
+    /// amount of bytes copied. Symlinks are recreated instead of followed.
+    fn copy_recursive(
+        source: &Path,
+        target: &Path,
+        pb: &Progress
+    ) -> Result<(), BeansError>
+    {
+        if let Err(e) = std::fs::create_dir(target)
+        {
+            return Err(BeansError::DirectoryCreateFailure {
+                location: target.to_string_lossy().to_string(),
+                error: e,
+                backtrace: std::backtrace::Backtrace::capture()
+            });
+        }
+        for entry in std::fs::read_dir(source)?
+        {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let target_location = target.join(entry.file_name());
+            if file_type.is_dir()
+            {
+                Self::copy_recursive(&entry.path(), &target_location, pb)?;
+            }
+            else if file_type.is_symlink()
+            {
+                Self::copy_symlink(&entry.path(), &target_location)?;
+            }
+            else
+            {
+                let map_err = |error| BeansError::FileWriteFailure {
+                    location: target_location.to_string_lossy().to_string(),
+                    error
+                };
+                let mut src = std::fs::File::open(entry.path())?;
+                let mut dst = std::fs::File::create_new(&target_location).map_err(map_err)?;
+                std::io::copy(&mut pb.wrap_read(&mut src), &mut dst).map_err(map_err)?;
+                std::fs::set_permissions(&target_location, entry.metadata()?.permissions())
+                    .map_err(map_err)?;
+                pb.file_extracted(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn copy_symlink(
+        source: &Path,
+        target: &Path
+    ) -> Result<(), BeansError>
+    {
+        std::os::unix::fs::symlink(std::fs::read_link(source)?, target)?;
+        Ok(())
+    }
+    #[cfg(target_os = "windows")]
+    fn copy_symlink(
+        source: &Path,
+        target: &Path
+    ) -> Result<(), BeansError>
+    {
+        std::fs::copy(source, target)?;
+        Ok(())
+    }
+
+    /// Remove a copy that failed, so the next attempt doesn't fail with
+    /// `MoveTargetExists`.
+    fn remove_copy(location: String)
+    {
+        if let Err(e) = std::fs::remove_dir_all(&location)
+        {
+            warn!("[RelocateWorkflow] Failed to remove incomplete copy at {location} ({e:})");
+        }
+    }
+
+    fn record_move(
+        ctx: &RunnerContext,
+        target_sourcemods: String
+    )
+    {
+        let mut entry = AdastralHistoryEntry::new(
+            AdastralOperation::Move,
+            ctx.current_version,
+            ctx.current_version,
+            &Ok::<(), BeansError>(())
+        );
+        entry.message = Some(format!(
+            "moved from {} to {}",
+            ctx.sourcemod_path, target_sourcemods
+        ));
+        version::record_history(Some(target_sourcemods), &ctx.appvar, entry);
+    }
+
+    /// Set `sourcemods_location` in the config file to `target_sourcemods` when
+    /// it was the sourcemods directory that the installation was moved from, so
+    /// it's found in the new location next time.
+    fn update_config(
+        ctx: &mut RunnerContext,
+        target_sourcemods: String
+    )
+    {
+        let configured = match ctx.config().file.sourcemods_location.clone()
+        {
+            Some(v) => v,
+            None => return
+        };
+        if helper::parse_location(configured) != helper::parse_location(ctx.sourcemod_path.clone())
+        {
+            return;
+        }
+        match ctx.config().save_value(
+            "sourcemods_location",
+            Some(toml::Value::String(target_sourcemods.clone()))
+        )
+        {
+            Ok(config) =>
+            {
+                ctx.options.config = config;
+                info!(
+                    "[RelocateWorkflow] Set sourcemods_location in the config to {target_sourcemods}"
+                )
+            }
+            Err(e) =>
+            {
+                warn!("[RelocateWorkflow] Failed to set sourcemods_location in the config ({e:})")
+            }
+        }
+    }
+
+    /// Message that is displayed once the installation has been moved.
+    fn post_relocate_msg(target_sourcemods: String) -> String
+    {
+        let current = helper::find_sourcemod_path()
+            .ok()
+            .map(helper::parse_location);
+        if current.as_ref() != Some(&target_sourcemods)
+        {
+            return format!("{target_sourcemods} is not the sourcemods directory that Steam uses, so the game will not show up in your library until it is changed in Steam.\nPlease restart Steam.");
+        }
+        String::from("Please restart Steam.")
+    }
+}
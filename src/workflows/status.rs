@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use log::{debug,
+          error};
+
+use crate::{appvar::AppVarData,
+            config::Config,
+            events::EventSink,
+            helper,
+            helper::InstallType,
+            version,
+            BeansError};
+
+/// Print a summary of the installation, without changing anything.
+///
+/// Like `CheckUpdateWorkflow`, `RunnerContext::create_auto` isn't used since
+/// it writes the dependencies and migrates `.revision` files.
+#[derive(Debug, Clone)]
+pub struct StatusWorkflow
+{
+    /// sourcemods directory from the arguments. When `None`,
+    /// `sourcemods_location` from the config is used, then
+    /// `helper::find_sourcemod_path`
+    pub sourcemods_location: Option<String>,
+    /// Mod to show the status of.
+    pub appvar: AppVarData,
+    /// Print the status as JSON instead of text.
+    pub json: bool,
+    /// Where the status is sent to when `json` is `true`.
+    pub events: Arc<dyn EventSink>,
+    pub config: Config
+}
+
+/// Output of `StatusWorkflow`. This is what is printed when `--json` is used.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StatusReport
+{
+    pub sourcemods_path: String,
+    /// `steam` when it was found from the Steam registry, `argument` when it
+    /// was provided with `--location`, or `config` when it's
+    /// `sourcemods_location` from the config
+    pub sourcemods_found_via: String,
+    pub mod_path: String,
+    pub install_type: InstallType,
+    pub installed_version: Option<usize>,
+    /// `true` when the version file couldn't be read. Use the adopt command
+    /// to repair it.
+    pub version_file_corrupt: bool,
+    pub latest_version: usize,
+    pub update_available: bool,
+    /// Patches that would be applied to update to `latest_version`, in order.
+    /// Empty when there isn't a way to update with patches.
+    pub patches: Vec<String>,
+    /// Sum of `tempreq` for everything in `patches`.
+    pub patch_size: Option<usize>,
+    /// Size of the mod directory. `None` when it's not installed.
+    pub install_size: Option<u64>,
+    /// Free space where the sourcemods directory is.
+    pub free_space: Option<u64>,
+    pub gameinfo_backup_count: usize,
+    pub tmp_dir: String,
+    pub tmp_dir_size: Option<u64>,
+    /// Downloads and embedded binaries (see `helper::get_cache_dir`).
+    pub cache_dir: String,
+    pub cache_dir_size: Option<u64>,
+    /// Process ID of the game, when it's running.
+    pub game_running_pid: Option<u32>
+}
+
+impl StatusWorkflow
+{
+    pub async fn run(&self) -> Result<(), BeansError>
+    {
+        let report = self.get_status().await?;
+        if self.json
+        {
+            self.events.report(serde_json::to_value(&report)?);
+        }
+        else
+        {
+            Self::print_report(&report, &self.appvar);
+        }
+        Ok(())
+    }
+
+    pub async fn get_status(&self) -> Result<StatusReport, BeansError>
+    {
+        let av = &self.appvar;
+        let configured = self
+            .config
+            .sourcemods_location
+            .clone()
+            .filter(|x| helper::dir_exists(x.clone()));
+        let (sourcemods, sourcemods_found_via) =
+            match (self.sourcemods_location.clone(), configured)
+            {
+                (Some(v), _) => (v, "argument"),
+                (None, Some(v)) => (v, "config"),
+                (None, None) => (helper::find_sourcemod_path()?, "steam")
+            };
+        let sourcemods = helper::parse_location(sourcemods);
+        let remote = version::get_version_list(av).await?;
+        let latest_version = match remote.versions.keys().max()
+        {
+            Some(v) => *v,
+            None =>
+            {
+                return Err(BeansError::RemoteVersionNotFound {
+                    version: None
+                });
+            }
+        };
+        let mod_path = helper::join_path(sourcemods.clone(), av.data_dir());
+        let install_type = helper::install_state(Some(sourcemods.clone()), av);
+        let mut version_file_corrupt = false;
+        let installed_version = match version::read_installed_version(sourcemods.clone(), av)
+        {
+            Ok(v) => v,
+            Err(
+                e @ BeansError::VersionFileCorrupt {
+                    ..
+                }
+            ) =>
+            {
+                error!("[StatusWorkflow::get_status] {e:}");
+                version_file_corrupt = true;
+                None
+            }
+            Err(e) => return Err(e)
+        };
+
+        let mut patches: Vec<String> = Vec::new();
+        let mut patch_size: Option<usize> = None;
+        if let Some(cv) = installed_version
+            && cv < latest_version
+            && let Some(chain) =
+                remote.find_patch_chain(&av.mod_info.short_name, cv, latest_version)
+        {
+            patch_size = Some(chain.iter().map(|p| p.tempreq).sum());
+            patches = chain.into_iter().map(|p| p.file).collect();
+        }
+
+        let install_size = match helper::dir_exists(mod_path.clone())
+        {
+            true => helper::get_directory_size(mod_path.clone()).ok(),
+            false => None
+        };
+        let free_space = match helper::get_free_space(sourcemods.clone())
+        {
+            Ok(v) => Some(v),
+            Err(e) =>
+            {
+                debug!("[StatusWorkflow::get_status] {e:}");
+                None
+            }
+        };
+        let backup_dir = helper::join_path(
+            mod_path.clone(),
+            helper::GAMEINFO_BACKUP_DIRNAME.to_string()
+        );
+        let gameinfo_backup_count = std::fs::read_dir(&backup_dir)
+            .map(|d| d.count())
+            .unwrap_or(0);
+        // nothing should be created, so the `find_*` functions are used.
+        let tmp_dir = helper::find_tmp_dir(&self.config);
+        let tmp_dir_size = helper::get_directory_size(tmp_dir.clone()).ok();
+        let cache_dir = helper::find_cache_dir(&self.config);
+        let cache_dir_size = helper::get_directory_size(cache_dir.clone()).ok();
+
+        Ok(StatusReport {
+            sourcemods_path: sourcemods,
+            sourcemods_found_via: sourcemods_found_via.to_string(),
+            mod_path: mod_path.clone(),
+            install_type,
+            installed_version,
+            version_file_corrupt,
+            latest_version,
+            update_available: installed_version.is_some_and(|v| v < latest_version),
+            patches,
+            patch_size,
+            install_size,
+            free_space,
+            gameinfo_backup_count,
+            tmp_dir,
+            tmp_dir_size,
+            cache_dir,
+            cache_dir_size,
+            game_running_pid: helper::is_game_running(mod_path).map(|p| p.as_u32())
+        })
+    }
+
+    fn print_report(
+        report: &StatusReport,
+        av: &AppVarData
+    )
+    {
+        let unknown = String::from("unknown");
+        let fmt_size = |v: Option<u64>| match v
+        {
+            Some(x) => helper::format_size(x as usize),
+            None => unknown.clone()
+        };
+        println!(
+            "sourcemods:        {} ({})",
+            report.sourcemods_path,
+            match report.sourcemods_found_via.as_str()
+            {
+                "steam" => "detected from Steam",
+                "config" => "sourcemods_location in the config",
+                _ => "provided with --location"
+            }
+        );
+        println!("Mod directory:     {}", report.mod_path);
+        println!("Install type:      {:?}", report.install_type);
+        println!("Installed version: {}", match report.installed_version
+        {
+            Some(v) => format!("v{v}"),
+            None if report.version_file_corrupt =>
+            {
+                String::from("unknown, the version file is corrupt (use the adopt command)")
+            }
+            None => String::from("not installed")
+        });
+        println!("Latest version:    v{}", report.latest_version);
+        if report.update_available
+        {
+            match report.patch_size
+            {
+                Some(size) => println!(
+                    "Update:            available with {} ({})",
+                    report.patches.join(", "),
+                    helper::format_size(size)
+                ),
+                None => println!("Update:            available, but there are no patches (a reinstall is required)")
+            }
+        }
+        else if report.installed_version.is_some()
+        {
+            println!("Update:            up to date");
+        }
+        println!("Install size:      {}", fmt_size(report.install_size));
+        println!("Free space:        {}", fmt_size(report.free_space));
+        println!("gameinfo backups:  {}", report.gameinfo_backup_count);
+        println!(
+            "Temporary files:   {} ({})",
+            fmt_size(report.tmp_dir_size),
+            report.tmp_dir
+        );
+        println!(
+            "Cache:             {} ({})",
+            fmt_size(report.cache_dir_size),
+            report.cache_dir
+        );
+        match report.game_running_pid
+        {
+            Some(pid) => println!(
+                "Game running:      yes, {} (pid: {})",
+                av.mod_info.name_stylized, pid
+            ),
+            None => println!("Game running:      no")
+        }
+    }
+}
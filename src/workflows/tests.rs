@@ -0,0 +1,346 @@
+use std::{collections::HashMap,
+          io::Write,
+          path::PathBuf,
+          sync::{Arc,
+                 Barrier,
+                 Mutex}};
+
+use super::*;
+use crate::{appvar::AppVarData,
+            events::{ChannelSink,
+                     Event,
+                     JsonSink,
+                     Phase,
+                     JSON_SCHEMA_VERSION},
+            helper,
+            helper::InstallType,
+            version::{AdastralVersionFile,
+                      RemoteVersionResponse},
+            BeansError,
+            RunnerContext,
+            RunnerOptions};
+
+/// Create an empty sourcemods directory for a test.
+fn create_test_dir(name: &str) -> PathBuf
+{
+    let root =
+        std::env::temp_dir().join(format!("beans-workflows-{}-{}", name, std::process::id()));
+    if root.exists()
+    {
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// `AppVarData::get` with a different mod.
+fn test_appvar(sourcemod_name: &str) -> AppVarData
+{
+    let mut av = AppVarData::get();
+    av.mod_info.sourcemod_name = sourcemod_name.to_string();
+    av.mod_info.short_name = sourcemod_name.to_string();
+    av.mod_info.name_stylized = sourcemod_name.to_string();
+    av
+}
+
+/// Install `appvar` into `sourcemods` as `version`, with a few files.
+fn create_test_mod(
+    sourcemods: &str,
+    appvar: &AppVarData,
+    version: usize
+)
+{
+    let mod_location = helper::join_path(sourcemods.to_string(), appvar.data_dir());
+    std::fs::create_dir_all(helper::join_path(mod_location.clone(), String::from("cfg"))).unwrap();
+    std::fs::write(format!("{mod_location}gameinfo.txt"), b"GameInfo {}").unwrap();
+    std::fs::write(format!("{mod_location}cfg/config.cfg"), b"name beans").unwrap();
+    AdastralVersionFile::new(version)
+        .write(Some(sourcemods.to_string()), appvar)
+        .unwrap();
+}
+
+/// Create a context without downloading the version list.
+fn create_test_context(
+    sourcemods: &str,
+    appvar: AppVarData,
+    options: RunnerOptions
+) -> RunnerContext
+{
+    let mut ctx = RunnerContext {
+        sourcemod_path: helper::parse_location(sourcemods.to_string()),
+        remote_version_list: RemoteVersionResponse {
+            versions: HashMap::new(),
+            patches: HashMap::new()
+        },
+        current_version: None,
+        version_file_corrupt: false,
+        appvar,
+        options
+    };
+    futures::executor::block_on(ctx.load_current_version()).unwrap();
+    ctx
+}
+
+#[test]
+fn contexts_for_different_mods_are_independent()
+{
+    let root = create_test_dir("independent");
+    let sourcemods = root.to_str().unwrap().to_string();
+    let av_a = test_appvar("beans_test_a");
+    let av_b = test_appvar("beans_test_b");
+    create_test_mod(&sourcemods, &av_a, 3);
+    create_test_mod(&sourcemods, &av_b, 7);
+
+    let ctx_a = create_test_context(&sourcemods, av_a.clone(), RunnerOptions::default());
+    let ctx_b = create_test_context(&sourcemods, av_b.clone(), RunnerOptions::default());
+    assert_eq!(ctx_a.current_version, Some(3));
+    assert_eq!(ctx_b.current_version, Some(7));
+    assert_ne!(
+        ctx_a.clone().get_mod_location(),
+        ctx_b.clone().get_mod_location()
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn uninstall_runs_concurrently_for_different_mods()
+{
+    let root = create_test_dir("uninstall");
+    let sourcemods = root.to_str().unwrap().to_string();
+    let av_a = test_appvar("beans_test_a");
+    let av_b = test_appvar("beans_test_b");
+    let av_other = test_appvar("beans_test_other");
+    create_test_mod(&sourcemods, &av_a, 1);
+    create_test_mod(&sourcemods, &av_b, 2);
+    create_test_mod(&sourcemods, &av_other, 3);
+
+    // `a` skips the confirmation prompt, and `b` only does a dry run, so
+    // neither of them wait for input. The options must not leak between them.
+    let ctx_a = create_test_context(&sourcemods, av_a.clone(), RunnerOptions {
+        confirm: true,
+        ..RunnerOptions::default()
+    });
+    let ctx_b = create_test_context(&sourcemods, av_b.clone(), RunnerOptions::default());
+    let barrier = Barrier::new(2);
+    std::thread::scope(|scope| {
+        let a = scope.spawn(|| {
+            let mut wf = UninstallWorkflow {
+                context: ctx_a,
+                options: UninstallOptions::default()
+            };
+            barrier.wait();
+            futures::executor::block_on(wf.run())
+        });
+        let b = scope.spawn(|| {
+            let mut wf = UninstallWorkflow {
+                context: ctx_b,
+                options: UninstallOptions {
+                    dry_run: true,
+                    ..UninstallOptions::default()
+                }
+            };
+            barrier.wait();
+            futures::executor::block_on(wf.run())
+        });
+        a.join().unwrap().unwrap();
+        b.join().unwrap().unwrap();
+    });
+
+    assert_eq!(
+        helper::install_state(Some(sourcemods.clone()), &av_a),
+        InstallType::NotInstalled
+    );
+    assert_eq!(
+        helper::install_state(Some(sourcemods.clone()), &av_b),
+        InstallType::Adastral
+    );
+    assert_eq!(
+        helper::install_state(Some(sourcemods.clone()), &av_other),
+        InstallType::Adastral
+    );
+    assert!(root.join("beans_test_b/cfg/config.cfg").exists());
+    assert!(root.join("beans_test_other/cfg/config.cfg").exists());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn uninstall_continues_when_version_file_is_corrupt()
+{
+    let root = create_test_dir("corrupt");
+    let sourcemods = root.to_str().unwrap().to_string();
+    let av = test_appvar("beans_test_corrupt");
+    create_test_mod(&sourcemods, &av, 1);
+    std::fs::write(root.join("beans_test_corrupt/.adastral"), b"{\"version\": ").unwrap();
+
+    // Creating the context must not try to repair the version file.
+    let ctx = create_test_context(&sourcemods, av.clone(), RunnerOptions {
+        confirm: true,
+        ..RunnerOptions::default()
+    });
+    assert_eq!(ctx.current_version, None);
+    assert!(ctx.version_file_corrupt);
+    assert!(root.join("beans_test_corrupt/.adastral").exists());
+
+    let mut wf = UninstallWorkflow {
+        context: ctx,
+        options: UninstallOptions::default()
+    };
+    futures::executor::block_on(wf.run()).unwrap();
+    assert_eq!(
+        helper::install_state(Some(sourcemods.clone()), &av),
+        InstallType::NotInstalled
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn uninstall_stops_when_cancelled()
+{
+    let root = create_test_dir("cancelled");
+    let sourcemods = root.to_str().unwrap().to_string();
+    let av = test_appvar("beans_test_cancelled");
+    create_test_mod(&sourcemods, &av, 2);
+
+    let options = RunnerOptions {
+        confirm: true,
+        ..RunnerOptions::default()
+    };
+    options.cancel.cancel();
+    let mut wf = UninstallWorkflow {
+        context: create_test_context(&sourcemods, av.clone(), options),
+        options: UninstallOptions::default()
+    };
+    let result = futures::executor::block_on(wf.run());
+    assert!(matches!(result, Err(BeansError::Cancelled)), "{result:?}");
+    assert_eq!(
+        helper::install_state(Some(sourcemods.clone()), &av),
+        InstallType::Adastral
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn uninstall_reports_events_and_waits_for_prompt()
+{
+    let root = create_test_dir("events");
+    let sourcemods = root.to_str().unwrap().to_string();
+    let av = test_appvar("beans_test_events");
+    create_test_mod(&sourcemods, &av, 4);
+
+    let (sink, handle) = ChannelSink::new();
+    let ctx = create_test_context(&sourcemods, av.clone(), RunnerOptions {
+        events: Arc::new(sink),
+        ..RunnerOptions::default()
+    });
+    let worker = std::thread::spawn(move || {
+        let mut wf = UninstallWorkflow {
+            context: ctx,
+            options: UninstallOptions::default()
+        };
+        futures::executor::block_on(wf.run())
+    });
+
+    // nothing can be removed until the prompt is answered.
+    let mut events: Vec<Event> = Vec::new();
+    for event in handle.events.iter()
+    {
+        if let Event::PromptRequired {
+            ..
+        } = event
+        {
+            assert!(root.join("beans_test_events/gameinfo.txt").exists());
+            handle.answer(true);
+        }
+        events.push(event);
+    }
+    worker.join().unwrap().unwrap();
+
+    assert!(matches!(events.first(), Some(Event::Message { .. })));
+    assert!(events.contains(&Event::PhaseStarted {
+        phase: Phase::Uninstall,
+        message: String::from("Uninstalling beans_test_events"),
+        total: None
+    }));
+    assert!(matches!(
+        events.last(),
+        Some(Event::Finished {
+            phase: Phase::Uninstall,
+            success: true,
+            ..
+        })
+    ));
+    assert_eq!(
+        helper::install_state(Some(sourcemods.clone()), &av),
+        InstallType::NotInstalled
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+/// Writer for `JsonSink::with_writer` that can be read after the sink is used.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer
+{
+    fn write(
+        &mut self,
+        buf: &[u8]
+    ) -> std::io::Result<usize>
+    {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>
+    {
+        Ok(())
+    }
+}
+
+#[test]
+fn json_output_declines_prompts_when_not_interactive()
+{
+    let root = create_test_dir("json");
+    let sourcemods = root.to_str().unwrap().to_string();
+    let av = test_appvar("beans_test_json");
+    create_test_mod(&sourcemods, &av, 5);
+
+    let buffer = SharedBuffer::default();
+    let sink = Arc::new(JsonSink::with_writer(Box::new(buffer.clone()), false));
+    let ctx = create_test_context(&sourcemods, av.clone(), RunnerOptions {
+        events: sink.clone(),
+        ..RunnerOptions::default()
+    });
+    let mut wf = UninstallWorkflow {
+        context: ctx,
+        options: UninstallOptions::default()
+    };
+    futures::executor::block_on(wf.run()).unwrap();
+    sink.result("uninstall", 0);
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<serde_json::Value> = output
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert!(lines.iter().all(|l| l["v"] == JSON_SCHEMA_VERSION));
+    assert!(lines
+        .iter()
+        .any(|l| l["type"] == "prompt" && l["kind"] == "confirm" && l["waiting"] == false));
+    let result = lines.last().unwrap();
+    assert_eq!(result["type"], "result");
+    assert_eq!(result["command"], "uninstall");
+    assert_eq!(result["exit_code"], 0);
+    // the prompt was declined, so nothing is removed.
+    assert_eq!(
+        helper::install_state(Some(sourcemods.clone()), &av),
+        InstallType::Adastral
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -1,8 +1,13 @@
-use log::{error,
+use std::collections::BTreeMap;
+
+use log::{debug,
+          error,
           info,
           trace};
 
-use crate::{appvar::AppVarData,
+use crate::{events::{EventSink,
+                     Operation,
+                     Phase},
             helper,
             BeansError,
             RunnerContext};
@@ -10,14 +15,49 @@ use crate::{appvar::AppVarData,
 #[derive(Debug, Clone)]
 pub struct UninstallWorkflow
 {
-    pub context: RunnerContext
+    pub context: RunnerContext,
+    pub options: UninstallOptions
+}
+
+/// Options for `UninstallWorkflow`. The default is to ask for confirmation,
+/// then delete the mod directory.
+#[derive(Debug, Clone, Default)]
+pub struct UninstallOptions
+{
+    /// Only show what would be removed. Nothing will be touched.
+    pub dry_run: bool,
+    /// Export the directories in `UNINSTALL_USER_DATA` to a `.tar.gz` file in
+    /// the sourcemods directory before uninstalling.
+    pub keep_user_data: bool,
+    /// Move the mod directory into the trash instead of deleting it.
+    pub trash: bool
 }
+
+/// Directories (relative to the mod directory) that are exported when
+/// `UninstallOptions.keep_user_data` is set.
+pub const UNINSTALL_USER_DATA: &[&str] = &["cfg", "custom", "demos"];
+
 impl UninstallWorkflow
 {
     pub async fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
     {
-        let av = AppVarData::get();
-        if ctx.current_version.is_none()
+        Self::uninstall(ctx, UninstallOptions::default()).await
+    }
+
+    /// Uninstall with `self.options`
+    pub async fn run(&mut self) -> Result<(), BeansError>
+    {
+        let mut ctx = self.context.clone();
+        Self::uninstall(&mut ctx, self.options.clone()).await
+    }
+
+    pub async fn uninstall(
+        ctx: &mut RunnerContext,
+        options: UninstallOptions
+    ) -> Result<(), BeansError>
+    {
+        let av = ctx.appvar.clone();
+        if ctx.current_version.is_none() && !ctx.version_file_corrupt
         {
             info!("{} is not installed.", av.mod_info.name_stylized);
             return Ok(());
@@ -36,7 +76,66 @@ impl UninstallWorkflow
             });
         }
 
-        if let Err(e) = std::fs::remove_dir_all(&mod_location)
+        let user_data_location = Self::user_data_location(ctx);
+        // find the trash before asking, so we don't fail after the user has
+        // confirmed.
+        let trash_dir = match options.trash
+        {
+            true => Some(Self::find_trash_dir(mod_location.clone())?),
+            false => None
+        };
+        if options.dry_run
+        {
+            Self::print_dry_run(
+                mod_location,
+                user_data_location,
+                trash_dir,
+                &options,
+                ctx.events()
+            )?;
+            return Ok(());
+        }
+
+        let (file_count, total_size) = Self::get_directory_size(mod_location.clone())?;
+        ctx.events().message(format!(
+            "{} ({} files, {}) will be {}.",
+            mod_location,
+            file_count,
+            helper::format_size(total_size),
+            match &trash_dir
+            {
+                Some(t) => format!("moved to the trash ({t})"),
+                None => String::from("deleted")
+            }
+        ));
+        if !ctx.prompt_confirm(&format!(
+            "Are you sure that you want to uninstall {}?",
+            av.mod_info.name_stylized
+        ))
+        {
+            info!("[UninstallWorkflow] Operation aborted by user");
+            return Ok(());
+        }
+        ctx.check_cancelled()?;
+
+        let operation = Operation::start(
+            ctx.options.events.clone(),
+            Phase::Uninstall,
+            format!("Uninstalling {}", av.mod_info.name_stylized)
+        );
+        if options.keep_user_data
+        {
+            Self::export_user_data(ctx, mod_location.clone(), user_data_location.clone())?;
+            info!("[UninstallWorkflow] Saved user data to {user_data_location}");
+        }
+        ctx.check_cancelled()?;
+
+        if options.trash
+        {
+            let target = helper::move_to_trash(mod_location.clone())?;
+            info!("[UninstallWorkflow] Moved {mod_location} to {target}");
+        }
+        else if let Err(e) = std::fs::remove_dir_all(&mod_location)
         {
             trace!("{:#?}", e);
             error!(
@@ -49,10 +148,177 @@ impl UninstallWorkflow
             });
         }
 
-        info!(
-            "[UninstallWorkflow] Successfully uninstalled {}. Please restart Steam.",
+        operation.finish(Some(format!(
+            "Successfully uninstalled {}. Please restart Steam.",
             av.mod_info.name_stylized
-        );
+        )));
+        Ok(())
+    }
+
+    /// Show everything in the mod directory that would be removed, and the
+    /// total size.
+    fn print_dry_run(
+        mod_location: String,
+        user_data_location: String,
+        trash_dir: Option<String>,
+        options: &UninstallOptions,
+        events: &dyn EventSink
+    ) -> Result<(), BeansError>
+    {
+        // group files by the top-level item they're in, since listing every file
+        // in the mod directory isn't very useful.
+        let mut items: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for f in helper::list_files_recursive(mod_location.clone())?
+        {
+            let size = Self::file_size(&mod_location, &f);
+            let key = match f.split_once('/')
+            {
+                Some((d, _)) => format!("{d}/"),
+                None => f
+            };
+            let item = items.entry(key).or_insert((0, 0));
+            item.0 += 1;
+            item.1 += size;
+        }
+
+        let mut lines: Vec<String> = vec![format!(
+            "The following would be removed from {mod_location};"
+        )];
+        let mut file_count = 0usize;
+        let mut total_size = 0usize;
+        for (name, (count, size)) in items.iter()
+        {
+            file_count += count;
+            total_size += size;
+            if name.ends_with('/')
+            {
+                lines.push(format!(
+                    "    {} ({} files, {})",
+                    name,
+                    count,
+                    helper::format_size(*size)
+                ));
+            }
+            else
+            {
+                lines.push(format!("    {} ({})", name, helper::format_size(*size)));
+            }
+        }
+        lines.push(format!(
+            "Total: {} files ({})",
+            file_count,
+            helper::format_size(total_size)
+        ));
+
+        if options.keep_user_data
+        {
+            lines.push(format!(
+                "{} would be saved to {}",
+                UNINSTALL_USER_DATA.join(", "),
+                user_data_location
+            ));
+        }
+        if let Some(t) = trash_dir
+        {
+            lines.push(format!("{mod_location} would be moved to the trash ({t})"));
+        }
+        lines.push(String::from(
+            "Nothing has been removed since --dry-run was used."
+        ));
+        events.message(lines.join("\n"));
+        Ok(())
+    }
+
+    /// Find the trash that `helper::move_to_trash` will move `mod_location`
+    /// to.
+    fn find_trash_dir(mod_location: String) -> Result<String, BeansError>
+    {
+        match helper::find_trash_dir(mod_location.clone())
+        {
+            Some(v) => Ok(v),
+            None =>
+            {
+                error!("[UninstallWorkflow] Couldn't find a trash directory for {mod_location}");
+                Err(BeansError::TrashFailure {
+                    location: mod_location,
+                    reason: String::from("Couldn't find a trash directory on the same filesystem")
+                })
+            }
+        }
+    }
+
+    /// Get the amount of files in `location`, and the sum of their size.
+    fn get_directory_size(location: String) -> Result<(usize, usize), BeansError>
+    {
+        let files = helper::list_files_recursive(location.clone())?;
+        let size = files.iter().map(|f| Self::file_size(&location, f)).sum();
+        Ok((files.len(), size))
+    }
+
+    fn file_size(
+        directory: &str,
+        file: &str
+    ) -> usize
+    {
+        std::fs::symlink_metadata(helper::join_path(directory.to_string(), file.to_string()))
+            .map(|m| m.len() as usize)
+            .unwrap_or(0)
+    }
+
+    /// Location of where the user data will be exported to.
+    /// `{sourcemods}/{sourcemod_name}-userdata-{timestamp}.tar.gz`
+    fn user_data_location(ctx: &RunnerContext) -> String
+    {
+        let av = ctx.appvar.clone();
+        helper::join_path(
+            ctx.sourcemod_path.clone(),
+            format!(
+                "{}-userdata-{}.tar.gz",
+                av.mod_info.sourcemod_name,
+                chrono::Local::now().format("%Y%m%d-%H%M%S")
+            )
+        )
+    }
+
+    /// Write the directories in `UNINSTALL_USER_DATA` that exist to a `.tar.gz`
+    /// file at `output_location`. Items in the archive are prefixed with
+    /// `{sourcemod_name}/`, so it can be extracted into the sourcemods
+    /// directory to restore them.
+    fn export_user_data(
+        ctx: &RunnerContext,
+        mod_location: String,
+        output_location: String
+    ) -> Result<(), BeansError>
+    {
+        let av = &ctx.appvar;
+        let map_err = |error: std::io::Error| {
+            error!("[UninstallWorkflow::export_user_data] Failed to write {output_location} ({error:})");
+            BeansError::UserDataExportFailure {
+                location: output_location.clone(),
+                error
+            }
+        };
+        let file = std::fs::File::create_new(&output_location).map_err(map_err)?;
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        builder.follow_symlinks(false);
+        for d in UNINSTALL_USER_DATA.iter()
+        {
+            let location = helper::join_path(mod_location.clone(), d.to_string());
+            if !helper::dir_exists(location.clone())
+            {
+                debug!("[UninstallWorkflow::export_user_data] {location} doesn't exist, ignoring");
+                continue;
+            }
+            debug!("[UninstallWorkflow::export_user_data] adding {location}");
+            builder
+                .append_dir_all(format!("{}/{}", av.mod_info.sourcemod_name, d), &location)
+                .map_err(map_err)?;
+        }
+        builder
+            .into_inner()
+            .and_then(|e| e.finish())
+            .map_err(map_err)?;
         Ok(())
     }
 }
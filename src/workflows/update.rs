@@ -1,9 +1,18 @@
 use log::{debug,
-          info};
+          error,
+          info,
+          warn};
 
-use crate::{appvar::AppVarData,
-            butler,
+use crate::{butler,
+            butler::ButlerReport,
+            events::{Operation,
+                     Phase},
             helper,
+            version,
+            version::{AdastralHistoryEntry,
+                      AdastralOperation,
+                      RemotePatch,
+                      RemoteVersion},
             BeansError,
             RunnerContext};
 
@@ -15,16 +24,15 @@ impl UpdateWorkflow
 {
     pub async fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
     {
-        let av = AppVarData::get();
-
+        ctx.require_version_file().await?;
         let current_version_id = match ctx.current_version
         {
             Some(v) => v,
             None =>
             {
-                println!(
-                    "[UpdateWorkflow::wizard] Unable to update game since it is not installed!"
-                );
+                ctx.events().warning(String::from(
+                    "Unable to update game since it is not installed!"
+                ));
                 return Ok(());
             }
         };
@@ -37,7 +45,9 @@ impl UpdateWorkflow
             Some(v) => v,
             None =>
             {
-                println!("[UpdateWorkflow::wizard] No patch is available for the version that is currently installed.");
+                ctx.events().message(String::from(
+                    "No patch is available for the version that is currently installed."
+                ));
                 return Ok(());
             }
         };
@@ -46,40 +56,100 @@ impl UpdateWorkflow
 
         if !helper::has_free_space(ctx.sourcemod_path.clone(), patch.clone().tempreq)?
         {
-            println!(
-                "[UpdateWorkflow::wizard] Not enough free space! Requires {}",
+            ctx.events().warning(format!(
+                "Not enough free space! Requires {}",
                 helper::format_size(patch.tempreq)
-            );
+            ));
         }
         debug!("remote_version: {:#?}", remote_version);
         if remote_version.signature_url.is_none()
         {
-            eprintln!(
-                "[UpdateWorkflow::wizard] Couldn't get signature URL for version {}",
+            ctx.events().warning(format!(
+                "Couldn't get signature URL for version {}",
                 current_version_id
-            );
+            ));
         }
         if remote_version.heal_url.is_none()
         {
-            eprintln!(
-                "[UpdateWorkflow::wizard] Couldn't get heal URL for version {}",
+            ctx.events().warning(format!(
+                "Couldn't get heal URL for version {}",
                 current_version_id
-            );
+            ));
         }
         if remote_version.signature_url.is_none() || remote_version.heal_url.is_none()
         {
-            eprintln!("[UpdateWorkflow::wizard] Unable to update, missing remote files!");
+            ctx.events()
+                .warning(String::from("Unable to update, missing remote files!"));
             return Ok(());
         }
 
+        let (target_version_id, _) = ctx.latest_remote_version();
+        let operation = Operation::start(
+            ctx.options.events.clone(),
+            Phase::Update,
+            format!(
+                "Updating from version {} to {}",
+                current_version_id, target_version_id
+            )
+        );
         let mod_dir_location = ctx.get_mod_location();
         let staging_dir_location = ctx.get_staging_location();
 
+        let gameinfo_content = std::fs::read(ctx.gameinfo_location()).ok();
         helper::backup_gameinfo(ctx)?;
 
         ctx.gameinfo_perms()?;
+        let result = Self::verify_and_patch(
+            ctx,
+            remote_version,
+            patch,
+            mod_dir_location,
+            staging_dir_location.clone()
+        )
+        .await;
+        let mut entry = AdastralHistoryEntry::new(
+            AdastralOperation::Update,
+            Some(current_version_id),
+            Some(target_version_id),
+            &result
+        );
+        if let Ok(report) = &result
+        {
+            entry.files = report.changed_files.clone();
+            entry.removed_files = report.removed_files.clone();
+        }
+        version::record_history(Some(ctx.sourcemod_path.clone()), &ctx.appvar, entry);
+        let report = match result
+        {
+            Ok(v) => v,
+            Err(e) =>
+            {
+                Self::cleanup_failed_update(ctx, gameinfo_content, staging_dir_location);
+                return Err(e);
+            }
+        };
+        butler::print_report("updated", &report, ctx.events());
+        ctx.current_version = Some(target_version_id);
+
+        ctx.gameinfo_perms()?;
+
+        operation.finish(Some(Self::post_update_msg(ctx)));
+        Ok(())
+    }
+    async fn verify_and_patch(
+        ctx: &mut RunnerContext,
+        remote_version: RemoteVersion,
+        patch: RemotePatch,
+        mod_dir_location: String,
+        staging_dir_location: String
+    ) -> Result<ButlerReport, BeansError>
+    {
+        let av = ctx.appvar.clone();
+        let events = ctx.options.events.clone();
+        let config = ctx.options.config.clone();
+        let cancel = ctx.options.cancel.clone();
         info!("[UpdateWorkflow] Verifying game");
-        if let Err(e) = butler::verify(
+        let verified = match butler::verify_and_heal(
             format!(
                 "{}{}",
                 &av.remote_info.base_url,
@@ -90,37 +160,76 @@ impl UpdateWorkflow
                 "{}{}",
                 &av.remote_info.base_url,
                 remote_version.heal_url.unwrap()
-            )
+            ),
+            &config,
+            &cancel,
+            events.as_ref()
         )
+        .await
         {
-            sentry::capture_error(&e);
-            return Err(e);
-        }
+            Ok(v) => v,
+            Err(e) =>
+            {
+                sentry::capture_error(&e);
+                return Err(e);
+            }
+        };
         ctx.gameinfo_perms()?;
+        ctx.check_cancelled()?;
         info!("[UpdateWorkflow] Patching game");
-        if let Err(e) = butler::patch_dl(
+        let patched = match butler::patch_dl(
             format!("{}{}", &av.remote_info.base_url, patch.file),
             staging_dir_location,
             patch.file,
-            mod_dir_location
+            mod_dir_location,
+            &config,
+            &cancel,
+            events.as_ref()
         )
         .await
         {
-            sentry::capture_error(&e);
-            return Err(e);
+            Ok(v) => v,
+            Err(e) =>
+            {
+                sentry::capture_error(&e);
+                return Err(e);
+            }
+        };
+        let mut report = verified;
+        report.extend(patched);
+        Ok(report)
+    }
+    /// Put back the `gameinfo.txt` from before the update, and remove the
+    /// staging directory that butler used.
+    fn cleanup_failed_update(
+        ctx: &mut RunnerContext,
+        gameinfo_content: Option<Vec<u8>>,
+        staging_dir_location: String
+    )
+    {
+        if let Some(data) = gameinfo_content
+        {
+            info!("[UpdateWorkflow] Restoring gameinfo.txt");
+            if let Err(e) = helper::restore_gameinfo(ctx, data)
+            {
+                error!(
+                    "[UpdateWorkflow::cleanup_failed_update] Failed to restore gameinfo.txt ({e:})"
+                );
+            }
+        }
+        if helper::dir_exists(staging_dir_location.clone())
+        {
+            debug!("[UpdateWorkflow::cleanup_failed_update] Removing {staging_dir_location}");
+            if let Err(e) = std::fs::remove_dir_all(&staging_dir_location)
+            {
+                warn!("[UpdateWorkflow::cleanup_failed_update] Failed to remove {staging_dir_location} ({e:})");
+            }
         }
-
-        ctx.gameinfo_perms()?;
-
-        Self::post_update_msg();
-        Ok(())
     }
-    fn post_update_msg()
+    /// Bring the console window to the front on Windows, then get the message
+    /// that is displayed once the update is finished.
+    fn post_update_msg(ctx: &RunnerContext) -> String
     {
-        let av = AppVarData::get();
-        println!("{}", av.sub(UPDATE_FINISH_MSG.to_string()));
-        debug!("[UpdateWorkflow::post_update_msg] Displayed INSTALL_FINISH_MSG");
-
         #[cfg(target_os = "windows")]
         winconsole::window::show(true);
         #[cfg(target_os = "windows")]
@@ -132,6 +241,7 @@ impl UpdateWorkflow
             rate: 0,
             until_foreground: true
         });
+        ctx.appvar.sub(UPDATE_FINISH_MSG.to_string())
     }
 }
 
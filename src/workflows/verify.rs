@@ -1,10 +1,14 @@
-use log::{debug,
-          error};
+use log::{error,
+          info};
 
-use crate::{appvar::AppVarData,
-            butler,
+use crate::{butler,
+            events::{Operation,
+                     Phase},
             helper,
-            version::RemoteVersion,
+            version,
+            version::{AdastralHistoryEntry,
+                      AdastralOperation,
+                      RemoteVersion},
             BeansError,
             RunnerContext};
 
@@ -16,16 +20,17 @@ impl VerifyWorkflow
 {
     pub async fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
     {
-        let av = AppVarData::get();
+        let av = ctx.appvar.clone();
 
+        ctx.require_version_file().await?;
         let current_version_id = match ctx.current_version
         {
             Some(v) => v,
             None =>
             {
-                println!(
-                    "[VerifyWorkflow::wizard] Unable to update game since it is not installed!"
-                );
+                ctx.events().warning(String::from(
+                    "Unable to verify game since it is not installed!"
+                ));
                 return Ok(());
             }
         };
@@ -51,27 +56,65 @@ impl VerifyWorkflow
             return Ok(());
         }
 
+        let operation = Operation::start(
+            ctx.options.events.clone(),
+            Phase::Verify,
+            format!("Verifying version {}", current_version_id)
+        );
+        let gameinfo_content = std::fs::read(ctx.gameinfo_location()).ok();
         helper::backup_gameinfo(ctx)?;
         let mod_dir_location = ctx.get_mod_location();
-        butler::verify(
+        let result = butler::verify_and_heal(
             format!(
                 "{}{}",
                 &av.remote_info.base_url,
                 remote.signature_url.unwrap()
             ),
             mod_dir_location.clone(),
-            format!("{}{}", &av.remote_info.base_url, remote.heal_url.unwrap())
-        )?;
-        Self::post_verify_msg();
+            format!("{}{}", &av.remote_info.base_url, remote.heal_url.unwrap()),
+            ctx.config(),
+            &ctx.options.cancel,
+            ctx.events()
+        )
+        .await;
+        let mut entry = AdastralHistoryEntry::new(
+            AdastralOperation::Verify,
+            Some(current_version_id),
+            Some(current_version_id),
+            &result
+        );
+        if let Ok(report) = &result
+        {
+            entry.files = report.changed_files.clone();
+            entry.removed_files = report.removed_files.clone();
+        }
+        version::record_history(Some(ctx.sourcemod_path.clone()), &ctx.appvar, entry);
+        let report = match result
+        {
+            Ok(v) => v,
+            Err(e) =>
+            {
+                if let Some(data) = gameinfo_content
+                {
+                    info!("[VerifyWorkflow] Restoring gameinfo.txt");
+                    if let Err(e) = helper::restore_gameinfo(ctx, data)
+                    {
+                        error!("[VerifyWorkflow::wizard] Failed to restore gameinfo.txt ({e:})");
+                    }
+                }
+                return Err(e);
+            }
+        };
+        butler::print_report("repaired", &report, ctx.events());
+        let message = Self::post_verify_msg(ctx);
         ctx.gameinfo_perms()?;
+        operation.finish(Some(message));
         Ok(())
     }
-    fn post_verify_msg()
+    /// Bring the console window to the front on Windows, then get the message
+    /// that is displayed once verifying is finished.
+    fn post_verify_msg(ctx: &RunnerContext) -> String
     {
-        let av = AppVarData::get();
-        println!("{}", av.sub(VERIFY_FINISH_MSG.to_string()));
-        debug!("[VerifyWorkflow::post_verify_msg] Displayed INSTALL_FINISH_MSG");
-
         #[cfg(target_os = "windows")]
         winconsole::window::show(true);
         #[cfg(target_os = "windows")]
@@ -83,6 +126,7 @@ impl VerifyWorkflow
             rate: 0,
             until_foreground: true
         });
+        ctx.appvar.sub(VERIFY_FINISH_MSG.to_string())
     }
 }
 
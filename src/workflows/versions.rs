@@ -0,0 +1,339 @@
+use std::sync::Arc;
+
+use log::{debug,
+          warn};
+
+use crate::{appvar::AppVarData,
+            events::EventSink,
+            helper,
+            version,
+            version::{RemotePatch,
+                      RemoteVersion,
+                      RemoteVersionResponse},
+            BeansError};
+
+/// Show the versions and patches that are available from `versions.json`.
+///
+/// Like `CheckUpdateWorkflow`, `RunnerContext::create_auto` isn't used since
+/// it writes the dependencies and migrates `.revision` files.
+#[derive(Debug, Clone)]
+pub struct VersionsWorkflow
+{
+    /// sourcemods directory, used to find the installed version. When `None`,
+    /// it's found with `helper::find_sourcemod_path`
+    pub sourcemods_location: Option<String>,
+    /// Mod to show the versions of.
+    pub appvar: AppVarData,
+    /// Print as JSON instead of text.
+    pub json: bool,
+    /// Where the output is sent to when `json` is `true`.
+    pub events: Arc<dyn EventSink>
+}
+
+/// `versions.json` and the installed version, from `VersionsWorkflow::load`.
+struct VersionsState
+{
+    remote: RemoteVersionResponse,
+    installed_version: Option<usize>,
+    latest_version: usize
+}
+
+/// Item in the output of `VersionsWorkflow::list`
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VersionSummary
+{
+    pub id: usize,
+    pub pre_sz: Option<usize>,
+    pub post_sz: Option<usize>,
+    pub has_signature: bool,
+    pub has_heal: bool,
+    pub installed: bool,
+    pub latest: bool
+}
+
+/// Item in the output of `VersionsWorkflow::patches`. `from` and `to` are
+/// `None` when they couldn't be found from the filename.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PatchSummary
+{
+    pub from: Option<usize>,
+    pub to: Option<usize>,
+    pub file: String,
+    pub url: String,
+    pub tempreq: usize
+}
+
+/// Output of `VersionsWorkflow::show`
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VersionDetails
+{
+    pub id: usize,
+    pub installed: bool,
+    pub latest: bool,
+    #[serde(flatten)]
+    pub version: RemoteVersion,
+    /// Patches that can be applied to this version.
+    pub patches_from: Vec<PatchSummary>,
+    /// Patches that result in this version.
+    pub patches_to: Vec<PatchSummary>
+}
+
+impl VersionsWorkflow
+{
+    /// Print every version, oldest first.
+    pub async fn list(&self) -> Result<(), BeansError>
+    {
+        let state = self.load().await?;
+        let mut versions: Vec<VersionSummary> = state
+            .remote
+            .versions
+            .iter()
+            .map(|(id, v)| VersionSummary {
+                id: *id,
+                pre_sz: v.pre_sz,
+                post_sz: v.post_sz,
+                has_signature: v.signature_url.is_some(),
+                has_heal: v.heal_url.is_some(),
+                installed: state.installed_version == Some(*id),
+                latest: *id == state.latest_version
+            })
+            .collect();
+        versions.sort_by_key(|v| v.id);
+
+        if self.json
+        {
+            self.events.report(serde_json::to_value(&versions)?);
+            return Ok(());
+        }
+        println!(
+            "{:<8} {:>12} {:>12} {:<10} {:<5}",
+            "version", "presz", "postsz", "signature", "heal"
+        );
+        for v in versions.iter()
+        {
+            let mut tags: Vec<&str> = Vec::new();
+            if v.installed
+            {
+                tags.push("installed");
+            }
+            if v.latest
+            {
+                tags.push("latest");
+            }
+            println!(
+                "{:<8} {:>12} {:>12} {:<10} {:<5} {}",
+                format!("v{}", v.id),
+                Self::fmt_size(v.pre_sz),
+                Self::fmt_size(v.post_sz),
+                Self::fmt_bool(v.has_signature),
+                Self::fmt_bool(v.has_heal),
+                tags.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Print every patch, sorted by the version that it goes from.
+    pub async fn patches(&self) -> Result<(), BeansError>
+    {
+        let state = self.load().await?;
+        let patches = self.get_patches(&state.remote, |_, _| true);
+        if self.json
+        {
+            self.events.report(serde_json::to_value(&patches)?);
+            return Ok(());
+        }
+        if patches.is_empty()
+        {
+            println!("No patches are available.");
+            return Ok(());
+        }
+        for p in patches.iter()
+        {
+            let versions = match (p.from, p.to)
+            {
+                (Some(f), Some(t)) => format!("v{f} -> v{t}"),
+                _ => String::from("unknown")
+            };
+            println!(
+                "{:<14} {} (tempreq: {})",
+                versions,
+                p.file,
+                helper::format_size(p.tempreq)
+            );
+        }
+        Ok(())
+    }
+
+    /// Print the details of `version_id`, and the patches that can be used
+    /// with it.
+    pub async fn show(
+        &self,
+        version_id: usize
+    ) -> Result<(), BeansError>
+    {
+        let av = self.appvar.clone();
+        let state = self.load().await?;
+        let version = match state.remote.versions.get(&version_id)
+        {
+            Some(v) => v.clone(),
+            None =>
+            {
+                return Err(BeansError::RemoteVersionNotFound {
+                    version: Some(version_id)
+                });
+            }
+        };
+        let details = VersionDetails {
+            id: version_id,
+            installed: state.installed_version == Some(version_id),
+            latest: version_id == state.latest_version,
+            version,
+            patches_from: self.get_patches(&state.remote, |f, _| f == Some(version_id)),
+            patches_to: self.get_patches(&state.remote, |_, t| t == Some(version_id))
+        };
+
+        if self.json
+        {
+            self.events.report(serde_json::to_value(&details)?);
+            return Ok(());
+        }
+        let unknown = String::from("unknown");
+        let url = |v: &Option<String>| match v
+        {
+            Some(x) => format!("{}{}", av.remote_info.base_url, x),
+            None => String::from("none")
+        };
+        println!("Version:   v{}", details.id);
+        println!("Installed: {}", Self::fmt_bool(details.installed));
+        println!("Latest:    {}", Self::fmt_bool(details.latest));
+        println!(
+            "File:      {}",
+            details.version.file.as_ref().unwrap_or(&unknown)
+        );
+        println!("URL:       {}", url(&details.version.url));
+        println!("presz:     {}", Self::fmt_size(details.version.pre_sz));
+        println!("postsz:    {}", Self::fmt_size(details.version.post_sz));
+        println!("Signature: {}", url(&details.version.signature_url));
+        println!("Heal:      {}", url(&details.version.heal_url));
+        for (title, patches) in [
+            ("Patches from this version", &details.patches_from),
+            ("Patches to this version", &details.patches_to)
+        ]
+        {
+            if patches.is_empty()
+            {
+                continue;
+            }
+            println!("{title};");
+            for p in patches.iter()
+            {
+                println!(
+                    "    {} (tempreq: {})",
+                    p.file,
+                    helper::format_size(p.tempreq)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the versions from `versions.json`, and the version that is
+    /// installed. Nothing is marked as installed when the sourcemods directory
+    /// can't be found, or the version file can't be read, since the versions
+    /// can still be listed.
+    async fn load(&self) -> Result<VersionsState, BeansError>
+    {
+        let remote = version::get_version_list(&self.appvar).await?;
+        let latest_version = match remote.versions.keys().max()
+        {
+            Some(v) => *v,
+            None =>
+            {
+                return Err(BeansError::RemoteVersionNotFound {
+                    version: None
+                });
+            }
+        };
+        let sourcemods = match self.sourcemods_location.clone()
+        {
+            Some(v) => Some(v),
+            None => match helper::find_sourcemod_path()
+            {
+                Ok(v) => Some(v),
+                Err(e) =>
+                {
+                    debug!("[VersionsWorkflow::load] {e:}");
+                    None
+                }
+            }
+        };
+        let installed_version = match sourcemods
+            .map(|s| version::read_installed_version(helper::parse_location(s), &self.appvar))
+        {
+            Some(Ok(v)) => v,
+            Some(Err(e)) =>
+            {
+                warn!("[VersionsWorkflow::load] Couldn't read the installed version ({e:})");
+                None
+            }
+            None => None
+        };
+        Ok(VersionsState {
+            remote,
+            installed_version,
+            latest_version
+        })
+    }
+
+    /// Get all the patches in `remote` where `filter(from, to)` is true.
+    fn get_patches<F>(
+        &self,
+        remote: &RemoteVersionResponse,
+        filter: F
+    ) -> Vec<PatchSummary>
+    where
+        F: Fn(Option<usize>, Option<usize>) -> bool
+    {
+        let av = self.appvar.clone();
+        let mut result: Vec<PatchSummary> = remote
+            .patches
+            .values()
+            .map(|p: &RemotePatch| {
+                let versions = p.versions(&av.mod_info.short_name);
+                PatchSummary {
+                    from: versions.map(|(f, _)| f),
+                    to: versions.map(|(_, t)| t),
+                    file: p.file.clone(),
+                    url: p.url.clone(),
+                    tempreq: p.tempreq
+                }
+            })
+            .filter(|p| filter(p.from, p.to))
+            .collect();
+        result.sort_by(|a, b| {
+            a.from
+                .cmp(&b.from)
+                .then(a.to.cmp(&b.to))
+                .then(a.file.cmp(&b.file))
+        });
+        result
+    }
+
+    fn fmt_size(v: Option<usize>) -> String
+    {
+        match v
+        {
+            Some(x) => helper::format_size(x),
+            None => String::from("unknown")
+        }
+    }
+    fn fmt_bool(v: bool) -> &'static str
+    {
+        match v
+        {
+            true => "yes",
+            false => "no"
+        }
+    }
+}